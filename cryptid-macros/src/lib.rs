@@ -0,0 +1,142 @@
+//! Compile-time `cryptid` tokens and ID types.
+//!
+//! `cryptid_token!` encodes a number into a token while compiling, producing a `&'static str`.
+//! This allows documentation, database seeds and contract tests to embed tokens which are
+//! guaranteed to match the code that generated them.
+//!
+//! The key is given as a byte string literal, which is meant for test keys, or else read from
+//! the `CRYPTID_TOKEN_KEY` environment variable at build time.  Never put a production key in
+//! the source code.  The tokens are made with the default `Config` parameters.
+//!
+//! Note that Cargo doesn't know that the expansion depends on `CRYPTID_TOKEN_KEY`, so the
+//! crate using the macro must be rebuilt (e.g. with `cargo clean`) after changing it.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_macros::cryptid_token;
+//!
+//! const TOKEN: &str = cryptid_token!("example", 12345, b"your-secure-key");
+//! assert_eq!(TOKEN, "example_VgwPy6rwatl");
+//! ```
+//!
+//! `cryptid_id!` declares an ID type: the marker struct, its `TypeMarker` implementation and
+//! an alias of `Field` for it, which is otherwise boilerplate repeated for every entity.
+//!
+//! ```
+//! use cryptid_macros::cryptid_id;
+//! use cryptid_rs::{Config, TypeMarker};
+//!
+//! cryptid_id!(pub InvoiceId, "invoice");
+//!
+//! Config::set_global(Config::new(b"your-secure-key"));
+//! let id = InvoiceId::from(12345);
+//! assert_eq!(id.to_string(), "invoice_PDC9062Evh8");
+//! assert_eq!(InvoiceIdMarker::name(), "invoice");
+//! ```
+
+use proc_macro::{TokenStream, TokenTree};
+
+use cryptid_rs::{Codec, Config};
+
+/// Expands to the token of a number: `cryptid_token!("name", number)` or
+/// `cryptid_token!("name", number, b"key")`.  See the crate documentation.
+#[proc_macro]
+pub fn cryptid_token(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(token) => format!("{:?}", token).parse().unwrap(),
+        Err(message) => format!("compile_error!({:?})", message).parse().unwrap(),
+    }
+}
+
+/// Declares an ID type: `cryptid_id!(InvoiceId, "invoice")` expands to
+///
+/// ```ignore
+/// #[derive(Debug)]
+/// struct InvoiceIdMarker;
+/// impl cryptid_rs::TypeMarker for InvoiceIdMarker {
+///     fn name() -> &'static str { "invoice" }
+/// }
+/// type InvoiceId = cryptid_rs::Field<InvoiceIdMarker>;
+/// ```
+///
+/// A visibility before the type name, such as `pub`, applies to both the marker and the alias.
+/// Types needing other `TypeMarker` settings are declared by hand.
+#[proc_macro]
+pub fn cryptid_id(input: TokenStream) -> TokenStream {
+    match expand_id(input) {
+        Ok(items) => items.parse().unwrap(),
+        Err(message) => format!("compile_error!({:?})", message).parse().unwrap(),
+    }
+}
+
+fn expand_id(input: TokenStream) -> Result<String, String> {
+    let usage = "Expected cryptid_id!(TypeName, \"name\"), optionally with a visibility";
+    let mut trees: Vec<TokenTree> = input.into_iter().collect();
+    if matches!(trees.last(), Some(TokenTree::Punct(punct)) if punct.as_char() == ',') {
+        trees.pop();
+    }
+    let (name, ty, visibility) = match trees.as_slice() {
+        [visibility @ .., TokenTree::Ident(ty), TokenTree::Punct(comma), TokenTree::Literal(name)]
+            if comma.as_char() == ',' =>
+        {
+            (name.to_string(), ty.to_string(), visibility)
+        }
+        _ => return Err(usage.to_string()),
+    };
+    let name = unquote(&name).ok_or(usage)?;
+    let visibility = TokenStream::from_iter(visibility.iter().cloned()).to_string();
+    Ok(format!(
+        "#[derive(Debug)]
+        {visibility} struct {ty}Marker;
+        impl ::cryptid_rs::TypeMarker for {ty}Marker {{
+            fn name() -> &'static str {{ {name:?} }}
+        }}
+        {visibility} type {ty} = ::cryptid_rs::Field<{ty}Marker>;"
+    ))
+}
+
+fn expand(input: TokenStream) -> Result<String, String> {
+    let usage =
+        "Expected cryptid_token!(\"name\", number) or cryptid_token!(\"name\", number, b\"key\")";
+    let mut arguments = Vec::new();
+    for (i, tree) in input.into_iter().enumerate() {
+        match (i % 2, tree) {
+            (0, TokenTree::Literal(literal)) => arguments.push(literal.to_string()),
+            (1, TokenTree::Punct(punct)) if punct.as_char() == ',' => {}
+            _ => return Err(usage.to_string()),
+        }
+    }
+    let (name, num, key) = match arguments.as_slice() {
+        [name, num] => (name, num, None),
+        [name, num, key] => (name, num, Some(key)),
+        _ => return Err(usage.to_string()),
+    };
+
+    let name = unquote(name).ok_or(usage)?;
+    let num: u64 = num
+        .trim_end_matches("u64")
+        .replace('_', "")
+        .parse()
+        .map_err(|_| format!("Invalid number {}", num))?;
+    let key = match key {
+        Some(key) => key
+            .strip_prefix('b')
+            .and_then(unquote)
+            .ok_or(usage)?
+            .to_string(),
+        None => std::env::var("CRYPTID_TOKEN_KEY")
+            .map_err(|_| "Set CRYPTID_TOKEN_KEY or give the key as the third argument")?,
+    };
+    let codec = Codec::try_new(name, &Config::new(key.as_bytes()))
+        .map_err(|_| format!("Invalid name {:?}", name))?;
+    Ok(codec.encode(num))
+}
+
+// Returns the contents of a plain string literal without escapes.
+fn unquote(literal: &str) -> Option<&str> {
+    literal
+        .strip_prefix('"')?
+        .strip_suffix('"')
+        .filter(|contents| !contents.contains('\\'))
+}
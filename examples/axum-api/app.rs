@@ -0,0 +1,164 @@
+//! The routes of the example API, shared by `main.rs` and the integration test in
+//! `tests/axum_api.rs`.
+//!
+//! IDs appear as tokens in paths and JSON bodies, while the handlers and the models work with
+//! typed `Field`s.  The models derive the Diesel traits of an `invoices` table; a `Vec` stands
+//! in for the database, so that the example runs without Postgres.  The models also derive
+//! `ToSchema`, and the OpenAPI document documents the IDs as prefixed tokens.
+
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use cryptid_rs::axum::IdPath;
+use cryptid_rs::{AccessPolicy, Error, ErrorCode, Field, TypeMarker};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{OpenApi, ToSchema};
+
+#[derive(Debug)]
+pub struct CustomerIdMarker;
+impl TypeMarker for CustomerIdMarker {
+    fn name() -> &'static str {
+        "customer"
+    }
+}
+pub type CustomerId = Field<CustomerIdMarker>;
+
+#[derive(Debug)]
+pub struct InvoiceIdMarker;
+impl TypeMarker for InvoiceIdMarker {
+    fn name() -> &'static str {
+        "invoice"
+    }
+}
+pub type InvoiceId = Field<InvoiceIdMarker>;
+
+diesel::table! {
+    invoices (id) {
+        id -> BigInt,
+        customer_id -> BigInt,
+        amount_cents -> BigInt,
+    }
+}
+
+#[derive(
+    Clone, Debug, PartialEq, Serialize, Deserialize, Queryable, Selectable, Insertable, ToSchema,
+)]
+#[diesel(table_name = invoices)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Invoice {
+    pub id: InvoiceId,
+    pub customer_id: CustomerId,
+    pub amount_cents: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewInvoice {
+    pub amount_cents: i64,
+}
+
+#[derive(OpenApi)]
+#[openapi(components(schemas(Invoice, NewInvoice)))]
+pub struct ApiDoc;
+
+#[derive(Clone, Default)]
+pub struct AppState {
+    invoices: Arc<Mutex<Vec<Invoice>>>,
+}
+
+/// Maps decoding errors to responses.  Invalid tokens and objects of other customers are both
+/// "not found", so that responses don't reveal which IDs exist.
+pub struct ApiError(Error);
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        ApiError(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let code = ErrorCode::from(&self.0);
+        let status = match code {
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::NOT_FOUND,
+        };
+        let body = serde_json::json!({ "error": format!("{:?}", code) });
+        (status, Json(body)).into_response()
+    }
+}
+
+// Allows access to the invoices of one customer.
+struct Owner<'a> {
+    customer_id: CustomerId,
+    invoices: &'a [Invoice],
+}
+
+impl AccessPolicy<InvoiceIdMarker> for Owner<'_> {
+    fn authorize(&self, id: InvoiceId) -> bool {
+        self.invoices
+            .iter()
+            .any(|invoice| invoice.id == id && invoice.customer_id == self.customer_id)
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route(
+            "/customers/{customer_id}/invoices",
+            get(list_invoices).post(create_invoice),
+        )
+        .route(
+            "/customers/{customer_id}/invoices/{invoice_id}",
+            get(get_invoice),
+        )
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .with_state(state)
+}
+
+async fn list_invoices(
+    State(state): State<AppState>,
+    IdPath(customer_id): IdPath<CustomerIdMarker>,
+) -> Json<Vec<Invoice>> {
+    let invoices = state.invoices.lock().unwrap();
+    let owned = invoices
+        .iter()
+        .filter(|invoice| invoice.customer_id == customer_id)
+        .cloned();
+    Json(owned.collect())
+}
+
+async fn create_invoice(
+    State(state): State<AppState>,
+    IdPath(customer_id): IdPath<CustomerIdMarker>,
+    Json(new): Json<NewInvoice>,
+) -> (StatusCode, Json<Invoice>) {
+    let mut invoices = state.invoices.lock().unwrap();
+    let invoice = Invoice {
+        id: InvoiceId::from(invoices.len() as u64 + 1),
+        customer_id,
+        amount_cents: new.amount_cents,
+    };
+    invoices.push(invoice.clone());
+    (StatusCode::CREATED, Json(invoice))
+}
+
+// The invoice token is decoded by hand, rather than with `Path<InvoiceId>`, to check ownership
+// while decoding and to map the errors with `ApiError`.
+async fn get_invoice(
+    State(state): State<AppState>,
+    Path((customer_id, invoice_id)): Path<(CustomerId, String)>,
+) -> Result<Json<Invoice>, ApiError> {
+    let invoices = state.invoices.lock().unwrap();
+    let owner = Owner {
+        customer_id,
+        invoices: &invoices,
+    };
+    let id = InvoiceId::decode_authorized(&invoice_id, &owner)?;
+    let invoice = invoices.iter().find(|invoice| invoice.id == id);
+    Ok(Json(invoice.cloned().ok_or(Error::Forbidden)?))
+}
@@ -0,0 +1,24 @@
+//! An example API server using `cryptid` IDs with Axum.
+//!
+//! Run with `CRYPTID_KEY=... cargo run --example axum-api --features axum,utoipa`, then try e.g.
+//! `curl -X POST -H 'Content-Type: application/json' -d '{"amount_cents": 100}'
+//! localhost:3000/customers/customer_<token>/invoices`, where the customer token is printed
+//! at startup.  The OpenAPI document is served at `localhost:3000/openapi.json`.
+
+mod app;
+
+use cryptid_rs::Config;
+
+#[tokio::main]
+async fn main() {
+    let key = std::env::var("CRYPTID_KEY").expect("Set CRYPTID_KEY to the secret key");
+    Config::set_global(Config::new(key));
+    println!("Customer 1 is {}", app::CustomerId::from(1));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
+        .await
+        .unwrap();
+    axum::serve(listener, app::router(app::AppState::default()))
+        .await
+        .unwrap();
+}
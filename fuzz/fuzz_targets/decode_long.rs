@@ -0,0 +1,17 @@
+#![no_main]
+use cryptid_rs::{Codec, Config, Error};
+use libfuzzer_sys::fuzz_target;
+
+// Decodes payloads of valid base62 digits, which may be too long for the token format.
+fuzz_target!(|data: &[u8]| {
+    let alphabet = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let payload: String = data
+        .iter()
+        .map(|&b| alphabet[b as usize % alphabet.len()] as char)
+        .collect();
+    let codec = Codec::new("test", &Config::new(b"random-key"));
+    let result = codec.decode(&format!("test_{}", payload));
+    if payload.len() > 22 {
+        assert_eq!(result, Err(Error::PayloadTooLong { max_chars: 22 }));
+    }
+});
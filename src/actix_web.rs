@@ -0,0 +1,167 @@
+//! Actix Web support (`actix-web` feature).
+//!
+//! `Field<T>` implements `FromRequest`, decoding the last parameter of the request path with
+//! the global config, and `Error` implements `ResponseError`.  Handlers can also take
+//! `web::Path<Field<T>>`, which deserializes the field like any other path parameter.
+//!
+//! Invalid IDs respond 404 Not Found by default, so that clients can't tell a tampered or
+//! mistyped token from an object which doesn't exist.  Use `set_error_status` to respond e.g.
+//! 400 Bad Request instead.  Rate limited decodes always respond 429 Too Many Requests.
+//!
+//! # Examples
+//!
+//! ```
+//! use actix_web::{web, App};
+//! use cryptid_rs::{Field, TypeMarker};
+//!
+//! #[derive(Debug)]
+//! pub struct UserIdMarker;
+//! impl TypeMarker for UserIdMarker {
+//!     fn name() -> &'static str { "user" }
+//! }
+//! pub type UserId = Field<UserIdMarker>;
+//!
+//! async fn get_user(user_id: UserId) -> String {
+//!     format!("User {}", user_id)
+//! }
+//!
+//! let app = App::new().route("/users/{user_id}", web::get().to(get_user));
+//! ```
+
+use std::future::{ready, Ready};
+use std::sync::atomic::{AtomicU16, Ordering};
+
+use ::actix_web::dev::Payload;
+use ::actix_web::http::StatusCode;
+use ::actix_web::{FromRequest, HttpRequest, HttpResponse, ResponseError};
+
+use crate::field::get_or_create_codec;
+use crate::{Error, Field, TypeMarker};
+
+static ERROR_STATUS: AtomicU16 = AtomicU16::new(404);
+
+/// Sets the status code of responses for invalid IDs in the whole process.
+pub fn set_error_status(status: StatusCode) {
+    ERROR_STATUS.store(status.as_u16(), Ordering::Relaxed);
+}
+
+/// Returns the status code of responses for invalid IDs, 404 Not Found by default.
+pub fn error_status() -> StatusCode {
+    StatusCode::from_u16(ERROR_STATUS.load(Ordering::Relaxed)).expect("Status code was valid")
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Error::EncryptionFailed => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => error_status(),
+        }
+    }
+
+    /// Responds with the status code and its reason as the body, which doesn't reveal why
+    /// decoding failed.
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        HttpResponse::build(status).body(status.canonical_reason().unwrap_or_default())
+    }
+}
+
+impl<T: TypeMarker> FromRequest for Field<T> {
+    type Error = ::actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    /// Decodes the last parameter of the request path, e.g. the invoice in
+    /// `/customers/{customer_id}/invoices/{invoice_id}`.  Reserved IDs are rejected like in
+    /// `Deserialize`.
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let result = match req.match_info().iter().last() {
+            Some((_, encoded)) => {
+                Field::decode_with(&get_or_create_codec::<T>(), encoded).map_err(Into::into)
+            }
+            None => Err(::actix_web::error::ErrorInternalServerError(
+                "No path parameters",
+            )),
+        };
+        ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use ::actix_web::{test, web, App};
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+        fn reserved_ids() -> &'static [u64] {
+            &[0]
+        }
+    }
+
+    async fn handler(id: Field<ExampleIdMarker>) -> String {
+        u64::from(id).to_string()
+    }
+
+    async fn path_handler(path: web::Path<(String, Field<ExampleIdMarker>)>) -> String {
+        u64::from(path.into_inner().1).to_string()
+    }
+
+    #[actix_rt::test]
+    async fn test_from_request() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let zero = Field::<ExampleIdMarker>::from(0).to_string();
+        let app = test::init_service(
+            App::new()
+                .route("/examples/{id}", web::get().to(handler))
+                .route("/a/{a}/examples/{id}", web::get().to(path_handler))
+                .route("/examples", web::get().to(handler)),
+        )
+        .await;
+
+        for (uri, expected) in [
+            ("/examples/example_VgwPy6rwatl", (StatusCode::OK, "12345")),
+            (
+                "/a/x/examples/example_VgwPy6rwatl",
+                (StatusCode::OK, "12345"),
+            ),
+            (
+                "/examples/example_VgwPy6rwatx",
+                (StatusCode::NOT_FOUND, "Not Found"),
+            ),
+            ("/examples/12345", (StatusCode::NOT_FOUND, "Not Found")),
+            (
+                &format!("/examples/{}", zero),
+                (StatusCode::NOT_FOUND, "Not Found"),
+            ),
+            (
+                "/examples",
+                (StatusCode::INTERNAL_SERVER_ERROR, "No path parameters"),
+            ),
+        ] {
+            let request = test::TestRequest::get().uri(uri).to_request();
+            let response = test::call_service(&app, request).await;
+            let status = response.status();
+            let body = test::read_body(response).await;
+            assert_eq!(
+                (status, &body[..]),
+                (expected.0, expected.1.as_bytes()),
+                "{}",
+                uri
+            );
+        }
+
+        assert_eq!(
+            Error::RateLimited.status_code(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        set_error_status(StatusCode::BAD_REQUEST);
+        assert_eq!(Error::IncorrectMAC.status_code(), StatusCode::BAD_REQUEST);
+        set_error_status(StatusCode::NOT_FOUND);
+    }
+}
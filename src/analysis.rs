@@ -0,0 +1,156 @@
+//! Tools for choosing token parameters.
+//!
+//! `length_table` shows how `hmac_length` and `zero_pad_length` trade token length against
+//! the probability of forging a token.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_rs::analysis::length_table;
+//! use cryptid_rs::Config;
+//!
+//! let table = length_table(&Config::new(b"your-secure-key"));
+//! let current = table.rows.iter().find(|row| row.current).unwrap();
+//!
+//! assert_eq!((current.hmac_length, current.zero_pad_length), (4, 4));
+//! assert_eq!(current.typical_length, 11);
+//! println!("{}", table);
+//! ```
+
+use std::fmt;
+
+use crate::codec::min_payload_length;
+use crate::order;
+use crate::{Alphabet, Config};
+
+/// Token lengths and forgery probabilities for all parameter combinations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LengthTable {
+    /// One row for each combination of `hmac_length` and `zero_pad_length`.
+    pub rows: Vec<LengthRow>,
+}
+
+/// Token lengths and forgery probability of one parameter combination.
+///
+/// The lengths are the number of characters after the prefix and the underscore.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LengthRow {
+    pub hmac_length: u8,
+    pub zero_pad_length: u8,
+    /// The shortest possible token.
+    pub min_length: usize,
+    /// The longest token of an ID below 2^32, which covers most databases.
+    pub typical_length: usize,
+    /// The longest possible token.
+    pub max_length: usize,
+    /// The probability that a single guessed token decodes.
+    pub forgery_probability: f64,
+    /// `true` if these are the parameters of the config given to `length_table`.
+    pub current: bool,
+}
+
+/// Computes the token lengths and forgery probabilities for each combination of
+/// `hmac_length` and `zero_pad_length`, in the token mode and the alphabet of `config`.
+/// Order-preserving mode needs a MAC, so its rows start from `hmac_length` 1.
+pub fn length_table(config: &Config) -> LengthTable {
+    let mut rows = Vec::new();
+    for hmac_length in config.order_preserving as u8..=8 {
+        for zero_pad_length in 0..=8u8 {
+            let h = hmac_length as usize;
+            let bits = 8 * hmac_length as i32;
+            let (min_length, typical_length, max_length, forgery_probability) =
+                if config.order_preserving {
+                    // The multiplier is at least 2^(bits-1).
+                    let width = order::width(config.alphabet.radix(), h);
+                    (width, width, width, 2f64.powi(1 - bits).min(1.0))
+                } else {
+                    let pad = zero_pad_length as usize;
+                    (
+                        min_payload_length(&config.alphabet, pad, h),
+                        digits(&config.alphabet, pad.max(4) + h).1,
+                        digits(&config.alphabet, 8 + h).1,
+                        2f64.powi(-bits),
+                    )
+                };
+            rows.push(LengthRow {
+                hmac_length,
+                zero_pad_length,
+                min_length,
+                typical_length,
+                max_length,
+                forgery_probability,
+                current: hmac_length == config.hmac_length
+                    && zero_pad_length == config.zero_pad_length,
+            });
+        }
+    }
+    LengthTable { rows }
+}
+
+// Returns the range of digits of a value with `bytes` bytes of ciphertext and MAC.
+fn digits(alphabet: &Alphabet, bytes: usize) -> (usize, usize) {
+    if bytes >= 16 {
+        // There is no sentinel byte, so the value can be anything.
+        return (1, alphabet.digit_count(u128::MAX));
+    }
+    // The sentinel byte 1 follows the data.
+    let min = 1u128 << (8 * bytes);
+    let max = (min << 1) - 1;
+    (alphabet.digit_count(min), alphabet.digit_count(max))
+}
+
+impl fmt::Display for LengthTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "hmac  pad  min  typical  max  forgery")?;
+        for row in &self.rows {
+            writeln!(
+                f,
+                "{:>4} {:>4} {:>4} {:>8} {:>4}  {:.1e}{}",
+                row.hmac_length,
+                row.zero_pad_length,
+                row.min_length,
+                row.typical_length,
+                row.max_length,
+                row.forgery_probability,
+                if row.current { "  (current)" } else { "" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Codec;
+
+    #[test]
+    fn test_lengths_match_tokens() {
+        let key = b"Test key here";
+        for order_preserving in [false, true] {
+            let config = Config::new(key).order_preserving(order_preserving);
+            for row in length_table(&config).rows {
+                let config = config
+                    .clone()
+                    .hmac_length(row.hmac_length)
+                    .unwrap()
+                    .zero_pad_length(row.zero_pad_length)
+                    .unwrap();
+                let codec = Codec::new("t", &config);
+                for num in [0, 1, 255, 12345, u32::MAX as u64, u64::MAX] {
+                    let length = codec.encode(num).len() - 2;
+                    assert!(length >= row.min_length, "{:?} {}", row, num);
+                    assert!(length <= row.max_length, "{:?} {}", row, num);
+                    if num <= u32::MAX as u64 {
+                        assert!(length <= row.typical_length, "{:?} {}", row, num);
+                    }
+                }
+            }
+        }
+
+        let table = length_table(&Config::new(key));
+        assert_eq!(table.rows.iter().filter(|row| row.current).count(), 1);
+        assert_eq!(table.rows[0].forgery_probability, 1.0);
+        assert!(table.to_string().contains("  (current)"));
+    }
+}
@@ -0,0 +1,130 @@
+//! async-graphql support (`async-graphql` feature).
+//!
+//! `Field<T>` is a GraphQL scalar, so IDs can be used directly as arguments, input fields and
+//! output fields.  Values are encoded and decoded like with Serde, with the global config.
+//! The scalar of the marker `user` is named `UserId`, so each marker gets its own type.
+//!
+//! Invalid IDs are input errors mentioning the expected prefix, but not why decoding failed,
+//! so that clients can't tell a tampered token from a mistyped one.
+//!
+//! # Examples
+//!
+//! ```
+//! use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+//! use cryptid_rs::{Field, TypeMarker};
+//!
+//! #[derive(Debug)]
+//! pub struct UserIdMarker;
+//! impl TypeMarker for UserIdMarker {
+//!     fn name() -> &'static str { "user" }
+//! }
+//! pub type UserId = Field<UserIdMarker>;
+//!
+//! struct Query;
+//!
+//! #[Object]
+//! impl Query {
+//!     async fn user_name(&self, id: UserId) -> String {
+//!         format!("User {}", u64::from(id))
+//!     }
+//! }
+//!
+//! let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+//! assert!(schema.sdl().contains("userName(id: UserId!): String!"));
+//! ```
+
+use std::borrow::Cow;
+
+use ::async_graphql::{InputValueError, InputValueResult, Scalar, ScalarType, TypeName, Value};
+
+use crate::field::{get_or_create_codec, schema_name};
+use crate::{Field, TypeMarker};
+
+impl<T: TypeMarker + Send + Sync> TypeName for Field<T> {
+    fn type_name() -> Cow<'static, str> {
+        Cow::Owned(schema_name::<T>())
+    }
+}
+
+/// An object ID, encrypted into a prefixed token.
+#[Scalar(name_type)]
+impl<T: TypeMarker + Send + Sync> ScalarType for Field<T> {
+    /// Decodes a token, or also a UUID with `SerdeFormat::Uuid`, like `Deserialize`.
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let Value::String(encoded) = &value else {
+            return Err(InputValueError::expected_type(value));
+        };
+        Field::decode_string(&get_or_create_codec::<T>(), encoded).map_err(|_| {
+            let prefix = T::prefix_case().apply(T::name());
+            InputValueError::custom(format!("Expected a token with the prefix {}_", prefix))
+        })
+    }
+
+    fn is_valid(value: &Value) -> bool {
+        matches!(value, Value::String(_))
+    }
+
+    /// Encodes the ID in the form of `T::serde_format()`.
+    fn to_value(&self) -> Value {
+        Value::String(self.encode_string(&*get_or_create_codec::<T>()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use ::async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+        fn reserved_ids() -> &'static [u64] {
+            &[0]
+        }
+    }
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn next(&self, id: Field<ExampleIdMarker>) -> Field<ExampleIdMarker> {
+            id.map(|id| id + 1)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scalar() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+        assert!(schema.sdl().contains("scalar ExampleId"));
+
+        let next = Field::<ExampleIdMarker>::from(12346).to_string();
+        let response = schema
+            .execute(r#"{ next(id: "example_VgwPy6rwatl") }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "next": next })
+        );
+
+        let zero = Field::<ExampleIdMarker>::from(0).to_string();
+        for id in [
+            "\"example_VgwPy6rwatx\"",
+            "\"user_VgwPy6rwatl\"",
+            &format!("\"{}\"", zero),
+            "12345",
+        ] {
+            let response = schema.execute(format!("{{ next(id: {}) }}", id)).await;
+            let message = &response.errors[0].message;
+            assert!(message.contains("ExampleId"), "{}", message);
+            if id.starts_with('"') {
+                assert!(message.contains("prefix example_"), "{}", message);
+            }
+        }
+    }
+}
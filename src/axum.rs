@@ -0,0 +1,197 @@
+//! Axum support (`axum` feature).
+//!
+//! `IdPath<T>` extracts a `Field<T>` from the path of a request.  Unlike `Path<Field<T>>`,
+//! which rejects invalid tokens with 400 Bad Request, it responds 404 Not Found, so that
+//! clients can't tell a tampered or mistyped token from an object which doesn't exist.
+//!
+//! # Examples
+//!
+//! ```
+//! use axum::routing::get;
+//! use axum::Router;
+//! use cryptid_rs::axum::IdPath;
+//! use cryptid_rs::TypeMarker;
+//!
+//! #[derive(Debug)]
+//! pub struct UserIdMarker;
+//! impl TypeMarker for UserIdMarker {
+//!     fn name() -> &'static str { "user" }
+//! }
+//!
+//! async fn get_user(IdPath(user_id): IdPath<UserIdMarker>) -> String {
+//!     format!("User {}", user_id)
+//! }
+//!
+//! let app: Router = Router::new().route("/users/{user_id}", get(get_user));
+//! ```
+
+use std::fmt;
+use std::ops::Deref;
+
+use ::axum::extract::{FromRequestParts, RawPathParams};
+use ::axum::http::request::Parts;
+use ::axum::http::StatusCode;
+use ::axum::response::{IntoResponse, Response};
+
+use crate::field::get_or_create_codec;
+use crate::{Error, Field, TypeMarker};
+
+/// An extractor decoding the last parameter of the request path into a `Field<T>` with the
+/// global config, e.g. the invoice in `/customers/{customer_id}/invoices/{invoice_id}`.
+///
+/// Reserved IDs are rejected like in `Deserialize`.
+#[derive(Debug)]
+pub struct IdPath<T: TypeMarker>(pub Field<T>);
+
+impl<T: TypeMarker> Deref for IdPath<T> {
+    type Target = Field<T>;
+
+    fn deref(&self) -> &Field<T> {
+        &self.0
+    }
+}
+
+impl<S, T> FromRequestParts<S> for IdPath<T>
+where
+    S: Send + Sync,
+    T: TypeMarker + Send,
+{
+    type Rejection = IdRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, IdRejection> {
+        let params = RawPathParams::from_request_parts(parts, state)
+            .await
+            .map_err(|_| IdRejection::MissingParameter)?;
+        let (_, encoded) = params.iter().last().ok_or(IdRejection::MissingParameter)?;
+        Ok(IdPath(Field::decode_with(
+            &get_or_create_codec::<T>(),
+            encoded,
+        )?))
+    }
+}
+
+/// The rejection of `IdPath`, which can also be returned by handlers for errors from decoding
+/// IDs in other ways, such as with `Field::decode_authorized`.
+#[derive(Debug)]
+pub enum IdRejection {
+    /// The route has no path parameters, which is a bug in the application.  Responds 500
+    /// Internal Server Error.
+    MissingParameter,
+    /// The token failed to decode.  Responds 429 Too Many Requests for
+    /// `Error::RateLimited`, and 404 Not Found for other errors.
+    Invalid(Error),
+}
+
+impl From<Error> for IdRejection {
+    fn from(error: Error) -> Self {
+        IdRejection::Invalid(error)
+    }
+}
+
+impl IdRejection {
+    /// Returns the status code of the response.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            IdRejection::MissingParameter => StatusCode::INTERNAL_SERVER_ERROR,
+            IdRejection::Invalid(Error::RateLimited) => StatusCode::TOO_MANY_REQUESTS,
+            IdRejection::Invalid(_) => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+impl IntoResponse for IdRejection {
+    /// Responds with the status code and its reason as the body, which doesn't reveal why
+    /// decoding failed.
+    fn into_response(self) -> Response {
+        let status = self.status();
+        (status, status.canonical_reason().unwrap_or_default()).into_response()
+    }
+}
+
+impl fmt::Display for IdRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdRejection::MissingParameter => write!(f, "No path parameters"),
+            IdRejection::Invalid(error) => write!(f, "Invalid ID: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for IdRejection {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use ::axum::body::Body;
+    use ::axum::http::Request;
+    use ::axum::routing::get;
+    use ::axum::Router;
+    use tower::ServiceExt;
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+        fn reserved_ids() -> &'static [u64] {
+            &[0]
+        }
+    }
+
+    async fn status(router: &Router, uri: &str) -> (StatusCode, String) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = ::axum::body::to_bytes(response.into_body(), 1024)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_id_path() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let zero = Field::<ExampleIdMarker>::from(0).to_string();
+        async fn handler(IdPath(id): IdPath<ExampleIdMarker>) -> String {
+            u64::from(id).to_string()
+        }
+        let router = Router::new()
+            .route("/examples/{id}", get(handler))
+            .route("/a/{a}/examples/{id}", get(handler))
+            .route("/examples", get(handler));
+
+        for (uri, expected) in [
+            ("/examples/example_VgwPy6rwatl", (StatusCode::OK, "12345")),
+            (
+                "/a/x/examples/example_VgwPy6rwatl",
+                (StatusCode::OK, "12345"),
+            ),
+            (
+                "/examples/example_VgwPy6rwatx",
+                (StatusCode::NOT_FOUND, "Not Found"),
+            ),
+            (
+                "/examples/user_VgwPy6rwatl",
+                (StatusCode::NOT_FOUND, "Not Found"),
+            ),
+            ("/examples/12345", (StatusCode::NOT_FOUND, "Not Found")),
+            (
+                &format!("/examples/{}", zero),
+                (StatusCode::NOT_FOUND, "Not Found"),
+            ),
+            (
+                "/examples",
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error"),
+            ),
+        ] {
+            let (status, body) = status(&router, uri).await;
+            assert_eq!((status, body.as_str()), expected, "{}", uri);
+        }
+        assert_eq!(
+            IdRejection::from(Error::RateLimited).status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+}
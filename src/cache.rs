@@ -0,0 +1,184 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::{Codec, Error};
+
+/// A codec which memoizes successfully decoded tokens, for hot tokens such as a user ID
+/// appearing in every request.
+///
+/// The cache holds up to about `capacity` recently used tokens.  Only tokens which decoded
+/// successfully are cached, so invalid tokens are verified every time.  Note that cache hits
+/// bypass the limiter and hooks of the codec.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{CachedCodec, Codec, Config};
+///
+/// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+/// let cached = CachedCodec::new(codec, 1000);
+///
+/// assert_eq!(cached.decode("example_VgwPy6rwatl"), Ok(12345));
+/// assert_eq!(cached.decode("example_VgwPy6rwatl"), Ok(12345));
+/// assert_eq!(cached.stats().hits, 1);
+/// assert_eq!(cached.stats().hit_rate(), 0.5);
+/// ```
+pub struct CachedCodec {
+    capacity: usize,
+    codec: Codec,
+    generations: Mutex<Generations<String, u64>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+// Two generations of entries approximate an LRU cache: when the current generation is full, it
+// becomes the previous one and the old previous generation is dropped.  Hits in the previous
+// generation are moved to the current one.
+pub(crate) struct Generations<K, V> {
+    current: HashMap<K, V>,
+    previous: HashMap<K, V>,
+}
+
+/// Counters of a `CachedCodec`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of decodes answered from the cache.
+    pub hits: u64,
+    /// Number of decodes passed to the codec.
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Returns the fraction of decodes answered from the cache, or 0 if there were none.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl CachedCodec {
+    /// Wraps `codec` with a cache of about `capacity` tokens.
+    pub fn new(codec: Codec, capacity: usize) -> Self {
+        CachedCodec {
+            capacity: capacity.div_ceil(2),
+            codec,
+            generations: Mutex::new(Generations::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the wrapped codec, e.g. for encoding.
+    pub fn codec(&self) -> &Codec {
+        &self.codec
+    }
+
+    /// Decodes `encoded` like `Codec::decode`, using the cache.
+    pub fn decode(&self, encoded: &str) -> Result<u64, Error> {
+        if let Some(num) = self.lookup(encoded) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(num);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let num = self.codec.decode(encoded)?;
+        if self.capacity > 0 {
+            self.insert(encoded.to_string(), num);
+        }
+        Ok(num)
+    }
+
+    /// Returns the hit and miss counters.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lookup(&self, encoded: &str) -> Option<u64> {
+        let mut generations = self.generations.lock().unwrap();
+        generations.get(self.capacity, encoded)
+    }
+
+    fn insert(&self, encoded: String, num: u64) {
+        let mut generations = self.generations.lock().unwrap();
+        generations.insert(self.capacity, encoded, num);
+    }
+}
+
+impl<K, V> Default for Generations<K, V> {
+    fn default() -> Self {
+        Generations {
+            current: HashMap::new(),
+            previous: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> Generations<K, V> {
+    // Returns the value of `key`, moving it to the current generation.
+    pub(crate) fn get<Q>(&mut self, capacity: usize, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(value) = self.current.get(key) {
+            return Some(value.clone());
+        }
+        let (key, value) = self.previous.remove_entry(key)?;
+        self.insert(capacity, key, value.clone());
+        Some(value)
+    }
+
+    // Inserts an entry into the current generation, which holds up to `capacity` entries.
+    pub(crate) fn insert(&mut self, capacity: usize, key: K, value: V) {
+        if self.current.len() >= capacity {
+            self.previous = mem::take(&mut self.current);
+        }
+        self.current.insert(key, value);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.current.len() + self.previous.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn test_cache() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        let tokens: Vec<String> = (0..10).map(|num| codec.encode(num)).collect();
+        let cached = CachedCodec::new(codec, 4);
+
+        for (num, token) in tokens.iter().enumerate() {
+            assert_eq!(cached.decode(token), Ok(num as u64));
+            assert_eq!(cached.decode(&tokens[0]), Ok(0));
+        }
+        assert_eq!(
+            cached.stats(),
+            CacheStats {
+                hits: 10,
+                misses: 10
+            }
+        );
+
+        // Only recently used tokens are kept.
+        assert_eq!(cached.decode(&tokens[1]), Ok(1));
+        assert_eq!(cached.stats().misses, 11);
+
+        // Failures are not cached.
+        assert_eq!(cached.decode("test_hHLBCl4rZ3v"), Err(Error::IncorrectMAC));
+        assert_eq!(cached.decode("test_hHLBCl4rZ3v"), Err(Error::IncorrectMAC));
+        assert_eq!(cached.stats().misses, 13);
+    }
+}
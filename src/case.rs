@@ -0,0 +1,61 @@
+/// Casing of the type prefix in encoded tokens.
+///
+/// The casing only affects how the prefix is rendered on encode.  Keys are always derived
+/// from the codec name as given, and decoding accepts the prefix in any of the casings, so
+/// services using different casings for the same type can read each other's tokens.
+///
+/// The separator between the prefix and the encrypted payload is always an underscore.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PrefixCase {
+    /// The name as given, e.g. `user_account_hHLBCl4rZ3u`.
+    #[default]
+    Snake,
+    /// Underscores in the name replaced with hyphens, e.g. `user-account_hHLBCl4rZ3u`.
+    Kebab,
+    /// The name in upper case, e.g. `USER_ACCOUNT_hHLBCl4rZ3u`.
+    Screaming,
+}
+
+impl PrefixCase {
+    const ALL: [PrefixCase; 3] = [PrefixCase::Snake, PrefixCase::Kebab, PrefixCase::Screaming];
+
+    /// Renders `name` in this casing.
+    pub fn apply(self, name: &str) -> String {
+        name.chars().map(|c| self.map_char(c)).collect()
+    }
+
+    /// Returns `true` if `candidate` is `name` rendered in any of the casings.
+    pub(crate) fn matches_any(name: &str, candidate: &str) -> bool {
+        Self::ALL.iter().any(|case| {
+            name.len() == candidate.len()
+                && name.chars().map(|c| case.map_char(c)).eq(candidate.chars())
+        })
+    }
+
+    fn map_char(self, c: char) -> char {
+        match (self, c) {
+            (PrefixCase::Kebab, '_') => '-',
+            (PrefixCase::Screaming, c) => c.to_ascii_uppercase(),
+            (_, c) => c,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_casing() {
+        assert_eq!(PrefixCase::Snake.apply("user_account"), "user_account");
+        assert_eq!(PrefixCase::Kebab.apply("user_account"), "user-account");
+        assert_eq!(PrefixCase::Screaming.apply("user_account"), "USER_ACCOUNT");
+
+        assert!(PrefixCase::matches_any("user_account", "user_account"));
+        assert!(PrefixCase::matches_any("user_account", "user-account"));
+        assert!(PrefixCase::matches_any("user_account", "USER_ACCOUNT"));
+        assert!(!PrefixCase::matches_any("user_account", "USER-ACCOUNT"));
+        assert!(!PrefixCase::matches_any("user_account", "User_Account"));
+        assert!(!PrefixCase::matches_any("user", "users"));
+    }
+}
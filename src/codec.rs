@@ -1,14 +1,25 @@
 use std::fmt;
+use std::ops::Range;
+use std::sync::{Arc, Once};
 
 use aes::Aes256;
-use base62;
 use fpe::ff1::{BinaryNumeralString, FF1};
-use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
+use once_cell::sync::OnceCell;
 use sha2::Sha256;
 use uuid::Uuid;
+use zeroize::Zeroizing;
 
-use crate::Config;
+use crate::fingerprint::{all_fingerprints, fingerprint, is_other_fingerprint, Fingerprint};
+use crate::hkdf::Hkdf;
+use crate::order::OrderPreserving;
+use crate::radix;
+#[cfg(feature = "metrics")]
+use crate::telemetry;
+use crate::PrefixCase;
+use crate::{
+    Alphabet, CodecHook, CodecRegistry, Config, ConfigError, DecodeLimiter, Envelope, NegativeIds,
+};
 
 type HmacSha256 = Hmac<Sha256>;
 
@@ -17,11 +28,41 @@ type HmacSha256 = Hmac<Sha256>;
 pub enum Error {
     DecodingFailed,
     DecryptionFailed,
+    /// The token was an empty string, e.g. an unset optional ID sent as `""`.
+    EmptyToken,
     EncryptionFailed,
+    Forbidden,
+    /// The ID was outside the range of `Config::allowed_range`, so it was not encoded, or it
+    /// doesn't fit in the integer type of a field, or a range of IDs doesn't fit in `u64`.
+    IdOutOfRange,
     IncorrectMAC,
     InvalidDataLength,
-    InvalidPrefix { received: String, expected: String },
-    SentinelMismatch { received: u8, expected: u8 },
+    InvalidPrefix {
+        received: String,
+        expected: Arc<str>,
+    },
+    InvalidString,
+    /// The token exceeded the `Limits` of `Codec::decode_with_limits`.
+    LimitExceeded,
+    NegativeId,
+    ParameterMismatch,
+    PayloadTooLong {
+        max_chars: usize,
+    },
+    RateLimited,
+    Rejected,
+    ReservedId,
+    SentinelMismatch {
+        received: u8,
+        expected: u8,
+    },
+    /// The token looks like a valid token of another type, i.e. it has another valid prefix.
+    /// `hint` is the name of the type it decodes with, if the config has `type_hints`.
+    WrongType {
+        received_prefix: String,
+        expected: Arc<str>,
+        hint: Option<String>,
+    },
 }
 
 impl fmt::Display for Error {
@@ -33,46 +74,324 @@ impl fmt::Display for Error {
             Error::DecryptionFailed => {
                 write!(f, "FF1 decryption failed")
             }
+            Error::EmptyToken => {
+                write!(f, "Token is empty")
+            }
             Error::EncryptionFailed => {
                 write!(f, "FF1 encryption failed")
             }
+            Error::Forbidden => {
+                write!(f, "Access was denied by the access policy")
+            }
+            Error::IdOutOfRange => {
+                write!(f, "ID is outside the allowed range")
+            }
             Error::IncorrectMAC => {
                 write!(f, "Incorrect MAC")
             }
             Error::InvalidDataLength => {
                 write!(f, "Invalid data length")
             }
+            Error::InvalidString => {
+                write!(
+                    f,
+                    "String must be 1 to {} printable ASCII characters",
+                    MAX_STRING_LENGTH
+                )
+            }
+            Error::LimitExceeded => {
+                write!(f, "Token exceeded the limits of decoding")
+            }
+            Error::NegativeId => {
+                write!(f, "ID is out of the allowed range")
+            }
+            Error::ParameterMismatch => {
+                write!(f, "Token was created with different parameters")
+            }
+            Error::PayloadTooLong { max_chars } => {
+                write!(
+                    f,
+                    "Token payload is too long, at most {} characters",
+                    max_chars
+                )
+            }
+            Error::RateLimited => {
+                write!(f, "Too many decode attempts")
+            }
+            Error::Rejected => {
+                write!(f, "Token was rejected by a hook")
+            }
+            Error::ReservedId => {
+                write!(f, "ID is reserved and never refers to an object")
+            }
             Error::SentinelMismatch { received, expected } => {
                 write!(f, "Sentinel byte was {}, expected {}", received, expected)
             }
             Error::InvalidPrefix { received, expected } => {
                 write!(f, "Prefix was {}, expected {}", received, expected)
             }
+            Error::WrongType {
+                received_prefix,
+                expected,
+                hint,
+            } => {
+                write!(
+                    f,
+                    "Token has prefix {}, expected {}",
+                    received_prefix, expected
+                )?;
+                match hint {
+                    Some(name) => write!(f, "; this looks like a `{}` ID", name),
+                    None => Ok(()),
+                }
+            }
         }
     }
 }
 
+#[cfg(feature = "base62")]
 impl From<base62::DecodeError> for Error {
-    fn from(_: base62::DecodeError) -> Error {
-        Error::DecodingFailed
+    fn from(error: base62::DecodeError) -> Error {
+        match error {
+            base62::DecodeError::ArithmeticOverflow => Error::PayloadTooLong {
+                max_chars: crate::radix::U128_DIGITS,
+            },
+            _ => Error::DecodingFailed,
+        }
     }
 }
 
 impl std::error::Error for Error {}
 
+/// Numeric error codes returned by `Codec::decode_raw`, for C and other foreign callers.
+///
+/// The values are stable and will not change.  There is one code for each `Error` variant,
+/// and `Panic` for unexpected panics, e.g. in a `CodecHook`.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    DecodingFailed = 1,
+    DecryptionFailed = 2,
+    EncryptionFailed = 3,
+    IncorrectMAC = 4,
+    InvalidDataLength = 5,
+    InvalidPrefix = 6,
+    InvalidString = 7,
+    NegativeId = 8,
+    ParameterMismatch = 9,
+    PayloadTooLong = 10,
+    RateLimited = 11,
+    Rejected = 12,
+    SentinelMismatch = 13,
+    /// The token was not valid UTF-8.
+    InvalidUtf8 = 14,
+    Panic = 15,
+    Forbidden = 16,
+    ReservedId = 17,
+    WrongType = 18,
+    LimitExceeded = 19,
+    EmptyToken = 20,
+    IdOutOfRange = 21,
+}
+
+impl From<&Error> for ErrorCode {
+    fn from(error: &Error) -> ErrorCode {
+        match error {
+            Error::DecodingFailed => ErrorCode::DecodingFailed,
+            Error::DecryptionFailed => ErrorCode::DecryptionFailed,
+            Error::EmptyToken => ErrorCode::EmptyToken,
+            Error::EncryptionFailed => ErrorCode::EncryptionFailed,
+            Error::Forbidden => ErrorCode::Forbidden,
+            Error::IdOutOfRange => ErrorCode::IdOutOfRange,
+            Error::IncorrectMAC => ErrorCode::IncorrectMAC,
+            Error::InvalidDataLength => ErrorCode::InvalidDataLength,
+            Error::InvalidPrefix { .. } => ErrorCode::InvalidPrefix,
+            Error::InvalidString => ErrorCode::InvalidString,
+            Error::LimitExceeded => ErrorCode::LimitExceeded,
+            Error::NegativeId => ErrorCode::NegativeId,
+            Error::ParameterMismatch => ErrorCode::ParameterMismatch,
+            Error::PayloadTooLong { .. } => ErrorCode::PayloadTooLong,
+            Error::RateLimited => ErrorCode::RateLimited,
+            Error::Rejected => ErrorCode::Rejected,
+            Error::ReservedId => ErrorCode::ReservedId,
+            Error::SentinelMismatch { .. } => ErrorCode::SentinelMismatch,
+            Error::WrongType { .. } => ErrorCode::WrongType,
+        }
+    }
+}
+
+/// The form in which a token was received, returned by `Codec::decode_with_form`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenForm {
+    /// The token had the type prefix.
+    Prefixed,
+    /// The prefix was missing, which is only accepted with `Codec::accept_unprefixed`.
+    Unprefixed,
+}
+
+/// The layout of the IDs in the plaintext of composite tokens, see
+/// `Codec::encode_composite_with`.  `Codec::decode_composite` accepts both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompositeLayout {
+    /// Each ID as a varint of 7 bits per byte, which suits IDs of different sizes.  This is
+    /// the layout of `Codec::encode_composite`.
+    Varint,
+    /// All IDs with the same number of bits, rounded up to a multiple of 4, which is shorter
+    /// for several small IDs of about the same size.
+    Packed,
+}
+
+/// Bounds on the cost of `Codec::decode_with_limits`, for decoding untrusted input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum length of the token in bytes, e.g. `Codec::max_encoded_len`.
+    pub max_len: usize,
+    /// The maximum number of keys to verify the token with.  Each one is an HMAC and an FF1
+    /// decryption, which dominate the cost of decoding.  One suffices without key rotation,
+    /// tokens of a codec with `Config::previous_key` may take two, and naming the type of a
+    /// token of another type with `Config::type_hints` up to two more.
+    pub max_work: usize,
+}
+
+// The number of keys a decode may still verify a token with.
+struct Work(usize);
+
+impl Work {
+    fn unlimited() -> Work {
+        Work(usize::MAX)
+    }
+
+    // Accounts for verifying with one key, failing if the limit has been reached.
+    fn spend(&mut self) -> Result<(), Error> {
+        self.0 = self.0.checked_sub(1).ok_or(Error::LimitExceeded)?;
+        Ok(())
+    }
+}
+
+/// A token returned by `Codec::encode_parts`, with accessors for its components.
+///
+/// The prefix is the part before the last underscore, so splitting the token on the first
+/// underscore goes wrong for names containing underscores.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EncodedToken {
+    full: String,
+    prefix_length: usize,
+}
+
+impl EncodedToken {
+    /// Returns the whole token, as returned by `Codec::encode`.
+    pub fn full(&self) -> &str {
+        &self.full
+    }
+
+    /// Returns the prefix without the underscore, e.g. `example`.
+    pub fn prefix(&self) -> &str {
+        &self.full[..self.prefix_length]
+    }
+
+    /// Returns the payload after the underscore.
+    pub fn payload(&self) -> &str {
+        &self.full[self.prefix_length + 1..]
+    }
+
+    /// Returns the whole token, consuming `self`.
+    pub fn into_string(self) -> String {
+        self.full
+    }
+}
+
+impl fmt::Display for EncodedToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.full)
+    }
+}
+
+impl AsRef<str> for EncodedToken {
+    fn as_ref(&self) -> &str {
+        &self.full
+    }
+}
+
+impl From<EncodedToken> for String {
+    fn from(token: EncodedToken) -> String {
+        token.full
+    }
+}
+
 // Maximum number of bytes we can base62 encode (an u128).
 const MAX_BUFFER: usize = 16;
 
 // The sentinel byte, in case we don't fill the full 16 bytes.
 const SENTINEL: u8 = 1;
 
+/// Maximum length of strings encoded with `Codec::encode_str`.
+pub const MAX_STRING_LENGTH: usize = 32;
+
+/// Maximum number of IDs in tokens encoded with `Codec::encode_composite`.
+pub const MAX_COMPOSITE_PARTS: usize = 4;
+
+// Marks the packed layout in the first byte of composite plaintexts, which is otherwise the
+// number of IDs.  The number of IDs less one and the width of the IDs in nibbles less one are
+// in bits 4-5 and 0-3, so there can be at most 4 IDs.
+const PACKED_COMPOSITE: u8 = 0x80;
+
+// Separates encrypted strings from other token formats.
+const STRING_TWEAK: &[u8] = b"cryptid/string";
+
+// Separates 128 bit tokens from other token formats.
+const U128_TWEAK: &[u8] = b"cryptid/u128";
+
+// Separates UUID primary key tokens from other token formats.
+const UUID_PK_TWEAK: &[u8] = b"cryptid/uuid-pk";
+
+// Separates range tokens from other token formats.
+const RANGE_TWEAK: &[u8] = b"cryptid/range";
+
+// Separates composite tokens from other token formats.
+const COMPOSITE_TWEAK: &[u8] = b"cryptid/composite";
+
+// Separates scoped tokens from other token formats.  The scope is appended to it.
+const SCOPE_TWEAK: &[u8] = b"cryptid/scope";
+
+// Separates tokens with a nonce, which is appended to it.
+const NONCE_TWEAK: &[u8] = b"cryptid/nonce";
+
+// Minimum plaintext length, to satisfy the FF1 minimum domain size.
+const MIN_PLAINTEXT: usize = 3;
+
+// Characters trimmed from the end of tokens with `Codec::trim_debris`.  None of them can
+// occur in a payload.
+const URL_DEBRIS: &[char] = &['/', '?', '.', ')'];
+
 /// Core encoder/decoder.
 pub struct Codec {
+    accept_unprefixed: bool,
+    allowed_range: Option<Range<u64>>,
+    alphabet: Alphabet,
+    #[cfg(feature = "metrics")]
+    counters: telemetry::Counters,
     ff1: FF1<Aes256>,
+    fingerprint: Option<Fingerprint>,
+    // The fingerprints of all parameter sets with the alphabet, computed on the first token
+    // which fails to verify with parameter binding.
+    fingerprints: OnceCell<Vec<Vec<Fingerprint>>>,
     hmac: HmacSha256,
     hmac_length: usize,
-    prefix: String,
-    zero_pad_length: usize,
+    hooks: Vec<Arc<dyn CodecHook>>,
+    key_version: u8,
+    limiter: Option<Arc<DecodeLimiter>>,
+    max_payload_length: usize,
+    name: Arc<str>,
+    // The number of digits of the nonce of tokens.
+    nonce_length: usize,
+    order: Option<OrderPreserving>,
+    prefix: Arc<str>,
+    // Codecs of the previous keys of the config.
+    previous: Vec<Codec>,
+    trim_debris: bool,
+    type_hints: Option<Arc<CodecRegistry>>,
+    type_id: Option<u16>,
+    pub(crate) zero_pad_length: usize,
 }
 
 impl Codec {
@@ -93,6 +412,15 @@ impl Codec {
     ///
     /// A new instance of `Codec`.
     ///
+    /// Names which are not valid prefixes (see `try_new`), such as `user.account`, are still
+    /// accepted for compatibility with earlier versions, but they are deprecated: a warning is
+    /// printed to stderr, and they will be rejected in a future version.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tokens don't fit in the target of `config`, or if `config` is
+    /// order-preserving without a MAC.  See `try_new`.
+    ///
     /// # Examples
     ///
     /// ```
@@ -101,22 +429,232 @@ impl Codec {
     /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
     /// ```
     pub fn new(name: &str, config: &Config) -> Codec {
-        let hkdf = Hkdf::<Sha256>::new(None, config.key);
-        let mut ff1_key = [0u8; 32];
-        let mut hmac_key = [0u8; 32];
-        hkdf.expand(format!("{}/ff1", name).as_bytes(), &mut ff1_key)
-            .expect("Length 32 should be valid");
-        hkdf.expand(format!("{}/hmac", name).as_bytes(), &mut hmac_key)
-            .expect("Length 32 should be valid");
+        match Self::try_new_lenient(name, config) {
+            Ok(codec) => codec,
+            Err(ConfigError::UnsatisfiableTarget) => {
+                panic!("Tokens of codec {:?} don't fit in the target", name)
+            }
+            Err(ConfigError::InvalidMacLength) => {
+                panic!("Order-preserving codec {:?} needs an HMAC", name)
+            }
+            Err(error) => panic!("Invalid config for codec {:?}: {:?}", name, error),
+        }
+    }
+
+    /// Like `new`, but returns `ConfigError::InvalidPrefix` if `name` is not a valid prefix,
+    /// `ConfigError::UnsatisfiableTarget` if the tokens don't fit in the target of `config`
+    /// (see `Config::target`), and `ConfigError::InvalidMacLength` if `config` is
+    /// order-preserving with `hmac_length` 0.
+    ///
+    /// A name must be nonempty and consist of ASCII letters, digits, underscores and hyphens,
+    /// so that tokens are ASCII and can be sliced at any byte offset.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config, ConfigError};
+    ///
+    /// let config = Config::new(b"your-secure-key");
+    /// assert!(Codec::try_new("user_account", &config).is_ok());
+    /// assert!(matches!(
+    ///     Codec::try_new("käyttäjä", &config),
+    ///     Err(ConfigError::InvalidPrefix)
+    /// ));
+    /// ```
+    pub fn try_new(name: &str, config: &Config) -> Result<Codec, ConfigError> {
+        if !is_valid_name(name) {
+            return Err(ConfigError::InvalidPrefix);
+        }
+        Self::try_new_lenient(name, config)
+    }
+
+    /// Like `try_new`, but accepts the names of earlier versions which are not valid prefixes,
+    /// warning about them once per process.
+    pub(crate) fn try_new_lenient(name: &str, config: &Config) -> Result<Codec, ConfigError> {
+        if !is_valid_name(name) {
+            static WARNING: Once = Once::new();
+            WARNING.call_once(|| {
+                eprintln!(
+                    "cryptid: codec name {:?} is deprecated; names should consist of ASCII \
+                     letters, digits, underscores and hyphens",
+                    name
+                )
+            });
+        }
+        // Without a MAC, order-preserving tokens would be the raw IDs.
+        if config.order_preserving && config.hmac_length == 0 {
+            return Err(ConfigError::InvalidMacLength);
+        }
+        let codec = Self::create(name, config);
+        if let Some(target) = config.target {
+            if !target.allows_name(name) || codec.max_encoded_len() > target.max_length() {
+                return Err(ConfigError::UnsatisfiableTarget);
+            }
+        }
+        Ok(codec)
+    }
+
+    fn create(name: &str, config: &Config) -> Codec {
+        let hkdf = config.hkdf();
+        // The FF1 key followed by the HMAC key, zeroized once the codec has its own copies.
+        let mut keys = Zeroizing::new([0u8; 64]);
+        #[cfg(feature = "shared-keys")]
+        match &config.key_cache {
+            Some(cache) => cache.get_or_derive(&hkdf, name, &mut keys, |keys| {
+                derive_keys(&hkdf, name, keys)
+            }),
+            None => derive_keys(&hkdf, name, &mut keys),
+        }
+        #[cfg(not(feature = "shared-keys"))]
+        derive_keys(&hkdf, name, &mut keys);
+        let (ff1_key, hmac_key) = keys.split_at(32);
+        let order = config.order_preserving.then(|| {
+            OrderPreserving::new(&hkdf, name, config.hmac_length as usize, &config.alphabet)
+        });
+        let nonce_length = match &order {
+            Some(_) => 0,
+            None => config.nonce_length as usize,
+        };
         Codec {
-            ff1: FF1::<Aes256>::new(&ff1_key, 2).expect("Radix 2 should be valid"),
-            hmac: HmacSha256::new_from_slice(&hmac_key).expect("Key length 32 should be valid"),
+            accept_unprefixed: false,
+            allowed_range: config.allowed_range.clone(),
+            alphabet: config.alphabet.clone(),
+            #[cfg(feature = "metrics")]
+            counters: telemetry::Counters::default(),
+            ff1: FF1::<Aes256>::new(ff1_key, 2).expect("Radix 2 should be valid"),
+            fingerprint: config.bind_parameters.then(|| {
+                fingerprint(
+                    &config.alphabet,
+                    config.hmac_length as usize,
+                    config.zero_pad_length as usize,
+                    config.order_preserving,
+                    nonce_length,
+                )
+            }),
+            fingerprints: OnceCell::new(),
+            hmac: HmacSha256::new_from_slice(hmac_key).expect("Key length 32 should be valid"),
             hmac_length: config.hmac_length as usize,
-            prefix: format!("{}_", name),
+            hooks: Vec::new(),
+            key_version: config.key_version,
+            limiter: None,
+            max_payload_length: match &order {
+                Some(order) => order.width(),
+                None => max_payload_length(config.alphabet.radix(), config.hmac_length as usize),
+            } + (config.key_version != 0) as usize
+                + nonce_length,
+            name: name.into(),
+            nonce_length,
+            order,
+            prefix: format!("{}_", name).into(),
+            previous: config
+                .previous_keys
+                .iter()
+                .map(|(version, key)| Codec::create(name, &config.with_previous_key(*version, key)))
+                .collect(),
+            trim_debris: false,
+            type_hints: config.type_hints.as_ref().map(|hints| hints.0.clone()),
+            type_id: None,
             zero_pad_length: config.zero_pad_length as usize,
         }
     }
 
+    /// Makes the codec consult `limiter` before every decode attempt, failing with
+    /// `Error::RateLimited` when the limit has been exceeded.
+    pub fn with_limiter(mut self, limiter: Arc<DecodeLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Sets the type ID which identifies the codec in envelopes instead of the prefix.  See
+    /// `Envelope`.  The IDs are chosen by the application and must be unique among the codecs
+    /// of the services exchanging envelopes.
+    pub fn with_type_id(mut self, type_id: u16) -> Self {
+        self.type_id = Some(type_id);
+        self
+    }
+
+    /// Makes the codec also accept tokens with the prefix missing, as sent by some clients
+    /// which strip it.  The MAC is verified as usual.  Use `decode_with_form` to find out
+    /// which form was received, e.g. to log misbehaving clients.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config, TokenForm};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key")).accept_unprefixed(true);
+    ///
+    /// assert_eq!(codec.decode("VgwPy6rwatl"), Ok(12345));
+    /// assert_eq!(codec.decode_with_form("VgwPy6rwatl"), Ok((12345, TokenForm::Unprefixed)));
+    /// assert_eq!(codec.decode_with_form("example_VgwPy6rwatl"), Ok((12345, TokenForm::Prefixed)));
+    /// ```
+    pub fn accept_unprefixed(mut self, accept: bool) -> Self {
+        self.accept_unprefixed = accept;
+        self
+    }
+
+    /// Makes the codec ignore URL debris, i.e. trailing `/`, `?`, `.` and `)` characters, when
+    /// decoding.  Tokens copy-pasted from chats and Markdown often end with them.  Use
+    /// `decode_with_debris` to find out what was trimmed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key")).trim_debris(true);
+    ///
+    /// assert_eq!(codec.decode("example_VgwPy6rwatl)."), Ok(12345));
+    /// assert_eq!(codec.decode_with_debris("example_VgwPy6rwatl/?"), Ok((12345, "/?")));
+    /// ```
+    pub fn trim_debris(mut self, enabled: bool) -> Self {
+        self.trim_debris = enabled;
+        self
+    }
+
+    /// Installs `hook` to be called around `encode` and `decode`, after any hooks installed
+    /// earlier.  See `CodecHook`.
+    pub fn with_hook(mut self, hook: Arc<dyn CodecHook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Renders the token prefix in the given casing on encode.  Decoding accepts the prefix
+    /// in any casing regardless of this setting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config, PrefixCase};
+    ///
+    /// let config = Config::new(b"your-secure-key");
+    /// let codec = Codec::new("example", &config).with_prefix_case(PrefixCase::Screaming);
+    ///
+    /// assert_eq!(codec.encode(12345), "EXAMPLE_VgwPy6rwatl");
+    /// assert_eq!(codec.decode("example_VgwPy6rwatl"), Ok(12345));
+    /// ```
+    pub fn with_prefix_case(mut self, case: PrefixCase) -> Self {
+        self.prefix = format!("{}_", case.apply(&self.name)).into();
+        self
+    }
+
+    /// Returns the type ID set with `with_type_id`.
+    pub fn type_id(&self) -> Option<u16> {
+        self.type_id
+    }
+
+    /// Returns the name of the codec.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the encode and decode counts of the codec (`metrics` feature).  See
+    /// `telemetry::prefix_stats`.
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> telemetry::PrefixStats {
+        self.counters.stats(&self.name)
+    }
+
     /// Encodes a given numeric value into a secure string representation.
     ///
     /// This method applies format-preserving encryption to the number and
@@ -132,6 +670,10 @@ impl Codec {
     /// A `Result` which is `Ok` containing the encoded string if successful,
     /// or an `Error` if encoding fails.
     ///
+    /// # Panics
+    ///
+    /// Panics if `num` is outside `Config::allowed_range`.  See `try_encode`.
+    ///
     /// # Examples
     ///
     /// ```
@@ -143,17 +685,280 @@ impl Codec {
     /// assert_eq!(encoded, "example_VgwPy6rwatl");
     /// ```
     pub fn encode(&self, num: u64) -> String {
-        let encoded = base62::encode(self.encode_u128(num));
-        format!("{}{}", self.prefix, encoded)
+        let mut encoded = String::new();
+        self.encode_into(num, &mut encoded);
+        encoded
+    }
+
+    /// Like `encode`, but returns `Error::IdOutOfRange` if `num` is outside
+    /// `Config::allowed_range`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config, Error};
+    ///
+    /// let config = Config::new(b"your-secure-key").allowed_range(1..1_000_000);
+    /// let codec = Codec::new("example", &config);
+    ///
+    /// assert_eq!(codec.try_encode(12345).unwrap(), "example_VgwPy6rwatl");
+    /// assert_eq!(codec.try_encode(1_700_000_000), Err(Error::IdOutOfRange));
+    /// ```
+    pub fn try_encode(&self, num: u64) -> Result<String, Error> {
+        self.check_allowed(num)?;
+        Ok(self.encode(num))
+    }
+
+    /// Returns `Error::IdOutOfRange` if `num` is outside `Config::allowed_range`.
+    pub(crate) fn check_allowed(&self, num: u64) -> Result<(), Error> {
+        match &self.allowed_range {
+            Some(range) if !range.contains(&num) => Err(Error::IdOutOfRange),
+            _ => Ok(()),
+        }
+    }
+
+    // Panics if `num` is outside `Config::allowed_range`, for the infallible encode methods.
+    fn expect_allowed(&self, num: u64) {
+        if self.check_allowed(num).is_err() {
+            panic!(
+                "ID is outside the allowed range {:?} of codec {:?}",
+                self.allowed_range, self.name
+            );
+        }
+    }
+
+    /// Like `encode`, but writes the token into `out`, replacing its contents, so that the
+    /// buffer of `out` can be reused.
+    pub(crate) fn encode_into(&self, num: u64, out: &mut String) {
+        self.expect_allowed(num);
+        #[cfg(feature = "metrics")]
+        let start = std::time::Instant::now();
+        for hook in &self.hooks {
+            hook.before_encode(self, num);
+        }
+        out.clear();
+        out.reserve(self.max_encoded_len());
+        out.push_str(&self.prefix);
+        let nonce = self.random_nonce();
+        let mut tweak = [0; NONCE_TWEAK.len() + 4];
+        let value = match &self.order {
+            Some(order) => order.encode(num),
+            None => self.encrypt_u128(nonce_tweak(nonce, &mut tweak), num),
+        };
+        self.push_payload(out, nonce, value);
+        for hook in &self.hooks {
+            hook.after_encode(self, num, out);
+        }
+        #[cfg(feature = "metrics")]
+        telemetry::record_encode(&self.name, &self.counters, start.elapsed());
+    }
+
+    /// Like `encode`, but returns the token with accessors for the prefix and the payload, for
+    /// building URLs, file names and such from the components.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// let token = codec.encode_parts(12345);
+    ///
+    /// assert_eq!(token.full(), "example_VgwPy6rwatl");
+    /// assert_eq!(token.prefix(), "example");
+    /// assert_eq!(token.payload(), "VgwPy6rwatl");
+    /// ```
+    pub fn encode_parts(&self, num: u64) -> EncodedToken {
+        EncodedToken {
+            full: self.encode(num),
+            prefix_length: self.prefix.len() - 1,
+        }
+    }
+
+    /// Returns the maximum length of tokens returned by `encode`, for preallocating buffers and
+    /// sizing e.g. `VARCHAR` columns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// assert_eq!(codec.max_encoded_len(), "example_".len() + 17);
+    /// assert!(codec.encode(u64::MAX).len() <= codec.max_encoded_len());
+    /// ```
+    pub fn max_encoded_len(&self) -> usize {
+        self.prefix.len() + self.max_payload_length
+    }
+
+    /// Returns the minimum length of tokens returned by `encode`.  With `max_encoded_len`, it
+    /// bounds the length of valid tokens, e.g. for validation rules of API schemas and forms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// assert_eq!(codec.min_encoded_len(), "example_".len() + 11);
+    /// assert!(codec.encode(0).len() >= codec.min_encoded_len());
+    /// ```
+    pub fn min_encoded_len(&self) -> usize {
+        let payload_length = match &self.order {
+            Some(order) => order.width(),
+            None => min_payload_length(&self.alphabet, self.zero_pad_length, self.hmac_length),
+        };
+        self.prefix.len() + (self.key_version != 0) as usize + self.nonce_length + payload_length
+    }
+
+    /// Encodes `num` into a fixed 16 byte binary form, for binary protocols which don't need
+    /// a string representation.
+    ///
+    /// The bytes are the big-endian representation of the same 128 bit value which `encode`
+    /// renders as base62, so they carry the same MAC.  The type prefix is not included.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// let bytes = codec.encode_bytes16(12345);
+    ///
+    /// assert_eq!(codec.decode_bytes16(bytes).unwrap(), 12345);
+    /// ```
+    pub fn encode_bytes16(&self, num: u64) -> [u8; 16] {
+        self.encode_value(num).to_be_bytes()
+    }
+
+    /// Returns the bucket of `num` among `buckets` buckets, in `0..buckets`, for grouping IDs
+    /// in public metrics and dashboards without exposing them.
+    ///
+    /// The bucket is derived from the deterministic ciphertext of `num`, which is part of its
+    /// token without a nonce, so it is stable for a key and spreads IDs evenly.  Being one of
+    /// `buckets` values, it leaks at most `log2(buckets)` bits about the token, and nothing
+    /// about the raw ID which the token doesn't already reveal.
+    ///
+    /// The ciphertext is scaled to the range rather than reduced modulo `buckets`, so buckets
+    /// are nested: `bucket(num, k * n) / k == bucket(num, n)`, and a dashboard can change the
+    /// granularity without IDs moving between coarse buckets.
+    ///
+    /// With order-preserving tokens the buckets follow the order of the tokens, and thus of the
+    /// raw IDs.  The IDs are spread linearly over `Config::allowed_range` if it is set, and
+    /// otherwise by their magnitude, so that e.g. IDs up to 2^32 take half of the buckets
+    /// instead of all falling into the first one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buckets` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// let bucket = codec.bucket(12345, 16);
+    ///
+    /// assert!(bucket < 16);
+    /// assert_eq!(codec.bucket(12345, 64) / 4, bucket);
+    /// ```
+    pub fn bucket(&self, num: u64, buckets: u64) -> u64 {
+        assert!(buckets > 0, "The number of buckets should be positive");
+        // A fraction of 2^64, scaled to `buckets` by multiplying and shifting.
+        let fraction = match (&self.order, &self.allowed_range) {
+            // Rounded up, so that IDs at equal distances get equally many buckets.
+            (Some(_), Some(range)) if range.contains(&num) => {
+                let length = (range.end - range.start) as u128;
+                ((((num - range.start) as u128) << 64).div_ceil(length)) as u64
+            }
+            // The bits of a positive `f64` grow with its value, and those of 1 to 2^64 differ
+            // by 64 << 52, so that each power of two takes 1/64 of the fraction.
+            (Some(_), _) => {
+                let magnitude = (num.max(1) as f64).to_bits() - 1f64.to_bits();
+                ((magnitude as u128) << 6).min(u64::MAX as u128) as u64
+            }
+            (None, _) => {
+                let ct = encrypt_number(
+                    &self.ff1,
+                    &self.hmac,
+                    0,
+                    None,
+                    &[],
+                    self.zero_pad_length,
+                    num,
+                );
+                le_vec_to_num(&ct) << (64 - 8 * ct.len())
+            }
+        };
+        ((fraction as u128 * buckets as u128) >> 64) as u64
+    }
+
+    /// Returns the token of `num` with the nonce digits zero, which is the token `encode` returns
+    /// without nonces, without calling the hooks.
+    pub(crate) fn encode_canonical(&self, num: u64) -> String {
+        let mut encoded = String::with_capacity(self.max_encoded_len());
+        encoded.push_str(&self.prefix);
+        self.push_payload(&mut encoded, 0, self.encode_value(num));
+        encoded
+    }
+
+    /// Writes the payload of the token of `num` to the start of `out`, returning its length.
+    pub(crate) fn write_payload(&self, num: u64, out: &mut [u8]) -> usize {
+        let mut start = 0;
+        if self.key_version != 0 {
+            out[0] = self.alphabet.digit(self.key_version) as u8;
+            start = 1;
+        }
+        let width = self.order.as_ref().map_or(0, |order| order.width());
+        start
+            + self
+                .alphabet
+                .write_u128(&mut out[start..], self.encode_value(num), width)
+    }
+
+    // Appends the payload rendering `value`, encrypted with `nonce`, to `out`.
+    fn push_payload(&self, out: &mut String, nonce: u32, value: u128) {
+        if self.key_version != 0 {
+            out.push(self.alphabet.digit(self.key_version));
+        }
+        if self.nonce_length != 0 {
+            self.alphabet
+                .push_u128(out, nonce as u128, self.nonce_length);
+        }
+        let width = self.order.as_ref().map_or(0, |order| order.width());
+        self.alphabet.push_u128(out, value, width);
+    }
+
+    // Returns a random nonce of `nonce_length` digits.
+    fn random_nonce(&self) -> u32 {
+        if self.nonce_length == 0 {
+            return 0;
+        }
+        let mut bytes = [0u8; 4];
+        getrandom::getrandom(&mut bytes).expect("System random number generator should work");
+        let radix = self.alphabet.radix() as u32;
+        u32::from_le_bytes(bytes) % radix.pow(self.nonce_length as u32)
+    }
+
+    /// Encrypts `num` into the 128 bit value which is rendered in the token.
+    fn encode_value(&self, num: u64) -> u128 {
+        self.expect_allowed(num);
+        match &self.order {
+            Some(order) => order.encode(num),
+            None => self.encrypt_u128(&[], num),
+        }
     }
 
     /// Encrypts `num` into a 128 bit value.  Note that high order bits may be zeroes,
     /// so that a short string representation can be made.
-    fn encode_u128(&self, num: u64) -> u128 {
+    fn encrypt_u128(&self, tweak: &[u8], num: u64) -> u128 {
         let bytes = encrypt_number(
             &self.ff1,
             &self.hmac,
             self.hmac_length,
+            self.fingerprint.as_ref(),
+            tweak,
             self.zero_pad_length,
             num,
         );
@@ -166,13 +971,68 @@ impl Codec {
     }
 
     /// Encrypts `num` into an UUID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num` is outside `Config::allowed_range`.
     pub fn encode_uuid(&self, num: u64) -> Uuid {
+        self.expect_allowed(num);
         // 8 bytes for hmac and 8 bytes for payload gets us a nice random 128 bit value.
-        let vec = encrypt_number(&self.ff1, &self.hmac, 8, 8, num);
+        let vec = encrypt_number(&self.ff1, &self.hmac, 8, None, &[], 8, num);
         let num = u128::from_le_bytes(vec.try_into().expect("Should have exactly 16 bytes"));
         Uuid::from_u128_le(num)
     }
 
+    /// Decodes an UUID produced by `encode_uuid` back into the original number, verifying
+    /// the MAC.
+    ///
+    /// Like with `decode`, the hooks are called with the hyphenated UUID, and UUIDs encoded
+    /// with a previous key of the config are accepted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config, Error};
+    /// use uuid::Uuid;
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// let uuid = codec.encode_uuid(12345);
+    ///
+    /// assert_eq!(codec.decode_uuid(uuid), Ok(12345));
+    /// assert_eq!(codec.decode_uuid(Uuid::nil()), Err(Error::IncorrectMAC));
+    /// ```
+    pub fn decode_uuid(&self, uuid: Uuid) -> Result<u64, Error> {
+        let mut buffer = Uuid::encode_buffer();
+        let encoded = uuid.hyphenated().encode_lower(&mut buffer);
+        for hook in &self.hooks {
+            hook.before_decode(self, encoded)?;
+        }
+        self.check_limiter()?;
+        // UUIDs have no version digit, so each key is tried in turn.
+        let num = std::iter::once(self)
+            .chain(&self.previous)
+            .find_map(|codec| codec.decode_uuid_number(uuid).ok())
+            .ok_or(Error::IncorrectMAC)?;
+        for hook in &self.hooks {
+            hook.after_decode(self, encoded, num)?;
+        }
+        Ok(num)
+    }
+
+    // Decodes a UUID with the key of this codec.
+    fn decode_uuid_number(&self, uuid: Uuid) -> Result<u64, Error> {
+        let bytes = uuid.to_u128_le().to_le_bytes();
+        let (encrypted, received_mac) = bytes.split_at(8);
+        if compute_mac(self, &[], encrypted)[..8] != *received_mac {
+            return Err(Error::IncorrectMAC);
+        }
+        let decrypted = self
+            .ff1
+            .decrypt(&[], &BinaryNumeralString::from_bytes_le(encrypted))
+            .map_err(|_| Error::DecryptionFailed)?;
+        Ok(le_vec_to_num(&decrypted.to_bytes_le()))
+    }
+
     /// Decodes a previously encoded string back into its original numeric value.
     ///
     /// This method first verifies the integrity of the encoded data using HMAC,
@@ -199,250 +1059,2470 @@ impl Codec {
     /// assert_eq!(decoded, 12345);
     /// ```
     pub fn decode(&self, encoded: &str) -> Result<u64, Error> {
-        // Ensure prefix matches (from last underscore).
-        let received = match encoded.rfind('_') {
-            None => "".to_string(),
-            Some(i) => encoded[..i + 1].to_string(),
-        };
-        if received != self.prefix {
-            let expected = self.prefix.clone();
-            return Err(Error::InvalidPrefix { received, expected });
-        }
-
-        let tail = &encoded[self.prefix.len()..];
-        let num = base62::decode(tail).map_err(Error::from)?;
-        let num_array = num.to_le_bytes();
+        self.decode_with_form(encoded).map(|(num, _)| num)
+    }
 
-        let length;
-        if self.hmac_length + self.zero_pad_length < MAX_BUFFER {
-            length = last_nonzero(&num_array);
-            if num_array[length] != SENTINEL {
-                return Err(Error::SentinelMismatch {
-                    received: num_array[length],
-                    expected: SENTINEL,
-                });
+    /// Decodes a token given as bytes, returning an `ErrorCode` on failure.
+    ///
+    /// This never unwinds, so it can be called directly from an `extern "C"` function: panics
+    /// are caught and returned as `ErrorCode::Panic`.  (With `panic = "abort"` a panic aborts
+    /// the process before it can be caught.)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config, ErrorCode};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// assert_eq!(codec.decode_raw(b"example_VgwPy6rwatl"), Ok(12345));
+    /// assert_eq!(codec.decode_raw(b"example_VgwPy6rwatm"), Err(ErrorCode::IncorrectMAC));
+    /// assert_eq!(ErrorCode::IncorrectMAC as i32, 4);
+    /// ```
+    pub fn decode_raw(&self, token: &[u8]) -> Result<u64, ErrorCode> {
+        let encoded = std::str::from_utf8(token).map_err(|_| ErrorCode::InvalidUtf8)?;
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.decode(encoded)))
+            .map_err(|_| ErrorCode::Panic)?
+            .map_err(|error| ErrorCode::from(&error))
+    }
+
+    /// Like `decode`, but also returns the characters trimmed from the end of `encoded` with
+    /// `trim_debris`, or an empty string if there were none.
+    pub fn decode_with_debris<'a>(&self, encoded: &'a str) -> Result<(u64, &'a str), Error> {
+        let token = self.without_debris(encoded);
+        let num = self.decode(token)?;
+        Ok((num, &encoded[token.len()..]))
+    }
+
+    /// Like `decode`, but also returns whether the token had the prefix.  Tokens without the
+    /// prefix are only accepted with `accept_unprefixed`.
+    pub fn decode_with_form(&self, encoded: &str) -> Result<(u64, TokenForm), Error> {
+        self.decode_metered(encoded, &mut Work::unlimited())
+    }
+
+    /// Like `decode`, but bounds the cost of decoding untrusted input: tokens longer than
+    /// `limits.max_len` are rejected before any other work, and decoding is abandoned before
+    /// verifying the token with more than `limits.max_work` keys.  Both fail with
+    /// `Error::LimitExceeded`.
+    ///
+    /// The limiter and the hooks of the codec are called like with `decode`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config, Error, Limits};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// let limits = Limits { max_len: codec.max_encoded_len(), max_work: 1 };
+    ///
+    /// assert_eq!(codec.decode_with_limits("example_VgwPy6rwatl", limits), Ok(12345));
+    /// let long = format!("example_{}", "x".repeat(1000));
+    /// assert_eq!(codec.decode_with_limits(&long, limits), Err(Error::LimitExceeded));
+    /// ```
+    pub fn decode_with_limits(&self, encoded: &str, limits: Limits) -> Result<u64, Error> {
+        if encoded.len() > limits.max_len {
+            return Err(Error::LimitExceeded);
+        }
+        self.decode_metered(encoded, &mut Work(limits.max_work))
+            .map(|(num, _)| num)
+    }
+
+    // Decodes `encoded`, recording metrics.
+    fn decode_metered(&self, encoded: &str, work: &mut Work) -> Result<(u64, TokenForm), Error> {
+        #[cfg(feature = "metrics")]
+        let start = std::time::Instant::now();
+        let encoded = self.without_debris(encoded);
+        let result = self.decode_hooked(encoded, work);
+        #[cfg(feature = "metrics")]
+        telemetry::record_decode(&self.name, &self.counters, start.elapsed(), &result);
+        result
+    }
+
+    // Decodes `encoded`, calling the hooks.
+    fn decode_hooked(&self, encoded: &str, work: &mut Work) -> Result<(u64, TokenForm), Error> {
+        for hook in &self.hooks {
+            hook.before_decode(self, encoded)?;
+        }
+        self.check_limiter()?;
+        let (num, form) = self.decode_with_work(encoded, work)?;
+        for hook in &self.hooks {
+            hook.after_decode(self, encoded, num)?;
+        }
+        Ok((num, form))
+    }
+
+    /// Decodes `encoded` without calling the hooks.
+    pub(crate) fn decode_token(&self, encoded: &str) -> Result<(u64, TokenForm), Error> {
+        self.check_limiter()?;
+        self.decode_unlimited(encoded)
+    }
+
+    /// Decodes `encoded` without calling the hooks or the limiter.
+    pub(crate) fn decode_unlimited(&self, encoded: &str) -> Result<(u64, TokenForm), Error> {
+        self.decode_with_work(encoded, &mut Work::unlimited())
+    }
+
+    // Decodes `encoded` with at most as many keys as `work` allows.
+    fn decode_with_work(&self, encoded: &str, work: &mut Work) -> Result<(u64, TokenForm), Error> {
+        let (tail, form) = self.strip_prefix(encoded, work)?;
+        Ok((self.decode_versioned(tail, work)?, form))
+    }
+
+    // Decodes a payload with the key of its version.  A payload of a versioned key starts with
+    // the version digit, and unversioned payloads are tried with the key of version 0.
+    fn decode_versioned(&self, tail: &str, work: &mut Work) -> Result<u64, Error> {
+        if self.key_version == 0 && self.previous.is_empty() {
+            work.spend()?;
+            return self.decode_number(tail);
+        }
+        let mut result = None;
+        if let Some(version) = tail
+            .bytes()
+            .next()
+            .and_then(|c| self.alphabet.digit_value(c))
+        {
+            if let Some(codec) = self.key(version).filter(|_| version != 0) {
+                work.spend()?;
+                // The digit is ASCII, so this is a character boundary.
+                match codec.decode_number(&tail[1..]) {
+                    Ok(num) => return Ok(num),
+                    Err(error) => result = Some(Err(error)),
+                }
+            }
+        }
+        match self.key(0) {
+            Some(codec) => {
+                work.spend()?;
+                codec.decode_number(tail)
+            }
+            None => result.unwrap_or_else(|| {
+                self.decode_digits(tail)?;
+                Err(Error::IncorrectMAC)
+            }),
+        }
+    }
+
+    // Returns the codec of the key with `version`.
+    fn key(&self, version: u8) -> Option<&Codec> {
+        if self.key_version == version {
+            return Some(self);
+        }
+        self.previous
+            .iter()
+            .find(|codec| codec.key_version == version)
+    }
+
+    // Decodes a payload without a version digit with the key of this codec.
+    fn decode_number(&self, tail: &str) -> Result<u64, Error> {
+        if let Some(order) = &self.order {
+            if tail.len() != order.width() {
+                return Err(Error::InvalidDataLength);
+            }
+        }
+        if self.nonce_length == 0 {
+            return self.decode_value(self.decode_digits(tail)?);
+        }
+        let (nonce, tail) = self.split_nonce(tail)?;
+        let mut tweak = [0; NONCE_TWEAK.len() + 4];
+        self.decrypt_u128(nonce_tweak(nonce, &mut tweak), self.decode_digits(tail)?)
+    }
+
+    // Splits the nonce digits from the start of a payload.
+    fn split_nonce<'a>(&self, tail: &'a str) -> Result<(u32, &'a str), Error> {
+        let (digits, tail) = tail
+            .split_at_checked(self.nonce_length)
+            .ok_or(Error::DecodingFailed)?;
+        let nonce = digits.bytes().try_fold(0u32, |nonce, c| {
+            let radix = self.alphabet.radix() as u32;
+            self.alphabet
+                .digit_value(c)
+                .map(|value| nonce * radix + value as u32)
+        });
+        Ok((nonce.ok_or(Error::DecodingFailed)?, tail))
+    }
+
+    // Decodes the digits of a payload, rejecting overlong payloads before parsing them.
+    fn decode_digits(&self, payload: &str) -> Result<u128, Error> {
+        let max_chars = self.alphabet.digit_count(u128::MAX);
+        if payload.len() > max_chars {
+            return Err(Error::PayloadTooLong { max_chars });
+        }
+        self.alphabet.decode_u128(payload)
+    }
+
+    /// Encodes a signed ID, such as a value of a `BIGINT` sequence, mapping negative IDs with
+    /// `negative_ids`.
+    ///
+    /// The token is that of the mapped `u64`, so it is the same as the token of a `Field<T>`
+    /// with the same `TypeMarker::negative_ids()`.  Returns `Error::NegativeId` for negative
+    /// IDs with `NegativeIds::Reject`, and `Error::IdOutOfRange` like `try_encode`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config, Error, NegativeIds};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// let encoded = codec.encode_i64(12345, NegativeIds::Reject).unwrap();
+    /// assert_eq!(encoded, "example_VgwPy6rwatl");
+    /// assert_eq!(codec.decode_i64(&encoded, NegativeIds::Reject), Ok(12345));
+    ///
+    /// assert_eq!(codec.encode_i64(-1, NegativeIds::Reject), Err(Error::NegativeId));
+    /// let encoded = codec.encode_i64(-1, NegativeIds::Reinterpret).unwrap();
+    /// assert_eq!(codec.decode_i64(&encoded, NegativeIds::Reinterpret), Ok(-1));
+    /// assert_eq!(codec.decode_i64(&encoded, NegativeIds::Reject), Err(Error::NegativeId));
+    /// ```
+    pub fn encode_i64(&self, num: i64, negative_ids: NegativeIds) -> Result<String, Error> {
+        self.try_encode(negative_ids.map_i64(num)?)
+    }
+
+    /// Decodes a token into a signed ID, mapping IDs back with `negative_ids`.  See
+    /// `encode_i64`.
+    pub fn decode_i64(&self, encoded: &str, negative_ids: NegativeIds) -> Result<i64, Error> {
+        negative_ids.unmap_i64(self.decode(encoded)?)
+    }
+
+    /// Encodes `num` into a token which only decodes with `decode_scoped` and the same
+    /// `scope`, such as the ID of the organization owning the object.
+    ///
+    /// A token leaked from one scope can't then be replayed in another, even if the
+    /// application forgets an ownership check.  The scope is mixed into the encryption and the
+    /// MAC, and it is not stored in the token.  Scoped tokens have the default layout, also
+    /// in order-preserving mode, and they are not versioned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num` is outside `Config::allowed_range`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config, Error};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// let org_id = 42;
+    /// let encoded = codec.encode_scoped(12345, org_id);
+    ///
+    /// assert_eq!(codec.decode_scoped(&encoded, org_id), Ok(12345));
+    /// assert_eq!(codec.decode_scoped(&encoded, 43), Err(Error::IncorrectMAC));
+    /// assert!(codec.decode(&encoded).is_err());
+    /// ```
+    pub fn encode_scoped(&self, num: u64, scope: u64) -> String {
+        self.expect_allowed(num);
+        let mut encoded = String::with_capacity(self.max_encoded_len());
+        encoded.push_str(&self.prefix);
+        self.alphabet
+            .push_u128(&mut encoded, self.encrypt_u128(&scope_tweak(scope), num), 0);
+        encoded
+    }
+
+    /// Decodes a token produced by `encode_scoped` with the same `scope`, verifying the MAC.
+    pub fn decode_scoped(&self, encoded: &str, scope: u64) -> Result<u64, Error> {
+        self.check_limiter()?;
+        let (tail, _) = self.strip_prefix(encoded, &mut Work::unlimited())?;
+        self.decrypt_u128(&scope_tweak(scope), self.decode_digits(tail)?)
+    }
+
+    /// Encrypts a short string, such as an invoice number, into a token.
+    ///
+    /// The string must consist of 1 to `MAX_STRING_LENGTH` printable ASCII characters
+    /// (no spaces or control characters), otherwise `Error::InvalidString` is returned.  The
+    /// token has the same prefix and MAC as numeric tokens, but it is a separate format that
+    /// can only be decoded with `decode_str`.  Note that the token length reveals the
+    /// approximate length of the string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("invoice", &Config::new(b"your-secure-key"));
+    /// let encoded = codec.encode_str("INV-2024-0042").unwrap();
+    ///
+    /// assert!(encoded.starts_with("invoice_"));
+    /// assert_eq!(codec.decode_str(&encoded).unwrap(), "INV-2024-0042");
+    /// ```
+    pub fn encode_str(&self, value: &str) -> Result<String, Error> {
+        if !is_valid_string(value) {
+            return Err(Error::InvalidString);
+        }
+        // Strings never contain zero bytes, so padding with them is unambiguous.
+        let mut plaintext = value.as_bytes().to_vec();
+        plaintext.resize(plaintext.len().max(MIN_PLAINTEXT), 0);
+        Ok(self.encode_payload(STRING_TWEAK, &plaintext))
+    }
+
+    /// Decodes a token produced by `encode_str` back into the string, verifying the MAC.
+    pub fn decode_str(&self, encoded: &str) -> Result<String, Error> {
+        let mut plaintext = self.decode_payload(STRING_TWEAK, MIN_PLAINTEXT, encoded)?;
+        while plaintext.last() == Some(&0) {
+            plaintext.pop();
+        }
+        match String::from_utf8(plaintext) {
+            Ok(value) if is_valid_string(&value) => Ok(value),
+            _ => Err(Error::InvalidString),
+        }
+    }
+
+    /// Encrypts a 128 bit ID, such as a Snowflake-style ID or the value of a UUID, into a
+    /// token.
+    ///
+    /// The token has the same prefix and MAC as numeric tokens, but it is a separate format
+    /// that can only be decoded with `decode_u128`, so `encode(5)` and `encode_u128(5)` are
+    /// different tokens.  Like with `encode_str`, the token is not versioned and doesn't have a
+    /// nonce or preserve order, and it is longer for larger IDs.  `Config::allowed_range`
+    /// restricts 64-bit IDs only, so it doesn't apply.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("event", &Config::new(b"your-secure-key"));
+    /// let id = 0x0190_7d3f_5a2b_7c4e_8f1a_2b3c_4d5e_6f70;
+    /// let encoded = codec.encode_u128(id);
+    ///
+    /// assert!(encoded.starts_with("event_"));
+    /// assert_eq!(codec.decode_u128(&encoded), Ok(id));
+    /// assert!(codec.decode(&encoded).is_err());
+    /// ```
+    pub fn encode_u128(&self, num: u128) -> String {
+        self.encode_wide(U128_TWEAK, num)
+    }
+
+    /// Decodes a token produced by `encode_u128` back into the ID, verifying the MAC.
+    pub fn decode_u128(&self, encoded: &str) -> Result<u128, Error> {
+        self.decode_wide(U128_TWEAK, encoded)
+    }
+
+    /// Encrypts an existing UUID primary key into a token, for tables whose keys are UUIDs
+    /// rather than integers.
+    ///
+    /// All 128 bits of the UUID are encrypted, so not even the version or the timestamp of a
+    /// time-ordered UUID is revealed.  Like `encode_u128`, this is a separate format which can
+    /// only be decoded with `decode_uuid_pk`.  Unlike `encode_uuid`, which encrypts an integer
+    /// ID into a UUID, the UUID is the plaintext here, so `Config::allowed_range` doesn't
+    /// apply.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    /// use uuid::Uuid;
+    ///
+    /// let codec = Codec::new("order", &Config::new(b"your-secure-key"));
+    /// let id = Uuid::parse_str("0190d2b4-7c5e-7a3b-9f1e-2d4c6b8a0e13").unwrap();
+    /// let encoded = codec.encode_uuid_pk(id);
+    ///
+    /// assert!(encoded.starts_with("order_"));
+    /// assert_eq!(codec.decode_uuid_pk(&encoded), Ok(id));
+    /// assert!(codec.decode_u128(&encoded).is_err());
+    /// ```
+    pub fn encode_uuid_pk(&self, uuid: Uuid) -> String {
+        self.encode_wide(UUID_PK_TWEAK, uuid.as_u128())
+    }
+
+    /// Decodes a token produced by `encode_uuid_pk` back into the UUID, verifying the MAC.
+    pub fn decode_uuid_pk(&self, encoded: &str) -> Result<Uuid, Error> {
+        self.decode_wide(UUID_PK_TWEAK, encoded)
+            .map(Uuid::from_u128)
+    }
+
+    /// Encrypts the range of `count` IDs from `start` into a single token, e.g. for handing
+    /// out blocks of IDs to clients without revealing the numbers.
+    ///
+    /// Like `encode_u128`, this is a separate format with the same prefix and MAC, which can
+    /// only be decoded with `decode_range`.  Returns `Error::IdOutOfRange` if the range
+    /// doesn't fit in `u64` or an ID of a non-empty range is outside `Config::allowed_range`.
+    /// An empty range is checked like its start.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("block", &Config::new(b"your-secure-key"));
+    /// let encoded = codec.encode_range(1000, 50).unwrap();
+    ///
+    /// assert!(encoded.starts_with("block_"));
+    /// assert_eq!(codec.decode_range(&encoded), Ok(1000..1050));
+    /// assert!(codec.decode(&encoded).is_err());
+    /// ```
+    pub fn encode_range(&self, start: u64, count: u64) -> Result<String, Error> {
+        start.checked_add(count).ok_or(Error::IdOutOfRange)?;
+        // The allowed range is contiguous, so checking the first and last IDs is enough.
+        self.check_allowed(start)?;
+        self.check_allowed(start + count.saturating_sub(1))?;
+        Ok(self.encode_wide(RANGE_TWEAK, (count as u128) << 64 | start as u128))
+    }
+
+    /// Decodes a token produced by `encode_range` back into the range, verifying the MAC.
+    pub fn decode_range(&self, encoded: &str) -> Result<Range<u64>, Error> {
+        let num = self.decode_wide(RANGE_TWEAK, encoded)?;
+        let (start, count) = (num as u64, (num >> 64) as u64);
+        let end = start.checked_add(count).ok_or(Error::IdOutOfRange)?;
+        Ok(start..end)
+    }
+
+    /// Encrypts several IDs, such as `(tenant_id, object_id)`, into a single token, so that
+    /// APIs don't need to expose them as separate IDs.
+    ///
+    /// Like `encode_u128`, this is a separate format with the same prefix and MAC, which can
+    /// only be decoded with `decode_composite`.  The token length grows with the size of the
+    /// IDs.  Returns `Error::InvalidDataLength` for more than `MAX_COMPOSITE_PARTS` IDs.  The
+    /// IDs may belong to different types, so `Config::allowed_range` doesn't apply to them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("doc", &Config::new(b"your-secure-key"));
+    /// let encoded = codec.encode_composite(&[42, 12345]).unwrap();
+    ///
+    /// assert!(encoded.starts_with("doc_"));
+    /// assert_eq!(codec.decode_composite(&encoded), Ok(vec![42, 12345]));
+    /// assert!(codec.decode(&encoded).is_err());
+    /// ```
+    pub fn encode_composite(&self, parts: &[u64]) -> Result<String, Error> {
+        self.encode_composite_with(parts, CompositeLayout::Varint)
+    }
+
+    /// Like `encode_composite`, but with the IDs in `layout`.  The layout is recorded in the
+    /// token, so `decode_composite` decodes either.  Use `composite_len` to compare the
+    /// lengths of the layouts for typical IDs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, CompositeLayout, Config};
+    ///
+    /// let codec = Codec::new("cell", &Config::new(b"your-secure-key"));
+    /// let parts = [3, 14, 15, 9];
+    /// let packed = codec.encode_composite_with(&parts, CompositeLayout::Packed).unwrap();
+    ///
+    /// assert!(packed.len() < codec.encode_composite(&parts).unwrap().len());
+    /// assert_eq!(codec.decode_composite(&packed), Ok(parts.to_vec()));
+    /// ```
+    pub fn encode_composite_with(
+        &self,
+        parts: &[u64],
+        layout: CompositeLayout,
+    ) -> Result<String, Error> {
+        let plaintext = self.composite_plaintext(parts, layout)?;
+        Ok(self.encode_payload(COMPOSITE_TWEAK, &plaintext))
+    }
+
+    /// Returns the maximum length of the token `encode_composite_with` returns for `parts`
+    /// and `layout`, without encrypting anything.  Tokens may be a character shorter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, CompositeLayout, Config};
+    ///
+    /// let codec = Codec::new("cell", &Config::new(b"your-secure-key"));
+    /// let parts = [3, 14, 15, 9];
+    /// for layout in [CompositeLayout::Varint, CompositeLayout::Packed] {
+    ///     let len = codec.composite_len(&parts, layout).unwrap();
+    ///     assert!(codec.encode_composite_with(&parts, layout).unwrap().len() <= len);
+    /// }
+    /// ```
+    pub fn composite_len(&self, parts: &[u64], layout: CompositeLayout) -> Result<usize, Error> {
+        let length = self.composite_plaintext(parts, layout)?.len() + self.hmac_length;
+        // The largest payload of this length, ending with the sentinel.
+        let mut bytes = vec![u8::MAX; length];
+        bytes.push(SENTINEL);
+        Ok(self.prefix.len() + self.alphabet.encode(&bytes).len())
+    }
+
+    /// Decodes a token produced by `encode_composite` or `encode_composite_with` back into
+    /// the IDs, verifying the MAC.
+    pub fn decode_composite(&self, encoded: &str) -> Result<Vec<u64>, Error> {
+        let min_length = self.zero_pad_length.max(MIN_PLAINTEXT);
+        let plaintext = self.decode_payload(COMPOSITE_TWEAK, min_length, encoded)?;
+        let (&header, rest) = plaintext.split_first().ok_or(Error::InvalidDataLength)?;
+        if header & PACKED_COMPOSITE != 0 {
+            return unpack_composite(header, rest);
+        }
+        let count = header as usize;
+        if count > MAX_COMPOSITE_PARTS {
+            return Err(Error::InvalidDataLength);
+        }
+        let mut rest = rest;
+        let mut parts = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut part = 0u64;
+            let mut shift = 0;
+            loop {
+                let (&byte, tail) = rest.split_first().ok_or(Error::InvalidDataLength)?;
+                rest = tail;
+                if shift > 63 || (shift == 63 && byte > 1) {
+                    return Err(Error::InvalidDataLength);
+                }
+                part |= u64::from(byte & 0x7f) << shift;
+                shift += 7;
+                if byte < 0x80 {
+                    break;
+                }
+            }
+            parts.push(part);
+        }
+        if rest.iter().any(|&b| b != 0) {
+            return Err(Error::InvalidDataLength);
+        }
+        Ok(parts)
+    }
+
+    // Returns the plaintext of a composite token, padded with zeros.
+    fn composite_plaintext(
+        &self,
+        parts: &[u64],
+        layout: CompositeLayout,
+    ) -> Result<Vec<u8>, Error> {
+        if parts.len() > MAX_COMPOSITE_PARTS {
+            return Err(Error::InvalidDataLength);
+        }
+        let mut plaintext = match layout {
+            // An empty list has nothing to pack.
+            CompositeLayout::Packed if !parts.is_empty() => pack_composite(parts),
+            _ => {
+                // The number of parts followed by each part as a LEB128 varint.
+                let mut plaintext = vec![parts.len() as u8];
+                for &part in parts {
+                    let mut rest = part;
+                    while rest >= 0x80 {
+                        plaintext.push(rest as u8 | 0x80);
+                        rest >>= 7;
+                    }
+                    plaintext.push(rest as u8);
+                }
+                plaintext
+            }
+        };
+        let min_length = self.zero_pad_length.max(MIN_PLAINTEXT);
+        plaintext.resize(plaintext.len().max(min_length), 0);
+        Ok(plaintext)
+    }
+
+    // Encrypts `num` with its trailing zero bytes left out.
+    fn encode_wide(&self, tweak: &[u8], num: u128) -> String {
+        let bytes = num.to_le_bytes();
+        let min_length = self.zero_pad_length.max(MIN_PLAINTEXT);
+        let length = (last_nonzero(&bytes) + 1).max(min_length);
+        self.encode_payload(tweak, &bytes[..length])
+    }
+
+    fn decode_wide(&self, tweak: &[u8], encoded: &str) -> Result<u128, Error> {
+        let min_length = self.zero_pad_length.max(MIN_PLAINTEXT);
+        let plaintext = self.decode_payload(tweak, min_length, encoded)?;
+        let mut bytes = [0u8; 16];
+        bytes
+            .get_mut(..plaintext.len())
+            .ok_or(Error::InvalidDataLength)?
+            .copy_from_slice(&plaintext);
+        Ok(u128::from_le_bytes(bytes))
+    }
+
+    /// Verifies `encoded` with this codec and re-encodes it with `target`, without exposing
+    /// the raw ID to the caller.
+    ///
+    /// This is meant for gateways translating between parameter sets, e.g. accepting tokens
+    /// with an 8 byte MAC from partners and emitting tokens with a 4 byte MAC internally.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let config = Config::new(b"your-secure-key");
+    /// let partner = Codec::new("example", &config.clone().hmac_length(8).unwrap());
+    /// let internal = Codec::new("example", &config);
+    ///
+    /// let token = partner.encode(12345);
+    /// assert_eq!(partner.reauthenticate(&token, &internal).unwrap(), "example_VgwPy6rwatl");
+    /// ```
+    pub fn reauthenticate(&self, encoded: &str, target: &Codec) -> Result<String, Error> {
+        self.decode(encoded).map(|num| target.encode(num))
+    }
+
+    /// Decodes a token without verifying its MAC, for incident response when tokens have been
+    /// mangled by some other system but the raw ID is still needed.
+    ///
+    /// **Security note:** The result is a best-effort guess.  Any string with the right prefix
+    /// decodes into some number, so the result must never be used for authorization or be
+    /// exposed to clients.  This is only available with the `forensics` feature, and it
+    /// bypasses the limiter and hooks of the codec.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// assert_eq!(codec.decode_unverified("example_VgwPy6rwatl"), Ok(12345));
+    /// ```
+    #[cfg(feature = "forensics")]
+    pub fn decode_unverified(&self, encoded: &str) -> Result<u64, Error> {
+        let mut work = Work::unlimited();
+        let (tail, _) = self.strip_prefix(encoded, &mut work)?;
+        // Valid tokens decode as usual.  This also picks the right layout for full-length
+        // tokens with an 8 byte MAC whose last byte happens to look like the sentinel.
+        if let Ok(num) = self.decode_versioned(tail, &mut work) {
+            return Ok(num);
+        }
+        let tail = match self.key_version {
+            0 => tail,
+            version => tail
+                .strip_prefix(self.alphabet.digit(version))
+                .ok_or(Error::DecodingFailed)?,
+        };
+        let (nonce, tail) = self.split_nonce(tail)?;
+        let num = self.decode_digits(tail)?;
+        if let Some(order) = &self.order {
+            return order.decode_unverified(num);
+        }
+        let num_array = num.to_le_bytes();
+        let length = match self.payload_length(&num_array) {
+            // With an 8 byte MAC, IDs of 8 bytes fill all 16 bytes without a sentinel.
+            Err(_) if self.hmac_length + 8 >= MAX_BUFFER => MAX_BUFFER,
+            result => result?,
+        };
+        let encrypted = length
+            .checked_sub(self.hmac_length)
+            .map(|length| &num_array[..length])
+            .ok_or(Error::InvalidDataLength)?;
+        let mut tweak = [0; NONCE_TWEAK.len() + 4];
+        let decrypted = self
+            .ff1
+            .decrypt(
+                nonce_tweak(nonce, &mut tweak),
+                &BinaryNumeralString::from_bytes_le(encrypted),
+            )
+            .map_err(|_| Error::DecryptionFailed)?
+            .to_bytes_le();
+        if decrypted.len() > 8 {
+            return Err(Error::InvalidDataLength);
+        }
+        Ok(le_vec_to_num(&decrypted))
+    }
+
+    /// Decodes the binary form produced by `encode_bytes16` back into the original number,
+    /// verifying the MAC.
+    pub fn decode_bytes16(&self, bytes: [u8; 16]) -> Result<u64, Error> {
+        self.check_limiter()?;
+        self.decode_value(u128::from_be_bytes(bytes))
+    }
+
+    /// Encodes `num` into an `Envelope`, for transport between internal services.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the codec has no type ID.  See `with_type_id`.
+    pub fn encode_envelope(&self, num: u64) -> Envelope {
+        Envelope {
+            type_id: self.type_id.expect("Codec should have a type ID"),
+            payload: self.encode_value(num),
+        }
+    }
+
+    /// Decodes an envelope produced by `encode_envelope` back into the original number,
+    /// verifying the type ID and the MAC.  Like `decode_bytes16`, this does not call the hooks.
+    pub fn decode_envelope(&self, envelope: &Envelope) -> Result<u64, Error> {
+        self.check_type_id(envelope)?;
+        self.check_limiter()?;
+        self.decode_value(envelope.payload)
+    }
+
+    /// Renders an envelope produced by `encode_envelope` into the token which `encode` returns
+    /// for the same number.  The MAC is not verified.
+    pub fn render_envelope(&self, envelope: &Envelope) -> Result<String, Error> {
+        self.check_type_id(envelope)?;
+        let mut encoded = String::with_capacity(self.max_encoded_len());
+        encoded.push_str(&self.prefix);
+        self.push_payload(&mut encoded, 0, envelope.payload);
+        Ok(encoded)
+    }
+
+    fn check_type_id(&self, envelope: &Envelope) -> Result<(), Error> {
+        if self.type_id != Some(envelope.type_id) {
+            return Err(Error::InvalidPrefix {
+                received: envelope.type_id.to_string(),
+                expected: self.prefix.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Encrypts and authenticates arbitrary `plaintext` into a token, for the token formats
+    /// which carry more than a single number.  Each format must use a distinct `tweak`.
+    ///
+    /// Unlike `encode`, the payload is not limited to 16 bytes and it always ends with a
+    /// sentinel byte.
+    pub(crate) fn encode_payload(&self, tweak: &[u8], plaintext: &[u8]) -> String {
+        let mut bytes = encrypt_bytes(
+            &self.ff1,
+            &self.hmac,
+            self.hmac_length,
+            self.fingerprint.as_ref(),
+            tweak,
+            plaintext,
+        );
+        bytes.push(SENTINEL);
+        format!("{}{}", self.prefix, self.alphabet.encode(&bytes))
+    }
+
+    /// Reverses `encode_payload`, verifying the MAC and requiring at least `min_length` bytes
+    /// of plaintext.
+    pub(crate) fn decode_payload(
+        &self,
+        tweak: &[u8],
+        min_length: usize,
+        encoded: &str,
+    ) -> Result<Vec<u8>, Error> {
+        self.check_limiter()?;
+        let (tail, _) = self.strip_prefix(encoded, &mut Work::unlimited())?;
+        let bytes = self.alphabet.decode(tail)?;
+        let length = last_nonzero(&bytes);
+        match bytes.get(length) {
+            Some(&SENTINEL) => decrypt_bytes(self, tweak, min_length, &bytes[..length]),
+            received => Err(Error::SentinelMismatch {
+                received: received.copied().unwrap_or(0),
+                expected: SENTINEL,
+            }),
+        }
+    }
+
+    /// Checks that `encoded` has the prefix of this codec, returning the rest of the string.
+    ///
+    /// Decode failures may be caused by attackers in volume, so this doesn't allocate except
+    /// for the received prefix in the error.
+    fn strip_prefix<'a>(
+        &self,
+        encoded: &'a str,
+        work: &mut Work,
+    ) -> Result<(&'a str, TokenForm), Error> {
+        if encoded.is_empty() {
+            return Err(Error::EmptyToken);
+        }
+        // Ensure prefix matches (from last underscore).  The whole received prefix is compared,
+        // so names which are suffixes of each other, like `user` and `power_user`, never accept
+        // each other's tokens.
+        let (received, tail) = match split_prefix(encoded) {
+            None if self.accept_unprefixed => return Ok((encoded, TokenForm::Unprefixed)),
+            None => ("", encoded),
+            Some(parts) => parts,
+        };
+        let matches = received == &*self.prefix
+            || received
+                .strip_suffix('_')
+                .is_some_and(|name| PrefixCase::matches_any(&self.name, name));
+        if !matches {
+            return Err(self.prefix_error(encoded, received, tail, work));
+        }
+        Ok((tail, TokenForm::Prefixed))
+    }
+
+    // Returns `WrongType` if `encoded` has a valid prefix of another type and a payload which
+    // could be a token, naming the type if it decodes with a codec of `type_hints`, or else
+    // `InvalidPrefix`.  Decoding with the other codec counts against `work`.
+    #[cold]
+    fn prefix_error(&self, encoded: &str, received: &str, tail: &str, work: &mut Work) -> Error {
+        let name = received.strip_suffix('_').unwrap_or(received);
+        if is_valid_name(name) && !tail.is_empty() && self.decode_digits(tail).is_ok() {
+            let mut hint = None;
+            if let Some(codec) = self.type_hints.as_ref().and_then(|hints| hints.get(name)) {
+                match codec.decode_with_work(encoded, work) {
+                    Ok(_) => hint = Some(codec.name().to_string()),
+                    Err(Error::LimitExceeded) => return Error::LimitExceeded,
+                    Err(_) => {}
+                }
             }
+            return Error::WrongType {
+                received_prefix: received.to_string(),
+                expected: self.prefix.clone(),
+                hint,
+            };
+        }
+        Error::InvalidPrefix {
+            received: received.to_string(),
+            expected: self.prefix.clone(),
+        }
+    }
+
+    fn without_debris<'a>(&self, encoded: &'a str) -> &'a str {
+        if self.trim_debris {
+            encoded.trim_end_matches(URL_DEBRIS)
         } else {
-            length = MAX_BUFFER;
+            encoded
+        }
+    }
+
+    fn check_limiter(&self) -> Result<(), Error> {
+        match &self.limiter {
+            Some(limiter) if !limiter.try_acquire() => Err(Error::RateLimited),
+            _ => Ok(()),
         }
+    }
+
+    /// Verifies and decrypts the 128 bit value rendered in a token.
+    fn decode_value(&self, num: u128) -> Result<u64, Error> {
+        if let Some(order) = &self.order {
+            return order.decode(num);
+        }
+        self.decrypt_u128(&[], num)
+    }
+
+    /// Verifies and decrypts a 128 bit value produced by `encrypt_u128` with `tweak`.
+    fn decrypt_u128(&self, tweak: &[u8], num: u128) -> Result<u64, Error> {
+        let num_array = num.to_le_bytes();
+        let result = self
+            .payload_length(&num_array)
+            .and_then(|length| decrypt_number(self, tweak, &num_array[..length]));
+        if result.is_err() && self.hmac_length + 8 >= MAX_BUFFER {
+            // With an 8 byte MAC, IDs of 8 bytes fill all 16 bytes without a sentinel.
+            if let Ok(num) = decrypt_number(self, tweak, &num_array) {
+                if num > u64::MAX >> 8 {
+                    return Ok(num);
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the length of the encrypted data and MAC in a rendered value, checking the
+    /// sentinel byte if there is one.
+    fn payload_length(&self, num_array: &[u8; MAX_BUFFER]) -> Result<usize, Error> {
+        if self.hmac_length + self.zero_pad_length >= MAX_BUFFER {
+            return Ok(MAX_BUFFER);
+        }
+        let length = last_nonzero(num_array);
+        if num_array[length] != SENTINEL {
+            return Err(Error::SentinelMismatch {
+                received: num_array[length],
+                expected: SENTINEL,
+            });
+        }
+        Ok(length)
+    }
+}
+
+pub(crate) fn is_valid_string(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_STRING_LENGTH
+        && value.bytes().all(|b| b.is_ascii_graphic())
+}
+
+// Returns whether `name` is a valid codec name.  See `Codec::try_new`.
+fn is_valid_name(name: &str) -> bool {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    !name.is_empty() && name.chars().all(valid)
+}
+
+// Returns the number of digits of the largest value with an 8 byte ciphertext, a MAC of
+// `hmac_length` bytes and the sentinel byte.
+pub(crate) const fn max_payload_length(radix: u8, hmac_length: usize) -> usize {
+    let bytes = 8 + hmac_length;
+    if bytes >= MAX_BUFFER {
+        return radix::digit_count(radix, u128::MAX);
+    }
+    radix::digit_count(radix, (2 << (8 * bytes)) - 1)
+}
+
+/// Returns the minimum number of digits of a payload with the plaintext padded to
+/// `zero_pad_length` bytes and `hmac_length` bytes of MAC.
+pub(crate) fn min_payload_length(
+    alphabet: &Alphabet,
+    zero_pad_length: usize,
+    hmac_length: usize,
+) -> usize {
+    let bytes = zero_pad_length.max(MIN_PLAINTEXT) + hmac_length;
+    if bytes >= MAX_BUFFER {
+        // There is no sentinel byte, so the value can be anything.
+        return 1;
+    }
+    // The sentinel byte follows the data.
+    alphabet.digit_count(1 << (8 * bytes))
+}
+
+fn last_nonzero(bytes: &[u8]) -> usize {
+    bytes.iter().rposition(|&b| b != 0).unwrap_or(0)
+}
+
+// Returns a memory representanion of `num` as a byte vector in little-endian byte
+// order, leaving out trailing zero bytes beyond `min_length`.
+pub(crate) fn num_to_le_vec(num: u64, min_length: usize) -> Vec<u8> {
+    let bytes = num.to_le_bytes();
+    bytes[..le_length(&bytes, min_length)].to_vec()
+}
+
+// Returns the length of the little-endian `bytes` without trailing zero bytes beyond
+// `min_length`.
+fn le_length(bytes: &[u8; 8], min_length: usize) -> usize {
+    (last_nonzero(bytes) + 1).max(min_length)
+}
+
+fn le_vec_to_num(bytes: &[u8]) -> u64 {
+    let mut arr = [0; 8];
+    arr[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(arr)
+}
+
+fn encrypt_number(
+    ff1: &FF1<Aes256>,
+    hmac: &HmacSha256,
+    hmac_length: usize,
+    fingerprint: Option<&Fingerprint>,
+    tweak: &[u8],
+    zero_pad_length: usize,
+    num: u64,
+) -> Vec<u8> {
+    let bytes = num.to_le_bytes();
+    let pt = &bytes[..le_length(&bytes, zero_pad_length.max(MIN_PLAINTEXT))];
+    encrypt_bytes(ff1, hmac, hmac_length, fingerprint, tweak, pt)
+}
+
+// Encrypts `pt` and appends a truncated MAC.  The `tweak` separates token formats from each
+// other: it is used as the FF1 tweak and prepended to the MAC input.  Plain numbers use an
+// empty tweak.  If a parameter `fingerprint` is given, it is XORed into the MAC.
+fn encrypt_bytes(
+    ff1: &FF1<Aes256>,
+    hmac: &HmacSha256,
+    hmac_length: usize,
+    fingerprint: Option<&Fingerprint>,
+    tweak: &[u8],
+    pt: &[u8],
+) -> Vec<u8> {
+    // Encrypt `pt` using form-preserving encryption.
+    let encrypted = ff1
+        .encrypt(tweak, &BinaryNumeralString::from_bytes_le(pt))
+        .expect("Radix 2 should be valid")
+        .to_bytes_le();
+
+    // Compute a truncated MAC from the ciphertext.
+    let mut hmac: HmacSha256 = hmac.clone();
+    hmac.update(tweak);
+    hmac.update(&encrypted);
+    let mut mac: [u8; 32] = hmac.finalize().into_bytes().into();
+    let truncated_mac = &mut mac[..hmac_length];
+    if let Some(fingerprint) = fingerprint {
+        xor_in_place(truncated_mac, fingerprint);
+    }
+
+    // Return the combined bytes.
+    let mut result = encrypted;
+    result.extend_from_slice(truncated_mac);
+
+    result
+}
+
+// Returns the tweak of payloads with `nonce`, written in `buf`.  Nonce 0 has the empty tweak of
+// payloads without a nonce, so that deterministic values, such as those of envelopes, render as
+// tokens with the nonce digits zero.
+fn nonce_tweak(nonce: u32, buf: &mut [u8; NONCE_TWEAK.len() + 4]) -> &[u8] {
+    if nonce == 0 {
+        return &[];
+    }
+    buf[..NONCE_TWEAK.len()].copy_from_slice(NONCE_TWEAK);
+    buf[NONCE_TWEAK.len()..].copy_from_slice(&nonce.to_le_bytes());
+    buf
+}
+
+/// Splits `encoded` after its last underscore into the prefix, including the underscore, and
+/// the payload, or returns `None` if there is no underscore.
+///
+/// The underscore is searched for in the bytes, and being ASCII, it can't be part of a
+/// multibyte character, so both parts are valid strings whatever the rest of the input is.
+pub(crate) fn split_prefix(encoded: &str) -> Option<(&str, &str)> {
+    let i = encoded.bytes().rposition(|b| b == b'_')?;
+    Some(encoded.split_at(i + 1))
+}
+
+// Packs 1 to `MAX_COMPOSITE_PARTS` IDs after a header byte, with the bits of each ID in order
+// from the least significant one.
+fn pack_composite(parts: &[u64]) -> Vec<u8> {
+    let bits = parts.iter().map(|part| 64 - part.leading_zeros()).max();
+    let nibbles = bits.unwrap_or(0).div_ceil(4).max(1) as usize;
+    let width = nibbles * 4;
+    let header = PACKED_COMPOSITE | ((parts.len() - 1) as u8) << 4 | (nibbles - 1) as u8;
+    let mut plaintext = vec![0; 1 + (parts.len() * width).div_ceil(8)];
+    plaintext[0] = header;
+    for (i, &part) in parts.iter().enumerate() {
+        for bit in (0..width).filter(|&bit| part >> bit & 1 == 1) {
+            let position = i * width + bit;
+            plaintext[1 + position / 8] |= 1 << (position % 8);
+        }
+    }
+    plaintext
+}
+
+// Reverses `pack_composite`, given the header byte and the rest of the plaintext.
+fn unpack_composite(header: u8, packed: &[u8]) -> Result<Vec<u64>, Error> {
+    if header & 0x40 != 0 {
+        return Err(Error::InvalidDataLength);
+    }
+    let count = (header >> 4 & 0x03) as usize + 1;
+    let width = ((header & 0x0f) as usize + 1) * 4;
+    let bits = count * width;
+    if packed.len() * 8 < bits {
+        return Err(Error::InvalidDataLength);
+    }
+    let is_set = |position: usize| packed[position / 8] >> (position % 8) & 1 == 1;
+    let parts = (0..count)
+        .map(|i| {
+            (0..width).fold(0, |part, bit| {
+                part | (is_set(i * width + bit) as u64) << bit
+            })
+        })
+        .collect();
+    // The padding bits after the IDs must be zero.
+    if (bits..packed.len() * 8).any(is_set) {
+        return Err(Error::InvalidDataLength);
+    }
+    Ok(parts)
+}
+
+fn scope_tweak(scope: u64) -> [u8; SCOPE_TWEAK.len() + 8] {
+    let mut tweak = [0; SCOPE_TWEAK.len() + 8];
+    tweak[..SCOPE_TWEAK.len()].copy_from_slice(SCOPE_TWEAK);
+    tweak[SCOPE_TWEAK.len()..].copy_from_slice(&scope.to_le_bytes());
+    tweak
+}
+
+fn decrypt_number(codec: &Codec, tweak: &[u8], encrypted_data: &[u8]) -> Result<u64, Error> {
+    let decrypted = decrypt_bytes(codec, tweak, codec.zero_pad_length, encrypted_data)?;
+    if decrypted.len() > 8 {
+        return Err(Error::InvalidDataLength);
+    }
+    Ok(le_vec_to_num(&decrypted))
+}
+
+// Verifies the MAC of `encrypted_data` and decrypts it, expecting at least `min_length`
+// bytes of ciphertext.
+fn decrypt_bytes(
+    codec: &Codec,
+    tweak: &[u8],
+    min_length: usize,
+    encrypted_data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    if encrypted_data.len() < codec.hmac_length + min_length {
+        if let Some(fingerprint) = &codec.fingerprint {
+            if other_parameters(codec, fingerprint, tweak, encrypted_data) {
+                return Err(Error::ParameterMismatch);
+            }
+        }
+        return Err(Error::InvalidDataLength);
+    }
+    let (encrypted, received_mac) =
+        encrypted_data.split_at(encrypted_data.len() - codec.hmac_length);
+
+    // Verify MAC
+    let mut mac = compute_mac(codec, tweak, encrypted);
+    let mask = &mut mac[..codec.hmac_length];
+    xor_in_place(mask, received_mac);
+    if let Some(fingerprint) = &codec.fingerprint {
+        if *mask != fingerprint[..codec.hmac_length] {
+            if other_parameters(codec, fingerprint, tweak, encrypted_data) {
+                return Err(Error::ParameterMismatch);
+            }
+            return Err(Error::IncorrectMAC);
+        }
+    } else if mask.iter().any(|&b| b != 0) {
+        return Err(Error::IncorrectMAC);
+    }
+
+    // Decrypt the data
+    let decrypted = codec
+        .ff1
+        .decrypt(tweak, &BinaryNumeralString::from_bytes_le(encrypted))
+        .map_err(|_| Error::DecryptionFailed)?;
+    Ok(decrypted.to_bytes_le())
+}
+
+// Derives the FF1 key followed by the HMAC key of the codec `name`.
+fn derive_keys(hkdf: &Hkdf, name: &str, keys: &mut [u8; 64]) {
+    let (ff1_key, hmac_key) = keys.split_at_mut(32);
+    hkdf.expand(format!("{}/ff1", name).as_bytes(), ff1_key)
+        .expect("Length 32 should be valid");
+    hkdf.expand(format!("{}/hmac", name).as_bytes(), hmac_key)
+        .expect("Length 32 should be valid");
+}
+
+fn compute_mac(codec: &Codec, tweak: &[u8], encrypted: &[u8]) -> [u8; 32] {
+    let mut hmac: HmacSha256 = codec.hmac.clone();
+    hmac.update(tweak);
+    hmac.update(encrypted);
+    hmac.finalize().into_bytes().into()
+}
+
+fn xor_in_place(bytes: &mut [u8], mask: &[u8]) {
+    for (b, m) in bytes.iter_mut().zip(mask) {
+        *b ^= m;
+    }
+}
+
+// Checks whether a token which failed to verify with parameter binding enabled carries a
+// valid MAC for a different parameter set, possibly with a different `hmac_length`.
+fn other_parameters(
+    codec: &Codec,
+    fingerprint: &Fingerprint,
+    tweak: &[u8],
+    encrypted_data: &[u8],
+) -> bool {
+    let all = codec
+        .fingerprints
+        .get_or_init(|| all_fingerprints(&codec.alphabet));
+    (1..=8).any(|hmac_length| {
+        if encrypted_data.len() <= hmac_length {
+            return false;
+        }
+        let (encrypted, received_mac) = encrypted_data.split_at(encrypted_data.len() - hmac_length);
+        let mut mac = compute_mac(codec, tweak, encrypted);
+        let mask = &mut mac[..hmac_length];
+        xor_in_place(mask, received_mac);
+        is_other_fingerprint(all, hmac_length, mask, fingerprint)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::radix;
+    use rand::{distributions::Uniform, Rng};
+
+    #[test]
+    fn test_defaults() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        let test_cases = vec![
+            (0, "test_g1HdsEGpXp5"),
+            (1, "test_bTPc8uxHEwv"),
+            (2, "test_dZ0iJdcLBgB"),
+            (123, "test_hHLBCl4rZ3u"),
+            (u64::MAX, "test_20cMzlnhTkILdJzWt"),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(codec.encode(input), expected);
+            assert_eq!(codec.decode(expected).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn test_uuid() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        let test_cases = [
+            (0, "59142369-adeb-8ef9-a1be-28f61c05d4d6"),
+            (1, "93196956-2d32-d8d2-54f7-9a86fc765f3a"),
+            (2, "3c10f25c-005e-6f6f-87a9-781efe02d14d"),
+            (123, "571fd9d5-e133-f7b0-b0df-f444e4dd1127"),
+            (u64::MAX, "a3b06cf5-dd4d-3f09-4000-9d3519d4d6c2"),
+        ];
+
+        for &(input, expected) in &test_cases {
+            assert_eq!(codec.encode_uuid(input), Uuid::parse_str(expected).unwrap());
+            let uuid = Uuid::parse_str(expected).unwrap();
+            assert_eq!(codec.decode_uuid(uuid).unwrap(), input);
+        }
+        assert_eq!(
+            codec.decode_uuid(Uuid::from_u128(codec.encode_uuid(0).as_u128() ^ 1)),
+            Err(Error::IncorrectMAC)
+        );
+    }
+
+    #[test]
+    fn test_long() {
+        let config = Config::new(b"Test key here")
+            .hmac_length(8)
+            .unwrap()
+            .zero_pad_length(8)
+            .unwrap();
+        let codec = Codec::new("test", &config);
+        assert_eq!(codec.encode(0), "test_6XNFaHOCeuIBNvRT4pIrVZ");
+        assert_eq!(codec.encode(1), "test_1m9BJW23Jk5hSIlfPxoboZ");
+        assert_eq!(codec.encode(2), "test_2MpvWPgnp5j1dIqFnJVOjU");
+        assert_eq!(codec.encode(123), "test_1BirgT1ZJhfSsKFLgxA5gt");
+        assert_eq!(codec.encode(u64::MAX), "test_5vegfyOLrrmwtgznQByI4J");
+        assert_eq!(codec.decode("test_6XNFaHOCeuIBNvRT4pIrVZ").unwrap(), 0);
+        assert_eq!(codec.decode("test_1m9BJW23Jk5hSIlfPxoboZ").unwrap(), 1);
+        assert_eq!(codec.decode("test_2MpvWPgnp5j1dIqFnJVOjU").unwrap(), 2);
+        assert_eq!(codec.decode("test_1BirgT1ZJhfSsKFLgxA5gt").unwrap(), 123);
+        assert_eq!(
+            codec.decode("test_5vegfyOLrrmwtgznQByI4J").unwrap(),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn test_short() {
+        let config = Config::new(b"Test key here")
+            .hmac_length(0)
+            .unwrap()
+            .zero_pad_length(3)
+            .unwrap();
+        let codec = Codec::new("test", &config);
+        assert_eq!(codec.encode(0), "test_1zG8O");
+        assert_eq!(codec.encode(1), "test_1R8PN");
+        assert_eq!(codec.encode(2), "test_1nzgo");
+        assert_eq!(codec.encode(123), "test_1YqNT");
+        assert_eq!(codec.encode(u64::MAX), "test_Mlu72Yai97j");
+        assert_eq!(codec.decode("test_1zG8O").unwrap(), 0);
+        assert_eq!(codec.decode("test_1R8PN").unwrap(), 1);
+        assert_eq!(codec.decode("test_1nzgo").unwrap(), 2);
+        assert_eq!(codec.decode("test_1YqNT").unwrap(), 123);
+        assert_eq!(codec.decode("test_Mlu72Yai97j").unwrap(), u64::MAX);
+
+        // Without HMAC, pretty much anything decodes to some number.
+        assert_eq!(codec.decode("test_1helloall").unwrap(), 20580488769766);
+
+        // Payloads longer than a u64 are rejected rather than panicking.
+        let long = format!("test_{}", base62::encode(u128::MAX >> 7));
+        assert_eq!(codec.decode(&long), Err(Error::InvalidDataLength));
+
+        // Shorter padding still pads to the FF1 minimum length.
+        let codec = Codec::new("test", &config.zero_pad_length(0).unwrap());
+        assert_eq!(codec.encode(0), "test_1zG8O");
+        assert_eq!(codec.decode("test_1zG8O").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_nonce() {
+        let config = Config::new(b"Test key here");
+        assert!(config.clone().nonce_length(5).is_err());
+        // Configs and the offset of the nonce in the payload.
+        for (nonced, offset) in [
+            (config.clone().nonce_length(1).unwrap(), 0),
+            (config.clone().nonce_length(4).unwrap(), 0),
+            (
+                config
+                    .clone()
+                    .nonce_length(2)
+                    .unwrap()
+                    .hmac_length(8)
+                    .unwrap(),
+                0,
+            ),
+            (
+                config
+                    .clone()
+                    .nonce_length(2)
+                    .unwrap()
+                    .key_version(3)
+                    .unwrap(),
+                1,
+            ),
+        ] {
+            let codec = Codec::new("test", &nonced).with_type_id(1);
+            for num in [0, 123, u64::MAX] {
+                let tokens: std::collections::HashSet<String> =
+                    (0..20).map(|_| codec.encode(num)).collect();
+                assert!(tokens.len() > 1);
+                for token in &tokens {
+                    assert!(token.len() <= codec.max_encoded_len());
+                    assert_eq!(codec.decode(token), Ok(num));
+                    #[cfg(feature = "forensics")]
+                    assert_eq!(codec.decode_unverified(token), Ok(num));
+                }
+                let rendered = codec.render_envelope(&codec.encode_envelope(num)).unwrap();
+                assert_eq!(codec.decode(&rendered), Ok(num));
+            }
+
+            // The nonce is authenticated.
+            let mut token = codec.encode(123).into_bytes();
+            let i = "test_".len() + offset;
+            token[i] = if token[i] == b'0' { b'1' } else { b'0' };
+            assert!(codec.decode(std::str::from_utf8(&token).unwrap()).is_err());
+            assert!(codec.decode("test_").is_err());
+            assert!(codec.decode("test_\u{e4}").is_err());
+        }
+
+        // Order-preserving tokens stay deterministic.
+        let ordered = config.order_preserving(true).nonce_length(3).unwrap();
+        let codec = Codec::new("test", &ordered);
+        assert_eq!(codec.encode(123), codec.encode(123));
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let legacy = Codec::new("test", &Config::new(b"Test key here"));
+        let config = Config::new(b"Key 1").key_version(1).unwrap();
+        let v1 = Codec::new("test", &config);
+        let token = v1.encode(123);
+        assert!(token.starts_with("test_1"));
+        assert_eq!(token.len(), legacy.encode(123).len() + 1);
+        assert_eq!(v1.decode(&token), Ok(123));
+        assert_eq!(v1.decode("test_hHLBCl4rZ3u"), Err(Error::IncorrectMAC));
+        assert_eq!(v1.decode("test_1"), Err(Error::DecodingFailed));
+        assert_eq!(v1.decode_with_form(&token), Ok((123, TokenForm::Prefixed)));
+
+        let config = Config::new(b"Key 2")
+            .key_version(2)
+            .unwrap()
+            .previous_key(1, b"Key 1")
+            .unwrap()
+            .previous_key(0, b"Test key here")
+            .unwrap();
+        for order_preserving in [false, true] {
+            let config = config.clone().order_preserving(order_preserving);
+            let v2 = Codec::new("test", &config);
+            let old = Codec::new(
+                "test",
+                &config.with_previous_key(1, &Arc::from(&b"Key 1"[..])),
+            );
+            let legacy = Codec::new(
+                "test",
+                &config.with_previous_key(0, &Arc::from(&b"Test key here"[..])),
+            );
+            for num in [0, 123, u64::MAX] {
+                let token = v2.encode(num);
+                assert!(token.starts_with("test_2"));
+                assert!(token.len() <= v2.max_encoded_len());
+                assert_eq!(v2.decode(&token), Ok(num));
+                assert_eq!(v2.decode(&old.encode(num)), Ok(num));
+                assert_eq!(v2.decode(&legacy.encode(num)), Ok(num));
+                assert!(old.decode(&token).is_err());
+                assert!(legacy.decode(&token).is_err());
+                assert_eq!(v2.decode_uuid(old.encode_uuid(num)), Ok(num));
+                assert_eq!(v2.decode_uuid(legacy.encode_uuid(num)), Ok(num));
+                assert!(old.decode_uuid(v2.encode_uuid(num)).is_err());
+            }
+        }
+
+        // Strings are not versioned.
+        let v2 = Codec::new("test", &config);
+        assert_eq!(
+            v2.decode_str(&v2.encode_str("INV-1").unwrap()).unwrap(),
+            "INV-1"
+        );
+        assert!(v2.decode("test_3hHLBCl4rZ3u").is_err());
+
+        assert!(Config::new(b"Key").key_version(62).is_err());
+        assert!(config.clone().previous_key(2, b"Key").is_err());
+        assert!(config.clone().previous_key(1, b"Key").is_err());
+        assert!(config.clone().previous_key(62, b"Key").is_err());
+        assert!(Config::new(b"Key")
+            .previous_key(3, b"Old")
+            .unwrap()
+            .key_version(3)
+            .is_err());
+    }
+
+    #[test]
+    fn test_alphabets() {
+        let base = Config::new(b"Test key here");
+        let configs = [
+            base.clone(),
+            base.clone().hmac_length(8).unwrap(),
+            base.clone().nonce_length(4).unwrap(),
+            base.clone().key_version(9).unwrap(),
+            base.clone().order_preserving(true),
+        ];
+        let unambiguous =
+            Alphabet::Custom("23456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz".into());
+        for alphabet in [Alphabet::Base32, Alphabet::Decimal, unambiguous.clone()] {
+            for config in &configs {
+                let config = config.clone().alphabet(alphabet.clone()).unwrap();
+                let codec = Codec::new("test", &config);
+                for num in [0, 123, u32::MAX as u64, u64::MAX] {
+                    let token = codec.encode(num);
+                    let payload = &token["test_".len()..];
+                    assert!(payload.chars().all(|c| alphabet.digits().contains(c)));
+                    assert!(token.len() <= codec.max_encoded_len(), "{}", token);
+                    assert_eq!(codec.decode(&token), Ok(num));
+                    assert_eq!(
+                        codec.decode_scoped(&codec.encode_scoped(num, 7), 7),
+                        Ok(num)
+                    );
+                }
+                let token = codec.encode_str("INV-1").unwrap();
+                assert!(token["test_".len()..]
+                    .chars()
+                    .all(|c| alphabet.digits().contains(c)));
+                assert_eq!(codec.decode_str(&token).unwrap(), "INV-1");
+            }
+        }
+
+        // Base62 tokens are not valid in other alphabets and vice versa.
+        let base32 = Codec::new("test", &base.clone().alphabet(Alphabet::Base32).unwrap());
+        let decimal = Codec::new("test", &base.clone().alphabet(Alphabet::Decimal).unwrap());
+        assert_eq!(
+            base32.decode("test_hHLBCl4rZ3u"),
+            Err(Error::DecodingFailed)
+        );
+        assert_eq!(
+            decimal.decode("test_hHLBCl4rZ3u"),
+            Err(Error::DecodingFailed)
+        );
+        let token = base32.encode(123);
+        assert_eq!(
+            base32.decode(&token.to_uppercase().replace("TEST_", "test_")),
+            Err(Error::DecodingFailed)
+        );
+        assert!(Codec::new("test", &base)
+            .decode(&decimal.encode(123))
+            .is_err());
+        assert_eq!(
+            decimal.decode(&format!("test_{}", "1".repeat(40))),
+            Err(Error::PayloadTooLong { max_chars: 39 })
+        );
+
+        let custom = Codec::new("test", &base.clone().alphabet(unambiguous).unwrap());
+        assert_eq!(
+            custom.decode("test_hHLBCl4rZ3u"),
+            Err(Error::DecodingFailed)
+        );
+        assert!(Codec::new("test", &base)
+            .decode(&custom.encode(123))
+            .is_err());
+        assert!(matches!(
+            base.clone()
+                .alphabet(Alphabet::Custom("0123456789_".into())),
+            Err(ConfigError::UnsupportedAlphabet)
+        ));
+
+        // Order-preserving tokens sort in every alphabet with digits in ASCII order.
+        for codec in [&base32, &decimal, &custom] {
+            let config = base.clone().order_preserving(true);
+            let ordered = Codec::new("test", &config.alphabet(codec.alphabet.clone()).unwrap());
+            assert!(ordered.encode(99) < ordered.encode(100));
+            assert!(ordered.encode(u64::MAX - 1) < ordered.encode(u64::MAX));
+        }
+
+        // Key versions must be single digits.
+        assert!(base
+            .clone()
+            .key_version(10)
+            .unwrap()
+            .alphabet(Alphabet::Decimal)
+            .is_err());
+        assert!(base
+            .clone()
+            .previous_key(10, b"Old")
+            .unwrap()
+            .alphabet(Alphabet::Decimal)
+            .is_err());
+        let decimal_config = base.clone().alphabet(Alphabet::Decimal).unwrap();
+        assert!(decimal_config.clone().key_version(10).is_err());
+        assert!(decimal_config.clone().previous_key(10, b"Old").is_err());
+        let v1 = Codec::new("test", &decimal_config.key_version(1).unwrap());
+        assert!(v1.encode(123).starts_with("test_1"));
+    }
+
+    #[test]
+    fn test_invalid_prefix() {
+        let config = Config::new(b"Test key here");
+        for name in ["a", "user_account", "user-account", "V2_Item"] {
+            assert!(Codec::try_new(name, &config).is_ok(), "{}", name);
+        }
+        for name in ["", "käyttäjä", "user account", "user/account", "ü_"] {
+            assert!(
+                matches!(
+                    Codec::try_new(name, &config),
+                    Err(ConfigError::InvalidPrefix)
+                ),
+                "{}",
+                name
+            );
+        }
+        // Names of earlier versions are still accepted by `new`.
+        for name in ["ü", "user.account", "käyttäjä"] {
+            let codec = Codec::new(name, &config);
+            let token = codec.encode(123);
+            assert!(token.starts_with(&format!("{}_", name)));
+            assert_eq!(codec.decode(&token), Ok(123));
+            assert_eq!(codec.decode_uuid(codec.encode_uuid(123)), Ok(123));
+        }
+    }
+
+    #[test]
+    fn test_scoped() {
+        let config = Config::new(b"Test key here");
+        for config in [
+            config.clone(),
+            config.clone().order_preserving(true),
+            config.clone().bind_parameters(true),
+            config.clone().hmac_length(8).unwrap(),
+            config.clone().key_version(1).unwrap(),
+        ] {
+            let codec = Codec::new("test", &config);
+            for num in [0, 123, u64::MAX] {
+                for scope in [0, 1, u64::MAX] {
+                    let encoded = codec.encode_scoped(num, scope);
+                    assert!(encoded.len() <= codec.max_encoded_len());
+                    assert_eq!(codec.decode_scoped(&encoded, scope), Ok(num));
+                    assert!(codec.decode_scoped(&encoded, scope ^ 2).is_err());
+                }
+            }
+        }
+
+        // The scope is separate from other formats.
+        let codec = Codec::new("test", &config.hmac_length(8).unwrap());
+        let encoded = codec.encode_scoped(123, 0);
+        assert_ne!(encoded, codec.encode(123));
+        assert!(codec.decode(&encoded).is_err());
+        assert!(codec.decode_scoped(&codec.encode(123), 0).is_err());
+        assert!(codec.decode_scoped("other_hHLBCl4rZ3u", 0).is_err());
+    }
+
+    #[test]
+    fn test_full_length() {
+        // With an 8 byte MAC, large IDs fill all 16 bytes and have no sentinel.
+        let config = Config::new(b"Test key here").hmac_length(8).unwrap();
+        let codec = Codec::new("test", &config.zero_pad_length(3).unwrap());
+        let mut rng = rand::thread_rng();
+        for num in [0, 1 << 55, 1 << 56, u64::MAX, rng.gen(), rng.gen()] {
+            assert_eq!(codec.decode(&codec.encode(num)), Ok(num), "{}", num);
+        }
+        let encoded = codec.encode(u64::MAX);
+        let mut mangled = encoded.clone();
+        mangled.replace_range(6..7, if &encoded[6..7] == "x" { "y" } else { "x" });
+        assert!(codec.decode(&mangled).is_err());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+
+        assert_eq!(
+            codec.decode("hHLBCl4rZ3u"),
+            Err(Error::InvalidPrefix {
+                received: "".to_string(),
+                expected: "test_".into()
+            })
+        );
+
+        assert_eq!(
+            codec.decode("_hHLBCl4rZ3u"),
+            Err(Error::InvalidPrefix {
+                received: "_".to_string(),
+                expected: "test_".into()
+            })
+        );
+
+        assert_eq!(
+            codec.decode("wrong_hHLBCl4rZ3u"),
+            Err(Error::WrongType {
+                received_prefix: "wrong_".to_string(),
+                expected: "test_".into(),
+                hint: None
+            })
+        );
+        assert_eq!(
+            codec.decode("wrong_hHLBCl4rZ3"),
+            Err(Error::WrongType {
+                received_prefix: "wrong_".to_string(),
+                expected: "test_".into(),
+                hint: None
+            })
+        );
+        // Only tokens which could be valid are of the wrong type.
+        for token in [
+            "wröng_hHLBCl4rZ3u",
+            "wrong_",
+            "wrong_hHLBCl4rZ3u!",
+            "-_abc/",
+        ] {
+            assert!(
+                matches!(codec.decode(token), Err(Error::InvalidPrefix { .. })),
+                "{}",
+                token
+            );
+        }
+
+        assert_eq!(
+            codec.decode("test_iHLBCl4rZ3u"),
+            Err(Error::SentinelMismatch {
+                received: 2,
+                expected: SENTINEL,
+            })
+        );
+
+        // Tampering with any part gives a MAC error.
+        assert_eq!(codec.decode("test_hHLBCl4rZ3v"), Err(Error::IncorrectMAC));
+        assert_eq!(codec.decode("test_hHMBCl4rZ3u"), Err(Error::IncorrectMAC));
+
+        // Invalid characters aren't allowed.
+        assert_eq!(codec.decode("test_hHLBCl+rZ3u"), Err(Error::DecodingFailed));
+
+        // Payloads which can't fit in a u128 are rejected before parsing.
+        let too_long = Err(Error::PayloadTooLong {
+            max_chars: radix::U128_DIGITS,
+        });
+        assert_eq!(codec.decode(&format!("test_{}", "z".repeat(22))), too_long);
+        assert_eq!(codec.decode(&format!("test_{}", "1".repeat(23))), too_long);
+        assert_eq!(
+            codec.decode(&format!("test_{}", "0".repeat(10000))),
+            too_long
+        );
+
+        // And just to validate the above, check that the correct string does decode.
+        assert_eq!(codec.decode("test_hHLBCl4rZ3u"), Ok(123));
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let config = Config::new(b"Test key here");
+        let mut registry = CodecRegistry::new();
+        registry.register("team", &config);
+        let team = registry.get("team").unwrap().encode(123);
+        let codec = Codec::new("test", &config.type_hints(Arc::new(registry)));
+
+        let wrong_type = |hint: Option<&str>| {
+            Err(Error::WrongType {
+                received_prefix: "team_".to_string(),
+                expected: "test_".into(),
+                hint: hint.map(str::to_string),
+            })
+        };
+        assert_eq!(codec.decode(&team), wrong_type(Some("team")));
+        // Tampered tokens and unregistered types get no hint.
+        let tampered = format!("{}0", team);
+        assert_eq!(codec.decode(&tampered), wrong_type(None));
+        assert!(matches!(
+            codec.decode("user_hHLBCl4rZ3u"),
+            Err(Error::WrongType { hint: None, .. })
+        ));
+    }
+
+    #[test]
+    fn test_overlapping_prefixes() {
+        let config = Config::new(b"Test key here");
+        let names = ["user", "poweruser", "power_user"];
+        let codecs = names.map(|name| Codec::new(name, &config));
+        for codec in &codecs {
+            let token = codec.encode(123);
+            assert_eq!(codec.decode(&token), Ok(123));
+            for other in codecs.iter().filter(|other| other.name() != codec.name()) {
+                assert_eq!(
+                    other.decode(&token),
+                    Err(Error::WrongType {
+                        received_prefix: format!("{}_", codec.name()),
+                        expected: format!("{}_", other.name()).into(),
+                        hint: None,
+                    }),
+                    "{} decoded by {}",
+                    token,
+                    other.name()
+                );
+            }
+        }
+        // Other cases of the longer names are not the shorter name either.
+        let token = codecs[2]
+            .encode(123)
+            .replacen("power_user", "POWER_USER", 1);
+        assert!(codecs[0].decode(&token).is_err());
+        assert_eq!(codecs[2].decode(&token), Ok(123));
+    }
+
+    #[test]
+    fn test_decode_raw() {
+        struct Panicking;
+        impl CodecHook for Panicking {
+            fn before_decode(&self, _: &Codec, encoded: &str) -> Result<(), Error> {
+                assert_ne!(encoded, "test_boom");
+                Ok(())
+            }
+        }
+
+        let codec =
+            Codec::new("test", &Config::new(b"Test key here")).with_hook(Arc::new(Panicking));
+        assert_eq!(codec.decode_raw(b"test_hHLBCl4rZ3u"), Ok(123));
+        assert_eq!(
+            codec.decode_raw(b"test_hHLBCl4rZ3v"),
+            Err(ErrorCode::IncorrectMAC)
+        );
+        assert_eq!(
+            codec.decode_raw(b"other_hHLBCl4rZ3u"),
+            Err(ErrorCode::WrongType)
+        );
+        assert_eq!(
+            codec.decode_raw(b"other_hHLBCl4rZ3u!"),
+            Err(ErrorCode::InvalidPrefix)
+        );
+        assert_eq!(codec.decode_raw(b""), Err(ErrorCode::EmptyToken));
+        assert_eq!(codec.decode_raw(b"test_\xff"), Err(ErrorCode::InvalidUtf8));
+        assert_eq!(codec.decode_raw(b"test_boom"), Err(ErrorCode::Panic));
+    }
+
+    #[test]
+    fn test_decode_with_limits() {
+        let config = Config::new(b"Test key here");
+        let codec = Codec::new("test", &config);
+        let limits = Limits {
+            max_len: codec.max_encoded_len(),
+            max_work: 1,
+        };
+        assert_eq!(
+            codec.decode_with_limits("test_hHLBCl4rZ3u", limits),
+            Ok(123)
+        );
+        assert_eq!(
+            codec.decode_with_limits("test_hHLBCl4rZ3v", limits),
+            Err(Error::IncorrectMAC)
+        );
+        let long = format!("test_{}", "0".repeat(1000));
+        assert_eq!(
+            codec.decode_with_limits(&long, limits),
+            Err(Error::LimitExceeded)
+        );
+        let no_work = Limits {
+            max_work: 0,
+            ..limits
+        };
+        assert_eq!(
+            codec.decode_with_limits("test_hHLBCl4rZ3u", no_work),
+            Err(Error::LimitExceeded)
+        );
+        assert_eq!(
+            ErrorCode::from(&Error::LimitExceeded) as i32,
+            ErrorCode::LimitExceeded as i32
+        );
+        assert_eq!(ErrorCode::LimitExceeded as i32, 19);
+
+        // Legacy tokens starting with the current version digit are tried with both keys.
+        // Their first digit is in the middle of the alphabet.
+        let rotated = Codec::new(
+            "test",
+            &Config::new(b"Key 1")
+                .key_version(30)
+                .unwrap()
+                .previous_key(0, b"Test key here")
+                .unwrap(),
+        );
+        let legacy = (0..1000)
+            .map(|num| (num, codec.encode(num)))
+            .find(|(_, token)| token.starts_with("test_U"))
+            .unwrap();
+        let two = Limits {
+            max_work: 2,
+            ..limits
+        };
+        assert_eq!(
+            rotated.decode_with_limits(&legacy.1, limits),
+            Err(Error::LimitExceeded)
+        );
+        assert_eq!(rotated.decode_with_limits(&legacy.1, two), Ok(legacy.0));
+        assert_eq!(
+            rotated.decode_with_limits(&rotated.encode(123), limits),
+            Ok(123)
+        );
+
+        // Decoding with the codec of a type hint counts too.
+        let mut registry = CodecRegistry::new();
+        registry.register("team", &config);
+        let team = registry.get("team").unwrap().encode(123);
+        let codec = Codec::new("test", &config.type_hints(Arc::new(registry)));
+        assert_eq!(
+            codec.decode_with_limits(&team, no_work),
+            Err(Error::LimitExceeded)
+        );
+        assert!(matches!(
+            codec.decode_with_limits(&team, limits),
+            Err(Error::WrongType { hint: Some(_), .. })
+        ));
+    }
+
+    #[cfg(feature = "forensics")]
+    #[test]
+    fn test_decode_unverified() {
+        let config = Config::new(b"Test key here");
+        for (codec, flip) in [
+            (Codec::new("test", &config), 1 << 40),
+            (
+                Codec::new("test", &config.clone().order_preserving(true)),
+                1,
+            ),
+        ] {
+            // Flip a bit in the MAC, or in the remainder in order-preserving mode.
+            let width = codec.order.as_ref().map_or(0, |order| order.width());
+            let value = base62::encode(codec.encode_value(123) ^ flip);
+            let mangled = format!("test_{:0>width$}", value, width = width);
+            assert_eq!(codec.decode(&mangled), Err(Error::IncorrectMAC));
+            assert_eq!(codec.decode_unverified(&mangled), Ok(123));
+        }
+
+        // Some full-length tokens with an 8 byte MAC end in a byte equal to the sentinel.
+        let codec = Codec::new("test", &config.hmac_length(8).unwrap());
+        for num in u64::MAX - 2000..=u64::MAX {
+            assert_eq!(codec.decode_unverified(&codec.encode(num)), Ok(num));
+        }
+    }
+
+    #[test]
+    fn test_trim_debris() {
+        let config = Config::new(b"Test key here");
+        let codec = Codec::new("test", &config);
+        assert!(codec.decode("test_hHLBCl4rZ3u/").is_err());
+
+        let codec = Codec::new("test", &config).trim_debris(true);
+        for (encoded, debris) in [
+            ("test_hHLBCl4rZ3u", ""),
+            ("test_hHLBCl4rZ3u/", "/"),
+            ("test_hHLBCl4rZ3u?", "?"),
+            ("test_hHLBCl4rZ3u).", ")."),
+            ("test_hHLBCl4rZ3u/?./", "/?./"),
+        ] {
+            assert_eq!(codec.decode(encoded), Ok(123));
+            assert_eq!(codec.decode_with_debris(encoded), Ok((123, debris)));
+        }
+        assert_eq!(
+            codec.decode("test_hHLBCl4rZ3u,"),
+            Err(Error::DecodingFailed)
+        );
+        assert_eq!(codec.decode("(test_hHLBCl4rZ3u)").ok(), None);
+        assert_eq!(
+            codec.decode_with_debris("test_hHLBCl4rZ3v."),
+            Err(Error::IncorrectMAC)
+        );
+    }
+
+    #[test]
+    fn test_unprefixed() {
+        let codec = Codec::new("test", &Config::new(b"Test key here")).accept_unprefixed(true);
+
+        assert_eq!(
+            codec.decode_with_form("hHLBCl4rZ3u"),
+            Ok((123, TokenForm::Unprefixed))
+        );
+        assert_eq!(
+            codec.decode_with_form("TEST_hHLBCl4rZ3u"),
+            Ok((123, TokenForm::Prefixed))
+        );
+        assert_eq!(codec.decode("hHLBCl4rZ3v"), Err(Error::IncorrectMAC));
+        assert!(matches!(
+            codec.decode("wrong_hHLBCl4rZ3u"),
+            Err(Error::WrongType { .. })
+        ));
 
-        decrypt_number(self, &num_array[..length])
+        let encoded = codec.encode_str("INV-1").unwrap();
+        let bare = &encoded["test_".len()..];
+        assert_eq!(codec.decode_str(bare).unwrap(), "INV-1");
+        // Empty strings are not unprefixed tokens.
+        assert_eq!(codec.decode(""), Err(Error::EmptyToken));
+        assert_eq!(codec.decode_str(""), Err(Error::EmptyToken));
     }
-}
 
-fn last_nonzero(bytes: &[u8]) -> usize {
-    bytes.iter().rposition(|&b| b != 0).unwrap_or(0)
-}
+    #[test]
+    fn test_unicode_garbage() {
+        let config = Config::new(b"Test key here");
+        let codecs = [
+            Codec::new("test", &config),
+            Codec::new("test", &config.clone().hmac_length(8).unwrap()),
+            Codec::new("test", &config.clone().nonce_length(2).unwrap()),
+            Codec::new("test", &config.clone().order_preserving(true)),
+            Codec::new(
+                "test",
+                &config
+                    .clone()
+                    .key_version(1)
+                    .unwrap()
+                    .previous_key(0, b"Old key")
+                    .unwrap(),
+            )
+            .accept_unprefixed(true)
+            .trim_debris(true),
+        ];
+        // Multibyte characters around and in place of the underscore, the version digit and
+        // the nonce digits.
+        let pieces = [
+            "test",
+            "_",
+            "é",
+            "😀",
+            "ｔest",
+            "_\u{301}",
+            "1",
+            "hHLBCl4rZ3u",
+            "/",
+        ];
+        let mut inputs = vec![String::new()];
+        for _ in 0..3 {
+            inputs = inputs
+                .iter()
+                .flat_map(|input| {
+                    pieces
+                        .iter()
+                        .map(move |piece| format!("{}{}", input, piece))
+                })
+                .collect();
+        }
+        for codec in &codecs {
+            for input in &inputs {
+                let _ = codec.decode_with_debris(input);
+                let _ = codec.decode_scoped(input, 1);
+                let _ = codec.decode_str(input);
+                #[cfg(feature = "forensics")]
+                let _ = codec.decode_unverified(input);
+            }
+            assert!(matches!(
+                codec.decode("tést_hHLBCl4rZ3u"),
+                Err(Error::InvalidPrefix { received, .. }) if received == "tést_"
+            ));
+        }
+    }
 
-// Returns a memory representanion of `num` as a byte vector in little-endian byte
-// order, leaving out trailing zero bytes beyond `min_length`.
-fn num_to_le_vec(num: u64, min_length: usize) -> Vec<u8> {
-    let bytes = num.to_le_bytes();
-    let prefix_length = (last_nonzero(&bytes) + 1).max(min_length);
-    bytes[..prefix_length].to_vec()
-}
+    #[test]
+    fn test_encode_parts() {
+        let config = Config::new(b"Test key here");
+        let codec = Codec::new("user_account", &config).with_prefix_case(PrefixCase::Kebab);
+        let token = codec.encode_parts(123);
+        assert_eq!(token.full(), codec.encode(123));
+        assert_eq!(token.prefix(), "user-account");
+        assert_eq!(
+            format!("{}_{}", token.prefix(), token.payload()),
+            token.full()
+        );
+        assert_eq!(token.to_string(), token.full());
+        assert_eq!(codec.decode(&String::from(token)), Ok(123));
 
-fn le_vec_to_num(bytes: &[u8]) -> u64 {
-    let mut arr = [0; 8];
-    arr[..bytes.len()].copy_from_slice(bytes);
-    u64::from_le_bytes(arr)
-}
+        let codec = Codec::new("a_b_c", &config);
+        let token = codec.encode_parts(123);
+        assert_eq!(token.prefix(), "a_b_c");
+        assert!(!token.payload().contains('_'));
+    }
 
-fn encrypt_number(
-    ff1: &FF1<Aes256>,
-    hmac: &HmacSha256,
-    hmac_length: usize,
-    zero_pad_length: usize,
-    num: u64,
-) -> Vec<u8> {
-    // Encrypt `num` using form-preserving encryption.
-    let pt = num_to_le_vec(num, zero_pad_length);
-    let encrypted_num = ff1
-        .encrypt(&[], &BinaryNumeralString::from_bytes_le(&pt))
-        .expect("Radix 2 should be valid")
-        .to_bytes_le();
+    #[test]
+    fn test_encoded_lens() {
+        let mut rng = rand::thread_rng();
+        for order_preserving in [false, true] {
+            for hmac_length in order_preserving as u8..=8 {
+                let config = Config::new(b"Test key here")
+                    .order_preserving(order_preserving)
+                    .hmac_length(hmac_length)
+                    .unwrap();
+                let codec = Codec::new("test", &config);
+                for num in (0..1000).map(|_| rng.gen()).chain([0, u64::MAX]) {
+                    let length = codec.encode(num).len();
+                    assert!(length <= codec.max_encoded_len(), "{}", hmac_length);
+                    assert!(length >= codec.min_encoded_len(), "{}", hmac_length);
+                    if order_preserving {
+                        assert_eq!(length, codec.max_encoded_len());
+                        assert_eq!(length, codec.min_encoded_len());
+                    }
+                }
+            }
+        }
 
-    // Compute a truncated MAC from the ciphertext.
-    let mut hmac: HmacSha256 = hmac.clone();
-    hmac.update(&encrypted_num);
-    let truncated_mac = &hmac.finalize().into_bytes()[..hmac_length];
+        // The minimum is reached by small IDs, which are padded to `zero_pad_length` bytes.
+        for zero_pad_length in [0, 4, 8] {
+            for alphabet in [Alphabet::Base62, Alphabet::Base32, Alphabet::Decimal] {
+                let config = Config::new(b"Test key here")
+                    .zero_pad_length(zero_pad_length)
+                    .unwrap()
+                    .alphabet(alphabet)
+                    .unwrap()
+                    .nonce_length(2)
+                    .unwrap()
+                    .key_version(1)
+                    .unwrap();
+                let codec = Codec::new("test", &config);
+                let shortest = (0..200).map(|num| codec.encode(num).len()).min();
+                assert_eq!(shortest, Some(codec.min_encoded_len()));
+                let longest = (0..200).map(|_| codec.encode(rng.gen()).len()).max();
+                assert!(longest <= Some(codec.max_encoded_len()));
+            }
+        }
+    }
 
-    // Return the combined bytes.
-    let mut result = encrypted_num.to_vec();
-    result.extend_from_slice(truncated_mac);
+    #[test]
+    fn test_random_roundtrips() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        let mut rng = rand::thread_rng();
+        let range = Uniform::new(0u64, u64::MAX);
 
-    result
-}
+        for _ in 0..10_000 {
+            let number = rng.sample(range);
+            let encoded = codec.encode(number);
+            let decoded = codec.decode(&encoded).expect("Decoding failed");
 
-fn decrypt_number(codec: &Codec, encrypted_data: &[u8]) -> Result<u64, Error> {
-    if encrypted_data.len() < codec.hmac_length + codec.zero_pad_length {
-        return Err(Error::InvalidDataLength);
+            assert_eq!(decoded, number, "Failed at number: {}", number);
+        }
     }
-    let (encrypted_num, received_mac) =
-        encrypted_data.split_at(encrypted_data.len() - codec.hmac_length);
 
-    // Verify MAC
-    let mut hmac: HmacSha256 = codec.hmac.clone();
-    hmac.update(&encrypted_num);
-    let truncated_mac = &hmac.finalize().into_bytes()[..codec.hmac_length];
-    if truncated_mac != received_mac {
-        return Err(Error::IncorrectMAC);
-    }
+    #[test]
+    fn test_random_long_payloads() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        let alphabet = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+        let mut rng = rand::thread_rng();
 
-    // Decrypt the number
-    let decrypted_num = codec
-        .ff1
-        .decrypt(&[], &BinaryNumeralString::from_bytes_le(encrypted_num))
-        .map_err(|_| Error::DecryptionFailed)?;
+        for _ in 0..10_000 {
+            let length = rng.gen_range(21..=40);
+            let payload: String = (0..length)
+                .map(|_| alphabet[rng.gen_range(0..alphabet.len())] as char)
+                .collect();
+            let result = codec.decode(&format!("test_{}", payload));
+            let too_long = Err(Error::PayloadTooLong {
+                max_chars: radix::U128_DIGITS,
+            });
+            // Leading zeros don't make a payload acceptable.
+            if payload.len() <= radix::U128_DIGITS && base62::decode(&payload).is_ok() {
+                assert_ne!(result, too_long, "{}", payload);
+            } else {
+                assert_eq!(result, too_long, "{}", payload);
+            }
+        }
+    }
 
-    // Convert decrypted bytes back to number
-    let num: u64 = le_vec_to_num(&decrypted_num.to_bytes_le());
-    Ok(num)
-}
+    #[test]
+    fn test_strings() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        for value in ["a", "ab", "INV-0042", "user@example.com", &"x".repeat(32)] {
+            let encoded = codec.encode_str(value).unwrap();
+            assert_eq!(codec.decode_str(&encoded).unwrap(), value);
+            assert!(codec.decode(&encoded).is_err());
+        }
+        assert_eq!(codec.encode_str(""), Err(Error::InvalidString));
+        assert_eq!(codec.encode_str("a b"), Err(Error::InvalidString));
+        assert_eq!(codec.encode_str("ä"), Err(Error::InvalidString));
+        assert_eq!(codec.encode_str(&"x".repeat(33)), Err(Error::InvalidString));
+        assert!(codec.decode_str("test_hHLBCl4rZ3u").is_err());
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use rand::{distributions::Uniform, Rng};
+    #[test]
+    fn test_u128() {
+        let base = Config::new(b"Test key here");
+        let configs = [
+            base.clone(),
+            base.clone().hmac_length(0).unwrap(),
+            base.clone().hmac_length(8).unwrap(),
+            base.clone().zero_pad_length(8).unwrap(),
+            base.clone().alphabet(Alphabet::Decimal).unwrap(),
+        ];
+        let values = [
+            0,
+            1,
+            u64::MAX as u128,
+            u64::MAX as u128 + 1,
+            u128::MAX,
+            0x0123_4567_89ab_cdef_fedc_ba98_7654_3210,
+        ];
+        for config in configs {
+            let codec = Codec::new("test", &config);
+            for value in values {
+                let encoded = codec.encode_u128(value);
+                assert_eq!(codec.decode_u128(&encoded), Ok(value));
+            }
+        }
+        // Without a MAC, any payload decodes, so only the default config rejects other formats.
+        let codec = Codec::new("test", &base);
+        for value in values {
+            assert!(codec.decode(&codec.encode_u128(value)).is_err());
+        }
+        assert_ne!(codec.encode_u128(123), codec.encode(123));
+        assert!(codec.decode_u128("test_hHLBCl4rZ3u").is_err());
+        let mut tampered = codec.encode_u128(u128::MAX);
+        let last = if tampered.ends_with('a') { "b" } else { "a" };
+        tampered.replace_range(tampered.len() - 1.., last);
+        assert!(codec.decode_u128(&tampered).is_err());
+    }
 
     #[test]
-    fn test_defaults() {
+    fn test_uuid_pk() {
         let codec = Codec::new("test", &Config::new(b"Test key here"));
-        let test_cases = vec![
-            (0, "test_g1HdsEGpXp5"),
-            (1, "test_bTPc8uxHEwv"),
-            (2, "test_dZ0iJdcLBgB"),
-            (123, "test_hHLBCl4rZ3u"),
-            (u64::MAX, "test_20cMzlnhTkILdJzWt"),
+        let uuids = [
+            Uuid::nil(),
+            Uuid::max(),
+            Uuid::parse_str("0190d2b4-7c5e-7a3b-9f1e-2d4c6b8a0e13").unwrap(),
+            Uuid::from_u128(0x0123_4567_89ab_cdef_fedc_ba98_7654_3210),
         ];
-
-        for (input, expected) in test_cases {
-            assert_eq!(codec.encode(input), expected);
-            assert_eq!(codec.decode(expected).unwrap(), input);
+        for uuid in uuids {
+            let encoded = codec.encode_uuid_pk(uuid);
+            assert_eq!(codec.decode_uuid_pk(&encoded), Ok(uuid));
+            assert!(codec.decode_u128(&encoded).is_err());
+            assert!(codec
+                .decode_uuid_pk(&codec.encode_u128(uuid.as_u128()))
+                .is_err());
+            assert!(!encoded.contains(&uuid.simple().to_string()));
         }
+        assert!(codec.decode_uuid_pk("test_hHLBCl4rZ3u").is_err());
     }
 
     #[test]
-    fn test_uuid() {
+    fn test_i64() {
         let codec = Codec::new("test", &Config::new(b"Test key here"));
-        let test_cases = [
-            (0, "59142369-adeb-8ef9-a1be-28f61c05d4d6"),
-            (1, "93196956-2d32-d8d2-54f7-9a86fc765f3a"),
-            (2, "3c10f25c-005e-6f6f-87a9-781efe02d14d"),
-            (123, "571fd9d5-e133-f7b0-b0df-f444e4dd1127"),
-            (u64::MAX, "a3b06cf5-dd4d-3f09-4000-9d3519d4d6c2"),
+        let policies = [
+            NegativeIds::Reject,
+            NegativeIds::Reinterpret,
+            NegativeIds::ZigZag,
         ];
-
-        for &(input, expected) in &test_cases {
-            assert_eq!(codec.encode_uuid(input), Uuid::parse_str(expected).unwrap());
+        for policy in policies {
+            for num in [0, 1, 123, i64::MAX] {
+                let encoded = codec.encode_i64(num, policy).unwrap();
+                assert_eq!(codec.decode_i64(&encoded, policy), Ok(num));
+            }
         }
+        assert_eq!(
+            codec.encode_i64(123, NegativeIds::Reject).unwrap(),
+            "test_hHLBCl4rZ3u"
+        );
+        for num in [-1, i64::MIN] {
+            assert_eq!(
+                codec.encode_i64(num, NegativeIds::Reject),
+                Err(Error::NegativeId)
+            );
+            for policy in [NegativeIds::Reinterpret, NegativeIds::ZigZag] {
+                let encoded = codec.encode_i64(num, policy).unwrap();
+                assert_eq!(codec.decode_i64(&encoded, policy), Ok(num));
+            }
+        }
+        assert_eq!(
+            codec.decode_i64(&codec.encode(u64::MAX), NegativeIds::Reject),
+            Err(Error::NegativeId)
+        );
     }
 
     #[test]
-    fn test_long() {
-        let config = Config::new(b"Test key here")
-            .hmac_length(8)
-            .unwrap()
-            .zero_pad_length(8)
-            .unwrap();
-        let codec = Codec::new("test", &config);
-        assert_eq!(codec.encode(0), "test_6XNFaHOCeuIBNvRT4pIrVZ");
-        assert_eq!(codec.encode(1), "test_1m9BJW23Jk5hSIlfPxoboZ");
-        assert_eq!(codec.encode(2), "test_2MpvWPgnp5j1dIqFnJVOjU");
-        assert_eq!(codec.encode(123), "test_1BirgT1ZJhfSsKFLgxA5gt");
-        assert_eq!(codec.encode(u64::MAX), "test_5vegfyOLrrmwtgznQByI4J");
-        assert_eq!(codec.decode("test_6XNFaHOCeuIBNvRT4pIrVZ").unwrap(), 0);
-        assert_eq!(codec.decode("test_1m9BJW23Jk5hSIlfPxoboZ").unwrap(), 1);
-        assert_eq!(codec.decode("test_2MpvWPgnp5j1dIqFnJVOjU").unwrap(), 2);
-        assert_eq!(codec.decode("test_1BirgT1ZJhfSsKFLgxA5gt").unwrap(), 123);
+    fn test_composite() {
+        let base = Config::new(b"Test key here");
+        let configs = [
+            base.clone(),
+            base.clone().hmac_length(0).unwrap(),
+            base.clone().zero_pad_length(8).unwrap(),
+        ];
+        let parts: [&[u64]; 6] = [
+            &[],
+            &[0],
+            &[42, 12345],
+            &[0, 0, 0],
+            &[u64::MAX, 1 << 63, 127, 128],
+            &[1 << 7, 1 << 14, 1 << 56, (1 << 63) - 1],
+        ];
+        for config in configs {
+            let codec = Codec::new("test", &config);
+            for parts in parts {
+                let encoded = codec.encode_composite(parts).unwrap();
+                assert_eq!(codec.decode_composite(&encoded).as_deref(), Ok(parts));
+            }
+        }
+        let codec = Codec::new("test", &base);
         assert_eq!(
-            codec.decode("test_5vegfyOLrrmwtgznQByI4J").unwrap(),
-            u64::MAX
+            codec.encode_composite(&[1; MAX_COMPOSITE_PARTS + 1]),
+            Err(Error::InvalidDataLength)
+        );
+        let encoded = codec.encode_composite(&[42, 12345]).unwrap();
+        assert!(codec.decode(&encoded).is_err());
+        assert!(codec.decode_u128(&encoded).is_err());
+        assert!(codec.decode_composite(&codec.encode_u128(42)).is_err());
+        assert!(codec.decode_composite("test_hHLBCl4rZ3u").is_err());
+
+        // Malformed plaintexts with a valid MAC are rejected too.
+        let malformed: [&[u8]; 4] = [
+            &[5, 0, 0, 0, 0, 0],
+            &[2, 1, 0x80],
+            &[1, 1, 1],
+            &[
+                1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02,
+            ],
+        ];
+        for plaintext in malformed {
+            let encoded = codec.encode_payload(COMPOSITE_TWEAK, plaintext);
+            assert_eq!(
+                codec.decode_composite(&encoded),
+                Err(Error::InvalidDataLength)
+            );
+        }
+        let encoded = codec.encode_payload(
+            COMPOSITE_TWEAK,
+            &[
+                1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01,
+            ],
         );
+        assert_eq!(codec.decode_composite(&encoded), Ok(vec![u64::MAX]));
     }
 
     #[test]
-    fn test_short() {
-        let config = Config::new(b"Test key here")
-            .hmac_length(0)
-            .unwrap()
-            .zero_pad_length(3)
-            .unwrap();
-        let codec = Codec::new("test", &config);
-        assert_eq!(codec.encode(0), "test_1zG8O");
-        assert_eq!(codec.encode(1), "test_1R8PN");
-        assert_eq!(codec.encode(2), "test_1nzgo");
-        assert_eq!(codec.encode(123), "test_1YqNT");
-        assert_eq!(codec.encode(u64::MAX), "test_Mlu72Yai97j");
-        assert_eq!(codec.decode("test_1zG8O").unwrap(), 0);
-        assert_eq!(codec.decode("test_1R8PN").unwrap(), 1);
-        assert_eq!(codec.decode("test_1nzgo").unwrap(), 2);
-        assert_eq!(codec.decode("test_1YqNT").unwrap(), 123);
-        assert_eq!(codec.decode("test_Mlu72Yai97j").unwrap(), u64::MAX);
+    fn test_packed_composite() {
+        let base = Config::new(b"Test key here");
+        let configs = [
+            base.clone(),
+            base.clone().hmac_length(0).unwrap(),
+            base.clone().zero_pad_length(8).unwrap(),
+        ];
+        let parts: [&[u64]; 7] = [
+            &[],
+            &[0],
+            &[15, 16],
+            &[3, 14, 15, 9],
+            &[0, 0, 0],
+            &[u64::MAX, 1 << 63, 127, 128],
+            &[1 << 7, 1 << 14, 1 << 56, (1 << 63) - 1],
+        ];
+        for config in configs {
+            let codec = Codec::new("test", &config);
+            for parts in parts {
+                for layout in [CompositeLayout::Varint, CompositeLayout::Packed] {
+                    let encoded = codec.encode_composite_with(parts, layout).unwrap();
+                    assert_eq!(codec.decode_composite(&encoded).as_deref(), Ok(parts));
+                    let len = codec.composite_len(parts, layout).unwrap();
+                    assert!(encoded.len() <= len && encoded.len() + 1 >= len);
+                }
+            }
+        }
 
-        // Without HMAC, pretty much anything decodes to some number.
-        assert_eq!(codec.decode("test_1helloall").unwrap(), 20580488769766);
+        let codec = Codec::new("test", &base);
+        let len = |parts: &[u64], layout| codec.composite_len(parts, layout).unwrap();
+        assert!(
+            len(&[3, 14, 15, 9], CompositeLayout::Packed)
+                < len(&[3, 14, 15, 9], CompositeLayout::Varint)
+        );
+        assert!(
+            len(&[1, 1 << 40], CompositeLayout::Packed)
+                > len(&[1, 1 << 40], CompositeLayout::Varint)
+        );
+        assert_eq!(
+            codec.composite_len(&[1; MAX_COMPOSITE_PARTS + 1], CompositeLayout::Packed),
+            Err(Error::InvalidDataLength)
+        );
+        assert_eq!(
+            codec.encode_composite_with(&[1; MAX_COMPOSITE_PARTS + 1], CompositeLayout::Packed),
+            Err(Error::InvalidDataLength)
+        );
+        assert_eq!(
+            codec.composite_plaintext(&[3, 14, 15, 9], CompositeLayout::Packed),
+            Ok(vec![0xb0, 0xe3, 0x9f, 0])
+        );
+
+        // Malformed packed plaintexts with a valid MAC are rejected.
+        let malformed: [&[u8]; 3] = [
+            &[0xc0, 0x01, 0x00],
+            &[0xb1, 0xff, 0xff],
+            &[0x80, 0x01, 0x10],
+        ];
+        for plaintext in malformed {
+            let encoded = codec.encode_payload(COMPOSITE_TWEAK, plaintext);
+            assert_eq!(
+                codec.decode_composite(&encoded),
+                Err(Error::InvalidDataLength)
+            );
+        }
     }
 
     #[test]
-    fn test_decode_errors() {
+    fn test_ranges() {
         let codec = Codec::new("test", &Config::new(b"Test key here"));
+        for (start, count) in [
+            (0, 0),
+            (1, 1),
+            (1000, 50),
+            (u64::MAX - 10, 10),
+            (0, u64::MAX),
+        ] {
+            let encoded = codec.encode_range(start, count).unwrap();
+            assert_eq!(codec.decode_range(&encoded), Ok(start..start + count));
+            assert!(codec.decode(&encoded).is_err());
+            assert!(codec.decode_u128(&encoded).is_err());
+        }
+        assert_eq!(codec.encode_range(u64::MAX, 1), Err(Error::IdOutOfRange));
+        let ranged = Codec::new("test", &Config::new(b"Test key here").allowed_range(1..100));
+        assert!(ranged.encode_range(1, 99).is_ok());
+        assert!(ranged.encode_range(99, 0).is_ok());
+        for (start, count) in [(0, 10), (90, 11), (100, 0)] {
+            assert_eq!(ranged.encode_range(start, count), Err(Error::IdOutOfRange));
+        }
+        let overflowing = codec.encode_wide(RANGE_TWEAK, 1 << 64 | u64::MAX as u128);
+        assert_eq!(codec.decode_range(&overflowing), Err(Error::IdOutOfRange));
+        assert!(codec.decode_range(&codec.encode_u128(5)).is_err());
+        assert!(codec.decode_range("test_hHLBCl4rZ3u").is_err());
+    }
 
+    #[test]
+    fn test_allowed_range() {
+        let config = Config::new(b"Test key here").allowed_range(1..1000);
+        let codec = Codec::new("test", &config);
+        assert_eq!(codec.try_encode(123).as_deref(), Ok("test_hHLBCl4rZ3u"));
+        assert_eq!(codec.try_encode(0), Err(Error::IdOutOfRange));
+        assert_eq!(codec.try_encode(1000), Err(Error::IdOutOfRange));
         assert_eq!(
-            codec.decode("hHLBCl4rZ3u"),
-            Err(Error::InvalidPrefix {
-                received: "".to_string(),
-                expected: "test_".to_string()
-            })
+            codec.encode_i64(-1, NegativeIds::Reject),
+            Err(Error::NegativeId)
         );
+        assert_eq!(
+            codec.encode_i64(5000, NegativeIds::Reject),
+            Err(Error::IdOutOfRange)
+        );
+        assert!(
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| codec.encode(5000))).is_err()
+        );
+
+        // Decoding is not restricted, so that existing tokens keep working.
+        let unrestricted = Codec::new("test", &Config::new(b"Test key here"));
+        assert_eq!(codec.decode(&unrestricted.encode(5000)), Ok(5000));
+    }
+
+    #[test]
+    fn test_bind_parameters() {
+        let config = Config::new(b"Test key here").bind_parameters(true);
+        let codec = Codec::new("test", &config);
+        let token = codec.encode(123);
+        assert_ne!(token, "test_hHLBCl4rZ3u");
+        assert_eq!(codec.decode(&token), Ok(123));
+        assert_eq!(codec.decode("test_hHLBCl4rZ3u"), Err(Error::IncorrectMAC));
 
+        let padded = Codec::new("test", &config.clone().zero_pad_length(3).unwrap());
+        assert_eq!(padded.decode(&token), Err(Error::ParameterMismatch));
+        let padded = Codec::new("test", &config.clone().zero_pad_length(5).unwrap());
+        assert_eq!(padded.decode(&token), Err(Error::ParameterMismatch));
+        let longer = Codec::new("test", &config.clone().hmac_length(6).unwrap());
+        assert_eq!(longer.decode(&token), Err(Error::ParameterMismatch));
+        let shorter = Codec::new("test", &config.clone().hmac_length(2).unwrap());
+        assert_eq!(shorter.decode(&token), Err(Error::ParameterMismatch));
         assert_eq!(
-            codec.decode("_hHLBCl4rZ3u"),
-            Err(Error::InvalidPrefix {
-                received: "_".to_string(),
-                expected: "test_".to_string()
-            })
+            codec.decode(&shorter.encode(123)),
+            Err(Error::ParameterMismatch)
         );
 
+        // Structured tokens are bound too.
+        let token = crate::TokenBuilder::new(123).encode(&codec);
         assert_eq!(
-            codec.decode("wrong_hHLBCl4rZ3u"),
-            Err(Error::InvalidPrefix {
-                received: "wrong_".to_string(),
-                expected: "test_".to_string()
-            })
+            crate::TokenFields::decode(&padded, &token),
+            Err(Error::ParameterMismatch)
         );
+    }
 
+    #[test]
+    fn test_reauthenticate() {
+        let config = Config::new(b"Test key here");
+        let long = Codec::new("test", &config.clone().hmac_length(8).unwrap());
+        let short = Codec::new("test", &config);
+
+        let token = long.encode(123);
         assert_eq!(
-            codec.decode("test_iHLBCl4rZ3u"),
-            Err(Error::SentinelMismatch {
-                received: 2,
-                expected: SENTINEL,
-            })
+            long.reauthenticate(&token, &short).unwrap(),
+            "test_hHLBCl4rZ3u"
+        );
+        assert_eq!(
+            short.reauthenticate("test_hHLBCl4rZ3u", &long).unwrap(),
+            token
+        );
+        assert_eq!(
+            long.reauthenticate("test_hHLBCl4rZ3u", &short),
+            Err(Error::InvalidDataLength)
         );
+    }
 
-        // Tampering with any part gives a MAC error.
-        assert_eq!(codec.decode("test_hHLBCl4rZ3v"), Err(Error::IncorrectMAC));
-        assert_eq!(codec.decode("test_hHMBCl4rZ3u"), Err(Error::IncorrectMAC));
+    #[test]
+    fn test_bytes16() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        for input in [0, 1, 2, 123, u64::MAX] {
+            let bytes = codec.encode_bytes16(input);
+            let token = codec.encode(input);
+            assert_eq!(
+                u128::from_be_bytes(bytes),
+                base62::decode(&token[5..]).unwrap()
+            );
+            assert_eq!(codec.decode_bytes16(bytes), Ok(input));
+        }
 
-        // Invalid characters aren't allowed.
-        assert_eq!(codec.decode("test_hHLBCl+rZ3u"), Err(Error::DecodingFailed));
+        let mut bytes = codec.encode_bytes16(123);
+        bytes[15] ^= 1;
+        assert_eq!(codec.decode_bytes16(bytes), Err(Error::IncorrectMAC));
+    }
 
-        // And just to validate the above, check that the correct string does decode.
-        assert_eq!(codec.decode("test_hHLBCl4rZ3u"), Ok(123));
+    #[test]
+    fn test_bucket() {
+        let config = Config::new(b"Test key here");
+        let codec = Codec::new("test", &config);
+        let mut counts = [0; 16];
+        for num in 0..4_000 {
+            let bucket = codec.bucket(num, 16);
+            counts[bucket as usize] += 1;
+            assert_eq!(codec.bucket(num, 64) / 4, bucket);
+            assert_eq!(codec.bucket(num, 1), 0);
+        }
+        assert!(
+            counts.iter().all(|&count| (150..350).contains(&count)),
+            "{:?}",
+            counts
+        );
+        for num in [u64::MAX, 1 << 40] {
+            assert!(codec.bucket(num, 1000) < 1000);
+        }
+
+        // Buckets don't depend on the nonce, and they do depend on the key.
+        let nonced = Codec::new("test", &config.clone().nonce_length(2).unwrap());
+        let other = Codec::new("test", &Config::new(b"Other key"));
+        let buckets = |codec: &Codec| (0..100).map(|num| codec.bucket(num, 1 << 20)).collect();
+        let expected: Vec<u64> = buckets(&codec);
+        assert_eq!(buckets(&nonced), expected);
+        assert_ne!(buckets(&other), expected);
+
+        // With order-preserving tokens, buckets follow the order of the IDs, and realistic IDs
+        // are spread by magnitude.
+        let ordered = Codec::new("test", &config.clone().order_preserving(true));
+        assert_eq!(ordered.bucket(0, 4), 0);
+        assert_eq!(ordered.bucket(1 << 32, 2), 1);
+        assert_eq!(ordered.bucket(u64::MAX, 4), 3);
+        let ids: Vec<u64> = (0..56).flat_map(|shift| [1 << shift, 3 << shift]).collect();
+        let mut buckets: Vec<u64> = ids.iter().map(|&num| ordered.bucket(num, 64)).collect();
+        assert!(buckets.windows(2).all(|w| w[0] <= w[1]));
+        buckets.dedup();
+        assert_eq!(buckets, (0..=56).collect::<Vec<_>>());
+        for &num in &ids {
+            assert_eq!(ordered.bucket(num, 64) / 4, ordered.bucket(num, 16));
+        }
+
+        // Within an allowed range, they are spread linearly.
+        let ranged = config.order_preserving(true).allowed_range(1000..2000);
+        let ranged = Codec::new("test", &ranged);
+        let mut counts = [0; 10];
+        for num in 1000..2000 {
+            counts[ranged.bucket(num, 10) as usize] += 1;
+        }
+        assert_eq!(counts, [100; 10]);
     }
 
     #[test]
-    fn test_random_roundtrips() {
-        let codec = Codec::new("test", &Config::new(b"Test key here"));
+    #[should_panic(expected = "The number of buckets should be positive")]
+    fn test_bucket_zero() {
+        Codec::new("test", &Config::new(b"Test key here")).bucket(1, 0);
+    }
+
+    #[test]
+    fn test_order_preserving() {
+        let config = Config::new(b"Test key here").order_preserving(true);
+        let codec = Codec::new("test", &config);
         let mut rng = rand::thread_rng();
-        let range = Uniform::new(0u64, u64::MAX);
+        let mut numbers: Vec<u64> = (0..1_000).map(|_| rng.gen()).collect();
+        numbers.extend([0, 1, 2, 123, u64::MAX - 1, u64::MAX]);
+        numbers.sort();
 
-        for _ in 0..10_000 {
-            let number = rng.sample(range);
-            let encoded = codec.encode(number);
-            let decoded = codec.decode(&encoded).expect("Decoding failed");
+        let encoded: Vec<String> = numbers.iter().map(|&n| codec.encode(n)).collect();
+        let mut sorted = encoded.clone();
+        sorted.sort();
+        assert_eq!(encoded, sorted);
+        assert!(encoded.iter().all(|e| e.len() == encoded[0].len()));
 
-            assert_eq!(decoded, number, "Failed at number: {}", number);
+        for (number, token) in numbers.iter().zip(&encoded) {
+            assert_eq!(codec.decode(token), Ok(*number));
         }
+
+        // Tampering is still detected.
+        let token = codec.encode(123);
+        let mut tampered = token.clone();
+        let last = if tampered.ends_with('a') { "b" } else { "a" };
+        tampered.replace_range(tampered.len() - 1.., last);
+        assert_eq!(codec.decode(&tampered), Err(Error::IncorrectMAC));
+        assert_eq!(
+            codec.decode(&token[..token.len() - 1]),
+            Err(Error::InvalidDataLength)
+        );
+
+        // The binary form sorts in the same order too.
+        let bytes: Vec<[u8; 16]> = numbers.iter().map(|&n| codec.encode_bytes16(n)).collect();
+        assert!(bytes.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(codec.decode_bytes16(bytes[0]), Ok(numbers[0]));
+
+        // Without a MAC the tokens would be the raw IDs, so it is required.
+        let unauthenticated = Config::new(b"Test key here").hmac_length(0).unwrap();
+        assert!(matches!(
+            Codec::try_new("test", &unauthenticated.clone().order_preserving(true)),
+            Err(ConfigError::InvalidMacLength)
+        ));
+        assert!(matches!(
+            config.hmac_length(0),
+            Err(ConfigError::InvalidMacLength)
+        ));
+
+        // The default mode is unaffected.
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        assert_eq!(codec.encode(123), "test_hHLBCl4rZ3u");
     }
 }
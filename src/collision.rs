@@ -0,0 +1,140 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::Codec;
+
+/// Checks that legacy external IDs can't be mistaken for tokens, for migrating from random
+/// IDs to tokens in the same namespace (e.g. the same URL path or API field).
+///
+/// A legacy ID collides if the codec decodes it to a raw ID in the planned range, as then it
+/// would be impossible to tell whether a client means the legacy object or the new one.  Since
+/// decoding is the exact inverse of encoding, this doesn't need to encode the range.  The
+/// limiter and hooks of the codec are not used.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{CollisionChecker, Codec, Config};
+///
+/// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+/// let checker = CollisionChecker::new(&codec, 1..=1_000_000);
+///
+/// let report = checker.check(["example_4f9a1c0b", "example_VgwPy6rwatl"]);
+/// assert_eq!(report.checked, 2);
+/// assert_eq!(report.collisions.len(), 1);
+/// assert_eq!(report.collisions[0].id, 12345);
+/// assert!(!report.is_clean());
+/// ```
+pub struct CollisionChecker<'a> {
+    codec: &'a Codec,
+    range: RangeInclusive<u64>,
+}
+
+/// The result of `CollisionChecker::check`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CollisionReport {
+    /// Number of legacy IDs checked.
+    pub checked: usize,
+    /// The legacy IDs which collide, in the order they were given.
+    pub collisions: Vec<Collision>,
+}
+
+/// A legacy ID which is also a token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Collision {
+    pub legacy_id: String,
+    /// The raw ID the token decodes to.
+    pub id: u64,
+}
+
+impl<'a> CollisionChecker<'a> {
+    /// Creates a checker for tokens of `codec` encoding raw IDs in `range`.
+    pub fn new(codec: &'a Codec, range: RangeInclusive<u64>) -> Self {
+        CollisionChecker { codec, range }
+    }
+
+    /// Checks each of `legacy_ids`.
+    pub fn check<I, S>(&self, legacy_ids: I) -> CollisionReport
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut report = CollisionReport::default();
+        for legacy_id in legacy_ids {
+            let legacy_id = legacy_id.as_ref();
+            report.checked += 1;
+            if let Some(id) = self.collision(legacy_id) {
+                report.collisions.push(Collision {
+                    legacy_id: legacy_id.to_string(),
+                    id,
+                });
+            }
+        }
+        report
+    }
+
+    /// Returns the raw ID `legacy_id` decodes to, if it is in the range.
+    pub fn collision(&self, legacy_id: &str) -> Option<u64> {
+        let (id, _) = self.codec.decode_unlimited(legacy_id).ok()?;
+        self.range.contains(&id).then_some(id)
+    }
+}
+
+impl CollisionReport {
+    /// Returns `true` if no legacy ID collides.
+    pub fn is_clean(&self) -> bool {
+        self.collisions.is_empty()
+    }
+}
+
+impl fmt::Display for CollisionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} legacy IDs collide",
+            self.collisions.len(),
+            self.checked
+        )?;
+        for collision in &self.collisions {
+            writeln!(f, "{} decodes to {}", collision.legacy_id, collision.id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, DecodeLimiter};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_check() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"))
+            .with_limiter(Arc::new(DecodeLimiter::new(0.0, 1)));
+        let legacy: Vec<String> = (0..100)
+            .map(|i| format!("test_{:011x}", i * 7919))
+            .chain([codec.encode(123), codec.encode(5000), "test_".to_string()])
+            .collect();
+
+        let report = CollisionChecker::new(&codec, 0..=1000).check(&legacy);
+        assert_eq!(report.checked, 103);
+        assert_eq!(
+            report.collisions,
+            vec![Collision {
+                legacy_id: "test_hHLBCl4rZ3u".to_string(),
+                id: 123,
+            }]
+        );
+        assert_eq!(
+            report.to_string(),
+            "1 of 103 legacy IDs collide\ntest_hHLBCl4rZ3u decodes to 123\n"
+        );
+
+        let report = CollisionChecker::new(&codec, 0..=u64::MAX).check(&legacy);
+        assert_eq!(report.collisions.len(), 2);
+        assert!(CollisionChecker::new(&codec, 0..=100)
+            .check(&legacy)
+            .is_clean());
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::field::{get_or_create_codec, try_get_or_create_codec, UNENCODED};
+use crate::{Error, TypeMarker};
+
+/// A generic type-safe field of `N` IDs encoded as one token, such as `(tenant_id, object_id)`
+/// in multi-tenant APIs.
+///
+/// With Serde, the IDs are encrypted with `Codec::encode_composite` using the marker's codec,
+/// and tokens of another number of IDs are rejected on deserialization.  Tokens in the
+/// `CompositeLayout::Packed` layout are accepted too.  `N` can be at most `MAX_COMPOSITE_PARTS`,
+/// otherwise serialization fails.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{CompositeField, Config, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct DocumentIdMarker;
+/// impl TypeMarker for DocumentIdMarker {
+///     fn name() -> &'static str { "doc" }
+/// }
+///
+/// type DocumentId = CompositeField<DocumentIdMarker, 2>;
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let id = DocumentId::from([42, 12345]);
+/// let json = serde_json::to_string(&id).unwrap();
+/// assert!(json.starts_with("\"doc_"));
+///
+/// let decoded: DocumentId = serde_json::from_str(&json).unwrap();
+/// let [tenant_id, document_id] = decoded.ids();
+/// assert_eq!((tenant_id, document_id), (42, 12345));
+/// ```
+#[derive(Debug)]
+pub struct CompositeField<T: TypeMarker, const N: usize> {
+    ids: [u64; N],
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T: TypeMarker, const N: usize> CompositeField<T, N> {
+    /// Returns the raw IDs.
+    pub fn ids(&self) -> [u64; N] {
+        self.ids
+    }
+}
+
+// The standard traits are implemented manually, as for `Field<T>`.
+impl<T: TypeMarker, const N: usize> Clone for CompositeField<T, N> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: TypeMarker, const N: usize> Copy for CompositeField<T, N> {}
+
+impl<T: TypeMarker, const N: usize> PartialEq for CompositeField<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ids == other.ids
+    }
+}
+
+impl<T: TypeMarker, const N: usize> Eq for CompositeField<T, N> {}
+
+impl<T: TypeMarker, const N: usize> Hash for CompositeField<T, N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ids.hash(state);
+    }
+}
+
+impl<T: TypeMarker, const N: usize> From<[u64; N]> for CompositeField<T, N> {
+    fn from(ids: [u64; N]) -> Self {
+        CompositeField {
+            ids,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: TypeMarker, const N: usize> From<CompositeField<T, N>> for [u64; N] {
+    /// Returns the raw IDs.
+    fn from(field: CompositeField<T, N>) -> Self {
+        field.ids
+    }
+}
+
+impl<T: TypeMarker, const N: usize> Serialize for CompositeField<T, N> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let codec = get_or_create_codec::<T>();
+        let encoded = codec
+            .encode_composite(&self.ids)
+            .map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&encoded)
+    }
+}
+
+impl<'de, T: TypeMarker, const N: usize> Deserialize<'de> for CompositeField<T, N> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        let codec = get_or_create_codec::<T>();
+        let ids = codec
+            .decode_composite(&encoded)
+            .map_err(serde::de::Error::custom)?;
+        let ids = <[u64; N]>::try_from(ids)
+            .map_err(|_| serde::de::Error::custom(Error::InvalidDataLength))?;
+        Ok(Self::from(ids))
+    }
+}
+
+impl<T: TypeMarker, const N: usize> fmt::Display for CompositeField<T, N> {
+    /// Formats the field as it is serialized, so that the raw IDs aren't revealed.
+    ///
+    /// Writes `<unencoded id>` if the global config has not been set or `N` is above
+    /// `MAX_COMPOSITE_PARTS`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match try_get_or_create_codec::<T>().map(|codec| codec.encode_composite(&self.ids)) {
+            Some(Ok(encoded)) => f.write_str(&encoded),
+            _ => f.write_str(UNENCODED),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[derive(Debug)]
+    struct DocumentIdMarker;
+    impl TypeMarker for DocumentIdMarker {
+        fn name() -> &'static str {
+            "doc"
+        }
+    }
+
+    #[test]
+    fn test_composite_field() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let id = CompositeField::<DocumentIdMarker, 2>::from([42, 12345]);
+        let json = serde_json::to_value(id).unwrap();
+        assert_eq!(json, id.to_string());
+        assert_eq!(serde_json::from_value(json.clone()).ok(), Some(id));
+
+        // Tokens of another number of IDs are rejected.
+        assert!(serde_json::from_value::<CompositeField<DocumentIdMarker, 3>>(json).is_err());
+        let single = serde_json::to_value(CompositeField::<DocumentIdMarker, 1>::from([42]));
+        assert!(
+            serde_json::from_value::<CompositeField<DocumentIdMarker, 2>>(single.unwrap()).is_err()
+        );
+
+        let too_many = CompositeField::<DocumentIdMarker, 5>::from([1; 5]);
+        assert!(serde_json::to_value(too_many).is_err());
+    }
+}
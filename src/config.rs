@@ -1,47 +1,196 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+#[cfg(feature = "test-chaos")]
 use once_cell::sync::Lazy;
-use std::sync::Mutex;
+use sha2::{Digest, Sha256};
+
+use crate::field;
+use crate::global::Global;
+use crate::hkdf::Hkdf;
+use crate::roundtrip::{self, RoundtripReport};
+#[cfg(feature = "shared-keys")]
+use crate::SharedKeyCache;
+use crate::{Alphabet, CodecRegistry, ContextTarget, Salt};
+
+// 62^4 nonces fit in a u32.
+pub(crate) const MAX_NONCE_LENGTH: u8 = 4;
+
+static GLOBAL_CONFIG: Global<Option<Config>> = Global::new(None);
+
+// A random key used in place of a missing global config with the `test-chaos` feature.
+#[cfg(feature = "test-chaos")]
+static EPHEMERAL_KEY: Lazy<[u8; 32]> = Lazy::new(|| {
+    let mut key = [0u8; 32];
+    getrandom::getrandom(&mut key).expect("System random number generator should work");
+    eprintln!(
+        "cryptid: test-chaos mode, no key configured; using an ephemeral random key, \
+         tokens are only valid in this process"
+    );
+    key
+});
 
-static GLOBAL_CONFIG: Lazy<Mutex<Option<Config>>> = Lazy::new(|| Mutex::new(None));
+/// Which key the global configuration uses, returned by `Config::key_mode`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyMode {
+    /// A key has been set with `Config::set_global`.
+    Configured,
+    /// No key has been set, and a random per-process key is used (`test-chaos` feature).
+    Ephemeral,
+    /// No key has been set.
+    Unset,
+}
+
+impl KeyMode {
+    // Returns the mode with or without a configured key.
+    fn of(configured: bool) -> KeyMode {
+        if configured {
+            KeyMode::Configured
+        } else if cfg!(feature = "test-chaos") {
+            KeyMode::Ephemeral
+        } else {
+            KeyMode::Unset
+        }
+    }
+}
 
 /// Configuring the cryptid library.
 #[derive(Clone)]
-pub struct Config<'a> {
+pub struct Config {
+    allow_unauthenticated: bool,
+    pub(crate) allowed_range: Option<Range<u64>>,
+    pub(crate) alphabet: Alphabet,
+    pub(crate) bind_parameters: bool,
     pub(crate) hmac_length: u8,
-    pub(crate) key: &'a [u8],
+    pub(crate) key: Arc<[u8]>,
+    #[cfg(feature = "shared-keys")]
+    pub(crate) key_cache: Option<Arc<SharedKeyCache>>,
+    pub(crate) key_version: u8,
+    pub(crate) nonce_length: u8,
+    pub(crate) order_preserving: bool,
+    pub(crate) previous_keys: Vec<(u8, Arc<[u8]>)>,
+    pub(crate) salt: Option<Salt>,
+    strict: bool,
+    pub(crate) target: Option<ContextTarget>,
+    pub(crate) type_hints: Option<TypeHints>,
     pub(crate) zero_pad_length: u8,
 }
 
+/// The registry of `Config::type_hints`.
+///
+/// Codecs may have hooks which are not unwind safe, but the registry is only read, so it
+/// doesn't keep configs from being unwind safe.
+#[derive(Clone)]
+pub(crate) struct TypeHints(pub(crate) Arc<CodecRegistry>);
+
+impl std::panic::RefUnwindSafe for TypeHints {}
+impl std::panic::UnwindSafe for TypeHints {}
+
 #[derive(Debug)]
 pub enum ConfigError {
     InvalidMacLength,
+    InvalidNonceLength,
+    InvalidPrefix,
+    InvalidSalt,
     InvalidVersion,
     InvalidZeroPadLength,
+    UnauthenticatedTokens,
+    UnsatisfiableTarget,
+    UnsupportedAlphabet,
 }
 
-impl<'a> Config<'a> {
+impl Config {
     /// Creates a new configuration with the given master `key` and other settings in
-    /// default values.
+    /// default values.  The config keeps its own copy of the key, so it can be e.g. fetched
+    /// from a secrets manager at runtime.
     /// - `mac_length` defaults to 4, which is large enough to make guessing impractical
     ///   but still keeps the strings relatively short. High security applications may want
     ///   to use a higher value.
     /// - `zero_pad_length` defaults to 4, which is large enough for most applications
     ///   to never see encoded strings increase in size, while still keeping the strings
     ///   relatively short.
-    pub fn new(key: &'a [u8]) -> Self {
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// // E.g. fetched from a secrets manager.
+    /// let key: Vec<u8> = b"your-secure-key".to_vec();
+    /// Config::set_global(Config::new(key));
+    ///
+    /// let codec = Codec::new("example", &Config::global().unwrap());
+    /// assert_eq!(codec.encode(12345), "example_VgwPy6rwatl");
+    /// ```
+    pub fn new(key: impl AsRef<[u8]>) -> Self {
         Config {
+            allow_unauthenticated: false,
+            allowed_range: None,
+            alphabet: Alphabet::Base62,
+            bind_parameters: false,
             hmac_length: 4,
-            key,
+            key: Arc::from(key.as_ref()),
+            #[cfg(feature = "shared-keys")]
+            key_cache: None,
+            key_version: 0,
+            nonce_length: 0,
+            order_preserving: false,
+            previous_keys: Vec::new(),
+            salt: None,
+            strict: false,
+            target: None,
+            type_hints: None,
             zero_pad_length: 4,
         }
     }
 
     /// Sets the number of bytes in the HMAC.
-    /// The value must be between 0 and 8.
+    /// The value must be between 0 and 8, and not 0 in strict or order-preserving mode.
     pub fn hmac_length(mut self, hmac_length: u8) -> Result<Self, ConfigError> {
-        if hmac_length > 8 {
+        if hmac_length > 8 || (hmac_length == 0 && self.order_preserving) {
             Err(ConfigError::InvalidMacLength)
         } else {
             self.hmac_length = hmac_length;
+            self.check_strict()
+        }
+    }
+
+    /// Enables strict mode, which forbids configurations where tokens are not authenticated.
+    ///
+    /// With `hmac_length` 0, tokens have no MAC, and any string of the right form decodes into
+    /// some number.  In strict mode this is an error, unless `allow_unauthenticated_tokens`
+    /// has been called, whether `hmac_length` is set before or after enabling strict mode.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Config, ConfigError};
+    ///
+    /// let config = Config::new(b"your-secure-key").strict().unwrap();
+    /// assert!(matches!(
+    ///     config.clone().hmac_length(0),
+    ///     Err(ConfigError::UnauthenticatedTokens)
+    /// ));
+    /// assert!(config.allow_unauthenticated_tokens().hmac_length(0).is_ok());
+    ///
+    /// let config = Config::new(b"your-secure-key").hmac_length(0).unwrap();
+    /// assert!(config.strict().is_err());
+    /// ```
+    pub fn strict(mut self) -> Result<Self, ConfigError> {
+        self.strict = true;
+        self.check_strict()
+    }
+
+    /// Allows `hmac_length` 0 in strict mode.
+    pub fn allow_unauthenticated_tokens(mut self) -> Self {
+        self.allow_unauthenticated = true;
+        self
+    }
+
+    fn check_strict(self) -> Result<Self, ConfigError> {
+        if self.strict && self.hmac_length == 0 && !self.allow_unauthenticated {
+            Err(ConfigError::UnauthenticatedTokens)
+        } else {
             Ok(self)
         }
     }
@@ -57,15 +206,391 @@ impl<'a> Config<'a> {
         }
     }
 
+    /// Sets the number of random digits added to each token (0 by default, at most 4).
+    ///
+    /// With a nonce, encoding the same ID twice gives different tokens, which all decode to
+    /// the ID, so that clients can't tell from the tokens that two responses refer to the same
+    /// object.  The nonce is mixed into the encryption and the MAC.  Each digit makes tokens
+    /// one character longer and multiplies the number of tokens of an ID by the radix of the
+    /// alphabet, e.g. by 62 with base62.  Nonces are not used in the order-preserving mode or
+    /// in the binary forms, which are deterministic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let config = Config::new(b"your-secure-key").nonce_length(3).unwrap();
+    /// let codec = Codec::new("example", &config);
+    /// let tokens: Vec<String> = (0..3).map(|_| codec.encode(12345)).collect();
+    ///
+    /// assert!(tokens[0] != tokens[1] || tokens[1] != tokens[2]);
+    /// assert!(tokens.iter().all(|token| codec.decode(token) == Ok(12345)));
+    /// ```
+    pub fn nonce_length(mut self, digits: u8) -> Result<Self, ConfigError> {
+        if digits > MAX_NONCE_LENGTH {
+            return Err(ConfigError::InvalidNonceLength);
+        }
+        self.nonce_length = digits;
+        Ok(self)
+    }
+
+    /// Enables or disables the order-preserving token mode (disabled by default).
+    ///
+    /// In this mode, encoded tokens of the same type sort lexicographically in the same order
+    /// as the raw IDs, which allows range scans by external ID.  The tokens have a fixed length
+    /// which depends on `hmac_length`, and `zero_pad_length` is not used.  The MAC is what
+    /// hides the IDs in this mode, so `hmac_length` must not be 0: setting it to 0 fails, and
+    /// creating a codec with both fails with `ConfigError::InvalidMacLength`.
+    ///
+    /// **Security note:** This mode does not hide the raw IDs.  The tokens are still
+    /// authenticated, but a token is the raw ID times a secret multiplier plus a smaller
+    /// remainder, so the differences between the tokens of a few nearby IDs reveal the
+    /// multiplier, and with it the raw ID behind every token.  Only use it when the raw IDs
+    /// may be public and the ordering is needed.
+    pub fn order_preserving(mut self, enabled: bool) -> Self {
+        self.order_preserving = enabled;
+        self
+    }
+
+    /// Binds tokens to the parameters they were created with (disabled by default).
+    ///
+    /// When enabled, a fingerprint of the token format parameters (`hmac_length`,
+    /// `zero_pad_length`, order-preserving mode, `nonce_length`, the alphabet and the format
+    /// version) is mixed into the MAC.
+    /// A token then only decodes with exactly the same parameters, and decoding a token
+    /// created with different parameters fails with `Error::ParameterMismatch` instead of
+    /// occasionally decoding into a wrong value.  The strength of the MAC is not affected.
+    ///
+    /// Enabling this changes all tokens, so it should be decided before tokens are exposed.
+    /// It has no effect when `hmac_length` is 0, in order-preserving mode, or on
+    /// `Codec::encode_uuid`.
+    pub fn bind_parameters(mut self, enabled: bool) -> Self {
+        self.bind_parameters = enabled;
+        self
+    }
+
+    /// Sets the alphabet of the tokens (`Alphabet::Base62` by default).  Fails if the digits
+    /// of a custom alphabet are not valid, if a key version is not a single digit of the
+    /// alphabet, or if the alphabet is not allowed in the target of the config.
+    ///
+    /// Changing the alphabet changes all tokens, so it should be decided before tokens are
+    /// exposed.  Individual types can use another alphabet with `TypeMarker::alphabet`.
+    pub fn alphabet(mut self, alphabet: Alphabet) -> Result<Self, ConfigError> {
+        if !alphabet.is_valid() {
+            return Err(ConfigError::UnsupportedAlphabet);
+        }
+        let max_version = alphabet.radix() - 1;
+        if self.key_version > max_version
+            || self.previous_keys.iter().any(|&(v, _)| v > max_version)
+        {
+            return Err(ConfigError::InvalidVersion);
+        }
+        if self
+            .target
+            .is_some_and(|target| !target.allows_alphabet(&alphabet))
+        {
+            return Err(ConfigError::UnsatisfiableTarget);
+        }
+        self.alphabet = alphabet;
+        Ok(self)
+    }
+
+    /// Restricts the IDs which can be encoded to `range` (all IDs by default), so that values
+    /// such as timestamps or hashes passed as IDs by mistake never become tokens.
+    ///
+    /// `Codec::try_encode` and `Codec::encode_range` return `Error::IdOutOfRange` for other
+    /// IDs, while `Codec::encode` and the other encode methods which can't fail panic.
+    /// Serializing a `Field<T>` fails, and formatting it writes a placeholder.  The range
+    /// doesn't apply to 128 bit IDs, UUID primary keys or composite tokens, whose values are
+    /// not IDs of this range.  Decoding is not affected.  Individual types can have their own
+    /// range with `TypeMarker::allowed_range`.
+    pub fn allowed_range(mut self, range: Range<u64>) -> Self {
+        self.allowed_range = Some(range);
+        self
+    }
+
+    /// Constrains tokens to fit in `target`, e.g. in subdomains (no target by default).  See
+    /// `ContextTarget`.
+    ///
+    /// The alphabet is changed to base32 if the target doesn't allow the current one, which
+    /// fails if a key version is not a single digit of it.  Creating a codec fails with
+    /// `ConfigError::UnsatisfiableTarget` if its tokens could be too long or its name is not
+    /// allowed in the target.
+    pub fn target(mut self, target: ContextTarget) -> Result<Self, ConfigError> {
+        if !target.allows_alphabet(&self.alphabet) {
+            self = self.alphabet(Alphabet::Base32)?;
+        }
+        self.target = Some(target);
+        Ok(self)
+    }
+
+    /// Sets the version of the key, between 1 and 61, for rotating keys (0, meaning no version,
+    /// by default).  With an alphabet of fewer digits, the version must be a single digit of
+    /// it, e.g. at most 9 with `Alphabet::Decimal`.
+    ///
+    /// Tokens of a versioned key embed the version as the first character after the prefix,
+    /// so that `Codec::decode` can pick the right key among the ones added with
+    /// `previous_key`.  Only numeric tokens are versioned; the other formats always use the
+    /// current key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config};
+    ///
+    /// let old_config = Config::new(b"your-secure-key");
+    /// let old_token = Codec::new("example", &old_config).encode(12345);
+    ///
+    /// let config = Config::new(b"your-new-secure-key")
+    ///     .key_version(2)
+    ///     .unwrap()
+    ///     .previous_key(0, b"your-secure-key")
+    ///     .unwrap();
+    /// let codec = Codec::new("example", &config);
+    ///
+    /// assert!(codec.encode(12345).starts_with("example_2"));
+    /// assert_eq!(codec.decode(&old_token), Ok(12345));
+    /// assert_eq!(codec.decode(&codec.encode(12345)), Ok(12345));
+    /// ```
+    pub fn key_version(mut self, version: u8) -> Result<Self, ConfigError> {
+        if version >= self.alphabet.radix() || self.previous_keys.iter().any(|&(v, _)| v == version)
+        {
+            return Err(ConfigError::InvalidVersion);
+        }
+        self.key_version = version;
+        Ok(self)
+    }
+
+    /// Adds a previous key with its version, so that tokens encoded with it still decode after
+    /// the key has been rotated.  Version 0 is for tokens created without a version.  The
+    /// other parameters of the config apply to the previous keys too.
+    ///
+    /// Each previous key is an additional chance for a forged token to decode, so previous
+    /// keys should be removed once their tokens are no longer in use.  The version must be
+    /// a single digit of the alphabet, at most 61, and differ from the versions of the other
+    /// keys.
+    pub fn previous_key(mut self, version: u8, key: impl AsRef<[u8]>) -> Result<Self, ConfigError> {
+        if version >= self.alphabet.radix()
+            || version == self.key_version
+            || self.previous_keys.iter().any(|&(v, _)| v == version)
+        {
+            return Err(ConfigError::InvalidVersion);
+        }
+        self.previous_keys.push((version, Arc::from(key.as_ref())));
+        Ok(self)
+    }
+
+    /// Returns the config of the previous key `key` with `version`.
+    pub(crate) fn with_previous_key(&self, version: u8, key: &Arc<[u8]>) -> Self {
+        Config {
+            key: key.clone(),
+            key_version: version,
+            previous_keys: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Sets a per-deployment salt, which is mixed into all keys derived from the master key
+    /// (no salt by default).  See `Salt`.
+    pub fn salt(mut self, salt: Salt) -> Self {
+        self.salt = Some(salt);
+        self
+    }
+
+    /// Reads the derived keys of codecs from `cache`, which is shared by the processes on the
+    /// machine, and stores them there when they are first derived (no cache by default).  See
+    /// `SharedKeyCache`.
+    #[cfg(feature = "shared-keys")]
+    pub fn key_cache(mut self, cache: Arc<SharedKeyCache>) -> Self {
+        self.key_cache = Some(cache);
+        self
+    }
+
+    /// Names the type of tokens of other types in `Error::WrongType`, when they decode with
+    /// the codec of their prefix in `registry` (no hints by default).
+    ///
+    /// A token which merely has another valid prefix is reported as `WrongType` without a
+    /// hint, so that API errors can tell integrators they mixed up IDs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use cryptid_rs::{Codec, CodecRegistry, Config, Error};
+    ///
+    /// let config = Config::new(b"your-secure-key");
+    /// let mut registry = CodecRegistry::new();
+    /// registry.register("team", &config);
+    /// let team = registry.get("team").unwrap().encode(12345);
+    ///
+    /// let codec = Codec::new("user", &config.type_hints(Arc::new(registry)));
+    /// let error = codec.decode(&team).unwrap_err();
+    /// assert!(matches!(&error, Error::WrongType { hint: Some(name), .. } if name == "team"));
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "Token has prefix team_, expected user_; this looks like a `team` ID"
+    /// );
+    /// ```
+    pub fn type_hints(mut self, registry: Arc<CodecRegistry>) -> Self {
+        self.type_hints = Some(TypeHints(registry));
+        self
+    }
+
+    /// Encodes and decodes a sample of IDs with a codec of this config, as tokens, UUIDs and
+    /// 16 byte binary values, and returns a report of the IDs which didn't decode back to
+    /// themselves.  Call it at startup to catch a combination of settings and features which
+    /// breaks tokens before any are handed out.
+    ///
+    /// The sample is the same on every run: the boundaries of each bit width, such as
+    /// `u32::MAX` and `i64::MAX`, followed by `n` pseudorandom IDs.  The key versions of
+    /// `previous_key` are not checked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::Config;
+    ///
+    /// let config = Config::new(b"your-secure-key").nonce_length(2).unwrap();
+    /// config.verify_roundtrip_sample(1000).expect("Tokens should round trip");
+    /// Config::set_global(config);
+    /// ```
+    pub fn verify_roundtrip_sample(&self, n: usize) -> Result<(), RoundtripReport> {
+        let report = roundtrip::verify(self, n);
+        match report.is_clean() {
+            true => Ok(()),
+            false => Err(report),
+        }
+    }
+
+    /// Returns the key derivation function for keys derived from the master key.
+    pub(crate) fn hkdf(&self) -> Hkdf {
+        let salt = self.salt.as_ref().map(|salt| &salt.as_bytes()[..]);
+        Hkdf::new(salt, &self.key)
+    }
+
+    /// Returns a digest of the key and all other settings which affect codecs, which
+    /// identifies the codecs created with the config without revealing the key.
+    pub(crate) fn digest(&self) -> [u8; 32] {
+        // The registry of type hints is identified by its address, which stays unique while
+        // codecs created with it hold it.
+        let type_hints = self
+            .type_hints
+            .as_ref()
+            .map_or(0, |hints| Arc::as_ptr(&hints.0) as usize);
+        let info = format!(
+            "cryptid/digest/{}/{}/{}/{}/{}/{}/{}:{}/{:?}/{:?}/{:x}",
+            self.hmac_length,
+            self.zero_pad_length,
+            self.order_preserving,
+            self.bind_parameters,
+            self.key_version,
+            self.nonce_length,
+            self.alphabet.name(),
+            self.alphabet.digits(),
+            self.allowed_range,
+            self.target,
+            type_hints,
+        );
+        let mut digest = [0u8; 32];
+        self.hkdf()
+            .expand(info.as_bytes(), &mut digest)
+            .expect("Length 32 should be valid");
+        for (version, key) in &self.previous_keys {
+            let previous = self.with_previous_key(*version, key).digest();
+            digest = Sha256::new()
+                .chain_update(digest)
+                .chain_update(previous)
+                .finalize()
+                .into();
+        }
+        digest
+    }
+
     /// Sets the global configuration. This should be called before the `Field` type methods
-    /// are called.
-    pub fn set_global(config: Config<'static>) {
-        let mut global_config = GLOBAL_CONFIG.lock().unwrap();
-        *global_config = Some(config);
+    /// are called.  Setting it again replaces the previous config, and the codecs of `Field`
+    /// types cached with it are dropped, so that they are created again from `config`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Config, Field, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct ExampleIdMarker;
+    /// impl TypeMarker for ExampleIdMarker {
+    ///     fn name() -> &'static str { "example" }
+    /// }
+    ///
+    /// let id = Field::<ExampleIdMarker>::from(12345);
+    /// Config::set_global(Config::new(b"your-secure-key"));
+    /// assert_eq!(id.to_string(), "example_VgwPy6rwatl");
+    /// Config::set_global(Config::new(b"your-new-secure-key"));
+    /// assert_ne!(id.to_string(), "example_VgwPy6rwatl");
+    /// ```
+    pub fn set_global(config: Config) {
+        GLOBAL_CONFIG.with(|global_config| *global_config = Some(config));
+        field::clear_codec_cache();
+    }
+
+    /// Replaces the global configuration, returning the previous one.
+    pub(crate) fn replace_global(config: Option<Config>) -> Option<Config> {
+        GLOBAL_CONFIG.with(|global_config| std::mem::replace(global_config, config))
     }
 
     /// Accesses the global configuration, if set.
-    pub fn global() -> Option<Config<'static>> {
-        GLOBAL_CONFIG.lock().unwrap().clone()
+    ///
+    /// With the `test-chaos` feature, a configuration with a random per-process key is
+    /// returned if none has been set, so that tests and staging clients can't depend on the
+    /// tokens being stable.  A notice is printed to stderr when the key is generated.
+    pub fn global() -> Option<Config> {
+        GLOBAL_CONFIG
+            .with(|config| config.clone())
+            .or_else(Config::ephemeral)
+    }
+
+    // The config used in place of a missing global config: one with the random per-process
+    // key with the `test-chaos` feature, and none without it.
+    fn ephemeral() -> Option<Config> {
+        #[cfg(feature = "test-chaos")]
+        return Some(Config::new(*EPHEMERAL_KEY));
+        #[cfg(not(feature = "test-chaos"))]
+        None
+    }
+
+    /// Returns which key the global configuration uses.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Config, KeyMode};
+    ///
+    /// Config::set_global(Config::new(b"your-secure-key"));
+    /// assert_eq!(Config::key_mode(), KeyMode::Configured);
+    /// ```
+    pub fn key_mode() -> KeyMode {
+        KeyMode::of(GLOBAL_CONFIG.with(|config| config.is_some()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Codec;
+
+    // The global config itself is shared by all tests, so the fallback is tested directly.
+    #[test]
+    fn test_key_mode() {
+        assert_eq!(KeyMode::of(true), KeyMode::Configured);
+        if cfg!(feature = "test-chaos") {
+            assert_eq!(KeyMode::of(false), KeyMode::Ephemeral);
+            // The same random key is used for the whole process.
+            let encode = || Codec::new("test", &Config::ephemeral().unwrap()).encode(123);
+            assert_eq!(encode(), encode());
+            assert_ne!(encode(), "test_hHLBCl4rZ3u");
+        } else {
+            assert_eq!(KeyMode::of(false), KeyMode::Unset);
+            assert!(Config::ephemeral().is_none());
+        }
     }
 }
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::{Alphabet, Codec, Config, ConfigError, Salt};
+
+/// Settings of the library in a form which can be deserialized from a configuration file, e.g.
+/// with the `figment` or `config` crates.
+///
+/// The key is given in one of three forms: `{ hex = "..." }`, `{ base64 = "..." }` or
+/// `{ env = "VARIABLE" }`, which reads the key from the environment variable when the spec is
+/// deserialized.  The other settings are optional and default as in `Config::new`.
+/// `profiles` overrides the token format settings for codecs of given names.  An alphabet is
+/// given by its name, such as `"base32"`, by its digits, or as `"custom:"` followed by custom
+/// digits (see `Alphabet`).
+///
+/// The key is stored in the spec, and configs created from it hold copies of the key.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::ConfigSpec;
+///
+/// let spec: ConfigSpec = serde_json::from_str(r#"{
+///     "key": { "base64": "eW91ci1zZWN1cmUta2V5" },
+///     "hmac_length": 4,
+///     "profiles": {
+///         "invoice": { "hmac_length": 8 },
+///         "ref": { "alphabet": "decimal" }
+///     }
+/// }"#).unwrap();
+///
+/// let codec = spec.codec("example").unwrap();
+/// assert_eq!(codec.encode(12345), "example_VgwPy6rwatl");
+/// assert!(spec.codec("invoice").unwrap().encode(12345).len() > "invoice_VgwPy6rwatl".len());
+/// assert!(spec.codec("ref").unwrap().encode(12345)[4..].bytes().all(|c| c.is_ascii_digit()));
+/// ```
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigSpec {
+    key: Key,
+    #[serde(default)]
+    pub alphabet: Option<String>,
+    #[serde(default)]
+    pub bind_parameters: bool,
+    #[serde(default)]
+    pub hmac_length: Option<u8>,
+    #[serde(default)]
+    pub order_preserving: Option<bool>,
+    #[serde(default)]
+    pub profiles: HashMap<String, FormatSpec>,
+    /// A `Salt` in its text form.
+    #[serde(default)]
+    pub salt: Option<String>,
+    #[serde(default)]
+    pub strict: bool,
+    #[serde(default)]
+    pub zero_pad_length: Option<u8>,
+}
+
+/// Token format settings of a `ConfigSpec`, which can be overridden per codec name.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FormatSpec {
+    pub alphabet: Option<String>,
+    pub hmac_length: Option<u8>,
+    pub order_preserving: Option<bool>,
+    pub zero_pad_length: Option<u8>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "KeySpec")]
+struct Key(Vec<u8>);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+enum KeySpec {
+    Base64(String),
+    Env(String),
+    Hex(String),
+}
+
+impl TryFrom<KeySpec> for Key {
+    type Error = String;
+
+    fn try_from(spec: KeySpec) -> Result<Self, String> {
+        let key = match spec {
+            KeySpec::Base64(value) => unbase64(&value).ok_or("Invalid base64 key")?,
+            KeySpec::Env(name) => std::env::var(&name)
+                .map_err(|_| format!("Environment variable {} is not set", name))?
+                .into_bytes(),
+            KeySpec::Hex(value) => unhex(&value).ok_or("Invalid hex key")?,
+        };
+        if key.is_empty() {
+            return Err("Empty key".to_string());
+        }
+        Ok(Key(key))
+    }
+}
+
+impl ConfigSpec {
+    /// Returns the config for codecs without a profile.
+    pub fn config(&self) -> Result<Config, ConfigError> {
+        self.build(&FormatSpec {
+            alphabet: self.alphabet.clone(),
+            hmac_length: self.hmac_length,
+            order_preserving: self.order_preserving,
+            zero_pad_length: self.zero_pad_length,
+        })
+    }
+
+    /// Returns the config for codecs named `name`, with the settings of its profile if there
+    /// is one.
+    pub fn config_for(&self, name: &str) -> Result<Config, ConfigError> {
+        match self.profiles.get(name) {
+            Some(profile) => self.build(&FormatSpec {
+                alphabet: profile.alphabet.clone().or_else(|| self.alphabet.clone()),
+                hmac_length: profile.hmac_length.or(self.hmac_length),
+                order_preserving: profile.order_preserving.or(self.order_preserving),
+                zero_pad_length: profile.zero_pad_length.or(self.zero_pad_length),
+            }),
+            None => self.config(),
+        }
+    }
+
+    /// Returns a codec named `name`, configured with `config_for`.
+    pub fn codec(&self, name: &str) -> Result<Codec, ConfigError> {
+        Codec::try_new(name, &self.config_for(name)?)
+    }
+
+    fn build(&self, format: &FormatSpec) -> Result<Config, ConfigError> {
+        let mut config = Config::new(&self.key.0)
+            .bind_parameters(self.bind_parameters)
+            .order_preserving(format.order_preserving.unwrap_or(false));
+        if let Some(salt) = &self.salt {
+            config = config.salt(salt.parse::<Salt>()?);
+        }
+        if let Some(alphabet) = &format.alphabet {
+            config = config.alphabet(alphabet.parse::<Alphabet>()?)?;
+        }
+        if let Some(hmac_length) = format.hmac_length {
+            config = config.hmac_length(hmac_length)?;
+        }
+        if let Some(zero_pad_length) = format.zero_pad_length {
+            config = config.zero_pad_length(zero_pad_length)?;
+        }
+        if self.strict {
+            config = config.strict()?;
+        }
+        Ok(config)
+    }
+}
+
+// Specs are generated with the lengths mostly in their valid ranges, so that most of them
+// build codecs, and with valid salts (`fuzzing` feature).
+#[cfg(feature = "fuzzing")]
+impl<'a> arbitrary::Arbitrary<'a> for ConfigSpec {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut key = Vec::<u8>::arbitrary(u)?;
+        key.push(u8::arbitrary(u)?);
+        Ok(ConfigSpec {
+            key: Key(key),
+            alphabet: arbitrary_alphabet(u)?,
+            bind_parameters: bool::arbitrary(u)?,
+            hmac_length: arbitrary_length(u)?,
+            order_preserving: Option::<bool>::arbitrary(u)?,
+            profiles: HashMap::<String, FormatSpec>::arbitrary(u)?,
+            salt: Option::<[u8; 16]>::arbitrary(u)?
+                .map(|bytes| Salt::from_bytes(bytes).to_string()),
+            strict: bool::arbitrary(u)?,
+            zero_pad_length: arbitrary_length(u)?,
+        })
+    }
+}
+
+#[cfg(feature = "fuzzing")]
+impl<'a> arbitrary::Arbitrary<'a> for FormatSpec {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(FormatSpec {
+            alphabet: arbitrary_alphabet(u)?,
+            hmac_length: arbitrary_length(u)?,
+            order_preserving: Option::<bool>::arbitrary(u)?,
+            zero_pad_length: arbitrary_length(u)?,
+        })
+    }
+}
+
+// Returns a MAC or padding length from 0 to 9, where 9 is invalid.
+#[cfg(feature = "fuzzing")]
+fn arbitrary_length(u: &mut arbitrary::Unstructured) -> arbitrary::Result<Option<u8>> {
+    use arbitrary::Arbitrary;
+    Ok(Option::<u8>::arbitrary(u)?.map(|length| length % 10))
+}
+
+// Returns mostly no alphabet, sometimes a supported one and rarely an arbitrary string.
+#[cfg(feature = "fuzzing")]
+fn arbitrary_alphabet(u: &mut arbitrary::Unstructured) -> arbitrary::Result<Option<String>> {
+    use arbitrary::Arbitrary;
+    Ok(match u.int_in_range(0..=7)? {
+        0 => Some(String::arbitrary(u)?),
+        1 => Some(
+            u.choose(&["base62", "base32", "decimal", "custom:9876543210"])?
+                .to_string(),
+        ),
+        _ => None,
+    })
+}
+
+// The key is left out, so that it doesn't end up in logs.
+impl fmt::Debug for ConfigSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConfigSpec")
+            .field("alphabet", &self.alphabet)
+            .field("bind_parameters", &self.bind_parameters)
+            .field("hmac_length", &self.hmac_length)
+            .field("order_preserving", &self.order_preserving)
+            .field("profiles", &self.profiles)
+            .field("salt", &self.salt)
+            .field("strict", &self.strict)
+            .field("zero_pad_length", &self.zero_pad_length)
+            .finish_non_exhaustive()
+    }
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// Decodes standard base64, with or without padding.
+fn unbase64(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in s.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    // Leftover bits must be padding zeros.
+    (bits < 6 && buffer == 0).then_some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<ConfigSpec, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn test_keys() {
+        let expected = Codec::new("test", &Config::new(b"Test key here")).encode(123);
+        std::env::set_var("CRYPTID_SPEC_TEST_KEY", "Test key here");
+        for key in [
+            r#"{ "hex": "54657374206b65792068657265" }"#,
+            r#"{ "base64": "VGVzdCBrZXkgaGVyZQ==" }"#,
+            r#"{ "base64": "VGVzdCBrZXkgaGVyZQ" }"#,
+            r#"{ "env": "CRYPTID_SPEC_TEST_KEY" }"#,
+        ] {
+            let spec = parse(&format!(r#"{{ "key": {} }}"#, key)).unwrap();
+            assert_eq!(spec.codec("test").unwrap().encode(123), expected);
+        }
+
+        for key in [
+            r#"{ "hex": "5465737" }"#,
+            r#"{ "hex": "zz" }"#,
+            r#"{ "hex": "" }"#,
+            r#"{ "base64": "VGVzd*" }"#,
+            r#"{ "base64": "VGVzdB" }"#,
+            r#"{ "env": "CRYPTID_SPEC_TEST_MISSING" }"#,
+            r#"{ "plain": "Test key here" }"#,
+            r#""Test key here""#,
+        ] {
+            assert!(
+                parse(&format!(r#"{{ "key": {} }}"#, key)).is_err(),
+                "{}",
+                key
+            );
+        }
+        assert_eq!(unbase64("VGVzdA="), Some(b"Test".to_vec()));
+    }
+
+    #[test]
+    fn test_settings() {
+        let spec = parse(
+            r#"{
+                "key": { "hex": "54657374206b65792068657265" },
+                "hmac_length": 2,
+                "zero_pad_length": 3,
+                "profiles": {
+                    "secret": { "hmac_length": 8 },
+                    "sorted": { "order_preserving": true }
+                }
+            }"#,
+        )
+        .unwrap();
+        let config = spec.config().unwrap();
+        assert_eq!((config.hmac_length, config.zero_pad_length), (2, 3));
+        assert!(!config.order_preserving);
+
+        let config = spec.config_for("secret").unwrap();
+        assert_eq!((config.hmac_length, config.zero_pad_length), (8, 3));
+        let config = spec.config_for("sorted").unwrap();
+        assert_eq!(config.hmac_length, 2);
+        assert!(config.order_preserving);
+        assert_eq!(spec.config_for("other").unwrap().hmac_length, 2);
+        assert!(!format!("{:?}", spec).contains("54657374"));
+
+        let invalid = [
+            (r#""hmac_length": 9"#, "InvalidMacLength"),
+            (r#""zero_pad_length": 9"#, "InvalidZeroPadLength"),
+            (
+                r#""hmac_length": 0, "strict": true"#,
+                "UnauthenticatedTokens",
+            ),
+            (r#""salt": "xyz""#, "InvalidSalt"),
+            (r#""alphabet": "abc""#, "UnsupportedAlphabet"),
+            (
+                r#""profiles": { "x": { "alphabet": "Base32" } }"#,
+                "UnsupportedAlphabet",
+            ),
+        ];
+        for (setting, error) in invalid {
+            let json = format!(r#"{{ "key": {{ "hex": "00" }}, {} }}"#, setting);
+            match parse(&json).unwrap().config_for("x") {
+                Err(e) => assert_eq!(format!("{:?}", e), error),
+                Ok(_) => panic!("{} should be invalid", setting),
+            }
+        }
+
+        let json = format!(
+            r#"{{ "key": {{ "hex": "00" }}, "alphabet": "{}" }}"#,
+            Alphabet::Base62.digits()
+        );
+        assert_eq!(
+            parse(&json).unwrap().config().unwrap().alphabet,
+            Alphabet::Base62
+        );
+        let spec = parse(
+            r#"{
+                "key": { "hex": "00" },
+                "alphabet": "decimal",
+                "profiles": { "sub": { "alphabet": "base32" }, "other": {} }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(spec.config().unwrap().alphabet, Alphabet::Decimal);
+        assert_eq!(spec.config_for("sub").unwrap().alphabet, Alphabet::Base32);
+        assert_eq!(
+            spec.config_for("other").unwrap().alphabet,
+            Alphabet::Decimal
+        );
+        let json = r#"{ "key": { "hex": "00" }, "alphabet": "custom:9876543210" }"#;
+        assert_eq!(
+            parse(json).unwrap().config().unwrap().alphabet,
+            Alphabet::Custom("9876543210".into())
+        );
+        assert!(parse(r#"{ "key": { "hex": "00" }, "unknown": 1 }"#).is_err());
+    }
+}
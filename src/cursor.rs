@@ -0,0 +1,179 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Field, TypeMarker};
+
+/// A keyset pagination cursor selecting the objects after a given ID.
+///
+/// With Serde, the cursor is the encoded token of the ID, so handlers can take it directly as
+/// a query parameter instead of decoding a raw string.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{After, Config, Field, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct ExampleIdMarker;
+/// impl TypeMarker for ExampleIdMarker {
+///     fn name() -> &'static str { "example" }
+/// }
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let after: After<ExampleIdMarker> = serde_json::from_str("\"example_VgwPy6rwatl\"").unwrap();
+///
+/// assert_eq!(after.field(), Field::from(12345));
+/// assert!(after.includes(&Field::from(12346)));
+/// assert!(!after.includes(&Field::from(12345)));
+/// ```
+#[derive(Debug)]
+pub struct After<T: TypeMarker>(Field<T>);
+
+/// A keyset pagination cursor selecting the objects before a given ID.
+///
+/// This is the counterpart of `After<T>`, for paging backwards.
+#[derive(Debug)]
+pub struct Before<T: TypeMarker>(Field<T>);
+
+impl<T: TypeMarker> After<T> {
+    /// Creates a cursor selecting the objects after `field`.
+    pub fn new(field: Field<T>) -> Self {
+        After(field)
+    }
+
+    /// Returns the ID of the cursor, e.g. for use in a query filter.
+    pub fn field(&self) -> Field<T> {
+        self.0
+    }
+
+    /// Returns `true` if `field` is after the cursor.
+    pub fn includes(&self, field: &Field<T>) -> bool {
+        field.id > self.0.id
+    }
+}
+
+impl<T: TypeMarker> Before<T> {
+    /// Creates a cursor selecting the objects before `field`.
+    pub fn new(field: Field<T>) -> Self {
+        Before(field)
+    }
+
+    /// Returns the ID of the cursor, e.g. for use in a query filter.
+    pub fn field(&self) -> Field<T> {
+        self.0
+    }
+
+    /// Returns `true` if `field` is before the cursor.
+    pub fn includes(&self, field: &Field<T>) -> bool {
+        field.id < self.0.id
+    }
+}
+
+impl<T: TypeMarker> Clone for After<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: TypeMarker> Copy for After<T> {}
+
+impl<T: TypeMarker> PartialEq for After<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: TypeMarker> Eq for After<T> {}
+
+impl<T: TypeMarker> Clone for Before<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: TypeMarker> Copy for Before<T> {}
+
+impl<T: TypeMarker> PartialEq for Before<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: TypeMarker> Eq for Before<T> {}
+
+impl<T: TypeMarker> Serialize for After<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: TypeMarker> Deserialize<'de> for After<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Field::deserialize(deserializer).map(After)
+    }
+}
+
+impl<T: TypeMarker> Serialize for Before<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: TypeMarker> Deserialize<'de> for Before<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Field::deserialize(deserializer).map(Before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Page {
+        after: Option<After<ExampleIdMarker>>,
+        before: Option<Before<ExampleIdMarker>>,
+    }
+
+    #[test]
+    fn test_cursors() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let page: Page = serde_json::from_str(r#"{"before": "example_VgwPy6rwatl"}"#).unwrap();
+        assert_eq!(page.after, None);
+        let before = page.before.unwrap();
+        assert_eq!(before, Before::new(Field::from(12345)));
+        assert!(before.includes(&Field::from(12344)));
+        assert!(!before.includes(&Field::from(12345)));
+        assert_eq!(
+            serde_json::to_string(&before).unwrap(),
+            "\"example_VgwPy6rwatl\""
+        );
+
+        let after = After::<ExampleIdMarker>::new(Field::from(10));
+        assert!(after.includes(&Field::from(11)));
+        assert!(!after.includes(&Field::from(10)));
+
+        let result = serde_json::from_str::<Page>(r#"{"after": "example_VgwPy6rwatm"}"#);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,290 @@
+//! Diesel support for `Field<T>`, `StrField<T>` and `UuidField<T>` (`diesel` feature, enabled
+//! by default).
+//!
+//! Fields map to Postgres `BIGINT` columns, converting with `Field::try_from_i64` and
+//! `Field::try_to_i64`, so `T::negative_ids()` applies.  String fields map to `TEXT` columns
+//! holding the plain string, and UUID fields to `UUID` columns holding the plain UUID.  The
+//! `AsExpression` derives are on the types themselves.
+//!
+//! Fields with other integer types, see `IdInt`, map to `BIGINT` columns too, except that
+//! `Field<T, i32>` maps to `INTEGER` columns.
+//!
+//! Nullable columns, such as optional foreign keys, are `Option<Field<T>>` and
+//! `Option<StrField<T>>`, and Postgres arrays, such as `BIGINT[]` lists of IDs, are
+//! `Vec<Field<T>>`.  Diesel's own impls for `Option` and `Vec` cover them in queries,
+//! inserts and `Queryable` structs alike.
+
+use std::io::Write;
+
+use ::diesel::deserialize::{self, FromSql, Queryable};
+use ::diesel::pg::{Pg, PgValue};
+use ::diesel::serialize::{self, IsNull, Output, ToSql};
+use ::diesel::sql_types::{BigInt, Integer, Text, Uuid};
+
+use crate::{Field, IdInt, StrField, TypeMarker, UuidField};
+
+impl<T: TypeMarker, I: IdInt> ToSql<BigInt, Pg> for Field<T, I> {
+    fn to_sql(&self, out: &mut Output<'_, '_, Pg>) -> serialize::Result {
+        <i64 as ToSql<BigInt, Pg>>::to_sql(&self.try_to_i64()?, &mut out.reborrow())
+    }
+}
+
+impl<T: TypeMarker, I: IdInt> FromSql<BigInt, Pg> for Field<T, I> {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let id = <i64 as FromSql<BigInt, Pg>>::from_sql(bytes)?;
+        Ok(Field::try_from_i64(id)?)
+    }
+}
+
+impl<T, I> Queryable<BigInt, Pg> for Field<T, I>
+where
+    T: TypeMarker,
+    I: IdInt,
+{
+    type Row = <i64 as Queryable<BigInt, Pg>>::Row;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        let id = <i64 as Queryable<BigInt, Pg>>::build(row)?;
+        Ok(Field::try_from_i64(id)?)
+    }
+}
+
+impl<T: TypeMarker> ToSql<Integer, Pg> for Field<T, i32> {
+    fn to_sql(&self, out: &mut Output<'_, '_, Pg>) -> serialize::Result {
+        <i32 as ToSql<Integer, Pg>>::to_sql(&self.to_int(), &mut out.reborrow())
+    }
+}
+
+impl<T: TypeMarker> FromSql<Integer, Pg> for Field<T, i32> {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let id = <i32 as FromSql<Integer, Pg>>::from_sql(bytes)?;
+        Ok(Field::try_from_int(id)?)
+    }
+}
+
+impl<T> Queryable<Integer, Pg> for Field<T, i32>
+where
+    T: TypeMarker,
+{
+    type Row = <i32 as Queryable<Integer, Pg>>::Row;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        let id = <i32 as Queryable<Integer, Pg>>::build(row)?;
+        Ok(Field::try_from_int(id)?)
+    }
+}
+
+impl<T: TypeMarker> ToSql<Text, Pg> for StrField<T> {
+    fn to_sql(&self, out: &mut Output<'_, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), &mut out.reborrow())
+    }
+}
+
+impl<T: TypeMarker> FromSql<Text, Pg> for StrField<T> {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        Ok(StrField::from_string(value))
+    }
+}
+
+impl<T> Queryable<Text, Pg> for StrField<T>
+where
+    T: TypeMarker,
+{
+    type Row = <String as Queryable<Text, Pg>>::Row;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        let value = <String as Queryable<Text, Pg>>::build(row)?;
+        Ok(StrField::from_string(value))
+    }
+}
+
+// The binary format of `UUID` is the 16 bytes of the UUID, so the `uuid` feature of Diesel
+// is not needed.
+impl<T: TypeMarker> ToSql<Uuid, Pg> for UuidField<T> {
+    fn to_sql(&self, out: &mut Output<'_, '_, Pg>) -> serialize::Result {
+        out.write_all(self.uuid().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl<T: TypeMarker> FromSql<Uuid, Pg> for UuidField<T> {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        Ok(UuidField::from(uuid::Uuid::from_slice(bytes.as_bytes())?))
+    }
+}
+
+impl<T> Queryable<Uuid, Pg> for UuidField<T>
+where
+    T: TypeMarker,
+{
+    type Row = Self;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        Ok(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::diesel::debug_query;
+    use ::diesel::prelude::*;
+
+    diesel::table! {
+        examples (id) {
+            id -> BigInt,
+            name -> Text,
+            parent_id -> Nullable<BigInt>,
+            member_ids -> Array<BigInt>,
+        }
+    }
+
+    diesel::table! {
+        orders (id) {
+            id -> Uuid,
+            example_id -> BigInt,
+        }
+    }
+
+    diesel::table! {
+        tags (id) {
+            id -> Integer,
+            example_id -> BigInt,
+        }
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+    }
+
+    type ExampleId = Field<ExampleIdMarker>;
+
+    #[derive(Queryable, Selectable, Identifiable, Insertable, Debug)]
+    #[diesel(table_name = examples)]
+    #[diesel(check_for_backend(diesel::pg::Pg))]
+    struct Example {
+        id: ExampleId,
+        name: String,
+        parent_id: Option<ExampleId>,
+        member_ids: Vec<ExampleId>,
+    }
+
+    #[test]
+    fn test_diesel_derives() {
+        let example = Example {
+            id: ExampleId::from(5),
+            name: "five".to_string(),
+            parent_id: Some(ExampleId::from(1)),
+            member_ids: vec![ExampleId::from(6), ExampleId::from(7)],
+        };
+        let query = diesel::update(&example).set(examples::name.eq("new"));
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .starts_with("UPDATE \"examples\" SET \"name\" = $1 WHERE (\"examples\".\"id\" = $2)"));
+        let query = diesel::delete(examples::table.find(example.id));
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .starts_with("DELETE FROM \"examples\" WHERE (\"examples\".\"id\" = $1)"));
+        let query = diesel::insert_into(examples::table).values(&example);
+        assert!(debug_query::<Pg, _>(&query).to_string().starts_with(
+            "INSERT INTO \"examples\" (\"id\", \"name\", \"parent_id\", \"member_ids\") \
+             VALUES ($1, $2, $3, $4)"
+        ));
+        // Nullable columns are `Option<Field<T>>`.
+        let query = diesel::update(&example).set(examples::parent_id.eq(Some(example.id)));
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .starts_with("UPDATE \"examples\" SET \"parent_id\" = $1"));
+        let query = diesel::update(&example).set(examples::parent_id.eq(None::<ExampleId>));
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .contains("binds: [None, "));
+        // Arrays are `Vec<Field<T>>`, and lists of IDs can be bound as arrays.
+        let query = examples::table.filter(examples::member_ids.contains(vec![example.id]));
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .contains("WHERE (\"examples\".\"member_ids\" @> $1)"));
+        let query = examples::table.filter(examples::id.eq_any(&example.member_ids));
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .contains("WHERE (\"examples\".\"id\" = ANY($1))"));
+        let query = diesel::update(&example).set(examples::member_ids.eq(Vec::<ExampleId>::new()));
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .contains("binds: [[], "));
+        let query = examples::table.select(Example::as_select());
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .starts_with("SELECT"));
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct TagIdMarker;
+    impl TypeMarker for TagIdMarker {
+        fn name() -> &'static str {
+            "tag"
+        }
+    }
+
+    #[derive(Queryable, Selectable, Identifiable, Insertable, Debug)]
+    #[diesel(table_name = tags)]
+    #[diesel(check_for_backend(diesel::pg::Pg))]
+    struct Tag {
+        id: Field<TagIdMarker, i32>,
+        example_id: Field<ExampleIdMarker, i64>,
+    }
+
+    #[test]
+    fn test_integer_columns() {
+        let tag = Tag {
+            id: Field::try_from_int(7).unwrap(),
+            example_id: Field::try_from_int(5).unwrap(),
+        };
+        let query = diesel::insert_into(tags::table).values(&tag);
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .starts_with("INSERT INTO \"tags\" (\"id\", \"example_id\") VALUES ($1, $2)"));
+        let query = diesel::delete(tags::table.find(tag.id));
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .starts_with("DELETE FROM \"tags\" WHERE (\"tags\".\"id\" = $1)"));
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct OrderIdMarker;
+    impl TypeMarker for OrderIdMarker {
+        fn name() -> &'static str {
+            "order"
+        }
+    }
+
+    #[derive(Queryable, Selectable, Identifiable, Insertable, Debug)]
+    #[diesel(table_name = orders)]
+    #[diesel(check_for_backend(diesel::pg::Pg))]
+    struct Order {
+        id: UuidField<OrderIdMarker>,
+        example_id: ExampleId,
+    }
+
+    #[test]
+    fn test_uuid_columns() {
+        let uuid = uuid::Uuid::from_u128(0x0123_4567_89ab_cdef_fedc_ba98_7654_3210);
+        let order = Order {
+            id: UuidField::from(uuid),
+            example_id: ExampleId::from(5),
+        };
+        let query = diesel::insert_into(orders::table).values(&order);
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .starts_with("INSERT INTO \"orders\" (\"id\", \"example_id\") VALUES ($1, $2)"));
+        let query = diesel::delete(orders::table.find(order.id));
+        assert!(debug_query::<Pg, _>(&query)
+            .to_string()
+            .starts_with("DELETE FROM \"orders\" WHERE (\"orders\".\"id\" = $1)"));
+    }
+}
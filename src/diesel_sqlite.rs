@@ -0,0 +1,261 @@
+//! Diesel support for SQLite (`diesel-sqlite` feature).
+//!
+//! Like with Postgres, fields map to `BIGINT` columns, converting with `Field::try_from_i64`
+//! and `Field::try_to_i64`, and string fields map to `TEXT` columns holding the plain string.
+//! `Field<T, i32>` maps to `INTEGER` columns.  The same model structs can then be used with
+//! both backends, e.g. SQLite in tests and Postgres in production.
+
+use ::diesel::deserialize::{self, FromSql, Queryable};
+use ::diesel::serialize::{self, IsNull, Output, ToSql};
+use ::diesel::sql_types::{BigInt, Integer, Text};
+use ::diesel::sqlite::{Sqlite, SqliteValue};
+
+use crate::{Field, IdInt, StrField, TypeMarker};
+
+impl<T: TypeMarker, I: IdInt> ToSql<BigInt, Sqlite> for Field<T, I> {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.try_to_i64()?);
+        Ok(IsNull::No)
+    }
+}
+
+impl<T: TypeMarker, I: IdInt> FromSql<BigInt, Sqlite> for Field<T, I> {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> deserialize::Result<Self> {
+        let id = <i64 as FromSql<BigInt, Sqlite>>::from_sql(bytes)?;
+        Ok(Field::try_from_i64(id)?)
+    }
+}
+
+impl<T, I> Queryable<BigInt, Sqlite> for Field<T, I>
+where
+    T: TypeMarker,
+    I: IdInt,
+{
+    type Row = <i64 as Queryable<BigInt, Sqlite>>::Row;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        let id = <i64 as Queryable<BigInt, Sqlite>>::build(row)?;
+        Ok(Field::try_from_i64(id)?)
+    }
+}
+
+impl<T: TypeMarker> ToSql<Integer, Sqlite> for Field<T, i32> {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.to_int());
+        Ok(IsNull::No)
+    }
+}
+
+impl<T: TypeMarker> FromSql<Integer, Sqlite> for Field<T, i32> {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> deserialize::Result<Self> {
+        let id = <i32 as FromSql<Integer, Sqlite>>::from_sql(bytes)?;
+        Ok(Field::try_from_int(id)?)
+    }
+}
+
+impl<T> Queryable<Integer, Sqlite> for Field<T, i32>
+where
+    T: TypeMarker,
+{
+    type Row = <i32 as Queryable<Integer, Sqlite>>::Row;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        let id = <i32 as Queryable<Integer, Sqlite>>::build(row)?;
+        Ok(Field::try_from_int(id)?)
+    }
+}
+
+impl<T: TypeMarker> ToSql<Text, Sqlite> for StrField<T> {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl<T: TypeMarker> FromSql<Text, Sqlite> for StrField<T> {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(StrField::from_string(value))
+    }
+}
+
+impl<T> Queryable<Text, Sqlite> for StrField<T>
+where
+    T: TypeMarker,
+{
+    type Row = <String as Queryable<Text, Sqlite>>::Row;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        let value = <String as Queryable<Text, Sqlite>>::build(row)?;
+        Ok(StrField::from_string(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NegativeIds;
+    use ::diesel::prelude::*;
+    use ::diesel::sql_query;
+
+    diesel::table! {
+        examples (id) {
+            id -> BigInt,
+            code -> Text,
+            parent_id -> Nullable<BigInt>,
+        }
+    }
+
+    diesel::table! {
+        tags (id) {
+            id -> Integer,
+            example_id -> BigInt,
+        }
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+        fn negative_ids() -> NegativeIds {
+            NegativeIds::Reinterpret
+        }
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct CodeMarker;
+    impl TypeMarker for CodeMarker {
+        fn name() -> &'static str {
+            "code"
+        }
+    }
+
+    type ExampleId = Field<ExampleIdMarker>;
+
+    #[derive(Queryable, Selectable, Identifiable, Insertable, Debug, PartialEq)]
+    #[diesel(table_name = examples)]
+    #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+    struct Example {
+        id: ExampleId,
+        code: StrField<CodeMarker>,
+        parent_id: Option<ExampleId>,
+    }
+
+    #[test]
+    fn test_sqlite() {
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        sql_query(
+            "CREATE TABLE examples (id BIGINT PRIMARY KEY, code TEXT NOT NULL, parent_id BIGINT)",
+        )
+        .execute(&mut conn)
+        .unwrap();
+
+        let examples = [5, u64::MAX].map(|id| Example {
+            id: ExampleId::from(id),
+            code: StrField::from_string(format!("INV-{}", id)),
+            parent_id: (id != 5).then(|| ExampleId::from(5)),
+        });
+        diesel::insert_into(examples::table)
+            .values(&examples[..])
+            .execute(&mut conn)
+            .unwrap();
+
+        let loaded = examples::table
+            .find(ExampleId::from(u64::MAX))
+            .select(Example::as_select())
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(loaded, examples[1]);
+        // Reinterpreted IDs are stored as negative numbers.
+        let raw: i64 = examples::table
+            .select(diesel::dsl::sql::<BigInt>("id"))
+            .filter(examples::code.eq(&examples[1].code))
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(raw, -1);
+
+        diesel::update(&examples[0])
+            .set(examples::code.eq(StrField::<CodeMarker>::from_string("new".to_string())))
+            .execute(&mut conn)
+            .unwrap();
+        let code: StrField<CodeMarker> = examples::table
+            .find(examples[0].id)
+            .select(examples::code)
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(code.as_str(), "new");
+
+        // Nullable columns load as `Option<Field<T>>`.
+        let parents: Vec<(ExampleId, Option<ExampleId>)> = examples::table
+            .select((examples::id, examples::parent_id))
+            .order(examples::id)
+            .load(&mut conn)
+            .unwrap();
+        assert_eq!(
+            parents,
+            [
+                (examples[1].id, Some(examples[0].id)),
+                (examples[0].id, None)
+            ]
+        );
+        let children: Vec<ExampleId> = examples::table
+            .filter(examples::parent_id.eq(examples[0].id))
+            .select(examples::id)
+            .load(&mut conn)
+            .unwrap();
+        assert_eq!(children, [examples[1].id]);
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct TagIdMarker;
+    impl TypeMarker for TagIdMarker {
+        fn name() -> &'static str {
+            "tag"
+        }
+    }
+
+    type TagId = Field<TagIdMarker, i32>;
+
+    #[derive(Queryable, Selectable, Identifiable, Insertable, Debug, PartialEq)]
+    #[diesel(table_name = tags)]
+    #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+    struct Tag {
+        id: TagId,
+        example_id: Field<ExampleIdMarker, u32>,
+    }
+
+    #[test]
+    fn test_sqlite_integer() {
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        sql_query("CREATE TABLE tags (id INTEGER PRIMARY KEY, example_id BIGINT NOT NULL)")
+            .execute(&mut conn)
+            .unwrap();
+
+        let tag = Tag {
+            id: TagId::try_from_int(7).unwrap(),
+            example_id: Field::try_from_int(u32::MAX).unwrap(),
+        };
+        diesel::insert_into(tags::table)
+            .values(&tag)
+            .execute(&mut conn)
+            .unwrap();
+        let loaded = tags::table
+            .find(tag.id)
+            .select(Tag::as_select())
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(loaded, tag);
+
+        // IDs which don't fit in the integer type fail to load.
+        diesel::update(&tag)
+            .set(tags::example_id.eq(ExampleId::from(u32::MAX as u64 + 1)))
+            .execute(&mut conn)
+            .unwrap();
+        assert!(tags::table
+            .select(Tag::as_select())
+            .first(&mut conn)
+            .is_err());
+    }
+}
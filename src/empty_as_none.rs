@@ -0,0 +1,122 @@
+//! Serde helpers for optional `Field<T>` values which clients send as empty strings.
+//!
+//! `Option<Field<T>>` deserializes `null` as `None`, and a missing field too when the field has
+//! `#[serde(default)]`, but an empty string is `Error::EmptyToken`.  Forms and some clients
+//! send unset IDs as `""`, so with `#[serde(default, with = "cryptid_rs::empty_as_none")]`
+//! empty strings are `None` as well.  `None` is still serialized as `null`.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_rs::{Config, Field, TypeMarker};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug)]
+//! pub struct ExampleIdMarker;
+//! impl TypeMarker for ExampleIdMarker {
+//!     fn name() -> &'static str { "example" }
+//! }
+//!
+//! #[derive(Deserialize, Serialize)]
+//! struct Filter {
+//!     #[serde(default, with = "cryptid_rs::empty_as_none")]
+//!     parent_id: Option<Field<ExampleIdMarker>>,
+//! }
+//!
+//! Config::set_global(Config::new(b"your-secure-key"));
+//! for json in [r#"{}"#, r#"{"parent_id": null}"#, r#"{"parent_id": ""}"#] {
+//!     let filter: Filter = serde_json::from_str(json).unwrap();
+//!     assert_eq!(filter.parent_id, None);
+//! }
+//! let filter: Filter = serde_json::from_str(r#"{"parent_id": "example_VgwPy6rwatl"}"#).unwrap();
+//! assert_eq!(filter.parent_id, Some(Field::from(12345)));
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::field::get_or_create_codec;
+use crate::{Field, IdInt, TypeMarker};
+
+/// Serializes `Some` as the field and `None` as `null`, like `Option<Field<T>>` itself.
+pub fn serialize<T, I, S>(value: &Option<Field<T, I>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: TypeMarker,
+    I: IdInt,
+    S: Serializer,
+{
+    value.serialize(serializer)
+}
+
+/// Deserializes `null` and empty strings as `None`, and other strings as the field.
+pub fn deserialize<'de, T, I, D>(deserializer: D) -> Result<Option<Field<T, I>>, D::Error>
+where
+    T: TypeMarker,
+    I: IdInt,
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(encoded) if !encoded.is_empty() => {
+            Field::decode_string(&*get_or_create_codec::<T>(), &encoded)
+                .map(Some)
+                .map_err(serde::de::Error::custom)
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, Error};
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+    }
+
+    type ExampleId = Field<ExampleIdMarker>;
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Strict {
+        #[serde(default)]
+        id: Option<ExampleId>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Lenient {
+        #[serde(default, with = "crate::empty_as_none")]
+        id: Option<ExampleId>,
+    }
+
+    #[test]
+    fn test_optional_fields() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let id = Some(ExampleId::from(12345));
+        let token = r#"{"id":"example_VgwPy6rwatl"}"#;
+
+        for json in [r#"{}"#, r#"{"id":null}"#] {
+            assert_eq!(serde_json::from_str(json).ok(), Some(Strict { id: None }));
+            assert_eq!(serde_json::from_str(json).ok(), Some(Lenient { id: None }));
+        }
+        assert_eq!(serde_json::from_str(token).ok(), Some(Strict { id }));
+        assert_eq!(serde_json::from_str(token).ok(), Some(Lenient { id }));
+
+        // Empty strings are a clear error unless they are accepted as `None`.
+        let error = serde_json::from_str::<Strict>(r#"{"id":""}"#).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with(&Error::EmptyToken.to_string()));
+        let lenient: Lenient = serde_json::from_str(r#"{"id":""}"#).unwrap();
+        assert_eq!(lenient, Lenient { id: None });
+        assert!(serde_json::from_str::<Lenient>(r#"{"id":"example_"}"#).is_err());
+
+        assert_eq!(serde_json::to_string(&Lenient { id }).unwrap(), token);
+        assert_eq!(
+            serde_json::to_string(&Lenient { id: None }).unwrap(),
+            r#"{"id":null}"#
+        );
+    }
+}
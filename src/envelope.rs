@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// A token in binary form, for transport between internal services, returned by
+/// `Codec::encode_envelope`.
+///
+/// Instead of the prefix, an envelope carries the type ID of the codec, given with
+/// `Codec::with_type_id`, and instead of the base62 payload the 128 bit value behind it.
+/// Compact binary Serde formats store it in a few bytes, and services at the edge render it
+/// back into the exact token string with `Codec::render_envelope` or
+/// `CodecRegistry::render_envelope`.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Codec, CodecRegistry, Config, Envelope};
+///
+/// let config = Config::new(b"your-secure-key");
+/// let mut registry = CodecRegistry::new();
+/// let codec = registry.insert(Codec::new("example", &config).with_type_id(7));
+/// let envelope = codec.encode_envelope(12345);
+/// assert_eq!(envelope.type_id(), 7);
+///
+/// let json = serde_json::to_string(&envelope).unwrap();
+/// let received: Envelope = serde_json::from_str(&json).unwrap();
+/// assert_eq!(registry.render_envelope(&received).unwrap(), "example_VgwPy6rwatl");
+/// assert_eq!(registry.decode_envelope(&received).unwrap(), ("example", 12345));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Envelope {
+    pub(crate) type_id: u16,
+    pub(crate) payload: u128,
+}
+
+impl Envelope {
+    /// Returns the type ID of the codec which created the envelope.
+    pub fn type_id(&self) -> u16 {
+        self.type_id
+    }
+
+    /// Returns the payload as big-endian bytes, the same as `Codec::encode_bytes16`.
+    pub fn payload(&self) -> [u8; 16] {
+        self.payload.to_be_bytes()
+    }
+}
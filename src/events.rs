@@ -0,0 +1,173 @@
+//! Helpers for event pipelines keyed by encoded IDs.
+//!
+//! Events are keyed and partitioned by the encoded token, the same string the HTTP layer
+//! exposes, so a consumer can route an event without decoding its key and producers in other
+//! languages can compute the same partition from the token alone.  With
+//! `Config::nonce_length`, each encoding of an ID is a different token, so partitions are
+//! computed from the canonical token without a nonce, and tokens with nonces must be decoded
+//! before partitioning.
+
+use serde::{Deserialize, Serialize};
+
+use crate::field::get_or_create_codec;
+use crate::{Codec, Error, Field, TypeMarker};
+
+/// Computes stable partitions from encoded tokens.
+///
+/// The partition is derived from a 64-bit FNV-1a hash of the full token string (including
+/// the prefix), which is stable across processes, platforms and releases.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::events::Partitioner;
+/// use cryptid_rs::{Config, Field, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct UserIdMarker;
+/// impl TypeMarker for UserIdMarker {
+///     fn name() -> &'static str { "user" }
+/// }
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let partitioner = Partitioner::new(12);
+/// let user_id = Field::<UserIdMarker>::from(12345);
+///
+/// let partition = partitioner.partition(&user_id).unwrap();
+/// assert_eq!(partition, partitioner.partition_token("user_Qo4cTPVnos2"));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Partitioner {
+    partitions: u32,
+}
+
+impl Partitioner {
+    /// Creates a partitioner for `partitions` partitions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `partitions` is zero.
+    pub fn new(partitions: u32) -> Self {
+        assert!(partitions > 0, "Number of partitions must be positive");
+        Partitioner { partitions }
+    }
+
+    /// Returns the partition for `field`, computed from its canonical token, which is its
+    /// encoded token without a nonce.  The partition of an ID is the same with or without
+    /// nonces.  Returns `Error::IdOutOfRange` if the ID is outside the allowed range of `T`,
+    /// as it has no token.
+    pub fn partition<T: TypeMarker>(&self, field: &Field<T>) -> Result<u32, Error> {
+        self.partition_with(&get_or_create_codec::<T>(), field.id)
+    }
+
+    // Returns the partition of `num` encoded with `codec`.
+    fn partition_with(&self, codec: &Codec, num: u64) -> Result<u32, Error> {
+        codec.check_allowed(num)?;
+        Ok(self.partition_token(&codec.encode_canonical(num)))
+    }
+
+    /// Returns the partition for an already encoded token.
+    ///
+    /// The partition is only stable for canonical tokens, i.e. tokens encoded without
+    /// `Config::nonce_length`.  A token with nonce digits gets a different partition with
+    /// every encoding, so decode it and use `partition` instead.
+    pub fn partition_token(&self, token: &str) -> u32 {
+        (fnv1a(token.as_bytes()) % self.partitions as u64) as u32
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// An event envelope keyed by a `Field<T>`.
+///
+/// With Serde, the key is serialized as the encoded token like any other `Field<T>`, so
+/// events carry the same external IDs as the HTTP API.  With nonces, the serialized key
+/// differs between serializations, so consumers should decode it and partition with
+/// `partition` rather than with `Partitioner::partition_token`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(serialize = "P: Serialize", deserialize = "P: Deserialize<'de>"))]
+pub struct Event<T: TypeMarker, P> {
+    pub key: Field<T>,
+    pub payload: P,
+}
+
+impl<T: TypeMarker, P> Event<T, P> {
+    /// Creates a new event with the given key and payload.
+    pub fn new(key: Field<T>, payload: P) -> Self {
+        Event { key, payload }
+    }
+
+    /// Returns the partition for the event's key.  See `Partitioner::partition`.
+    pub fn partition(&self, partitioner: &Partitioner) -> Result<u32, Error> {
+        partitioner.partition(&self.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[derive(Clone, Copy, Debug)]
+    struct TestMarker;
+    impl TypeMarker for TestMarker {
+        fn name() -> &'static str {
+            "test"
+        }
+    }
+
+    #[test]
+    fn test_partition_is_stable() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let partitioner = Partitioner::new(16);
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+
+        for id in 0..100 {
+            let field = Field::<TestMarker>::from(id);
+            let partition = partitioner.partition(&field).unwrap();
+            assert!(partition < 16);
+            assert_eq!(Event::new(field, ()).partition(&partitioner), Ok(partition));
+        }
+    }
+
+    #[test]
+    fn test_partition_with_nonces() {
+        let partitioner = Partitioner::new(16);
+        let config = Config::new(b"your-secure-key");
+        let codec = Codec::new("test", &config);
+        let nonced = Codec::new("test", &config.nonce_length(2).unwrap());
+        for id in [0, 123, 12345, u64::MAX] {
+            let partition = partitioner.partition_with(&codec, id).unwrap();
+            for _ in 0..8 {
+                assert_eq!(partitioner.partition_with(&nonced, id), Ok(partition));
+            }
+        }
+
+        let ranged = Codec::new("test", &Config::new(b"your-secure-key").allowed_range(1..5));
+        assert_eq!(
+            partitioner.partition_with(&ranged, 1),
+            partitioner.partition_with(&codec, 1)
+        );
+        assert_eq!(
+            partitioner.partition_with(&ranged, 5),
+            Err(Error::IdOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_event_serde() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let event = Event::new(Field::<TestMarker>::from(123), "created".to_string());
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.starts_with("{\"key\":\"test_"));
+
+        let decoded: Event<TestMarker, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(u64::from(decoded.key), 123);
+        assert_eq!(decoded.payload, "created");
+    }
+}
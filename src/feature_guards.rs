@@ -0,0 +1,132 @@
+//! Doc tests checking that each integration is only compiled with its feature.
+//!
+//! Integrations live in their own modules, named after the feature and gated on it in
+//! `lib.rs`, and they only add trait impls and items of their own.  Each guard below exists
+//! without its feature, and its test must fail to compile, so that no other feature pulls
+//! the integration in.  SeaORM and Diesel's SQLite backend have no guards, as naming their
+//! traits would need `sea-orm` or `diesel/sqlite` as dev-dependencies.  Run them with e.g.
+//! `cargo test --doc --no-default-features --features internal-base62`.
+
+/// ```compile_fail
+/// use cryptid_rs::actix_web::set_error_status;
+/// ```
+#[cfg(not(feature = "actix-web"))]
+pub struct ActixWeb;
+
+/// async-graphql is a dev-dependency, so that the missing impls can be named.
+///
+/// ```compile_fail
+/// #[derive(Debug)]
+/// struct ExampleIdMarker;
+/// impl cryptid_rs::TypeMarker for ExampleIdMarker {
+///     fn name() -> &'static str { "example" }
+/// }
+///
+/// fn check<F: async_graphql::ScalarType>() {}
+/// check::<cryptid_rs::Field<ExampleIdMarker>>();
+/// ```
+#[cfg(not(feature = "async-graphql"))]
+pub struct AsyncGraphql;
+
+/// ```compile_fail
+/// use cryptid_rs::axum::IdPath;
+/// ```
+#[cfg(not(feature = "axum"))]
+pub struct Axum;
+
+/// ```compile_fail
+/// use cryptid_rs::import::decode_column;
+/// ```
+#[cfg(not(feature = "csv"))]
+pub struct Csv;
+
+/// Diesel is a dev-dependency, so that the missing impls can be named.
+///
+/// ```compile_fail
+/// use diesel::pg::Pg;
+/// use diesel::serialize::ToSql;
+/// use diesel::sql_types::BigInt;
+///
+/// #[derive(Debug)]
+/// struct ExampleIdMarker;
+/// impl cryptid_rs::TypeMarker for ExampleIdMarker {
+///     fn name() -> &'static str { "example" }
+/// }
+///
+/// fn check<F: ToSql<BigInt, Pg>>() {}
+/// check::<cryptid_rs::Field<ExampleIdMarker>>();
+/// ```
+#[cfg(not(feature = "diesel"))]
+pub struct Diesel;
+
+/// ```compile_fail
+/// use cryptid_rs::fuzzing::TokenMutation;
+/// ```
+#[cfg(not(feature = "fuzzing"))]
+pub struct Fuzzing;
+
+/// ```compile_fail
+/// use cryptid_rs::json::IdEncoder;
+/// ```
+#[cfg(not(feature = "json"))]
+pub struct Json;
+
+/// Juniper is a dev-dependency, so that the missing impls can be named.
+///
+/// ```compile_fail
+/// #[derive(Debug)]
+/// struct ExampleIdMarker;
+/// impl cryptid_rs::TypeMarker for ExampleIdMarker {
+///     fn name() -> &'static str { "example" }
+/// }
+///
+/// fn check<F: juniper::GraphQLType>() {}
+/// check::<cryptid_rs::Field<ExampleIdMarker>>();
+/// ```
+#[cfg(not(feature = "juniper"))]
+pub struct Juniper;
+
+/// ```compile_fail
+/// use cryptid_rs::Claims;
+/// ```
+#[cfg(not(feature = "jwt"))]
+pub struct Jwt;
+
+/// ```compile_fail
+/// use cryptid_rs::keysplit::split_key;
+/// ```
+#[cfg(not(feature = "keysplit"))]
+pub struct Keysplit;
+
+/// ```compile_fail
+/// use cryptid_rs::telemetry::DECODE_ERRORS;
+/// ```
+#[cfg(not(feature = "metrics"))]
+pub struct Metrics;
+
+/// ```compile_fail
+/// use cryptid_rs::prost::IdValue;
+/// ```
+#[cfg(not(feature = "prost"))]
+pub struct Prost;
+
+/// ```compile_fail
+/// use cryptid_rs::trace::set_redaction;
+/// ```
+#[cfg(not(feature = "tracing"))]
+pub struct Tracing;
+
+/// utoipa is a dev-dependency, so that the missing impls can be named.
+///
+/// ```compile_fail
+/// #[derive(Debug)]
+/// struct ExampleIdMarker;
+/// impl cryptid_rs::TypeMarker for ExampleIdMarker {
+///     fn name() -> &'static str { "example" }
+/// }
+///
+/// fn check<F: utoipa::ToSchema>() {}
+/// check::<cryptid_rs::Field<ExampleIdMarker>>();
+/// ```
+#[cfg(not(feature = "utoipa"))]
+pub struct Utoipa;
@@ -1,47 +1,361 @@
-use std::cell::RefCell;
+use std::any::TypeId;
 use std::collections::HashMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::num::{NonZeroU64, TryFromIntError};
+use std::ops::Range;
 use std::sync::Arc;
 
-use diesel::deserialize::{self, FromSql, Queryable};
-use diesel::expression::AsExpression;
-use diesel::pg::{Pg, PgValue};
-use diesel::serialize::{self, Output, ToSql};
-use diesel::sql_types::BigInt;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use uuid::Uuid;
 
-use crate::{Codec, Config};
+use crate::global::Global;
+use crate::{AccessPolicy, Alphabet, Codec, Config, ConfigError, Error, IdCodec, PrefixCase};
 
-thread_local! {
-    static CODEC_CACHE: RefCell<HashMap<String, Arc<Codec>>> = RefCell::new(HashMap::new());
+// Codecs are cached for the whole process, so the derived keys and AES key schedules are
+// computed once per marker.  Pre-fork servers can compute them before forking with
+// `Field::preload`, so the workers inherit them.  The codecs are keyed by the marker type, as
+// markers with the same name may override the config differently, and stored with their
+// prefix for `registered_prefixes`.
+static CODEC_CACHE: Global<Option<Codecs>> = Global::new(None);
+
+type Codecs = HashMap<TypeId, (String, Arc<Codec>)>;
+
+/// Drops the cached codecs, so that they are created again from the global config.
+pub(crate) fn clear_codec_cache() {
+    CODEC_CACHE.with(|cache| *cache = None);
+}
+
+/// Written by `Display` in place of a token when the ID can't be encoded, e.g. before the
+/// global config has been set, as formatting must not panic.
+pub(crate) const UNENCODED: &str = "<unencoded id>";
+
+/// Returns the cached codec for the marker `T`, creating it from the global config if needed.
+///
+/// Panics if the global config has not been set, or if the marker is not valid with it, e.g.
+/// if its alphabet is not allowed in the target of the config.
+pub(crate) fn get_or_create_codec<T: TypeMarker>() -> Arc<Codec> {
+    match cached_or_new_codec::<T>() {
+        Some(Ok(codec)) => codec,
+        Some(Err(error)) => panic!("Invalid codec for marker {:?}: {:?}", T::name(), error),
+        None => panic!("The global config should be set"),
+    }
+}
+
+/// Like `get_or_create_codec`, but returns `None` instead of panicking.
+pub(crate) fn try_get_or_create_codec<T: TypeMarker>() -> Option<Arc<Codec>> {
+    cached_or_new_codec::<T>()?.ok()
+}
+
+// Returns the cached codec of `T`, or creates and caches it.  Returns `None` if the global
+// config has not been set.  The codec is created without holding the lock of the cache, so
+// that a slow key derivation doesn't block other markers.
+fn cached_or_new_codec<T: TypeMarker>() -> Option<Result<Arc<Codec>, ConfigError>> {
+    let cached = CODEC_CACHE.with(|cache| {
+        let (_, codec) = cache.as_ref()?.get(&TypeId::of::<T>())?;
+        Some(codec.clone())
+    });
+    if let Some(codec) = cached {
+        return Some(Ok(codec));
+    }
+    let codec = match new_codec::<T>(Config::global()?) {
+        Ok(codec) => Arc::new(codec),
+        Err(error) => return Some(Err(error)),
+    };
+    let prefix = T::prefix_case().apply(T::name());
+    Some(Ok(CODEC_CACHE.with(|cache| {
+        // Another thread may have cached a codec in the meantime, which is used instead.
+        let cache = cache.get_or_insert_with(HashMap::new);
+        let (_, codec) = cache.entry(TypeId::of::<T>()).or_insert((prefix, codec));
+        codec.clone()
+    })))
+}
+
+// Creates the codec of `T` with `config` and the overrides of the marker.
+fn new_codec<T: TypeMarker>(mut config: Config) -> Result<Codec, ConfigError> {
+    if let Some(alphabet) = T::alphabet() {
+        config = config.alphabet(alphabet)?;
+    }
+    if let Some(range) = T::allowed_range() {
+        config = config.allowed_range(range);
+    }
+    Ok(Codec::try_new_lenient(T::name(), &config)?.with_prefix_case(T::prefix_case()))
+}
+
+/// Returns the prefixes of the `Field<T>` types used so far in the process, sorted and without
+/// the underscore, e.g. for an admin diagnostics endpoint.
+///
+/// A type is registered when its codec is first needed, such as on the first serialization
+/// or `Field::preload`.  With the `metrics` feature, `telemetry::prefix_stats` also returns
+/// the encode and decode counts of each.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{registered_prefixes, Config, Field, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct UserIdMarker;
+/// impl TypeMarker for UserIdMarker {
+///     fn name() -> &'static str { "user" }
+/// }
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// Field::<UserIdMarker>::preload();
+/// assert!(registered_prefixes().contains(&"user".to_string()));
+/// ```
+pub fn registered_prefixes() -> Vec<String> {
+    let mut prefixes: Vec<String> = CODEC_CACHE.with(|cache| {
+        cache.as_ref().map_or_else(Vec::new, |cache| {
+            cache.values().map(|(prefix, _)| prefix.clone()).collect()
+        })
+    });
+    prefixes.sort();
+    prefixes.dedup();
+    prefixes
 }
 
-fn get_or_create_codec(name: &str) -> Arc<Codec> {
+/// Returns the cached codecs.
+#[cfg(feature = "metrics")]
+pub(crate) fn cached_codecs() -> Vec<Arc<Codec>> {
     CODEC_CACHE.with(|cache| {
-        let mut cache = cache.borrow_mut();
-        if let Some(codec) = cache.get(name) {
-            codec.clone()
-        } else {
-            let codec = Arc::new(Codec::new(name, &Config::global().unwrap()));
-            cache.insert(name.to_string(), codec.clone());
-            codec
-        }
+        cache.as_ref().map_or_else(Vec::new, |cache| {
+            cache.values().map(|(_, codec)| codec.clone()).collect()
+        })
     })
 }
 
-pub trait TypeMarker: std::fmt::Debug {
+/// Returns the name of `Field<T>` in API schemas: the marker name in Pascal case with `Id`
+/// appended, e.g. `UserAccountId` for `user_account`, so that each marker gets its own type.
+#[cfg(any(feature = "async-graphql", feature = "juniper", feature = "utoipa"))]
+pub(crate) fn schema_name<T: TypeMarker>() -> String {
+    let mut name = String::new();
+    for word in T::name().split(['_', '-']) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_ascii_uppercase());
+            name.extend(chars);
+        }
+    }
+    name.push_str("Id");
+    name
+}
+
+pub trait TypeMarker: std::fmt::Debug + 'static {
     fn name() -> &'static str;
+
+    /// The casing of the prefix in encoded tokens.  Defaults to `PrefixCase::Snake`, which
+    /// renders `name()` as is.
+    fn prefix_case() -> PrefixCase {
+        PrefixCase::Snake
+    }
+
+    /// The alphabet of the tokens, overriding the one of the global config.  Defaults to
+    /// `None`, which uses the alphabet of the config.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Alphabet, Config, Field, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct ReferenceIdMarker;
+    /// impl TypeMarker for ReferenceIdMarker {
+    ///     fn name() -> &'static str { "ref" }
+    ///     fn alphabet() -> Option<Alphabet> { Some(Alphabet::Decimal) }
+    /// }
+    ///
+    /// Config::set_global(Config::new(b"your-secure-key"));
+    /// let encoded = Field::<ReferenceIdMarker>::from(12345).to_string();
+    /// assert!(encoded["ref_".len()..].bytes().all(|c| c.is_ascii_digit()));
+    /// ```
+    fn alphabet() -> Option<Alphabet> {
+        None
+    }
+
+    /// The form of `Field<T>` values in Serde.  Defaults to `SerdeFormat::Token`.
+    fn serde_format() -> SerdeFormat {
+        SerdeFormat::Token
+    }
+
+    /// How negative database IDs are handled.  Defaults to `NegativeIds::Reject`.
+    fn negative_ids() -> NegativeIds {
+        NegativeIds::Reject
+    }
+
+    /// Raw IDs which are never real objects, such as 0 used as a placeholder.  Decoding a
+    /// token of one of them, with Serde or `Field::decode_authorized`, fails with
+    /// `Error::ReservedId`.  Encoding is not affected.  Defaults to none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Config, Error, Field, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct ExampleIdMarker;
+    /// impl TypeMarker for ExampleIdMarker {
+    ///     fn name() -> &'static str { "example" }
+    ///     fn reserved_ids() -> &'static [u64] { &[0] }
+    /// }
+    ///
+    /// Config::set_global(Config::new(b"your-secure-key"));
+    /// let nil = serde_json::to_value(Field::<ExampleIdMarker>::NIL).unwrap();
+    /// assert!(serde_json::from_value::<Field<ExampleIdMarker>>(nil).is_err());
+    /// ```
+    fn reserved_ids() -> &'static [u64] {
+        &[]
+    }
+
+    /// The raw IDs which can be encoded, overriding `Config::allowed_range`.  Serializing a
+    /// field of another ID fails with `Error::IdOutOfRange`, and formatting it writes a
+    /// placeholder.  Defaults to `None`, meaning the range of the global config.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Range;
+    /// use cryptid_rs::{Config, Field, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct OrderIdMarker;
+    /// impl TypeMarker for OrderIdMarker {
+    ///     fn name() -> &'static str { "order" }
+    ///     fn allowed_range() -> Option<Range<u64>> { Some(1..1 << 40) }
+    /// }
+    ///
+    /// Config::set_global(Config::new(b"your-secure-key"));
+    /// assert!(serde_json::to_value(Field::<OrderIdMarker>::from(12345)).is_ok());
+    /// // A millisecond timestamp passed as an ID by mistake.
+    /// assert!(serde_json::to_value(Field::<OrderIdMarker>::from(1_700_000_000_000)).is_err());
+    /// ```
+    fn allowed_range() -> Option<Range<u64>> {
+        None
+    }
+}
+
+/// How `Field<T>` maps signed database IDs to the unsigned IDs which are encoded.
+///
+/// Postgres `BigInt` columns are signed, and some schemas use negative IDs, e.g. as
+/// sentinels.  The policy applies to `Field::try_from_i64`, `Field::try_to_i64`, Diesel and
+/// SeaORM, and it can be given to `Codec::encode_i64` and `Codec::decode_i64`.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Error, Field, NegativeIds, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct AccountIdMarker;
+/// impl TypeMarker for AccountIdMarker {
+///     fn name() -> &'static str { "account" }
+///     fn negative_ids() -> NegativeIds { NegativeIds::Reinterpret }
+/// }
+///
+/// #[derive(Debug)]
+/// pub struct UserIdMarker;
+/// impl TypeMarker for UserIdMarker {
+///     fn name() -> &'static str { "user" }
+/// }
+///
+/// let account_id = Field::<AccountIdMarker>::try_from_i64(-1).unwrap();
+/// assert_eq!(u64::from(account_id), u64::MAX);
+/// assert_eq!(account_id.try_to_i64(), Ok(-1));
+/// assert_eq!(Field::<UserIdMarker>::try_from_i64(-1), Err(Error::NegativeId));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NegativeIds {
+    /// Negative IDs are an error, as are IDs above `i64::MAX` when converting back.
+    #[default]
+    Reject,
+    /// IDs are reinterpreted in two's complement, so -1 is `u64::MAX`.
+    Reinterpret,
+    /// IDs are mapped with zigzag encoding: 0, -1, 1, -2, ... become 0, 1, 2, 3, ...  This
+    /// keeps the encoded IDs of small negative numbers small, but it also changes the tokens
+    /// of positive IDs, so it must be chosen before tokens are exposed.
+    ZigZag,
+}
+
+impl NegativeIds {
+    /// Maps a signed ID to the ID which is encoded.
+    pub(crate) fn map_i64(self, id: i64) -> Result<u64, Error> {
+        match self {
+            NegativeIds::Reject => u64::try_from(id).map_err(|_| Error::NegativeId),
+            NegativeIds::Reinterpret => Ok(id as u64),
+            NegativeIds::ZigZag => Ok(((id << 1) ^ (id >> 63)) as u64),
+        }
+    }
+
+    /// Maps an encoded ID back to the signed ID.
+    pub(crate) fn unmap_i64(self, id: u64) -> Result<i64, Error> {
+        match self {
+            NegativeIds::Reject => i64::try_from(id).map_err(|_| Error::NegativeId),
+            NegativeIds::Reinterpret => Ok(id as i64),
+            NegativeIds::ZigZag => Ok((id >> 1) as i64 ^ -((id & 1) as i64)),
+        }
+    }
+}
+
+/// How `Field<T>` values are serialized with Serde.
+///
+/// Types in the `Uuid` form still accept tokens, so a type can be switched to UUIDs while
+/// clients still send the old form.  Types in the `Token` form accept only tokens.  UUIDs are
+/// accepted in upper or lower case, with or without hyphens, and in the braced and URN forms.
+/// UUIDs are always serialized in the lowercase hyphenated form.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Codec, Config, Field, SerdeFormat, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct OrderIdMarker;
+/// impl TypeMarker for OrderIdMarker {
+///     fn name() -> &'static str { "order" }
+///     fn serde_format() -> SerdeFormat { SerdeFormat::Uuid }
+/// }
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let id = Field::<OrderIdMarker>::from(12345);
+/// let json = serde_json::to_string(&id).unwrap();
+/// assert_eq!(json, format!("\"{}\"", id.encode_uuid()));
+///
+/// let decoded: Field<OrderIdMarker> = serde_json::from_str(&json).unwrap();
+/// assert_eq!(decoded, id);
+///
+/// // Tokens are still accepted.
+/// let token = Codec::new("order", &Config::new(b"your-secure-key")).encode(12345);
+/// let decoded: Field<OrderIdMarker> = serde_json::from_value(token.into()).unwrap();
+/// assert_eq!(decoded, id);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SerdeFormat {
+    /// The prefixed token produced by `Codec::encode`.
+    #[default]
+    Token,
+    /// The hyphenated UUID string produced by `Codec::encode_uuid`, without a prefix.
+    Uuid,
 }
 
 /// An generic type-safe object ID field (a wrapped u64).
 ///
+/// The integer type of the IDs is `u64` by default.  Tables with other types of keys, such as
+/// `INTEGER` primary keys, can use e.g. `Field<T, i32>`, see `IdInt`.
+///
+/// Optional IDs are `Option<Field<T>>`.  `null` deserializes as `None`, as does a missing
+/// field with `#[serde(default)]`, and an empty string fails with `Error::EmptyToken`, or is
+/// `None` with the `empty_as_none` helpers.
+///
 /// When serialized with Serde, the number is automatically encrypted and encoded
 /// into a URL safe string.  Deserialization decodes and decrypts the string back
 /// to an integer.  The string has an object type specific prefix defined in
 /// the type marker's `fn name()`.
 ///
-/// Traits are also provided for Diesel compatibility with Postgres BigInt fields.
+/// With the `diesel` feature (enabled by default), traits are also provided for Diesel
+/// compatibility with Postgres BigInt fields, and with SQLite ones with `diesel-sqlite`.
+/// A `Field<T>` can be used as the primary key of structs deriving `Queryable`,
+/// `Selectable`, `Insertable` and `Identifiable`, so `diesel::update(&obj)` and
+/// `table.find(id)` work with typed IDs.
 ///
 /// # Examples
 ///
@@ -68,11 +382,140 @@ pub trait TypeMarker: std::fmt::Debug {
 /// let obj_str = serde_json::to_string(&obj).unwrap();
 /// assert_eq!(obj_str, "{\"id\":\"example_VgwPy6rwatl\"}");
 /// ```
-#[derive(AsExpression, Debug, Clone, Copy)]
-#[diesel(sql_type = BigInt)]
-pub struct Field<T: TypeMarker> {
-    id: u64,
-    _marker: std::marker::PhantomData<T>,
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "diesel",
+    derive(diesel::expression::AsExpression),
+    diesel(sql_type = diesel::sql_types::BigInt),
+    diesel(sql_type = diesel::sql_types::Integer)
+)]
+pub struct Field<T: TypeMarker, I: IdInt = u64> {
+    // The ID which is encoded, which always converts back to `I`.
+    pub(crate) id: u64,
+    _marker: std::marker::PhantomData<(T, I)>,
+}
+
+mod sealed {
+    use super::NegativeIds;
+    use crate::Error;
+
+    pub trait Sealed: Sized {
+        fn to_id(self, negative_ids: NegativeIds) -> Result<u64, Error>;
+        fn from_id(id: u64, negative_ids: NegativeIds) -> Result<Self, Error>;
+    }
+}
+
+/// The integer types of IDs in `Field<T, I>`: `u64` (the default), `i64`, `u32` and `i32`.
+///
+/// All IDs are encoded as `u64`.  Signed IDs are mapped according to `T::negative_ids()`,
+/// like with `Field::try_from_i64`, so a `Field<T, i64>` has the same tokens as a `Field<T>`
+/// of the same ID.  Decoding a token of an ID which doesn't fit in `I` fails with
+/// `Error::IdOutOfRange`.
+///
+/// With Diesel, `Field<T, i32>` maps to `INTEGER` columns, and the other types to `BIGINT`
+/// columns.  The other integrations support `Field<T>` only.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Config, Error, Field, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct TagIdMarker;
+/// impl TypeMarker for TagIdMarker {
+///     fn name() -> &'static str { "tag" }
+/// }
+///
+/// type TagId = Field<TagIdMarker, i32>;
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let id = TagId::try_from_int(42).unwrap();
+/// assert_eq!(id.to_int(), 42);
+/// assert_eq!(TagId::try_from_int(-1), Err(Error::NegativeId));
+///
+/// let json = serde_json::to_string(&id).unwrap();
+/// assert_eq!(json, serde_json::to_string(&Field::<TagIdMarker>::from(42)).unwrap());
+/// let decoded: TagId = serde_json::from_str(&json).unwrap();
+/// assert_eq!(decoded, id);
+///
+/// // IDs which don't fit in `i32` are rejected.
+/// let json = serde_json::to_string(&Field::<TagIdMarker>::from(1 << 40)).unwrap();
+/// assert!(serde_json::from_str::<TagId>(&json).is_err());
+/// assert_eq!(TagId::try_from_i64(1 << 40), Err(Error::IdOutOfRange));
+/// ```
+pub trait IdInt: sealed::Sealed + Copy + fmt::Debug + Eq + Hash {}
+
+impl IdInt for u64 {}
+impl IdInt for i64 {}
+impl IdInt for u32 {}
+impl IdInt for i32 {}
+
+impl sealed::Sealed for u64 {
+    fn to_id(self, _: NegativeIds) -> Result<u64, Error> {
+        Ok(self)
+    }
+
+    fn from_id(id: u64, _: NegativeIds) -> Result<Self, Error> {
+        Ok(id)
+    }
+}
+
+impl sealed::Sealed for i64 {
+    fn to_id(self, negative_ids: NegativeIds) -> Result<u64, Error> {
+        negative_ids.map_i64(self)
+    }
+
+    fn from_id(id: u64, negative_ids: NegativeIds) -> Result<Self, Error> {
+        negative_ids.unmap_i64(id)
+    }
+}
+
+impl sealed::Sealed for u32 {
+    fn to_id(self, _: NegativeIds) -> Result<u64, Error> {
+        Ok(self.into())
+    }
+
+    fn from_id(id: u64, _: NegativeIds) -> Result<Self, Error> {
+        u32::try_from(id).map_err(|_| Error::IdOutOfRange)
+    }
+}
+
+impl sealed::Sealed for i32 {
+    fn to_id(self, negative_ids: NegativeIds) -> Result<u64, Error> {
+        negative_ids.map_i64(self.into())
+    }
+
+    fn from_id(id: u64, negative_ids: NegativeIds) -> Result<Self, Error> {
+        negative_ids
+            .unmap_i64(id)
+            .ok()
+            .and_then(|id| i32::try_from(id).ok())
+            .ok_or(Error::IdOutOfRange)
+    }
+}
+
+// The standard traits are implemented manually, so that they don't require the marker type
+// to implement them.  `Hash` and `Eq` are needed by Diesel's `Identifiable`.
+impl<T: TypeMarker, I: IdInt> Clone for Field<T, I> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: TypeMarker, I: IdInt> Copy for Field<T, I> {}
+
+impl<T: TypeMarker, I: IdInt> PartialEq for Field<T, I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T: TypeMarker, I: IdInt> Eq for Field<T, I> {}
+
+impl<T: TypeMarker, I: IdInt> Hash for Field<T, I> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
 }
 
 impl<T: TypeMarker> From<Field<T>> for u64 {
@@ -82,76 +525,792 @@ impl<T: TypeMarker> From<Field<T>> for u64 {
     }
 }
 
-impl<T: TypeMarker> fmt::Display for Field<T> {
+impl<T: TypeMarker> From<NonZeroU64> for Field<T> {
+    fn from(id: NonZeroU64) -> Self {
+        Field::from_non_zero(id)
+    }
+}
+
+impl<T: TypeMarker> TryFrom<Field<T>> for NonZeroU64 {
+    type Error = TryFromIntError;
+
+    /// Returns the raw ID, or an error for `Field::NIL`.
+    fn try_from(field: Field<T>) -> Result<Self, TryFromIntError> {
+        NonZeroU64::try_from(field.id)
+    }
+}
+
+impl<T: TypeMarker, I: IdInt> fmt::Display for Field<T, I> {
+    /// Formats the field as it is serialized, so that `format!` and log calls don't reveal
+    /// the raw ID.  Use `debug_display` to show the raw ID.
+    ///
+    /// Writes `<unencoded id>` if the global config has not been set, if the codec of `T`
+    /// can't be created with it, or if the ID is outside the allowed range (see
+    /// `TypeMarker::allowed_range`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Config, Field, KeyMode, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct ExampleIdMarker;
+    /// impl TypeMarker for ExampleIdMarker {
+    ///     fn name() -> &'static str { "example" }
+    /// }
+    ///
+    /// let id = Field::<ExampleIdMarker>::from(12345);
+    /// if Config::key_mode() == KeyMode::Unset {
+    ///     assert_eq!(format!("id={}", id), "id=<unencoded id>");
+    /// }
+    /// Config::set_global(Config::new(b"your-secure-key"));
+    /// assert_eq!(format!("id={}", id), "id=example_VgwPy6rwatl");
+    /// ```
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Field {{ id: {}, marker: {} }}", self.id, T::name())
+        match try_get_or_create_codec::<T>() {
+            Some(codec) if codec.check_allowed(self.id).is_ok() => {
+                f.write_str(&self.encode_string(&*codec))
+            }
+            _ => f.write_str(UNENCODED),
+        }
+    }
+}
+
+impl<T: TypeMarker> Default for Field<T> {
+    /// Returns `Field::NIL`.
+    fn default() -> Self {
+        Self::NIL
+    }
+}
+
+// Fields are generated from arbitrary raw IDs (`fuzzing` feature).
+#[cfg(feature = "fuzzing")]
+impl<'a, T: TypeMarker> arbitrary::Arbitrary<'a> for Field<T> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u64::arbitrary(u).map(Field::from)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        u64::size_hint(depth)
     }
 }
 
 impl<T: TypeMarker> Field<T> {
+    /// The nil placeholder value, with the raw ID 0.
+    ///
+    /// This is also the `Default` value, so structs containing `Field<T>` can derive
+    /// `Default`, for example in builders which need a value before a database insert
+    /// assigns the real ID.  Database sequences start from 1, so 0 is never a real ID.
+    ///
+    /// The nil value is not special with Serde: it serializes to the token of 0 and that
+    /// token deserializes back to nil.  Avoid exposing it in APIs, or reject the token with
+    /// `TypeMarker::reserved_ids`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Field, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct ExampleIdMarker;
+    /// impl TypeMarker for ExampleIdMarker {
+    ///     fn name() -> &'static str { "example" }
+    /// }
+    ///
+    /// #[derive(Default)]
+    /// struct NewExample {
+    ///     id: Field<ExampleIdMarker>,
+    ///     name: String,
+    /// }
+    ///
+    /// let example = NewExample { name: "example".to_string(), ..Default::default() };
+    /// assert!(example.id.is_nil());
+    /// assert!(!Field::<ExampleIdMarker>::from(1).is_nil());
+    /// ```
+    pub const NIL: Self = Field {
+        id: 0,
+        _marker: std::marker::PhantomData,
+    };
+
+    /// Returns `true` if this is the `NIL` placeholder value.
+    pub fn is_nil(&self) -> bool {
+        self.id == 0
+    }
+
     /// Creates a `Field<T>` value from a `u64`.
     ///
     /// This method converts a `u64` into a `Field<T>`, effectively changing its type.
     pub fn from(id: u64) -> Self {
         Field {
-            id: id,
+            id,
             _marker: std::marker::PhantomData,
         }
     }
 
+    /// Creates a `Field<T>` from a non-zero ID, such as the index of an arena or the key of an
+    /// `Option<NonZeroU64>` column.  Also available as `From<NonZeroU64>`, which the inherent
+    /// `from` shadows in `Field::from` calls.
+    pub const fn from_non_zero(id: NonZeroU64) -> Self {
+        Field {
+            id: id.get(),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the ID as a `NonZeroU64`, or `None` for `NIL`.
+    ///
+    /// `Field<T>` itself has no niche, as 0 is a valid ID, but `Option<NonZeroU64>` stores an
+    /// optional ID in 8 bytes.  Index newtypes built on `NonZeroU64` convert with this and
+    /// `from_non_zero`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::num::NonZeroU64;
+    /// use cryptid_rs::{Field, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct ExampleIdMarker;
+    /// impl TypeMarker for ExampleIdMarker {
+    ///     fn name() -> &'static str { "example" }
+    /// }
+    ///
+    /// #[derive(Clone, Copy, Debug, PartialEq)]
+    /// struct NodeIndex(NonZeroU64);
+    ///
+    /// let id = Field::<ExampleIdMarker>::from(5);
+    /// let index = id.non_zero().map(NodeIndex);
+    /// assert_eq!(std::mem::size_of_val(&index), 8);
+    /// assert_eq!(index.map(|index| Field::from_non_zero(index.0)), Some(id));
+    /// assert_eq!(Field::<ExampleIdMarker>::NIL.non_zero(), None);
+    /// ```
+    pub fn non_zero(&self) -> Option<NonZeroU64> {
+        NonZeroU64::new(self.id)
+    }
+
+    /// Returns a field of the same type with the ID transformed by `f`, e.g. for offsetting
+    /// fixture IDs or remapping IDs in data migrations without losing the type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Field, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct ExampleIdMarker;
+    /// impl TypeMarker for ExampleIdMarker {
+    ///     fn name() -> &'static str { "example" }
+    /// }
+    ///
+    /// let id = Field::<ExampleIdMarker>::from(5);
+    /// assert_eq!(id.map(|id| id + 1000), Field::from(1005));
+    /// assert_eq!(id.try_map(|id| id.checked_sub(10)), None);
+    /// assert_eq!(id.zip_with(Field::from(7), u64::max), Field::from(7));
+    /// ```
+    pub fn map(self, f: impl FnOnce(u64) -> u64) -> Self {
+        Field::from(f(self.id))
+    }
+
+    /// Like `map`, but for transformations which can fail, such as checked arithmetic.
+    pub fn try_map(self, f: impl FnOnce(u64) -> Option<u64>) -> Option<Self> {
+        f(self.id).map(Field::from)
+    }
+
+    /// Returns a field of the same type with the ID computed by `f` from the IDs of `self` and
+    /// `other`.
+    pub fn zip_with(self, other: Self, f: impl FnOnce(u64, u64) -> u64) -> Self {
+        Field::from(f(self.id, other.id))
+    }
+
+    /// Creates the codec of the marker `T` from the global config in advance.
+    ///
+    /// Codecs are otherwise created on first use, and then shared by all threads.  Pre-fork
+    /// servers can call this for each marker before forking, so that the key derivation is
+    /// done once and inherited by the worker processes.  Unrelated processes can share the
+    /// derived keys with `Config::key_cache` (`shared-keys` feature).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the global config has not been set, or if the codec of `T` can't be created
+    /// with it, e.g. because the alphabet of the marker is not valid.
+    pub fn preload() {
+        get_or_create_codec::<T>();
+    }
+
+    /// Returns a string showing the raw ID, such as `Field { id: 5, marker: example }`.
+    ///
+    /// This was the `Display` format before 0.2.  Only use it where the raw ID may be shown,
+    /// e.g. in debugging output.
+    pub fn debug_display(&self) -> String {
+        format!("Field {{ id: {}, marker: {} }}", self.id, T::name())
+    }
+
     /// Encrypts the ID into a `Uuid` value.
     pub fn encode_uuid(self) -> Uuid {
-        let codec_name = T::name();
-        let codec = get_or_create_codec(codec_name);
+        let codec = get_or_create_codec::<T>();
         codec.encode_uuid(self.id)
     }
+
+    /// Returns the bucket of the ID among `buckets` buckets with `Codec::bucket`, e.g. for
+    /// grouping by ID in public dashboards without exposing or decoding IDs.  The bucket
+    /// leaks at most `log2(buckets)` bits about the token.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the global config has not been set or `buckets` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Config, Field, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct ExampleIdMarker;
+    /// impl TypeMarker for ExampleIdMarker {
+    ///     fn name() -> &'static str { "example" }
+    /// }
+    ///
+    /// Config::set_global(Config::new(b"your-secure-key"));
+    /// let id = Field::<ExampleIdMarker>::from(12345);
+    /// assert!(id.bucket(10) < 10);
+    /// ```
+    pub fn bucket(&self, buckets: u64) -> u64 {
+        get_or_create_codec::<T>().bucket(self.id, buckets)
+    }
+
+    /// Decodes a `Uuid` returned by `encode_uuid`, verifying the MAC.  Reserved IDs are
+    /// rejected like in `Deserialize`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the global config has not been set.
+    pub fn decode_uuid(uuid: Uuid) -> Result<Self, Error> {
+        let id = get_or_create_codec::<T>().decode_uuid(uuid)?;
+        if T::reserved_ids().contains(&id) {
+            return Err(Error::ReservedId);
+        }
+        Ok(Field::from(id))
+    }
+
+    /// Returns the string form of the field like `Display`, but with `codec` instead of the
+    /// codec made from the global config.
+    ///
+    /// Handlers which take the codec as a parameter can be tested with a
+    /// `testing::MockCodec`, without configuring a key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::testing::MockCodec;
+    /// use cryptid_rs::{Field, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct ExampleIdMarker;
+    /// impl TypeMarker for ExampleIdMarker {
+    ///     fn name() -> &'static str { "example" }
+    /// }
+    ///
+    /// let codec = MockCodec::new("example");
+    /// let field = Field::<ExampleIdMarker>::from(12345);
+    ///
+    /// assert_eq!(field.encode_with(&codec), "example_12345");
+    /// assert_eq!(Field::decode_with(&codec, "example_12345"), Ok(field));
+    /// ```
+    pub fn encode_with<C: IdCodec + ?Sized>(&self, codec: &C) -> String {
+        self.encode_string(codec)
+    }
+
+    /// Decodes the string form of a field with `codec`, like `Deserialize` does with the codec
+    /// made from the global config.  See `encode_with`.
+    pub fn decode_with<C: IdCodec + ?Sized>(codec: &C, encoded: &str) -> Result<Self, Error> {
+        Self::decode_string(codec, encoded)
+    }
+
+    /// Serializes the field like `Serialize`, but with `codec` instead of the codec made from
+    /// the global config.
+    ///
+    /// This is for custom serializers which must choose the codec themselves, such as stores
+    /// encrypting with a different key.  The format is still given by `T::serde_format()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Codec, Config, Field, TypeMarker};
+    ///
+    /// #[derive(Debug)]
+    /// pub struct ExampleIdMarker;
+    /// impl TypeMarker for ExampleIdMarker {
+    ///     fn name() -> &'static str { "example" }
+    /// }
+    ///
+    /// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+    /// let field = Field::<ExampleIdMarker>::from(12345);
+    ///
+    /// let mut json = Vec::new();
+    /// field.serialize_with(&codec, &mut serde_json::Serializer::new(&mut json)).unwrap();
+    /// assert_eq!(json, b"\"example_VgwPy6rwatl\"");
+    ///
+    /// let mut deserializer = serde_json::Deserializer::from_slice(&json);
+    /// let decoded = Field::<ExampleIdMarker>::deserialize_with(&codec, &mut deserializer);
+    /// assert_eq!(decoded.unwrap(), field);
+    /// ```
+    pub fn serialize_with<C, S>(&self, codec: &C, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        C: IdCodec + ?Sized,
+        S: Serializer,
+    {
+        codec
+            .check_allowed(self.id)
+            .map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&self.encode_string(codec))
+    }
+
+    /// Deserializes a field like `Deserialize`, but with `codec` instead of the codec made
+    /// from the global config.  See `serialize_with`.
+    pub fn deserialize_with<'de, C, D>(codec: &C, deserializer: D) -> Result<Self, D::Error>
+    where
+        C: IdCodec + ?Sized,
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        Self::decode_string(codec, &encoded).map_err(serde::de::Error::custom)
+    }
+
+    /// Decodes `encoded` and checks with `ctx` that access to the object is allowed, returning
+    /// `Error::Forbidden` if not.  See `AccessPolicy`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the global config has not been set.
+    pub fn decode_authorized<C>(encoded: &str, ctx: &C) -> Result<Self, Error>
+    where
+        C: AccessPolicy<T>,
+    {
+        let field = Self::decode_string(&get_or_create_codec::<T>(), encoded)?;
+        if !ctx.authorize(field) {
+            return Err(Error::Forbidden);
+        }
+        Ok(field)
+    }
 }
 
-impl<T: TypeMarker> Serialize for Field<T> {
+impl<T: TypeMarker, I: IdInt> Field<T, I> {
+    /// Creates a field from an ID of the integer type `I`.  Signed IDs are mapped according to
+    /// `T::negative_ids()`, see `IdInt`.
+    pub fn try_from_int(id: I) -> Result<Self, Error> {
+        Self::from_id(id.to_id(T::negative_ids())?)
+    }
+
+    /// Returns the ID as the integer type `I`.
+    pub fn to_int(&self) -> I {
+        I::from_id(self.id, T::negative_ids()).expect("IDs of fields fit their integer type")
+    }
+
+    /// Creates a `Field<T>` from a signed database ID, according to `T::negative_ids()`.
+    pub fn try_from_i64(id: i64) -> Result<Self, Error> {
+        Self::from_id(T::negative_ids().map_i64(id)?)
+    }
+
+    /// Returns the signed database ID, according to `T::negative_ids()`.
+    pub fn try_to_i64(&self) -> Result<i64, Error> {
+        T::negative_ids().unmap_i64(self.id)
+    }
+
+    // Creates a field of the encoded ID, if it converts to `I`.
+    pub(crate) fn from_id(id: u64) -> Result<Self, Error> {
+        I::from_id(id, T::negative_ids())?;
+        Ok(Field {
+            id,
+            _marker: std::marker::PhantomData,
+        })
+    }
+
+    // Decodes a token, or also a UUID if `T::serde_format()` is `SerdeFormat::Uuid`.
+    pub(crate) fn decode_string<C: IdCodec + ?Sized>(
+        codec: &C,
+        encoded: &str,
+    ) -> Result<Self, Error> {
+        if encoded.is_empty() {
+            return Err(Error::EmptyToken);
+        }
+        // Tokens always contain an underscore, so they can't be mistaken for UUIDs.
+        let uuid = match T::serde_format() {
+            SerdeFormat::Token => None,
+            SerdeFormat::Uuid => Uuid::try_parse(encoded).ok(),
+        };
+        let id = match uuid {
+            Some(uuid) => codec.decode_uuid(uuid)?,
+            None => codec.decode(encoded)?,
+        };
+        if T::reserved_ids().contains(&id) {
+            return Err(Error::ReservedId);
+        }
+        Self::from_id(id)
+    }
+
+    // Returns the string form of `T::serde_format()`.
+    pub(crate) fn encode_string<C: IdCodec + ?Sized>(&self, codec: &C) -> String {
+        match T::serde_format() {
+            SerdeFormat::Token => codec.encode(self.id),
+            SerdeFormat::Uuid => codec.encode_uuid(self.id).hyphenated().to_string(),
+        }
+    }
+}
+
+impl<T: TypeMarker, I: IdInt> Serialize for Field<T, I> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let codec_name = T::name();
-        let codec = get_or_create_codec(codec_name);
-        serializer.serialize_str(&codec.encode(self.id))
+        let codec = get_or_create_codec::<T>();
+        codec
+            .check_allowed(self.id)
+            .map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&self.encode_string(&*codec))
     }
 }
 
-impl<'de, T: TypeMarker> Deserialize<'de> for Field<T> {
+impl<'de, T: TypeMarker, I: IdInt> Deserialize<'de> for Field<T, I> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
         let encoded = String::deserialize(deserializer)?;
-        let codec_name = T::name();
-        let codec = get_or_create_codec(codec_name);
-        let id = codec.decode(&encoded).map_err(serde::de::Error::custom)?;
-        Ok(Field::from(id))
+        Self::decode_string(&*get_or_create_codec::<T>(), &encoded)
+            .map_err(serde::de::Error::custom)
     }
 }
 
-impl<T: TypeMarker> ToSql<BigInt, Pg> for Field<T> {
-    fn to_sql(&self, out: &mut Output<'_, '_, Pg>) -> serialize::Result {
-        <i64 as ToSql<BigInt, Pg>>::to_sql(&(self.id as i64), &mut out.reborrow())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
     }
-}
 
-impl<T: TypeMarker> FromSql<BigInt, Pg> for Field<T> {
-    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
-        let id = <i64 as FromSql<BigInt, Pg>>::from_sql(bytes)?;
-        Ok(Field::from(id as u64))
+    type ExampleId = Field<ExampleIdMarker>;
+
+    #[test]
+    fn test_display() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let field = ExampleId::from(12345);
+        assert_eq!(field.to_string(), "example_VgwPy6rwatl");
+        assert_eq!(format!("id={}", field), "id=example_VgwPy6rwatl");
+        assert_eq!(
+            field.debug_display(),
+            "Field { id: 12345, marker: example }"
+        );
     }
-}
 
-impl<T> Queryable<BigInt, Pg> for Field<T>
-where
-    T: TypeMarker,
-{
-    type Row = <i64 as Queryable<BigInt, Pg>>::Row;
+    #[test]
+    fn test_registered_prefixes() {
+        // Other tests may add codecs at the same time, but the cache is only cleared by
+        // `with_test_key`.
+        crate::testing::with_test_key(|_| {
+            Field::<ZigZagMarker>::preload();
+            ExampleId::preload();
+            let prefixes = registered_prefixes();
+            assert!(prefixes.contains(&"example".to_string()));
+            assert!(prefixes.contains(&"zigzag".to_string()));
+            assert!(prefixes.windows(2).all(|pair| pair[0] < pair[1]));
+        });
+    }
+
+    #[test]
+    fn test_non_zero() {
+        let one = NonZeroU64::MIN;
+        assert_eq!(ExampleId::from_non_zero(one), ExampleId::from(1));
+        let max: ExampleId = NonZeroU64::MAX.into();
+        assert_eq!(max, ExampleId::from(u64::MAX));
+        let id: ExampleId = one.into();
+        assert_eq!(NonZeroU64::try_from(id), Ok(one));
+        assert!(NonZeroU64::try_from(ExampleId::NIL).is_err());
+    }
+
+    #[test]
+    fn test_custom_codec() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let codec = Codec::new("example", &Config::new(b"Test key here"));
+        let field = ExampleId::from(123);
+
+        let value = field
+            .serialize_with(&codec, serde_json::value::Serializer)
+            .unwrap();
+        assert_eq!(value, codec.encode(123));
+        assert_eq!(
+            ExampleId::deserialize_with(&codec, value.clone()).unwrap(),
+            field
+        );
+
+        // The global codec uses a different key.
+        assert_ne!(serde_json::to_value(field).unwrap(), value);
+        assert!(serde_json::from_value::<ExampleId>(value).is_err());
+    }
+
+    #[derive(Debug)]
+    struct UuidMarker;
+    impl TypeMarker for UuidMarker {
+        fn name() -> &'static str {
+            "uuid"
+        }
+        fn serde_format() -> SerdeFormat {
+            SerdeFormat::Uuid
+        }
+    }
+
+    #[test]
+    fn test_uuid_strings() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let field = Field::<UuidMarker>::from(12345);
+        let uuid = field.encode_uuid();
+        let json = serde_json::to_string(&field).unwrap();
+        assert_eq!(json, format!("\"{}\"", uuid.hyphenated()));
+        assert_eq!(field.to_string(), uuid.hyphenated().to_string());
+        assert_eq!(Field::decode_uuid(uuid), Ok(field));
+        assert_eq!(
+            Field::<UuidMarker>::decode_uuid(Uuid::nil()),
+            Err(Error::IncorrectMAC)
+        );
+
+        let forms = [
+            uuid.hyphenated().to_string(),
+            uuid.hyphenated().to_string().to_uppercase(),
+            uuid.simple().to_string(),
+            uuid.simple().to_string().to_uppercase(),
+            uuid.braced().to_string(),
+            uuid.urn().to_string(),
+        ];
+        for form in forms {
+            let decoded: Field<UuidMarker> = serde_json::from_value(form.into()).unwrap();
+            assert_eq!(decoded, field);
+        }
+
+        let mut tampered = uuid.simple().to_string();
+        tampered.replace_range(..1, if tampered.starts_with('0') { "1" } else { "0" });
+        assert!(serde_json::from_value::<Field<UuidMarker>>(tampered.into()).is_err());
+
+        // Types serialized as tokens don't accept UUIDs.
+        #[derive(Debug)]
+        struct TokenMarker;
+        impl TypeMarker for TokenMarker {
+            fn name() -> &'static str {
+                "uuid"
+            }
+        }
+        let json = serde_json::to_value(field).unwrap();
+        assert!(serde_json::from_value::<Field<TokenMarker>>(json).is_err());
+        let json = serde_json::to_value(Field::<TokenMarker>::from(12345)).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Field<UuidMarker>>(json).unwrap(),
+            field
+        );
+    }
+
+    #[derive(Debug)]
+    struct ReservedMarker;
+    impl TypeMarker for ReservedMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+        fn reserved_ids() -> &'static [u64] {
+            &[0, u64::MAX]
+        }
+    }
+
+    #[test]
+    fn test_reserved_ids() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        for id in [0, u64::MAX] {
+            let json = serde_json::to_value(Field::<ReservedMarker>::from(id)).unwrap();
+            assert_eq!(
+                serde_json::from_value::<Field<ReservedMarker>>(json.clone())
+                    .unwrap_err()
+                    .to_string(),
+                Error::ReservedId.to_string()
+            );
+            let uuid = Field::<ReservedMarker>::from(id).encode_uuid();
+            assert_eq!(
+                Field::<ReservedMarker>::decode_uuid(uuid),
+                Err(Error::ReservedId)
+            );
+            // Other markers with the same name accept them.
+            assert_eq!(
+                serde_json::from_value::<ExampleId>(json).unwrap(),
+                ExampleId::from(id)
+            );
+        }
+        let json = serde_json::to_value(Field::<ReservedMarker>::from(1)).unwrap();
+        assert!(serde_json::from_value::<Field<ReservedMarker>>(json).is_ok());
+    }
+
+    #[derive(Debug)]
+    struct RangedMarker;
+    impl TypeMarker for RangedMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+        fn allowed_range() -> Option<Range<u64>> {
+            Some(1..1 << 40)
+        }
+    }
+
+    #[test]
+    fn test_allowed_range() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let json = serde_json::to_value(Field::<RangedMarker>::from(12345)).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Field<RangedMarker>>(json).unwrap(),
+            Field::from(12345)
+        );
+        for id in [0, 1 << 40] {
+            let error = serde_json::to_value(Field::<RangedMarker>::from(id)).unwrap_err();
+            assert_eq!(error.to_string(), Error::IdOutOfRange.to_string());
+            assert_eq!(Field::<RangedMarker>::from(id).to_string(), UNENCODED);
+            // Other markers with the same name are not restricted.
+            assert!(serde_json::to_value(ExampleId::from(id)).is_ok());
+        }
+    }
+
+    #[derive(Debug)]
+    struct BadAlphabetMarker;
+    impl TypeMarker for BadAlphabetMarker {
+        fn name() -> &'static str {
+            "bad"
+        }
+        fn alphabet() -> Option<Alphabet> {
+            Some(Alphabet::Custom("0123456789".repeat(2).into()))
+        }
+    }
+
+    #[derive(Debug)]
+    struct LegacyNameMarker;
+    impl TypeMarker for LegacyNameMarker {
+        fn name() -> &'static str {
+            "user.account"
+        }
+    }
+
+    #[test]
+    fn test_invalid_marker() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        // Names of earlier versions are still accepted.
+        let field = Field::<LegacyNameMarker>::from(1);
+        let json = serde_json::to_value(field).unwrap();
+        assert!(json.as_str().unwrap().starts_with("user.account_"));
+        assert_eq!(
+            serde_json::from_value::<Field<LegacyNameMarker>>(json).unwrap(),
+            field
+        );
+
+        let field = Field::<BadAlphabetMarker>::from(1);
+        assert_eq!(field.to_string(), UNENCODED);
+        assert!(std::panic::catch_unwind(|| serde_json::to_value(field)).is_err());
+        // The panic doesn't break the codecs of other markers.
+        let json = serde_json::to_value(ExampleId::from(12345)).unwrap();
+        assert_eq!(json, ExampleId::from(12345).to_string());
+    }
+
+    #[derive(Debug)]
+    struct ZigZagMarker;
+    impl TypeMarker for ZigZagMarker {
+        fn name() -> &'static str {
+            "zigzag"
+        }
+        fn negative_ids() -> NegativeIds {
+            NegativeIds::ZigZag
+        }
+    }
+
+    #[test]
+    fn test_negative_ids() {
+        assert_eq!(ExampleId::try_from_i64(5), Ok(ExampleId::from(5)));
+        assert_eq!(ExampleId::try_from_i64(-5), Err(Error::NegativeId));
+        assert_eq!(
+            ExampleId::from(u64::MAX).try_to_i64(),
+            Err(Error::NegativeId)
+        );
+        assert_eq!(ExampleId::from(5).try_to_i64(), Ok(5));
+
+        for (signed, unsigned) in [(0, 0), (-1, 1), (1, 2), (-2, 3), (i64::MIN, u64::MAX)] {
+            let field = Field::<ZigZagMarker>::try_from_i64(signed).unwrap();
+            assert_eq!(u64::from(field), unsigned);
+            assert_eq!(field.try_to_i64(), Ok(signed));
+        }
+        assert_eq!(
+            Field::<ZigZagMarker>::from(u64::MAX - 1).try_to_i64(),
+            Ok(i64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_int_types() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let token = serde_json::to_value(ExampleId::from(12345)).unwrap();
+        assert_eq!(token, "example_VgwPy6rwatl");
+
+        let id = Field::<ExampleIdMarker, i32>::try_from_int(12345).unwrap();
+        assert_eq!(serde_json::to_value(id).unwrap(), token);
+        assert_eq!(serde_json::from_value(token.clone()).ok(), Some(id));
+        assert_eq!(id.to_int(), 12345);
+        let id = Field::<ExampleIdMarker, u32>::try_from_int(12345).unwrap();
+        assert_eq!(serde_json::from_value(token.clone()).ok(), Some(id));
+        let id = Field::<ExampleIdMarker, i64>::try_from_int(12345).unwrap();
+        assert_eq!(serde_json::from_value(token).ok(), Some(id));
+        assert_eq!(id.try_to_i64(), Ok(12345));
+
+        // IDs out of the range of the type are rejected in both directions.
+        assert_eq!(
+            Field::<ExampleIdMarker, i32>::try_from_int(-1),
+            Err(Error::NegativeId)
+        );
+        let token = serde_json::to_value(ExampleId::from(u32::MAX as u64 + 1)).unwrap();
+        for decoded in [
+            serde_json::from_value::<Field<ExampleIdMarker, u32>>(token.clone()).map(|_| ()),
+            serde_json::from_value::<Field<ExampleIdMarker, i32>>(token).map(|_| ()),
+        ] {
+            assert_eq!(
+                decoded.unwrap_err().to_string(),
+                Error::IdOutOfRange.to_string()
+            );
+        }
+        assert_eq!(
+            Field::<ExampleIdMarker, i32>::try_from_i64(i32::MAX as i64 + 1),
+            Err(Error::IdOutOfRange)
+        );
+        assert_eq!(
+            Field::<ExampleIdMarker, u32>::try_from_i64(-1),
+            Err(Error::NegativeId)
+        );
+
+        for id in [i32::MIN, -1, 0, 1, i32::MAX] {
+            let field = Field::<ZigZagMarker, i32>::try_from_int(id).unwrap();
+            assert_eq!(field.to_int(), id);
+            assert_eq!(field.try_to_i64(), Ok(id.into()));
+            let wide = Field::<ZigZagMarker, i64>::try_from_int(id.into()).unwrap();
+            assert_eq!(field.id, wide.id);
+        }
+    }
 
-    fn build(row: Self::Row) -> deserialize::Result<Self> {
-        let id = i64::build(row)?;
-        Ok(Field::from(id as u64))
+    #[test]
+    fn test_combinators() {
+        let id = ExampleId::from(10);
+        assert_eq!(id.map(|id| id * 2), ExampleId::from(20));
+        assert_eq!(
+            id.try_map(|id| id.checked_add(1)),
+            Some(ExampleId::from(11))
+        );
+        assert_eq!(
+            ExampleId::from(u64::MAX).try_map(|id| id.checked_add(1)),
+            None
+        );
+        assert_eq!(
+            id.zip_with(ExampleId::from(3), |a, b| a - b),
+            ExampleId::from(7)
+        );
     }
 }
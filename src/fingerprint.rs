@@ -0,0 +1,105 @@
+use sha2::{Digest, Sha256};
+
+use crate::config::MAX_NONCE_LENGTH;
+use crate::Alphabet;
+
+// Version of the token format, part of the parameter fingerprint.
+const FORMAT_VERSION: u8 = 2;
+
+/// A fingerprint of the parameters which affect the token format.
+///
+/// With `Config::bind_parameters`, the truncated MAC is XORed with the fingerprint.  This
+/// keeps the MAC exactly as strong, but a token only verifies under the parameters it was
+/// created with.  Because the fingerprints of all parameter sets are public, a decoder can
+/// also recognize a valid MAC made under different parameters and report it specifically.
+pub(crate) type Fingerprint = [u8; 8];
+
+pub(crate) fn fingerprint(
+    alphabet: &Alphabet,
+    hmac_length: usize,
+    zero_pad_length: usize,
+    order_preserving: bool,
+    nonce_length: usize,
+) -> Fingerprint {
+    let digest = Sha256::new()
+        .chain_update(b"cryptid/parameters")
+        .chain_update([
+            FORMAT_VERSION,
+            hmac_length as u8,
+            zero_pad_length as u8,
+            order_preserving as u8,
+            nonce_length as u8,
+        ])
+        .chain_update(alphabet.digits())
+        .finalize();
+    digest[..8].try_into().expect("Digest is 32 bytes")
+}
+
+/// Returns the fingerprints of all valid parameter sets with `alphabet`, indexed by
+/// `hmac_length`.  Tokens of other alphabets don't decode into the same bytes, so their
+/// fingerprints are not needed.
+pub(crate) fn all_fingerprints(alphabet: &Alphabet) -> Vec<Vec<Fingerprint>> {
+    (0..=8)
+        .map(|hmac_length| {
+            let mut fingerprints = Vec::new();
+            for zero_pad_length in 0..=8 {
+                for order in [false, true] {
+                    for nonce_length in 0..=MAX_NONCE_LENGTH as usize {
+                        fingerprints.push(fingerprint(
+                            alphabet,
+                            hmac_length,
+                            zero_pad_length,
+                            order,
+                            nonce_length,
+                        ));
+                    }
+                }
+            }
+            fingerprints
+        })
+        .collect()
+}
+
+/// Returns `true` if `mask`, the received MAC XORed with the expected untruncated MAC, is one
+/// of the fingerprints `all` with the given `hmac_length`, other than `own`.
+pub(crate) fn is_other_fingerprint(
+    all: &[Vec<Fingerprint>],
+    hmac_length: usize,
+    mask: &[u8],
+    own: &Fingerprint,
+) -> bool {
+    all[hmac_length]
+        .iter()
+        .any(|candidate| candidate != own && candidate[..hmac_length] == *mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprints() {
+        let own = fingerprint(&Alphabet::Base62, 4, 4, false, 0);
+        let others = [
+            fingerprint(&Alphabet::Base62, 3, 4, false, 0),
+            fingerprint(&Alphabet::Base62, 4, 3, false, 0),
+            fingerprint(&Alphabet::Base62, 4, 4, true, 0),
+            fingerprint(&Alphabet::Base62, 4, 4, false, 2),
+            fingerprint(&Alphabet::Base32, 4, 4, false, 0),
+            fingerprint(&Alphabet::Decimal, 4, 4, false, 0),
+        ];
+        for other in &others {
+            assert_ne!(own, *other);
+        }
+
+        let all = all_fingerprints(&Alphabet::Base62);
+        assert!(all[4].contains(&own));
+        assert!(is_other_fingerprint(&all, 3, &others[0][..3], &own));
+        for other in &others[1..4] {
+            assert!(is_other_fingerprint(&all, 4, &other[..4], &own));
+        }
+        // Only fingerprints of the same alphabet are recognized.
+        assert!(!is_other_fingerprint(&all, 4, &own[..4], &own));
+        assert!(!is_other_fingerprint(&all, 4, &others[4][..4], &own));
+    }
+}
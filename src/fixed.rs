@@ -0,0 +1,130 @@
+use crate::codec::max_payload_length;
+use crate::order;
+use crate::radix::BASE62_RADIX;
+use crate::{Alphabet, Codec, Config, ConfigError, Error};
+
+/// A codec with the MAC length and the zero padding length fixed at compile time, so that the
+/// maximum payload length is a constant.
+///
+/// `encode_payload` writes the payload into a caller-provided buffer, which can be an array of
+/// `MAX_PAYLOAD_LEN` bytes, for encoding without allocating.  Invalid parameters fail to
+/// compile.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Config, FixedCodec};
+///
+/// type ExampleCodec = FixedCodec<4, 4>;
+///
+/// let codec = ExampleCodec::new("example", &Config::new(b"your-secure-key")).unwrap();
+/// let mut buf = [0u8; ExampleCodec::MAX_PAYLOAD_LEN];
+///
+/// assert_eq!(codec.encode_payload(12345, &mut buf), "VgwPy6rwatl");
+/// assert_eq!(codec.codec().encode(12345), "example_VgwPy6rwatl");
+/// ```
+pub struct FixedCodec<const MAC: u8, const PAD: u8> {
+    codec: Codec,
+}
+
+impl<const MAC: u8, const PAD: u8> FixedCodec<MAC, PAD> {
+    /// The maximum number of characters in a payload, i.e. the token after the underscore,
+    /// including the digit of a key version.
+    pub const MAX_PAYLOAD_LEN: usize = {
+        let tokens = max_payload_length(BASE62_RADIX, MAC as usize);
+        let ordered = order::width(BASE62_RADIX, MAC as usize);
+        1 + if tokens > ordered { tokens } else { ordered }
+    };
+
+    const VALID: () = assert!(MAC <= 8 && PAD <= 8, "MAC and PAD must be at most 8");
+
+    /// Creates a codec named `name` with `config`, replacing its `hmac_length` and
+    /// `zero_pad_length` with `MAC` and `PAD`, without nonces and with base62 tokens.  Fails
+    /// if `MAC` is 0 and `config` is strict or order-preserving, or if `name` is not a valid
+    /// prefix.
+    pub fn new(name: &str, config: &Config) -> Result<Self, ConfigError> {
+        let () = Self::VALID;
+        let config = config
+            .clone()
+            .hmac_length(MAC)?
+            .zero_pad_length(PAD)?
+            .nonce_length(0)?
+            .alphabet(Alphabet::Base62)?;
+        Ok(FixedCodec {
+            codec: Codec::try_new(name, &config)?,
+        })
+    }
+
+    /// Returns the underlying codec, e.g. for encoding whole tokens.
+    pub fn codec(&self) -> &Codec {
+        &self.codec
+    }
+
+    /// Writes the payload of the token of `num` to the start of `buf`, returning it.  The
+    /// token is the prefix, an underscore and the payload.  Unlike `Codec::encode`, this
+    /// does not call the hooks of the codec.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is shorter than the payload, which can't happen if it has
+    /// `MAX_PAYLOAD_LEN` bytes.
+    pub fn encode_payload<'a>(&self, num: u64, buf: &'a mut [u8]) -> &'a str {
+        let length = self.codec.write_payload(num, buf);
+        std::str::from_utf8(&buf[..length]).expect("Alphabet is ASCII")
+    }
+
+    /// Decodes a whole token.  See `Codec::decode`.
+    pub fn decode(&self, encoded: &str) -> Result<u64, Error> {
+        self.codec.decode(encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::radix;
+
+    fn check<const MAC: u8, const PAD: u8>(config: &Config) {
+        let codec = FixedCodec::<MAC, PAD>::new("test", config).unwrap();
+        let config = config.clone().hmac_length(MAC).unwrap();
+        let expected = Codec::new("test", &config.zero_pad_length(PAD).unwrap());
+        let max_length = FixedCodec::<MAC, PAD>::MAX_PAYLOAD_LEN;
+        assert!(expected.max_encoded_len() <= "test_".len() + max_length);
+
+        let mut buf = [0u8; radix::U128_DIGITS + 1];
+        for num in [0, 1, 12345, u32::MAX as u64, u64::MAX] {
+            let token = expected.encode(num);
+            assert_eq!(codec.codec().encode(num), token);
+            let payload = codec.encode_payload(num, &mut buf[..max_length]);
+            assert_eq!(format!("test_{}", payload), token);
+            assert_eq!(codec.decode(&token), Ok(num));
+        }
+    }
+
+    #[test]
+    fn test_fixed() {
+        for config in [
+            Config::new(b"Test key here"),
+            Config::new(b"Test key here").order_preserving(true),
+            Config::new(b"Test key here").key_version(61).unwrap(),
+        ] {
+            if config.order_preserving {
+                assert!(FixedCodec::<0, 0>::new("test", &config).is_err());
+            } else {
+                check::<0, 0>(&config);
+            }
+            check::<4, 4>(&config);
+            check::<8, 3>(&config);
+            check::<8, 8>(&config);
+        }
+        // Nonces are dropped.
+        let config = Config::new(b"Test key here").nonce_length(2).unwrap();
+        let codec = FixedCodec::<4, 4>::new("test", &config).unwrap();
+        assert_eq!(codec.codec().encode(123), codec.codec().encode(123));
+        assert_eq!(FixedCodec::<4, 4>::MAX_PAYLOAD_LEN, 18);
+        assert_eq!(FixedCodec::<8, 8>::MAX_PAYLOAD_LEN, 23);
+
+        let strict = Config::new(b"Test key here").strict().unwrap();
+        assert!(FixedCodec::<0, 4>::new("test", &strict).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! Structured fuzzing inputs (`fuzzing` feature).
+//!
+//! With the feature, `Field<T>`, `ConfigSpec` and `FormatSpec` implement
+//! `arbitrary::Arbitrary`, so fuzz targets can take codec parameters and IDs as input instead of
+//! raw bytes.  `TokenMutation` describes edits of a valid token, for checking that decoding
+//! damaged tokens fails cleanly.
+//!
+//! # Examples
+//!
+//! ```
+//! use arbitrary::{Arbitrary, Unstructured};
+//! use cryptid_rs::fuzzing::TokenMutation;
+//! use cryptid_rs::ConfigSpec;
+//!
+//! let data: Vec<u8> = (0..255).collect();
+//! let mut u = Unstructured::new(&data);
+//! let (spec, num, mutations): (ConfigSpec, u64, Vec<TokenMutation>) =
+//!     Arbitrary::arbitrary(&mut u).unwrap();
+//!
+//! if let Ok(codec) = spec.codec("example") {
+//!     let token = codec.encode(num);
+//!     assert_eq!(codec.decode(&token), Ok(num));
+//!     let _ = codec.decode(&TokenMutation::apply_all(&token, &mutations));
+//! }
+//! ```
+
+use arbitrary::Arbitrary;
+
+use crate::Alphabet;
+
+/// An edit of a token.  Indices and lengths are taken modulo the length of the token, and
+/// digits modulo 62, so that every mutation applies to every token.
+#[derive(Arbitrary, Clone, Debug, PartialEq, Eq)]
+pub enum TokenMutation {
+    /// Replaces the character at `index` with a base62 digit.
+    Replace { index: usize, digit: u8 },
+    /// Inserts a base62 digit before the character at `index`.
+    Insert { index: usize, digit: u8 },
+    /// Removes the character at `index`.
+    Remove { index: usize },
+    /// Keeps the first `length` characters.
+    Truncate { length: usize },
+    /// Replaces the prefix, i.e. the characters before the last underscore.
+    Prefix(String),
+    /// Appends any character, such as one outside the base62 alphabet.
+    Append(char),
+}
+
+impl TokenMutation {
+    /// Returns `token` with the mutation applied.
+    pub fn apply(&self, token: &str) -> String {
+        let mut chars: Vec<char> = token.chars().collect();
+        // The position modulo the length, or past the end in an empty token.
+        let position = |index: usize, len: usize| index.checked_rem(len).unwrap_or(0);
+        match self {
+            TokenMutation::Replace { index, digit } => {
+                if !chars.is_empty() {
+                    let index = position(*index, chars.len());
+                    chars[index] = Alphabet::Base62.digit(digit % 62);
+                }
+            }
+            TokenMutation::Insert { index, digit } => {
+                let index = position(*index, chars.len() + 1);
+                chars.insert(index, Alphabet::Base62.digit(digit % 62));
+            }
+            TokenMutation::Remove { index } => {
+                if !chars.is_empty() {
+                    chars.remove(position(*index, chars.len()));
+                }
+            }
+            TokenMutation::Truncate { length } => {
+                chars.truncate(position(*length, chars.len() + 1));
+            }
+            TokenMutation::Prefix(prefix) => {
+                let tail = token.rfind('_').map_or(token, |i| &token[i..]);
+                return format!("{}{}", prefix, tail);
+            }
+            TokenMutation::Append(c) => chars.push(*c),
+        }
+        chars.into_iter().collect()
+    }
+
+    /// Returns `token` with `mutations` applied in order.
+    pub fn apply_all(token: &str, mutations: &[TokenMutation]) -> String {
+        mutations
+            .iter()
+            .fold(token.to_string(), |token, mutation| mutation.apply(&token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Codec, Config, ConfigSpec, Field, TypeMarker};
+    use arbitrary::Unstructured;
+
+    #[derive(Debug)]
+    struct TestMarker;
+    impl TypeMarker for TestMarker {
+        fn name() -> &'static str {
+            "test"
+        }
+    }
+
+    #[test]
+    fn test_mutations() {
+        let token = "test_hHLBCl4rZ3u";
+        for (mutation, expected) in [
+            (
+                TokenMutation::Replace {
+                    index: 5,
+                    digit: 62,
+                },
+                "test_0HLBCl4rZ3u",
+            ),
+            (
+                TokenMutation::Insert {
+                    index: 16,
+                    digit: 1,
+                },
+                "test_hHLBCl4rZ3u1",
+            ),
+            (TokenMutation::Remove { index: 20 }, "testhHLBCl4rZ3u"),
+            (TokenMutation::Truncate { length: 5 }, "test_"),
+            (TokenMutation::Prefix("other".into()), "other_hHLBCl4rZ3u"),
+            (TokenMutation::Append('ä'), "test_hHLBCl4rZ3uä"),
+        ] {
+            assert_eq!(mutation.apply(token), expected, "{:?}", mutation);
+            assert!(mutation.apply("").len() <= 6);
+        }
+        let mutations = [
+            TokenMutation::Append('ä'),
+            TokenMutation::Remove { index: 0 },
+        ];
+        assert_eq!(
+            TokenMutation::apply_all(token, &mutations),
+            "est_hHLBCl4rZ3uä"
+        );
+
+        // Multibyte characters next to the underscore are rejected without panicking.
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        for mutations in [
+            [
+                TokenMutation::Prefix("testé".into()),
+                TokenMutation::Append('_'),
+            ],
+            [
+                TokenMutation::Prefix("😀".into()),
+                TokenMutation::Remove { index: 1 },
+            ],
+            [
+                TokenMutation::Append('é'),
+                TokenMutation::Insert { index: 5, digit: 1 },
+            ],
+        ] {
+            let mutated = TokenMutation::apply_all(token, &mutations);
+            assert!(codec.decode(&mutated).is_err(), "{}", mutated);
+        }
+    }
+
+    #[test]
+    fn test_arbitrary() {
+        let data: Vec<u8> = (0..16384u32).map(|i| ((i * 7919) >> 3) as u8).collect();
+        let mut u = Unstructured::new(&data);
+        let mut codecs = 0;
+        while !u.is_empty() {
+            let Ok((spec, field, mutations)) =
+                <(ConfigSpec, Field<TestMarker>, Vec<TokenMutation>)>::arbitrary(&mut u)
+            else {
+                break;
+            };
+            let Ok(codec) = spec.codec("test") else {
+                continue;
+            };
+            codecs += 1;
+            let token = codec.encode(field.into());
+            assert_eq!(codec.decode(&token), Ok(field.into()));
+            let _ = codec.decode(&TokenMutation::apply_all(&token, &mutations));
+        }
+        assert!(codecs > 0);
+    }
+}
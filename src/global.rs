@@ -0,0 +1,43 @@
+//! Process-wide mutable state, such as the global config.
+//!
+//! On most targets this is a `Mutex`.  On wasm targets without threads (e.g.
+//! `wasm32-unknown-unknown` in browsers) there is only ever one thread, so a `RefCell` is used
+//! instead, avoiding the synchronization primitives.
+
+#[cfg(not(all(target_family = "wasm", not(target_feature = "atomics"))))]
+pub(crate) struct Global<T>(std::sync::Mutex<T>);
+
+#[cfg(not(all(target_family = "wasm", not(target_feature = "atomics"))))]
+impl<T> Global<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Global(std::sync::Mutex::new(value))
+    }
+
+    /// Calls `f` with exclusive access to the value.  A panic in an earlier call leaves the
+    /// value as it was then, and the value is used as is instead of panicking again.
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner))
+    }
+}
+
+#[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+pub(crate) struct Global<T>(std::cell::RefCell<T>);
+
+// Without the atomics target feature, wasm code runs in a single thread.
+#[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+unsafe impl<T> Sync for Global<T> {}
+
+#[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+impl<T> Global<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Global(std::cell::RefCell::new(value))
+    }
+
+    /// Calls `f` with exclusive access to the value.
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.0.borrow_mut())
+    }
+}
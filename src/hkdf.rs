@@ -0,0 +1,92 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HKDF-SHA256 (RFC 5869) over the HMAC already used for tokens, for deriving the keys of
+/// codecs from the master key.  Only the operations used by the crate are implemented.
+pub(crate) struct Hkdf {
+    prk: HmacSha256,
+}
+
+/// The requested output was longer than 255 blocks of SHA256.
+#[derive(Debug)]
+pub(crate) struct InvalidLength;
+
+impl Hkdf {
+    /// Extracts the pseudorandom key from `ikm`.  A missing salt is a block of zeros, which
+    /// HMAC treats the same as an empty salt.
+    pub(crate) fn new(salt: Option<&[u8]>, ikm: &[u8]) -> Self {
+        let mut extract =
+            HmacSha256::new_from_slice(salt.unwrap_or(&[])).expect("HMAC takes keys of any length");
+        extract.update(ikm);
+        let prk = extract.finalize().into_bytes();
+        Hkdf {
+            prk: HmacSha256::new_from_slice(&prk).expect("HMAC takes keys of any length"),
+        }
+    }
+
+    /// Fills `okm` with key material for `info`.
+    pub(crate) fn expand(&self, info: &[u8], okm: &mut [u8]) -> Result<(), InvalidLength> {
+        if okm.len() > 255 * 32 {
+            return Err(InvalidLength);
+        }
+        let mut previous: Option<[u8; 32]> = None;
+        for (counter, chunk) in (1..=255u8).zip(okm.chunks_mut(32)) {
+            let mut mac = self.prk.clone();
+            if let Some(block) = &previous {
+                mac.update(block);
+            }
+            mac.update(info);
+            mac.update(&[counter]);
+            let block: [u8; 32] = mac.finalize().into_bytes().into();
+            chunk.copy_from_slice(&block[..chunk.len()]);
+            previous = Some(block);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_rfc5869() {
+        // Test cases 1 and 3 of RFC 5869.
+        let ikm = [0x0b; 22];
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        let mut okm = [0u8; 42];
+        Hkdf::new(Some(&salt), &ikm)
+            .expand(&info, &mut okm)
+            .unwrap();
+        assert_eq!(
+            hex(&okm),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+        );
+
+        let expected =
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8";
+        for salt in [None, Some(&[][..]), Some(&[0u8; 32][..])] {
+            Hkdf::new(salt, &ikm).expand(&[], &mut okm).unwrap();
+            assert_eq!(hex(&okm), expected);
+        }
+    }
+
+    #[test]
+    fn test_lengths() {
+        let hkdf = Hkdf::new(None, b"Test key here");
+        let mut long = vec![0u8; 255 * 32];
+        hkdf.expand(b"info", &mut long).unwrap();
+        let mut short = [0u8; 40];
+        hkdf.expand(b"info", &mut short).unwrap();
+        assert_eq!(short[..], long[..40]);
+        hkdf.expand(b"info", &mut []).unwrap();
+        assert!(hkdf.expand(b"info", &mut [0u8; 255 * 32 + 1]).is_err());
+    }
+}
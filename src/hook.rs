@@ -0,0 +1,149 @@
+use crate::{Codec, DecodeLimiter, Error};
+
+/// Hooks called around `Codec::encode` and `Codec::decode`.
+///
+/// Hooks are installed with `Codec::with_hook` and called in installation order.  They allow
+/// cross-cutting concerns such as metrics, deny-lists or honeytoken detection to be layered on
+/// a codec.  All methods have empty default implementations, so a hook only implements the
+/// ones it needs.
+///
+/// The decode hooks can reject a token by returning an error, which is then returned from
+/// `decode`.  `Error::Rejected` is meant for this, but any error can be returned.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use cryptid_rs::{Codec, CodecHook, Config, Error};
+///
+/// // Rejects tokens of IDs which have been deleted.
+/// struct DenyList(Vec<u64>);
+/// impl CodecHook for DenyList {
+///     fn after_decode(&self, _codec: &Codec, _encoded: &str, id: u64) -> Result<(), Error> {
+///         if self.0.contains(&id) {
+///             return Err(Error::Rejected);
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let codec = Codec::new("example", &Config::new(b"your-secure-key"))
+///     .with_hook(Arc::new(DenyList(vec![12345])));
+///
+/// assert_eq!(codec.decode("example_VgwPy6rwatl"), Err(Error::Rejected));
+/// ```
+pub trait CodecHook: Send + Sync {
+    /// Called before `id` is encoded.
+    fn before_encode(&self, _codec: &Codec, _id: u64) {}
+
+    /// Called after `id` has been encoded into `encoded`.
+    fn after_encode(&self, _codec: &Codec, _id: u64, _encoded: &str) {}
+
+    /// Called before `encoded` is decoded.  Returning an error rejects the token without
+    /// decoding it.
+    fn before_decode(&self, _codec: &Codec, _encoded: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called after `encoded` has been successfully decoded into `id`.  Returning an error
+    /// rejects the token.
+    fn after_decode(&self, _codec: &Codec, _encoded: &str, _id: u64) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl CodecHook for DecodeLimiter {
+    fn before_decode(&self, _codec: &Codec, _encoded: &str) -> Result<(), Error> {
+        if self.try_acquire() {
+            Ok(())
+        } else {
+            Err(Error::RateLimited)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl CodecHook for Recorder {
+        fn before_encode(&self, codec: &Codec, id: u64) {
+            let call = format!("before_encode {} {}", codec.name(), id);
+            self.calls.lock().unwrap().push(call);
+        }
+
+        fn after_encode(&self, _codec: &Codec, id: u64, encoded: &str) {
+            let call = format!("after_encode {} {}", id, encoded);
+            self.calls.lock().unwrap().push(call);
+        }
+
+        fn before_decode(&self, _codec: &Codec, encoded: &str) -> Result<(), Error> {
+            let call = format!("before_decode {}", encoded);
+            self.calls.lock().unwrap().push(call);
+            Ok(())
+        }
+
+        fn after_decode(&self, _codec: &Codec, encoded: &str, id: u64) -> Result<(), Error> {
+            let call = format!("after_decode {} {}", encoded, id);
+            self.calls.lock().unwrap().push(call);
+            Ok(())
+        }
+    }
+
+    struct Counter(AtomicUsize);
+
+    impl CodecHook for Counter {
+        fn before_decode(&self, _codec: &Codec, _encoded: &str) -> Result<(), Error> {
+            if self.0.fetch_add(1, Ordering::Relaxed) > 0 {
+                return Err(Error::Rejected);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_hooks() {
+        let recorder = Arc::new(Recorder::default());
+        let codec = Codec::new("test", &Config::new(b"Test key here")).with_hook(recorder.clone());
+
+        assert_eq!(codec.encode(123), "test_hHLBCl4rZ3u");
+        assert_eq!(codec.decode("test_hHLBCl4rZ3u"), Ok(123));
+        assert_eq!(codec.decode("test_hHLBCl4rZ3v"), Err(Error::IncorrectMAC));
+        let uuid = uuid::Uuid::parse_str("571FD9D5E133F7B0B0DFF444E4DD1127").unwrap();
+        assert_eq!(codec.decode_uuid(uuid), Ok(123));
+        assert_eq!(
+            *recorder.calls.lock().unwrap(),
+            [
+                "before_encode test 123",
+                "after_encode 123 test_hHLBCl4rZ3u",
+                "before_decode test_hHLBCl4rZ3u",
+                "after_decode test_hHLBCl4rZ3u 123",
+                "before_decode test_hHLBCl4rZ3v",
+                "before_decode 571fd9d5-e133-f7b0-b0df-f444e4dd1127",
+                "after_decode 571fd9d5-e133-f7b0-b0df-f444e4dd1127 123",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejecting_hooks() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"))
+            .with_hook(Arc::new(Counter(AtomicUsize::new(0))))
+            .with_hook(Arc::new(DecodeLimiter::new(0.0, 2)));
+        assert_eq!(codec.decode("test_hHLBCl4rZ3u"), Ok(123));
+        assert_eq!(codec.decode("test_hHLBCl4rZ3u"), Err(Error::Rejected));
+
+        let codec = Codec::new("test", &Config::new(b"Test key here"))
+            .with_hook(Arc::new(DecodeLimiter::new(0.0, 1)));
+        assert_eq!(codec.decode("test_hHLBCl4rZ3u"), Ok(123));
+        assert_eq!(codec.decode("test_hHLBCl4rZ3u"), Err(Error::RateLimited));
+    }
+}
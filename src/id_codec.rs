@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::{Codec, Error};
+
+/// The operations of `Codec` which `Field` needs, so that code taking a codec can also be
+/// given a different implementation, such as `testing::MockCodec` in unit tests.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::testing::MockCodec;
+/// use cryptid_rs::{Codec, Config, Error, IdCodec};
+///
+/// fn parse_id(codec: &impl IdCodec, input: &str) -> Result<u64, Error> {
+///     codec.decode(input.trim())
+/// }
+///
+/// let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+/// assert_eq!(parse_id(&codec, " example_VgwPy6rwatl "), Ok(12345));
+/// assert_eq!(parse_id(&MockCodec::new("example"), "example_12345"), Ok(12345));
+/// ```
+pub trait IdCodec {
+    /// Encodes `num` into a token.  See `Codec::encode`.
+    fn encode(&self, num: u64) -> String;
+
+    /// Decodes a token returned by `encode`.  See `Codec::decode`.
+    fn decode(&self, encoded: &str) -> Result<u64, Error>;
+
+    /// Encodes `num` into a UUID.  See `Codec::encode_uuid`.
+    fn encode_uuid(&self, num: u64) -> Uuid;
+
+    /// Decodes a UUID returned by `encode_uuid`.
+    fn decode_uuid(&self, uuid: Uuid) -> Result<u64, Error>;
+
+    /// Returns an error if `num` may not be encoded, so that serializers can fail instead of
+    /// panicking.  Defaults to allowing all IDs.  See `Config::allowed_range`.
+    fn check_allowed(&self, _num: u64) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl IdCodec for Codec {
+    fn encode(&self, num: u64) -> String {
+        Codec::encode(self, num)
+    }
+
+    fn decode(&self, encoded: &str) -> Result<u64, Error> {
+        Codec::decode(self, encoded)
+    }
+
+    fn encode_uuid(&self, num: u64) -> Uuid {
+        Codec::encode_uuid(self, num)
+    }
+
+    fn decode_uuid(&self, uuid: Uuid) -> Result<u64, Error> {
+        Codec::decode_uuid(self, uuid)
+    }
+
+    fn check_allowed(&self, num: u64) -> Result<(), Error> {
+        Codec::check_allowed(self, num)
+    }
+}
+
+// Shared codecs, such as the cached codecs of `Field`, can be passed as they are.
+impl<C: IdCodec + ?Sized> IdCodec for Arc<C> {
+    fn encode(&self, num: u64) -> String {
+        (**self).encode(num)
+    }
+
+    fn decode(&self, encoded: &str) -> Result<u64, Error> {
+        (**self).decode(encoded)
+    }
+
+    fn encode_uuid(&self, num: u64) -> Uuid {
+        (**self).encode_uuid(num)
+    }
+
+    fn decode_uuid(&self, uuid: Uuid) -> Result<u64, Error> {
+        (**self).decode_uuid(uuid)
+    }
+
+    fn check_allowed(&self, num: u64) -> Result<(), Error> {
+        (**self).check_allowed(num)
+    }
+}
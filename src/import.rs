@@ -0,0 +1,189 @@
+//! Decoding columns of tokens in CSV files (`csv` feature).
+//!
+//! Bulk imports from customers often contain a few malformed tokens.  `decode_column` decodes
+//! every row it can and reports the rest with their position in the file, instead of failing
+//! on the first bad row.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_rs::import::decode_column;
+//! use cryptid_rs::{Codec, Config, ErrorCode};
+//!
+//! let codec = Codec::new("example", &Config::new(b"your-secure-key"));
+//! let data = "name,id\nAlice,example_VgwPy6rwatl\nBob,example_VgwPy6rwatx\n";
+//! let mut reader = csv::Reader::from_reader(data.as_bytes());
+//! let column = decode_column(&mut reader, "id", &codec).unwrap();
+//!
+//! assert_eq!(column.ids().collect::<Vec<_>>(), vec![12345]);
+//! let error = column.errors().next().unwrap();
+//! assert_eq!((error.row, error.line), (1, 3));
+//! assert_eq!(error.token, "example_VgwPy6rwatx");
+//! assert_eq!(error.code(), ErrorCode::IncorrectMAC);
+//! ```
+
+use std::fmt;
+use std::io;
+
+use crate::{Codec, Error, ErrorCode};
+
+/// The decoded values of a column, one for each data row of the file.
+#[derive(Debug, PartialEq)]
+pub struct DecodedColumn {
+    pub rows: Vec<Result<u64, RowError>>,
+}
+
+/// A token which failed to decode.
+#[derive(Debug, PartialEq)]
+pub struct RowError {
+    /// The index of the data row, starting from zero and not counting the header row.
+    pub row: usize,
+    /// The line number of the row in the file, starting from one.
+    pub line: u64,
+    /// The token as it appears in the file.
+    pub token: String,
+    pub error: Error,
+}
+
+/// An error which prevents reading the column at all.
+#[derive(Debug)]
+pub enum ImportError {
+    /// The header row has no column of the given name.
+    MissingColumn(String),
+    /// The file is not valid CSV, or reading it failed.
+    Csv(csv::Error),
+}
+
+/// Decodes the tokens in the column named `column_name` of `reader` with `codec`.
+///
+/// The first row of `reader` must be a header row.  Rows which fail to decode are reported in
+/// the result, while unreadable files and a missing column fail the whole import.
+pub fn decode_column<R: io::Read>(
+    reader: &mut csv::Reader<R>,
+    column_name: &str,
+    codec: &Codec,
+) -> Result<DecodedColumn, ImportError> {
+    let column = reader
+        .headers()?
+        .iter()
+        .position(|name| name == column_name)
+        .ok_or_else(|| ImportError::MissingColumn(column_name.to_string()))?;
+
+    let mut rows = Vec::new();
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let token = record.get(column).unwrap_or("");
+        rows.push(codec.decode(token).map_err(|error| RowError {
+            row: rows.len(),
+            line: record.position().map_or(0, |position| position.line()),
+            token: token.to_string(),
+            error,
+        }));
+    }
+    Ok(DecodedColumn { rows })
+}
+
+impl DecodedColumn {
+    /// Returns the successfully decoded IDs in file order.
+    pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.rows
+            .iter()
+            .filter_map(|row| row.as_ref().ok().copied())
+    }
+
+    /// Returns the rows which failed to decode in file order.
+    pub fn errors(&self) -> impl Iterator<Item = &RowError> {
+        self.rows.iter().filter_map(|row| row.as_ref().err())
+    }
+
+    /// Returns `true` if all rows decoded.
+    pub fn is_clean(&self) -> bool {
+        self.rows.iter().all(Result::is_ok)
+    }
+}
+
+impl RowError {
+    /// Returns the category of the error, stable across versions.
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::from(&self.error)
+    }
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Row {} (line {}): {:?}: {}",
+            self.row, self.line, self.token, self.error
+        )
+    }
+}
+
+impl std::error::Error for RowError {}
+
+impl From<csv::Error> for ImportError {
+    fn from(error: csv::Error) -> Self {
+        ImportError::Csv(error)
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::MissingColumn(name) => write!(f, "No column named {:?}", name),
+            ImportError::Csv(error) => write!(f, "Invalid CSV: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn test_decode_column() {
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        let data = "\
+id,note
+test_hHLBCl4rZ3u,ok
+test_hHLBCl4rZ3v,\"bad
+mac\"
+,empty
+other_hHLBCl4rZ3u,prefix
+";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let column = decode_column(&mut reader, "id", &codec).unwrap();
+        assert!(!column.is_clean());
+        assert_eq!(column.ids().collect::<Vec<_>>(), vec![123]);
+        let errors: Vec<_> = column
+            .errors()
+            .map(|error| (error.row, error.line, error.token.as_str(), error.code()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (1, 3, "test_hHLBCl4rZ3v", ErrorCode::IncorrectMAC),
+                (2, 5, "", ErrorCode::EmptyToken),
+                (3, 6, "other_hHLBCl4rZ3u", ErrorCode::WrongType),
+            ]
+        );
+        assert_eq!(
+            column.rows[1].as_ref().unwrap_err().to_string(),
+            "Row 1 (line 3): \"test_hHLBCl4rZ3v\": Incorrect MAC"
+        );
+
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        assert!(matches!(
+            decode_column(&mut reader, "missing", &codec),
+            Err(ImportError::MissingColumn(name)) if name == "missing"
+        ));
+        let mut reader = csv::Reader::from_reader("id\ntest_hHLBCl4rZ3u,extra\n".as_bytes());
+        assert!(matches!(
+            decode_column(&mut reader, "id", &codec),
+            Err(ImportError::Csv(_))
+        ));
+    }
+}
@@ -0,0 +1,209 @@
+//! Encoding raw integer IDs embedded in `serde_json::Value` trees.
+//!
+//! This is intended for retrofitting legacy endpoints which return raw IDs, and for scrubbing
+//! payloads before they are sent to third parties.
+
+use serde_json::Value;
+
+use crate::CodecRegistry;
+
+/// Why a matching field was left unchanged.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SkipReason {
+    /// The value is not an unsigned integer.
+    NotAnInteger,
+    /// No codec with the given name is registered.
+    UnknownCodec(String),
+    /// The value is outside the `Config::allowed_range` of the codec.
+    OutOfRange,
+}
+
+/// The result of an `IdEncoder::encode` pass over a value.
+///
+/// Paths are JSON Pointers (RFC 6901) to the affected values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// Paths of the values which were replaced with encoded tokens.
+    pub encoded: Vec<String>,
+    /// Paths of the values whose field name matched a rule but which were left unchanged.
+    pub skipped: Vec<(String, SkipReason)>,
+}
+
+struct Rule {
+    pattern: String,
+    codec: String,
+}
+
+/// Replaces integer ID fields in JSON values with encoded tokens.
+///
+/// Fields are selected by object key using patterns which may contain a single `*` wildcard.
+/// The codec name may also contain `*`, which is replaced with the text matched by the
+/// wildcard.  Rules are tried in the order they were added, and the first matching rule wins.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::json::IdEncoder;
+/// use cryptid_rs::{CodecRegistry, Config};
+/// use serde_json::json;
+///
+/// let config = Config::new(b"your-secure-key");
+/// let mut registry = CodecRegistry::new();
+/// registry.register("example", &config);
+///
+/// let encoder = IdEncoder::new(&registry)
+///     .rule("id", "example")
+///     .rule("*_id", "*");
+/// let mut value = json!({"id": 12345, "example_id": 12345, "count": 3});
+/// let report = encoder.encode(&mut value);
+///
+/// assert_eq!(value, json!({
+///     "id": "example_VgwPy6rwatl",
+///     "example_id": "example_VgwPy6rwatl",
+///     "count": 3,
+/// }));
+/// assert_eq!(report.encoded, vec!["/example_id", "/id"]);
+/// ```
+pub struct IdEncoder<'a> {
+    registry: &'a CodecRegistry,
+    rules: Vec<Rule>,
+}
+
+impl<'a> IdEncoder<'a> {
+    /// Creates an encoder with no rules, looking up codecs from `registry`.
+    pub fn new(registry: &'a CodecRegistry) -> Self {
+        IdEncoder {
+            registry,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Adds a rule encoding fields matching `pattern` with the codec named `codec`.
+    pub fn rule(mut self, pattern: &str, codec: &str) -> Self {
+        self.rules.push(Rule {
+            pattern: pattern.to_string(),
+            codec: codec.to_string(),
+        });
+        self
+    }
+
+    /// Encodes all matching fields in `value` in place, returning a report of the changes.
+    pub fn encode(&self, value: &mut Value) -> Report {
+        let mut report = Report::default();
+        self.walk(value, &mut String::new(), &mut report);
+        report
+    }
+
+    fn walk(&self, value: &mut Value, path: &mut String, report: &mut Report) {
+        let len = path.len();
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map.iter_mut() {
+                    push_pointer(path, key);
+                    match self.codec_for(key) {
+                        Some(codec) => self.encode_field(child, codec, path, report),
+                        None => self.walk(child, path, report),
+                    }
+                    path.truncate(len);
+                }
+            }
+            Value::Array(items) => {
+                for (i, child) in items.iter_mut().enumerate() {
+                    push_pointer(path, &i.to_string());
+                    self.walk(child, path, report);
+                    path.truncate(len);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn encode_field(&self, value: &mut Value, codec: String, path: &str, report: &mut Report) {
+        let Some(num) = value.as_u64() else {
+            report
+                .skipped
+                .push((path.to_string(), SkipReason::NotAnInteger));
+            return;
+        };
+        match self.registry.get(&codec).map(|codec| codec.try_encode(num)) {
+            Some(Ok(encoded)) => {
+                *value = Value::String(encoded);
+                report.encoded.push(path.to_string());
+            }
+            Some(Err(_)) => report
+                .skipped
+                .push((path.to_string(), SkipReason::OutOfRange)),
+            None => report
+                .skipped
+                .push((path.to_string(), SkipReason::UnknownCodec(codec))),
+        }
+    }
+
+    fn codec_for(&self, key: &str) -> Option<String> {
+        self.rules.iter().find_map(|rule| {
+            let captured = match rule.pattern.split_once('*') {
+                None => (key == rule.pattern).then_some(""),
+                Some((head, tail)) => (key.len() >= head.len() + tail.len()
+                    && key.starts_with(head)
+                    && key.ends_with(tail))
+                .then(|| &key[head.len()..key.len() - tail.len()]),
+            }?;
+            Some(rule.codec.replace('*', captured))
+        })
+    }
+}
+
+fn push_pointer(path: &mut String, token: &str) {
+    path.push('/');
+    path.push_str(&token.replace('~', "~0").replace('/', "~1"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use serde_json::json;
+
+    #[test]
+    fn test_encode_nested() {
+        let config = Config::new(b"Test key here");
+        let mut registry = CodecRegistry::new();
+        registry.register("test", &config);
+        registry.register("team", &config.clone().allowed_range(1..5));
+
+        let encoder = IdEncoder::new(&registry)
+            .rule("owner_id", "test")
+            .rule("*_id", "*");
+        let mut value = json!({
+            "items": [{"test_id": 123}, {"test_id": "test_hHLBCl4rZ3u"}],
+            "owner_id": 1,
+            "team_id": 5,
+            "user_id": 7,
+            "a/b": {"test_id": 2},
+        });
+        let report = encoder.encode(&mut value);
+
+        assert_eq!(value["items"][0]["test_id"], "test_hHLBCl4rZ3u");
+        assert_eq!(value["owner_id"], "test_bTPc8uxHEwv");
+        assert_eq!(value["a/b"]["test_id"], "test_dZ0iJdcLBgB");
+        assert_eq!(value["team_id"], 5);
+        assert_eq!(
+            report,
+            Report {
+                encoded: vec![
+                    "/a~1b/test_id".to_string(),
+                    "/items/0/test_id".to_string(),
+                    "/owner_id".to_string(),
+                ],
+                skipped: vec![
+                    ("/items/1/test_id".to_string(), SkipReason::NotAnInteger),
+                    ("/team_id".to_string(), SkipReason::OutOfRange),
+                    (
+                        "/user_id".to_string(),
+                        SkipReason::UnknownCodec("user".to_string())
+                    ),
+                ],
+            }
+        );
+    }
+}
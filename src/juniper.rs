@@ -0,0 +1,208 @@
+//! Juniper support (`juniper` feature).
+//!
+//! `Field<T>` is a GraphQL scalar, so IDs can be used directly as arguments, input fields and
+//! output fields.  Values are encoded and decoded like with Serde, with the global config.
+//! The scalar of the marker `user` is named `UserId`, so each marker gets its own type.
+//!
+//! Invalid IDs in variables are errors mentioning the expected prefix, but not why decoding
+//! failed, so that clients can't tell a tampered token from a mistyped one.  Juniper reports
+//! invalid literals in queries as invalid values of the scalar, without the details.
+//!
+//! The traits are implemented by hand rather than with `GraphQLScalar`, which names the
+//! scalar with a literal.  Juniper's compile-time reflection, which checks that objects
+//! implement their interfaces, sees all `Field<T>` types as `Field`.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_rs::{Field, TypeMarker};
+//! use juniper::{graphql_object, EmptyMutation, EmptySubscription, RootNode};
+//!
+//! #[derive(Debug)]
+//! pub struct UserIdMarker;
+//! impl TypeMarker for UserIdMarker {
+//!     fn name() -> &'static str { "user" }
+//! }
+//! pub type UserId = Field<UserIdMarker>;
+//!
+//! struct Query;
+//!
+//! #[graphql_object]
+//! impl Query {
+//!     fn user_name(id: UserId) -> String {
+//!         format!("User {}", u64::from(id))
+//!     }
+//! }
+//!
+//! let schema = RootNode::new(Query, EmptyMutation::<()>::new(), EmptySubscription::<()>::new());
+//! assert!(schema.schema.concrete_type_by_name("UserId").is_some());
+//! ```
+
+use ::juniper::macros::reflect::{BaseSubTypes, BaseType, Type, Types, WrappedType, WrappedValue};
+use ::juniper::marker::{IsInputType, IsOutputType};
+use ::juniper::meta::MetaType;
+use ::juniper::parser::ScalarToken;
+use ::juniper::{
+    ArcStr, BoxFuture, ExecutionResult, Executor, FieldError, FromInputValue, GraphQLType,
+    GraphQLValue, GraphQLValueAsync, InputValue, ParseScalarResult, ParseScalarValue, Registry,
+    ScalarValue, Selection, ToInputValue, ToScalarValue, Value,
+};
+
+use crate::field::{get_or_create_codec, schema_name};
+use crate::{Field, TypeMarker};
+
+impl<T: TypeMarker, S: ScalarValue> GraphQLType<S> for Field<T> {
+    fn name(_: &()) -> Option<ArcStr> {
+        Some(schema_name::<T>().into())
+    }
+
+    fn meta(info: &(), registry: &mut Registry<S>) -> MetaType<S> {
+        registry
+            .build_scalar_type::<Self>(info)
+            .description("An object ID, encrypted into a prefixed token.")
+            .into_meta()
+    }
+}
+
+impl<T: TypeMarker, S: ScalarValue> GraphQLValue<S> for Field<T> {
+    type Context = ();
+    type TypeInfo = ();
+
+    fn type_name(&self, info: &()) -> Option<ArcStr> {
+        <Self as GraphQLType<S>>::name(info)
+    }
+
+    fn resolve(
+        &self,
+        _: &(),
+        _: Option<&[Selection<'_, S>]>,
+        _: &Executor<'_, '_, (), S>,
+    ) -> ExecutionResult<S> {
+        Ok(Value::Scalar(self.to_scalar_value()))
+    }
+}
+
+impl<T: TypeMarker + Sync, S: ScalarValue + Send + Sync> GraphQLValueAsync<S> for Field<T> {
+    fn resolve_async<'b>(
+        &'b self,
+        info: &'b (),
+        selection_set: Option<&'b [Selection<'_, S>]>,
+        executor: &'b Executor<'_, '_, (), S>,
+    ) -> BoxFuture<'b, ExecutionResult<S>> {
+        let value = self.resolve(info, selection_set, executor);
+        Box::pin(::juniper::futures::future::ready(value))
+    }
+}
+
+impl<T: TypeMarker, S: ScalarValue> ToScalarValue<S> for Field<T> {
+    /// Encodes the ID in the form of `T::serde_format()`.
+    fn to_scalar_value(&self) -> S {
+        S::from_displayable(&self.encode_string(&*get_or_create_codec::<T>()))
+    }
+}
+
+impl<T: TypeMarker, S: ScalarValue> ToInputValue<S> for Field<T> {
+    fn to_input_value(&self) -> InputValue<S> {
+        InputValue::Scalar(self.to_scalar_value())
+    }
+}
+
+impl<T: TypeMarker, S: ScalarValue> FromInputValue<S> for Field<T> {
+    type Error = FieldError<S>;
+
+    /// Decodes a token, or also a UUID with `SerdeFormat::Uuid`, like `Deserialize`.
+    fn from_input_value(input: &InputValue<S>) -> Result<Self, FieldError<S>> {
+        let prefix = T::prefix_case().apply(T::name());
+        let error = || FieldError::from(format!("Expected a token with the prefix {}_", prefix));
+        let encoded = input
+            .as_scalar()
+            .and_then(S::try_as_str)
+            .ok_or_else(error)?;
+        Field::decode_string(&get_or_create_codec::<T>(), encoded).map_err(|_| error())
+    }
+}
+
+impl<T: TypeMarker, S: ScalarValue> ParseScalarValue<S> for Field<T> {
+    fn from_str(token: ScalarToken<'_>) -> ParseScalarResult<S> {
+        <String as ParseScalarValue<S>>::from_str(token)
+    }
+}
+
+impl<T: TypeMarker, S: ScalarValue> IsInputType<S> for Field<T> {}
+
+impl<T: TypeMarker, S: ScalarValue> IsOutputType<S> for Field<T> {}
+
+impl<T: TypeMarker, S: ScalarValue> BaseType<S> for Field<T> {
+    const NAME: Type = "Field";
+}
+
+impl<T: TypeMarker, S: ScalarValue> BaseSubTypes<S> for Field<T> {
+    const NAMES: Types = &[<Self as BaseType<S>>::NAME];
+}
+
+impl<T: TypeMarker, S: ScalarValue> WrappedType<S> for Field<T> {
+    const VALUE: WrappedValue = 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use ::juniper::{
+        execute_sync, graphql_object, graphql_value, EmptyMutation, EmptySubscription, RootNode,
+        Variables,
+    };
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+        fn reserved_ids() -> &'static [u64] {
+            &[0]
+        }
+    }
+
+    struct Query;
+
+    #[graphql_object]
+    impl Query {
+        fn next(id: Field<ExampleIdMarker>) -> Field<ExampleIdMarker> {
+            id.map(|id| id + 1)
+        }
+    }
+
+    #[test]
+    fn test_scalar() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let schema = RootNode::new(
+            Query,
+            EmptyMutation::<()>::new(),
+            EmptySubscription::<()>::new(),
+        );
+        assert!(schema.schema.concrete_type_by_name("ExampleId").is_some());
+
+        let next = Field::<ExampleIdMarker>::from(12346).to_string();
+        let query = r#"{ next(id: "example_VgwPy6rwatl") }"#;
+        let (data, errors) = execute_sync(query, None, &schema, &Variables::new(), &()).unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(data, graphql_value!({ "next": next }));
+
+        let zero = Field::<ExampleIdMarker>::from(0).to_string();
+        for id in ["example_VgwPy6rwatx", "user_VgwPy6rwatl", &zero] {
+            let query = format!(r#"{{ next(id: "{}") }}"#, id);
+            let error = execute_sync(&query, None, &schema, &Variables::new(), &()).unwrap_err();
+            let message = error.to_string();
+            assert!(message.contains("for type \"ExampleId\""), "{}", message);
+        }
+
+        // Errors in variables have the details.
+        let query = "query ($id: ExampleId!) { next(id: $id) }";
+        let mut variables = Variables::new();
+        variables.insert("id".to_string(), InputValue::scalar("example_x"));
+        let error = execute_sync(query, None, &schema, &variables, &()).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("the prefix example_"), "{}", message);
+    }
+}
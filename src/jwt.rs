@@ -0,0 +1,149 @@
+use serde_json::{Map, Value};
+
+use crate::field::get_or_create_codec;
+use crate::{Error, Field, TypeMarker};
+
+/// Helpers for carrying `Field<T>` values in JSON Web Token claims.
+///
+/// The trait is implemented for `serde_json::Map<String, Value>`, which is what most JWT
+/// libraries use for custom claims.  IDs are always stored as encoded tokens, so raw database
+/// IDs never end up in the (only base64-encoded, not encrypted) token payload.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Claims, Config, Field, TypeMarker};
+/// use serde_json::{Map, Value};
+///
+/// #[derive(Debug)]
+/// pub struct UserIdMarker;
+/// impl TypeMarker for UserIdMarker {
+///     fn name() -> &'static str { "user" }
+/// }
+///
+/// type UserId = Field<UserIdMarker>;
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let mut claims = Map::new();
+/// claims.insert_id("sub", UserId::from(12345)).unwrap();
+/// assert_eq!(claims["sub"], Value::from("user_Qo4cTPVnos2"));
+///
+/// let user_id: UserId = claims.id("sub").unwrap().unwrap();
+/// assert_eq!(u64::from(user_id), 12345);
+/// ```
+pub trait Claims {
+    /// Inserts `field` as an encoded token under the claim `name`, returning the previous
+    /// value of the claim, if any.
+    ///
+    /// Returns `Error::IdOutOfRange` without inserting anything if the ID is outside the
+    /// allowed range of `T` (see `TypeMarker::allowed_range`).
+    fn insert_id<T: TypeMarker>(
+        &mut self,
+        name: &str,
+        field: Field<T>,
+    ) -> Result<Option<Value>, Error>;
+
+    /// Decodes the claim `name` into a `Field<T>`.
+    ///
+    /// Returns `Ok(None)` if the claim is missing, and an error if it is not a string or does
+    /// not decode as a `T` token, like with `Deserialize`.  Reserved IDs of `T` fail with
+    /// `Error::ReservedId`.
+    fn id<T: TypeMarker>(&self, name: &str) -> Result<Option<Field<T>>, Error>;
+}
+
+impl Claims for Map<String, Value> {
+    fn insert_id<T: TypeMarker>(
+        &mut self,
+        name: &str,
+        field: Field<T>,
+    ) -> Result<Option<Value>, Error> {
+        let encoded = get_or_create_codec::<T>().try_encode(field.into())?;
+        Ok(self.insert(name.to_string(), Value::String(encoded)))
+    }
+
+    fn id<T: TypeMarker>(&self, name: &str) -> Result<Option<Field<T>>, Error> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(Value::String(encoded)) => {
+                Field::decode_string(&get_or_create_codec::<T>(), encoded).map(Some)
+            }
+            Some(_) => Err(Error::DecodingFailed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[derive(Debug)]
+    struct TestMarker;
+    impl TypeMarker for TestMarker {
+        fn name() -> &'static str {
+            "test"
+        }
+    }
+
+    #[derive(Debug)]
+    struct RangedMarker;
+    impl TypeMarker for RangedMarker {
+        fn name() -> &'static str {
+            "test"
+        }
+        fn allowed_range() -> Option<std::ops::Range<u64>> {
+            Some(1..100)
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReservedMarker;
+    impl TypeMarker for ReservedMarker {
+        fn name() -> &'static str {
+            "test"
+        }
+        fn reserved_ids() -> &'static [u64] {
+            &[0]
+        }
+    }
+
+    #[test]
+    fn test_claims() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let mut claims = Map::new();
+        claims.insert("iat".to_string(), Value::from(1700000000));
+
+        assert_eq!(
+            claims.insert_id("sub", Field::<TestMarker>::from(123)),
+            Ok(None)
+        );
+        assert!(claims["sub"].as_str().unwrap().starts_with("test_"));
+        assert_eq!(
+            claims.id::<TestMarker>("sub").unwrap().map(u64::from),
+            Some(123)
+        );
+
+        assert!(claims.id::<TestMarker>("missing").unwrap().is_none());
+
+        // IDs outside the allowed range are not inserted.
+        assert_eq!(
+            claims.insert_id("other", Field::<RangedMarker>::from(0)),
+            Err(Error::IdOutOfRange)
+        );
+        assert!(!claims.contains_key("other"));
+
+        // Reserved IDs are rejected like in `Deserialize`.
+        claims
+            .insert_id("sub", Field::<TestMarker>::from(0))
+            .unwrap();
+        assert_eq!(claims.id::<ReservedMarker>("sub"), Err(Error::ReservedId));
+        assert_eq!(
+            claims.id::<TestMarker>("sub").unwrap().map(u64::from),
+            Some(0)
+        );
+        assert!(matches!(
+            claims.id::<TestMarker>("iat"),
+            Err(Error::DecodingFailed)
+        ));
+    }
+}
@@ -0,0 +1,295 @@
+//! Shamir secret sharing for master keys.
+//!
+//! `split_key` splits a master key into `n` shares, any `k` of which are enough to
+//! reconstruct it with `reconstruct`, while fewer than `k` shares reveal nothing about the
+//! key.  This allows m-of-n key ceremonies, where no single person holds the master key.
+//!
+//! Shares have a text form, `cryptid-share:<threshold>:<index>:<hex data>:<checksum>`, which
+//! is safe to print, copy into password managers or read aloud.  The checksum only detects
+//! typos; it does not authenticate the share.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_rs::keysplit::{reconstruct, split_key, Share};
+//!
+//! let key = b"your-secure-key";
+//! let shares = split_key(key, 5, 3).unwrap();
+//! let texts: Vec<String> = shares.iter().map(|share| share.to_string()).collect();
+//!
+//! let parsed: Vec<Share> = [&texts[4], &texts[0], &texts[2]]
+//!     .iter()
+//!     .map(|text| text.parse().unwrap())
+//!     .collect();
+//! assert_eq!(reconstruct(&parsed).unwrap(), key);
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+/// Errors from splitting keys and reconstructing them from shares.
+#[derive(Debug, PartialEq)]
+pub enum KeySplitError {
+    /// The threshold must be between 2 and the number of shares, which must be at most 255,
+    /// and the key must not be empty.
+    InvalidParameters,
+    /// Fewer shares were given than the threshold requires.
+    NotEnoughShares,
+    /// The shares have different thresholds or lengths, or duplicate indices.
+    InconsistentShares,
+    /// A share string could not be parsed or its checksum is wrong.
+    InvalidShareFormat,
+    /// The system random number generator failed.
+    RandomFailed,
+}
+
+impl fmt::Display for KeySplitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeySplitError::InvalidParameters => write!(f, "Invalid key split parameters"),
+            KeySplitError::NotEnoughShares => write!(f, "Not enough shares"),
+            KeySplitError::InconsistentShares => write!(f, "Shares are not consistent"),
+            KeySplitError::InvalidShareFormat => write!(f, "Invalid share format"),
+            KeySplitError::RandomFailed => write!(f, "Generating random numbers failed"),
+        }
+    }
+}
+
+impl std::error::Error for KeySplitError {}
+
+/// One share of a split key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Share {
+    threshold: u8,
+    index: u8,
+    data: Vec<u8>,
+}
+
+impl Share {
+    /// The number of shares needed to reconstruct the key.
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// The index of this share, from 1 to the number of shares.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    fn checksum(&self) -> String {
+        let digest = Sha256::new()
+            .chain_update([self.threshold, self.index])
+            .chain_update(&self.data)
+            .finalize();
+        hex(&digest[..2])
+    }
+}
+
+impl fmt::Display for Share {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cryptid-share:{}:{}:{}:{}",
+            self.threshold,
+            self.index,
+            hex(&self.data),
+            self.checksum()
+        )
+    }
+}
+
+impl FromStr for Share {
+    type Err = KeySplitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        let [tag, threshold, index, data, checksum] = parts[..] else {
+            return Err(KeySplitError::InvalidShareFormat);
+        };
+        let share = Share {
+            threshold: threshold
+                .parse()
+                .map_err(|_| KeySplitError::InvalidShareFormat)?,
+            index: index
+                .parse()
+                .map_err(|_| KeySplitError::InvalidShareFormat)?,
+            data: unhex(data).ok_or(KeySplitError::InvalidShareFormat)?,
+        };
+        if tag != "cryptid-share"
+            || share.threshold < 2
+            || share.index == 0
+            || share.data.is_empty()
+            || !checksum.eq_ignore_ascii_case(&share.checksum())
+        {
+            return Err(KeySplitError::InvalidShareFormat);
+        }
+        Ok(share)
+    }
+}
+
+/// Splits `key` into `n` shares, any `k` of which reconstruct the key.
+pub fn split_key(key: &[u8], n: u8, k: u8) -> Result<Vec<Share>, KeySplitError> {
+    if k < 2 || k > n || key.is_empty() {
+        return Err(KeySplitError::InvalidParameters);
+    }
+    let mut shares: Vec<Share> = (1..=n)
+        .map(|index| Share {
+            threshold: k,
+            index,
+            data: Vec::with_capacity(key.len()),
+        })
+        .collect();
+    let mut coefficients = vec![0u8; k as usize];
+    for &secret in key {
+        getrandom::getrandom(&mut coefficients[1..]).map_err(|_| KeySplitError::RandomFailed)?;
+        coefficients[0] = secret;
+        for share in shares.iter_mut() {
+            // Evaluate the polynomial at x = index with Horner's method.
+            let y = coefficients
+                .iter()
+                .rev()
+                .fold(0, |acc, &c| gf_mul(acc, share.index) ^ c);
+            share.data.push(y);
+        }
+    }
+    coefficients.fill(0);
+    Ok(shares)
+}
+
+/// Reconstructs a key from at least `threshold` shares.
+pub fn reconstruct(shares: &[Share]) -> Result<Vec<u8>, KeySplitError> {
+    let first = shares.first().ok_or(KeySplitError::NotEnoughShares)?;
+    if shares.len() < first.threshold as usize {
+        return Err(KeySplitError::NotEnoughShares);
+    }
+    let shares = &shares[..first.threshold as usize];
+    for (i, share) in shares.iter().enumerate() {
+        if share.threshold != first.threshold
+            || share.data.len() != first.data.len()
+            || shares[..i].iter().any(|other| other.index == share.index)
+        {
+            return Err(KeySplitError::InconsistentShares);
+        }
+    }
+
+    // Lagrange interpolation at x = 0.  In GF(256), subtraction is XOR.
+    let mut key = vec![0u8; first.data.len()];
+    for (i, share) in shares.iter().enumerate() {
+        let mut basis = 1u8;
+        for (j, other) in shares.iter().enumerate() {
+            if i != j {
+                basis = gf_mul(basis, gf_div(other.index, other.index ^ share.index));
+            }
+        }
+        for (byte, &y) in key.iter_mut().zip(&share.data) {
+            *byte ^= gf_mul(basis, y);
+        }
+    }
+    Ok(key)
+}
+
+// Multiplication in GF(256) with the AES polynomial x^8 + x^4 + x^3 + x + 1.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = (a << 1) ^ if a & 0x80 != 0 { 0x1b } else { 0 };
+        b >>= 1;
+    }
+    product
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    // b^254 is the inverse of b, since the multiplicative group has order 255.
+    let mut inverse = 1;
+    for _ in 0..254 {
+        inverse = gf_mul(inverse, b);
+    }
+    gf_mul(a, inverse)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gf() {
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(gf_div(1, a), a), 1);
+        }
+    }
+
+    #[test]
+    fn test_any_k_shares() {
+        let key: Vec<u8> = (0..64).collect();
+        let shares = split_key(&key, 5, 3).unwrap();
+        for a in 0..5 {
+            for b in 0..5 {
+                for c in 0..5 {
+                    if a != b && b != c && a != c {
+                        let subset = [shares[a].clone(), shares[b].clone(), shares[c].clone()];
+                        assert_eq!(reconstruct(&subset).unwrap(), key);
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            reconstruct(&shares[..2]),
+            Err(KeySplitError::NotEnoughShares)
+        );
+        let duplicate = [shares[0].clone(), shares[1].clone(), shares[0].clone()];
+        assert_eq!(
+            reconstruct(&duplicate),
+            Err(KeySplitError::InconsistentShares)
+        );
+    }
+
+    #[test]
+    fn test_parameters_and_format() {
+        assert_eq!(
+            split_key(b"key", 3, 1),
+            Err(KeySplitError::InvalidParameters)
+        );
+        assert_eq!(
+            split_key(b"key", 2, 3),
+            Err(KeySplitError::InvalidParameters)
+        );
+        assert_eq!(split_key(b"", 3, 2), Err(KeySplitError::InvalidParameters));
+
+        let share = &split_key(b"key", 3, 2).unwrap()[1];
+        let text = share.to_string();
+        assert!(text.starts_with("cryptid-share:2:2:"));
+        assert_eq!(&text.parse::<Share>().unwrap(), share);
+
+        let mut typo = text.clone().into_bytes();
+        let pos = "cryptid-share:2:2:".len();
+        typo[pos] = if typo[pos] == b'0' { b'1' } else { b'0' };
+        assert_eq!(
+            String::from_utf8(typo).unwrap().parse::<Share>(),
+            Err(KeySplitError::InvalidShareFormat)
+        );
+        assert_eq!(
+            "not-a-share".parse::<Share>(),
+            Err(KeySplitError::InvalidShareFormat)
+        );
+    }
+}
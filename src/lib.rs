@@ -10,8 +10,10 @@
 //! The encoded IDs include a customizable object type prefix, inspired by Stripe's API.  This
 //! prevents accidentally or intentionally mixing IDs of different types of objects.
 //!
-//! `cryptid` uses [format-preserving encryption (FPE)](https://en.wikipedia.org/wiki/Format-preserving_encryption)
-//! with AES (FF1 with AES256) and HMAC (SHA256) for integrity checks.
+//! `cryptid` uses [format-preserving encryption (FPE)][fpe] with AES (FF1 with AES256) and
+//! HMAC (SHA256) for integrity checks.
+//!
+//! [fpe]: https://en.wikipedia.org/wiki/Format-preserving_encryption
 //!
 //! Please note that leaking the encryption key means you lose all the security benefits.
 //! Anyone can then decrypt and encrypt your IDs, and you'll be just as (in)secure as using plain
@@ -67,11 +69,159 @@
 //! assert_eq!(decoded, 12345);
 //! ```
 //!
+//! # Features
+//!
+//! Each integration lives in its own module, named after its feature:
+//!
+//! - `diesel` (default): Diesel support for Postgres `BIGINT`, `INTEGER`, `TEXT` and `UUID`
+//!   columns.
+//! - `diesel-sqlite`: Diesel support for SQLite `BIGINT`, `INTEGER` and `TEXT` columns too.
+//! - `actix-web`: the `actix_web` module, with `FromRequest` for `Field<T>`.
+//! - `async-graphql`: `Field<T>` as a GraphQL scalar.
+//! - `axum`: the `axum` module, with the `IdPath` extractor.
+//! - `csv`: the `import` module, for decoding columns of IDs.
+//! - `fuzzing`: the `fuzzing` module and `arbitrary::Arbitrary` implementations.
+//! - `json`: the `json` module, for encoding IDs in arbitrary JSON.
+//! - `juniper`: `Field<T>` as a Juniper GraphQL scalar.
+//! - `jwt`: `Claims`, for IDs in JWT claims.
+//! - `keysplit`: the `keysplit` module, for splitting keys into shares.
+//! - `metrics`: the `telemetry` module, recording encode and decode metrics and counts.
+//! - `prost`: the `prost` module, for IDs in protobuf messages and `tonic::Status` errors.
+//! - `sea-orm`: SeaORM support for `BIGINT` columns.
+//! - `shared-keys`: `SharedKeyCache`, for sharing derived keys between processes (Unix only).
+//! - `test-chaos`: for tests and staging only.  Without a global config, `Field` uses a random
+//!   per-process key instead of panicking, so clients can't depend on tokens being stable (see
+//!   `Config::global` and `KeyMode::Ephemeral`).  It fails to compile without debug
+//!   assertions, e.g. in release builds.
+//! - `tracing`: the `trace` module, for logging fields without leaking raw IDs.
+//! - `utoipa`: OpenAPI schemas for `Field<T>`.
+//!
+//! Serde and UUID support are always included, as `Field` and `IdCodec` depend on them.  The
+//! smallest build, e.g. for CLI tools or WebAssembly, disables the default features and
+//! enables `internal-base62`, which uses the internal base62 implementation instead of the
+//! `base62` crate and leaves out Diesel:
+//!
+//! ```toml
+//! cryptid-rs = { version = "0.2", default-features = false, features = ["internal-base62"] }
+//! ```
+
+#[cfg(not(any(feature = "base62", feature = "internal-base62")))]
+compile_error!("Enable either the `base62` (default) or the `internal-base62` feature");
+
+// The random key would silently invalidate all tokens on every restart in production.
+#[cfg(all(feature = "test-chaos", not(debug_assertions)))]
+compile_error!("The `test-chaos` feature is only for builds with debug assertions");
 
+#[cfg(feature = "actix-web")]
+pub mod actix_web;
+pub mod analysis;
+pub mod anonymize;
+#[cfg(feature = "async-graphql")]
+mod async_graphql;
+#[cfg(feature = "axum")]
+pub mod axum;
+mod cache;
+mod case;
 mod codec;
+mod collision;
+mod composite_field;
 mod config;
+mod config_spec;
+mod cursor;
+#[cfg(feature = "diesel")]
+mod diesel;
+#[cfg(feature = "diesel-sqlite")]
+mod diesel_sqlite;
+pub mod empty_as_none;
+mod envelope;
+pub mod events;
+#[cfg(doctest)]
+mod feature_guards;
 mod field;
+mod fingerprint;
+mod fixed;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+mod global;
+mod hkdf;
+mod hook;
+mod id_codec;
+#[cfg(feature = "csv")]
+pub mod import;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "juniper")]
+mod juniper;
+#[cfg(feature = "jwt")]
+mod jwt;
+#[cfg(feature = "keysplit")]
+pub mod keysplit;
+mod limiter;
+mod order;
+mod policy;
+mod poly;
+mod pool;
+#[cfg(feature = "prost")]
+pub mod prost;
+mod radix;
+mod range_token;
+mod registry;
+pub mod rekey;
+mod roundtrip;
+mod salt;
+mod scratch;
+#[cfg(feature = "sea-orm")]
+mod sea_orm;
+#[cfg(feature = "shared-keys")]
+mod shared_keys;
+pub mod spec;
+mod str_field;
+mod target;
+#[cfg(feature = "metrics")]
+pub mod telemetry;
+pub mod testing;
+mod token;
+#[cfg(feature = "tracing")]
+pub mod trace;
+mod typed_ref;
+mod u128_field;
+#[cfg(feature = "utoipa")]
+mod utoipa;
+mod uuid_field;
 
-pub use codec::{Codec, Error};
-pub use config::{Config, ConfigError};
-pub use field::{Field, TypeMarker};
+pub use cache::{CacheStats, CachedCodec};
+pub use case::PrefixCase;
+pub use codec::{
+    Codec, CompositeLayout, EncodedToken, Error, ErrorCode, Limits, TokenForm, MAX_COMPOSITE_PARTS,
+    MAX_STRING_LENGTH,
+};
+pub use collision::{Collision, CollisionChecker, CollisionReport};
+pub use composite_field::CompositeField;
+pub use config::{Config, ConfigError, KeyMode};
+pub use config_spec::{ConfigSpec, FormatSpec};
+pub use cursor::{After, Before};
+pub use envelope::Envelope;
+pub use field::{registered_prefixes, Field, IdInt, NegativeIds, SerdeFormat, TypeMarker};
+pub use fixed::FixedCodec;
+pub use hook::CodecHook;
+pub use id_codec::IdCodec;
+#[cfg(feature = "jwt")]
+pub use jwt::Claims;
+pub use limiter::DecodeLimiter;
+pub use policy::AccessPolicy;
+pub use poly::{Poly2, Poly3, Poly4, PolyField, PolyMarkers};
+pub use pool::CodecPool;
+pub use radix::Alphabet;
+pub use range_token::RangeToken;
+pub use registry::{CodecRegistry, DecodeAttempt, DecodeTrace};
+pub use roundtrip::{RoundtripFailure, RoundtripReport};
+pub use salt::Salt;
+pub use scratch::{with_scratch, Scratch};
+#[cfg(feature = "shared-keys")]
+pub use shared_keys::SharedKeyCache;
+pub use str_field::StrField;
+pub use target::ContextTarget;
+pub use token::{TokenBuilder, TokenFields};
+pub use typed_ref::TypedRef;
+pub use u128_field::U128Field;
+pub use uuid_field::UuidField;
@@ -0,0 +1,82 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// A token bucket limiting the rate of decode attempts.
+///
+/// A limiter can be shared by several codecs (see `Codec::with_limiter`), in which case the
+/// rate applies to their combined decode attempts.  Every attempt consumes a token, whether
+/// it succeeds or not.  This offers basic protection against brute-force guessing of MACs in
+/// lower layers, but it is not a replacement for per-client rate limiting in the web layer.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use cryptid_rs::{Codec, Config, DecodeLimiter, Error};
+///
+/// let limiter = Arc::new(DecodeLimiter::new(100.0, 2));
+/// let codec = Codec::new("example", &Config::new(b"your-secure-key")).with_limiter(limiter);
+///
+/// assert_eq!(codec.decode("example_VgwPy6rwatl"), Ok(12345));
+/// assert_eq!(codec.decode("example_VgwPy6rwatl"), Ok(12345));
+/// assert_eq!(codec.decode("example_VgwPy6rwatl"), Err(Error::RateLimited));
+/// ```
+#[derive(Debug)]
+pub struct DecodeLimiter {
+    burst: f64,
+    rate: f64,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl DecodeLimiter {
+    /// Creates a limiter allowing `rate` decode attempts per second on average, with bursts
+    /// of up to `burst` attempts.  The bucket starts full.
+    pub fn new(rate: f64, burst: u32) -> Self {
+        DecodeLimiter {
+            burst: burst as f64,
+            rate,
+            state: Mutex::new(Bucket {
+                tokens: burst as f64,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token from the bucket, returning `false` if none are available.
+    pub fn try_acquire(&self) -> bool {
+        let mut bucket = self.state.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_refill() {
+        let limiter = DecodeLimiter::new(50.0, 3);
+        assert!((0..3).all(|_| limiter.try_acquire()));
+        assert!(!limiter.try_acquire());
+
+        sleep(Duration::from_millis(50));
+        assert!(limiter.try_acquire());
+    }
+}
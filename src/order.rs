@@ -0,0 +1,104 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::hkdf::Hkdf;
+use crate::radix;
+use crate::{Alphabet, Error};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Order-preserving token payloads ("OPE-lite").
+///
+/// The raw ID `num` is mapped to `num * multiplier + remainder(num)`, where `multiplier` is a
+/// secret derived from the key and `remainder` is a keyed PRF reduced modulo `multiplier`.  The
+/// mapping is strictly increasing, so the fixed-width rendering sorts in the same order as
+/// the raw IDs.  The remainder doubles as the integrity check on decode.
+///
+/// This is much weaker than the default FF1 mode: the multiplier is confined to
+/// [2^(bits-1), 2^bits), so the differences between the tokens of nearby IDs reveal it, and
+/// with it every raw ID.  Without a MAC the multiplier would be 1 and the token the raw ID,
+/// so `Codec::try_new` rejects `hmac_length` 0 in this mode.
+pub(crate) struct OrderPreserving {
+    hmac: HmacSha256,
+    multiplier: u128,
+    width: usize,
+}
+
+impl OrderPreserving {
+    pub(crate) fn new(hkdf: &Hkdf, name: &str, hmac_length: usize, alphabet: &Alphabet) -> Self {
+        let mut hmac_key = [0u8; 32];
+        let mut multiplier_bytes = [0u8; 8];
+        hkdf.expand(format!("{}/ope", name).as_bytes(), &mut hmac_key)
+            .expect("Length 32 should be valid");
+        hkdf.expand(
+            format!("{}/ope-multiplier", name).as_bytes(),
+            &mut multiplier_bytes,
+        )
+        .expect("Length 8 should be valid");
+
+        // The multiplier is a secret in [2^(bits-1), 2^bits), so that the remainder carries
+        // roughly as much authentication as an HMAC of `hmac_length` bytes would.
+        let bits = 8 * hmac_length as u32;
+        let multiplier = if bits == 0 {
+            1
+        } else {
+            let base = 1u128 << (bits - 1);
+            base + (u64::from_le_bytes(multiplier_bytes) as u128 & (base - 1))
+        };
+
+        OrderPreserving {
+            hmac: HmacSha256::new_from_slice(&hmac_key).expect("Key length 32 should be valid"),
+            multiplier,
+            width: width(alphabet.radix(), hmac_length),
+        }
+    }
+
+    /// Number of characters in a rendered value.
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    pub(crate) fn encode(&self, num: u64) -> u128 {
+        num as u128 * self.multiplier + self.remainder(num)
+    }
+
+    pub(crate) fn decode(&self, value: u128) -> Result<u64, Error> {
+        let num: u64 = (value / self.multiplier)
+            .try_into()
+            .map_err(|_| Error::InvalidDataLength)?;
+        if value % self.multiplier != self.remainder(num) {
+            return Err(Error::IncorrectMAC);
+        }
+        Ok(num)
+    }
+
+    /// Returns the number in `value` without checking the remainder.
+    #[cfg(feature = "forensics")]
+    pub(crate) fn decode_unverified(&self, value: u128) -> Result<u64, Error> {
+        (value / self.multiplier)
+            .try_into()
+            .map_err(|_| Error::InvalidDataLength)
+    }
+
+    fn remainder(&self, num: u64) -> u128 {
+        if self.multiplier == 1 {
+            return 0;
+        }
+        let mut hmac = self.hmac.clone();
+        hmac.update(&num.to_le_bytes());
+        let digest = hmac.finalize().into_bytes();
+        let bytes: [u8; 16] = digest[..16].try_into().expect("Digest is 32 bytes");
+        u128::from_le_bytes(bytes) % self.multiplier
+    }
+}
+
+/// Number of characters in a rendered value with the given `hmac_length`.
+pub(crate) const fn width(radix: u8, hmac_length: usize) -> usize {
+    let bits = 8 * hmac_length as u32;
+    let max = if bits == 64 {
+        u128::MAX
+    } else {
+        (1u128 << (64 + bits)) - 1
+    };
+    radix::digit_count(radix, max)
+}
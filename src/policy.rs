@@ -0,0 +1,124 @@
+use crate::{Field, TypeMarker};
+
+/// Checks whether the caller may access an object, for `Field::decode_authorized`.
+///
+/// Opaque IDs make IDs hard to guess, but they don't replace authorization: a token may leak,
+/// or be shared by a user with someone else.  Implementing `AccessPolicy<T>` for the request
+/// context of an application, and decoding with `Field::decode_authorized`, makes the
+/// ownership check part of decoding, so it can't be forgotten.
+///
+/// The policy is implemented by the context type, and a context can implement it for several
+/// markers.  Many APIs respond the same way to forbidden and nonexistent objects, so as not to
+/// reveal which IDs exist; `Error::Forbidden` can be mapped to a "not found" response for this.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{AccessPolicy, Config, Error, Field, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct ExampleIdMarker;
+/// impl TypeMarker for ExampleIdMarker {
+///     fn name() -> &'static str { "example" }
+/// }
+///
+/// struct RequestContext {
+///     owned_examples: Vec<u64>,
+/// }
+///
+/// impl AccessPolicy<ExampleIdMarker> for RequestContext {
+///     fn authorize(&self, id: Field<ExampleIdMarker>) -> bool {
+///         self.owned_examples.contains(&u64::from(id))
+///     }
+/// }
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let owner = RequestContext { owned_examples: vec![12345] };
+/// let other = RequestContext { owned_examples: vec![] };
+///
+/// let example = Field::<ExampleIdMarker>::decode_authorized("example_VgwPy6rwatl", &owner);
+/// assert_eq!(example.map(u64::from), Ok(12345));
+/// assert_eq!(
+///     Field::<ExampleIdMarker>::decode_authorized("example_VgwPy6rwatl", &other),
+///     Err(Error::Forbidden)
+/// );
+/// ```
+pub trait AccessPolicy<T: TypeMarker> {
+    /// Returns `true` if the object `id` may be accessed.
+    fn authorize(&self, id: Field<T>) -> bool;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, Error};
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+    }
+
+    #[derive(Debug)]
+    struct UserIdMarker;
+    impl TypeMarker for UserIdMarker {
+        fn name() -> &'static str {
+            "user"
+        }
+    }
+
+    // Allows even IDs of examples, and only the user's own ID.
+    struct Context {
+        user: u64,
+    }
+
+    impl AccessPolicy<ExampleIdMarker> for Context {
+        fn authorize(&self, id: Field<ExampleIdMarker>) -> bool {
+            u64::from(id) % 2 == 0
+        }
+    }
+
+    impl AccessPolicy<UserIdMarker> for Context {
+        fn authorize(&self, id: Field<UserIdMarker>) -> bool {
+            u64::from(id) == self.user
+        }
+    }
+
+    #[test]
+    fn test_decode_authorized() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let ctx = Context { user: 12345 };
+
+        let even = Field::<ExampleIdMarker>::from(2);
+        let odd = Field::<ExampleIdMarker>::from(3);
+        assert_eq!(Field::decode_authorized(&even.to_string(), &ctx), Ok(even));
+        assert_eq!(
+            Field::<ExampleIdMarker>::decode_authorized(&odd.to_string(), &ctx),
+            Err(Error::Forbidden)
+        );
+
+        assert_eq!(
+            Field::<UserIdMarker>::decode_authorized("user_Qo4cTPVnos2", &ctx),
+            Ok(Field::from(12345))
+        );
+        assert_eq!(
+            Field::<UserIdMarker>::decode_authorized(
+                &Field::<UserIdMarker>::from(1).to_string(),
+                &ctx
+            ),
+            Err(Error::Forbidden)
+        );
+
+        // Decoding errors are returned before the policy is consulted.
+        assert_eq!(
+            Field::<UserIdMarker>::decode_authorized("example_VgwPy6rwatl", &ctx),
+            Err(Error::WrongType {
+                received_prefix: "example_".to_string(),
+                expected: "user_".into(),
+                hint: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::codec::split_prefix;
+use crate::field::get_or_create_codec;
+use crate::{Error, Field, TypeMarker};
+
+/// The ID of a polymorphic relation, which refers to an object of one of the marker types in
+/// the tuple `M`, such as the target of a comment in `commentable_type` and `commentable_id`
+/// columns.
+///
+/// `PolyField<(A, B)>` is the enum `Poly2<A, B>`, with a variant holding a `Field` of each
+/// marker, and likewise for three and four markers.  With Serde, a value is serialized like
+/// the `Field` it holds, and a token is deserialized into the variant whose codec accepts it,
+/// i.e. whose prefix it has.  `from_parts`, `name` and `id` convert to and from the type
+/// discriminant and the raw ID stored in a database.
+///
+/// UUIDs have no prefix, so they are decoded with each marker in turn, and the first one
+/// whose MAC matches is chosen.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Config, Field, Poly2, PolyField, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct PostIdMarker;
+/// impl TypeMarker for PostIdMarker {
+///     fn name() -> &'static str { "post" }
+/// }
+///
+/// #[derive(Debug)]
+/// pub struct PhotoIdMarker;
+/// impl TypeMarker for PhotoIdMarker {
+///     fn name() -> &'static str { "photo" }
+/// }
+///
+/// type CommentableId = PolyField<(PostIdMarker, PhotoIdMarker)>;
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let target = CommentableId::from_parts("photo", 12345).unwrap();
+/// let json = serde_json::to_string(&target).unwrap();
+///
+/// match serde_json::from_str::<CommentableId>(&json).unwrap() {
+///     Poly2::A(post_id) => panic!("Expected a photo, got {}", post_id),
+///     Poly2::B(photo_id) => assert_eq!(u64::from(photo_id), 12345),
+/// }
+/// assert_eq!((target.name(), target.id()), ("photo", 12345));
+/// ```
+pub type PolyField<M> = <M as PolyMarkers>::Field;
+
+/// Tuples of markers which `PolyField` is defined for.
+pub trait PolyMarkers {
+    /// The enum of the fields of the markers.
+    type Field;
+}
+
+// Returns the error of decoding `encoded` with none of the markers matching: the first error
+// other than a wrong prefix or type, or else a wrong prefix like `CodecRegistry::decode`.
+fn no_match(encoded: &str, errors: impl IntoIterator<Item = Error>) -> Error {
+    errors
+        .into_iter()
+        .find(|error| !matches!(error, Error::InvalidPrefix { .. } | Error::WrongType { .. }))
+        .unwrap_or_else(|| Error::InvalidPrefix {
+            received: split_prefix(encoded)
+                .map_or("", |(prefix, _)| prefix)
+                .to_string(),
+            expected: "".into(),
+        })
+}
+
+macro_rules! poly {
+    ($(#[$doc:meta])* $name:ident, $($marker:ident),+) => {
+        $(#[$doc])*
+        #[derive(Debug)]
+        pub enum $name<$($marker: TypeMarker),+> {
+            $($marker(Field<$marker>)),+
+        }
+
+        impl<$($marker: TypeMarker),+> PolyMarkers for ($($marker,)+) {
+            type Field = $name<$($marker),+>;
+        }
+
+        impl<$($marker: TypeMarker),+> $name<$($marker),+> {
+            /// Returns the variant of the marker named `name`, holding the raw ID `id`, or
+            /// `None` if no marker has the name.
+            pub fn from_parts(name: &str, id: u64) -> Option<Self> {
+                $(
+                    if name == $marker::name() {
+                        return Some($name::$marker(Field::from(id)));
+                    }
+                )+
+                None
+            }
+
+            /// Returns the name of the marker of the variant, e.g. for a type column.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($name::$marker(_) => $marker::name()),+
+                }
+            }
+
+            /// Returns the raw ID.
+            pub fn id(&self) -> u64 {
+                match self {
+                    $($name::$marker(field) => u64::from(*field)),+
+                }
+            }
+
+            /// Decodes a token or UUID of any of the markers.  See `PolyField`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the global config has not been set.
+            pub fn decode(encoded: &str) -> Result<Self, Error> {
+                let errors = [$(
+                    match Field::<$marker>::decode_with(
+                        &get_or_create_codec::<$marker>(),
+                        encoded,
+                    ) {
+                        Ok(field) => return Ok($name::$marker(field)),
+                        Err(error) => error,
+                    }
+                ),+];
+                Err(no_match(encoded, errors))
+            }
+        }
+
+        impl<$($marker: TypeMarker),+> Clone for $name<$($marker),+> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<$($marker: TypeMarker),+> Copy for $name<$($marker),+> {}
+
+        impl<$($marker: TypeMarker),+> PartialEq for $name<$($marker),+> {
+            fn eq(&self, other: &Self) -> bool {
+                match (self, other) {
+                    $(($name::$marker(a), $name::$marker(b)) => a == b,)+
+                    _ => false,
+                }
+            }
+        }
+
+        impl<$($marker: TypeMarker),+> Eq for $name<$($marker),+> {}
+
+        impl<$($marker: TypeMarker),+> fmt::Display for $name<$($marker),+> {
+            /// Formats the field of the variant.  See `Field`.
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    $($name::$marker(field) => field.fmt(f)),+
+                }
+            }
+        }
+
+        impl<$($marker: TypeMarker),+> Serialize for $name<$($marker),+> {
+            // The generic parameters are not named S and D, which would clash with markers.
+            fn serialize<Ser>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+            where
+                Ser: Serializer,
+            {
+                match self {
+                    $($name::$marker(field) => field.serialize(serializer)),+
+                }
+            }
+        }
+
+        impl<'de, $($marker: TypeMarker),+> Deserialize<'de> for $name<$($marker),+> {
+            fn deserialize<De>(deserializer: De) -> Result<Self, De::Error>
+            where
+                De: Deserializer<'de>,
+            {
+                let encoded = String::deserialize(deserializer)?;
+                Self::decode(&encoded).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+poly!(
+    /// A `PolyField` of two markers.
+    Poly2, A, B
+);
+poly!(
+    /// A `PolyField` of three markers.
+    Poly3, A, B, C
+);
+poly!(
+    /// A `PolyField` of four markers.
+    Poly4, A, B, C, D
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, NegativeIds, SerdeFormat};
+
+    #[derive(Debug)]
+    struct PostIdMarker;
+    impl TypeMarker for PostIdMarker {
+        fn name() -> &'static str {
+            "post"
+        }
+    }
+
+    #[derive(Debug)]
+    struct PhotoIdMarker;
+    impl TypeMarker for PhotoIdMarker {
+        fn name() -> &'static str {
+            "photo"
+        }
+        fn serde_format() -> SerdeFormat {
+            SerdeFormat::Uuid
+        }
+    }
+
+    #[derive(Debug)]
+    struct VideoIdMarker;
+    impl TypeMarker for VideoIdMarker {
+        fn name() -> &'static str {
+            "video"
+        }
+        fn negative_ids() -> NegativeIds {
+            NegativeIds::Reinterpret
+        }
+    }
+
+    type Target = PolyField<(PostIdMarker, PhotoIdMarker, VideoIdMarker)>;
+
+    #[test]
+    fn test_poly_field() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        for (name, id) in [("post", 1), ("photo", 2), ("video", 3), ("video", u64::MAX)] {
+            let target = Target::from_parts(name, id).unwrap();
+            assert_eq!((target.name(), target.id()), (name, id));
+            let json = serde_json::to_value(target).unwrap();
+            assert_eq!(serde_json::from_value::<Target>(json).unwrap(), target);
+            assert_eq!(Target::decode(&target.to_string()), Ok(target));
+        }
+        assert!(matches!(
+            Target::decode(&Field::<PostIdMarker>::from(5).to_string()),
+            Ok(Poly3::A(field)) if u64::from(field) == 5
+        ));
+        let photo = Field::<PhotoIdMarker>::from(5).encode_uuid().to_string();
+        assert!(matches!(Target::decode(&photo), Ok(Poly3::B(_))));
+        assert_ne!(
+            Target::from_parts("post", 1),
+            Target::from_parts("video", 1)
+        );
+        assert_eq!(Target::from_parts("user", 1), None);
+
+        // A wrong prefix is reported only if no marker has the prefix.
+        assert_eq!(
+            Target::decode("user_Qo4cTPVnos2"),
+            Err(Error::InvalidPrefix {
+                received: "user_".to_string(),
+                expected: "".into()
+            })
+        );
+        let mut tampered = Field::<VideoIdMarker>::from(5).to_string();
+        tampered.push('0');
+        let error = Target::decode(&tampered).unwrap_err();
+        assert!(
+            !matches!(error, Error::InvalidPrefix { .. } | Error::WrongType { .. }),
+            "{:?}",
+            error
+        );
+        assert!(serde_json::from_str::<Target>("\"user_Qo4cTPVnos2\"").is_err());
+    }
+}
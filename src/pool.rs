@@ -0,0 +1,158 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::cache::Generations;
+use crate::{Codec, Config};
+
+/// A bounded cache of codecs for servers which hold many keys, such as one per tenant.
+///
+/// Creating a codec derives keys and computes AES key schedules, so codecs should be reused.
+/// The pool keys codecs by name and a digest of the config, and holds up to about `capacity`
+/// recently used codecs, so that the derived keys of inactive tenants are eventually dropped.
+/// Codecs are returned as `Arc`s, so evicting a codec in use doesn't invalidate it.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{CodecPool, Config};
+///
+/// let pool = CodecPool::new(100);
+/// let tenant_a = Config::new(b"your-secure-key");
+/// let tenant_b = Config::new(b"another-secure-key");
+///
+/// assert_eq!(pool.get("example", &tenant_a).encode(12345), "example_VgwPy6rwatl");
+/// assert_ne!(pool.get("example", &tenant_b).encode(12345), "example_VgwPy6rwatl");
+/// assert_eq!(pool.len(), 2);
+/// ```
+pub struct CodecPool {
+    capacity: usize,
+    generations: Mutex<Codecs>,
+}
+
+// Codecs keyed by name and config digest.
+type Codecs = Generations<(String, [u8; 32]), Arc<Codec>>;
+
+impl CodecPool {
+    /// Creates an empty pool of about `capacity` codecs.
+    pub fn new(capacity: usize) -> Self {
+        CodecPool {
+            capacity: capacity.div_ceil(2),
+            generations: Mutex::new(Generations::default()),
+        }
+    }
+
+    /// Returns the codec named `name` for `config`, creating it if it is not in the pool.
+    pub fn get(&self, name: &str, config: &Config) -> Arc<Codec> {
+        let key = (name.to_string(), config.digest());
+        if let Some(codec) = self.lock().get(self.capacity, &key) {
+            return codec;
+        }
+        // The codec is created without holding the lock, so that other threads aren't blocked.
+        let codec = Arc::new(Codec::new(name, config));
+        if self.capacity > 0 {
+            self.lock().insert(self.capacity, key, codec.clone());
+        }
+        codec
+    }
+
+    /// Returns the number of codecs in the pool.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if the pool holds no codecs.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops all codecs from the pool, e.g. after rotating keys.
+    pub fn clear(&self) {
+        *self.lock() = Generations::default();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Codecs> {
+        // A panic while holding the lock can't leave the generations inconsistent.
+        self.generations
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Alphabet, CodecRegistry, ContextTarget, Salt};
+
+    #[test]
+    fn test_pool() {
+        let config = Config::new(b"Test key here");
+        let pool = CodecPool::new(4);
+        let codec = pool.get("test", &config);
+        assert_eq!(codec.encode(123), "test_hHLBCl4rZ3u");
+        assert!(Arc::ptr_eq(&codec, &pool.get("test", &config)));
+        assert!(Arc::ptr_eq(&codec, &pool.get("test", &config.clone())));
+
+        // Any difference in the config gives a different codec.
+        let configs = [
+            Config::new(b"Test key here!"),
+            config.clone().hmac_length(8).unwrap(),
+            config.clone().zero_pad_length(3).unwrap(),
+            config.clone().order_preserving(true),
+            config.clone().bind_parameters(true),
+            config.clone().salt(Salt::from_bytes(*b"0123456789abcdef")),
+        ];
+        for other in &configs {
+            assert!(!Arc::ptr_eq(&codec, &pool.get("test", other)));
+        }
+        assert!(!Arc::ptr_eq(&codec, &pool.get("other", &config)));
+
+        // Only recently used codecs are kept.
+        assert!(pool.len() <= 4);
+        assert!(!Arc::ptr_eq(&codec, &pool.get("test", &config)));
+        assert_eq!(codec.encode(123), "test_hHLBCl4rZ3u");
+
+        pool.clear();
+        assert!(pool.is_empty());
+        let pool = CodecPool::new(0);
+        pool.get("test", &config);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_config_digest() {
+        let config = Config::new(b"Test key here");
+        let configs = [
+            config.clone().key_version(1).unwrap(),
+            config.clone().previous_key(1, b"Old key").unwrap(),
+            config.clone().nonce_length(2).unwrap(),
+            config.clone().alphabet(Alphabet::Decimal).unwrap(),
+            config.clone().target(ContextTarget::UrlPath).unwrap(),
+            config.clone().type_hints(Arc::new(CodecRegistry::new())),
+            config
+                .clone()
+                .alphabet(Alphabet::Custom("9876543210".into()))
+                .unwrap(),
+            config
+                .clone()
+                .alphabet(Alphabet::Custom("0123456798".into()))
+                .unwrap(),
+            config.clone().allowed_range(1..5),
+            config.clone().allowed_range(1..6),
+        ];
+        let pool = CodecPool::new(2 * configs.len() + 2);
+        let codec = pool.get("test", &config);
+        for other in &configs {
+            let other_codec = pool.get("test", other);
+            assert!(!Arc::ptr_eq(&codec, &other_codec));
+            assert!(Arc::ptr_eq(&other_codec, &pool.get("test", other)));
+        }
+
+        // A decimal config doesn't get the cached base62 codec.
+        let decimal = pool.get("test", &configs[3]);
+        assert!(decimal.encode(123)[5..].bytes().all(|c| c.is_ascii_digit()));
+
+        // Codecs with different allowed ranges are not shared.
+        let n = configs.len();
+        assert!(pool.get("test", &configs[n - 2]).try_encode(5).is_err());
+        assert!(pool.get("test", &configs[n - 1]).try_encode(5).is_ok());
+    }
+}
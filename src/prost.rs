@@ -0,0 +1,282 @@
+//! Protobuf support with prost and tonic (`prost` feature).
+//!
+//! IDs are carried in protobuf messages as strings, in the same form as with Serde, so that
+//! gRPC and REST APIs share the same opaque IDs.  `Field<T>` converts into a `String` and back
+//! with the global config, and `Error` converts into a `tonic::Status`, so that a handler can
+//! decode a string field with `try_into()?`.
+//!
+//! Invalid IDs are `NOT_FOUND` errors, like in the Actix Web and Axum integrations, so that
+//! clients can't tell a tampered or mistyped token from an object which doesn't exist.  Rate
+//! limited decodes are `RESOURCE_EXHAUSTED`.
+//!
+//! `IdValue<T>` is a message with the string as its only field, wire compatible with
+//! `google.protobuf.StringValue`, for messages which name the type of the ID in the Rust code
+//! or need an optional ID.  prost can't report errors of its own from decoding a message, so
+//! the string is decoded into an ID when the value is converted into a `Field<T>`.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_rs::{Config, Field, TypeMarker};
+//!
+//! #[derive(Debug)]
+//! pub struct UserIdMarker;
+//! impl TypeMarker for UserIdMarker {
+//!     fn name() -> &'static str { "user" }
+//! }
+//! pub type UserId = Field<UserIdMarker>;
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! pub struct GetUserRequest {
+//!     #[prost(string, tag = "1")]
+//!     pub user_id: String,
+//! }
+//!
+//! fn get_user(request: GetUserRequest) -> Result<String, tonic::Status> {
+//!     let user_id: UserId = request.user_id.try_into()?;
+//!     Ok(format!("User {}", u64::from(user_id)))
+//! }
+//!
+//! Config::set_global(Config::new(b"your-secure-key"));
+//! let request = GetUserRequest { user_id: UserId::from(12345).into() };
+//! assert_eq!(get_user(request).unwrap(), "User 12345");
+//!
+//! let request = GetUserRequest { user_id: "user_x".to_string() };
+//! assert_eq!(get_user(request).unwrap_err().code(), tonic::Code::NotFound);
+//! ```
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use ::prost::bytes::{Buf, BufMut};
+use ::prost::encoding::{self, DecodeContext, WireType};
+use ::prost::{DecodeError, Message};
+use ::tonic::Status;
+
+use crate::field::get_or_create_codec;
+use crate::{Error, Field, TypeMarker};
+
+impl<T: TypeMarker> From<Field<T>> for String {
+    /// Encodes the ID in the form of `T::serde_format()`.
+    fn from(field: Field<T>) -> String {
+        field.encode_string(&*get_or_create_codec::<T>())
+    }
+}
+
+impl<T: TypeMarker> TryFrom<&str> for Field<T> {
+    type Error = Error;
+
+    /// Decodes a token, or also a UUID with `SerdeFormat::Uuid`, like `Deserialize`.
+    fn try_from(encoded: &str) -> Result<Self, Error> {
+        Field::decode_string(&get_or_create_codec::<T>(), encoded)
+    }
+}
+
+impl<T: TypeMarker> TryFrom<String> for Field<T> {
+    type Error = Error;
+
+    /// Decodes a token, or also a UUID with `SerdeFormat::Uuid`, like `Deserialize`.
+    fn try_from(encoded: String) -> Result<Self, Error> {
+        Field::try_from(encoded.as_str())
+    }
+}
+
+impl From<Error> for Status {
+    /// Returns a status with the code of the error and a message which doesn't reveal why
+    /// decoding failed.
+    fn from(error: Error) -> Status {
+        match error {
+            Error::RateLimited => Status::resource_exhausted("Too many requests"),
+            Error::EncryptionFailed => Status::internal("Internal error"),
+            _ => Status::not_found("Not found"),
+        }
+    }
+}
+
+/// A protobuf message carrying a `Field<T>` as a string in field 1, wire compatible with
+/// `google.protobuf.StringValue`.
+///
+/// The string is decoded when the value is converted into a `Field<T>`, which fails like
+/// `Field::try_from` for invalid IDs.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::prost::IdValue;
+/// use cryptid_rs::{Config, Field, TypeMarker};
+/// use prost::Message;
+///
+/// #[derive(Debug)]
+/// pub struct UserIdMarker;
+/// impl TypeMarker for UserIdMarker {
+///     fn name() -> &'static str { "user" }
+/// }
+///
+/// #[derive(Clone, PartialEq, prost::Message)]
+/// pub struct User {
+///     #[prost(message, optional, tag = "1")]
+///     pub manager_id: Option<IdValue<UserIdMarker>>,
+/// }
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let user = User { manager_id: Some(Field::from(12345).into()) };
+/// let decoded = User::decode(&*user.encode_to_vec()).unwrap();
+/// let manager_id = Field::<UserIdMarker>::try_from(decoded.manager_id.unwrap()).unwrap();
+/// assert_eq!(u64::from(manager_id), 12345);
+/// ```
+pub struct IdValue<T: TypeMarker> {
+    value: String,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: TypeMarker> IdValue<T> {
+    /// Returns the string in the message, which is not necessarily a valid ID.
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+}
+
+// The standard traits are implemented manually, so that they don't require the marker type
+// to implement them.
+impl<T: TypeMarker> Clone for IdValue<T> {
+    fn clone(&self) -> Self {
+        IdValue {
+            value: self.value.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: TypeMarker> PartialEq for IdValue<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: TypeMarker> Eq for IdValue<T> {}
+
+impl<T: TypeMarker> Default for IdValue<T> {
+    fn default() -> Self {
+        IdValue {
+            value: String::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: TypeMarker> fmt::Debug for IdValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("IdValue").field(&self.value).finish()
+    }
+}
+
+impl<T: TypeMarker> From<Field<T>> for IdValue<T> {
+    fn from(field: Field<T>) -> Self {
+        IdValue {
+            value: field.into(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: TypeMarker> TryFrom<IdValue<T>> for Field<T> {
+    type Error = Error;
+
+    fn try_from(value: IdValue<T>) -> Result<Self, Error> {
+        Field::try_from(value.as_str())
+    }
+}
+
+impl<T: TypeMarker> Message for IdValue<T> {
+    fn encode_raw(&self, buf: &mut impl BufMut) {
+        if !self.value.is_empty() {
+            encoding::string::encode(1, &self.value, buf);
+        }
+    }
+
+    fn merge_field(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut impl Buf,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError> {
+        match tag {
+            1 => encoding::string::merge(wire_type, &mut self.value, buf, ctx),
+            _ => encoding::skip_field(wire_type, tag, buf, ctx),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self.value.is_empty() {
+            true => 0,
+            false => encoding::string::encoded_len(1, &self.value),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.value.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use ::tonic::Code;
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+        fn reserved_ids() -> &'static [u64] {
+            &[0]
+        }
+    }
+
+    type ExampleId = Field<ExampleIdMarker>;
+
+    #[test]
+    fn test_conversions() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let encoded: String = ExampleId::from(12345).into();
+        assert_eq!(encoded, "example_VgwPy6rwatl");
+        assert_eq!(ExampleId::try_from(encoded), Ok(ExampleId::from(12345)));
+
+        let zero = ExampleId::from(0).to_string();
+        for invalid in ["example_VgwPy6rwatx", "user_VgwPy6rwatl", "", &zero] {
+            let status = Status::from(ExampleId::try_from(invalid).unwrap_err());
+            assert_eq!(status.code(), Code::NotFound, "{}", invalid);
+            assert_eq!(status.message(), "Not found");
+        }
+        assert_eq!(
+            Status::from(Error::RateLimited).code(),
+            Code::ResourceExhausted
+        );
+    }
+
+    #[test]
+    fn test_id_value() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let value = IdValue::from(ExampleId::from(12345));
+        let bytes = value.encode_to_vec();
+        assert_eq!(bytes[..2], [0x0a, 19]);
+        assert_eq!(&bytes[2..], b"example_VgwPy6rwatl");
+
+        let decoded = IdValue::<ExampleIdMarker>::decode(&*bytes).unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!(ExampleId::try_from(decoded), Ok(ExampleId::from(12345)));
+
+        // Unknown fields are skipped, and the empty string is the default.
+        let unknown = [0x10, 0x01];
+        let empty = IdValue::<ExampleIdMarker>::decode(&unknown[..]).unwrap();
+        assert_eq!(empty, IdValue::default());
+        assert_eq!(empty.encoded_len(), 0);
+        assert!(ExampleId::try_from(empty).is_err());
+
+        // Invalid UTF-8 is a decode error of prost.
+        assert!(IdValue::<ExampleIdMarker>::decode(&[0x0a, 0x01, 0xff][..]).is_err());
+    }
+}
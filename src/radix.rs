@@ -0,0 +1,562 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::{ConfigError, Error};
+
+const ALPHABET: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+// Crockford's base32 in lowercase, without `i`, `l`, `o` and `u`.
+const BASE32: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+
+const DECIMAL: &[u8; 10] = b"0123456789";
+
+/// Number of base62 digits, for computing lengths in constants.
+pub(crate) const BASE62_RADIX: u8 = ALPHABET.len() as u8;
+
+/// Number of base62 digits of `u128::MAX`.
+pub(crate) const U128_DIGITS: usize = 22;
+
+/// Number of decimal digits of `u128::MAX`, the most of any alphabet.
+pub(crate) const MAX_U128_DIGITS: usize = 39;
+
+/// The digits of token payloads, set with `Config::alphabet` or `TypeMarker::alphabet`.
+///
+/// Tokens of other alphabets are longer than base62 tokens, but fit where base62 doesn't:
+/// decimal tokens can be typed on a phone keypad or sent in SMS messages, and base32 tokens
+/// are lowercase, so they can be used in subdomains and on case-insensitive file systems.
+/// Decoding rejects characters outside the alphabet, including uppercase base32 digits.
+///
+/// Other digits can be given with `Alphabet::Custom`, e.g. to leave out characters which look
+/// alike, for IDs which are read aloud or typed from print.  Custom alphabets are parsed from
+/// `custom:` followed by the digits, so they can be given in configuration files.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Alphabet, Codec, Config};
+///
+/// let config = Config::new(b"your-secure-key").alphabet(Alphabet::Decimal).unwrap();
+/// let codec = Codec::new("ref", &config);
+/// let encoded = codec.encode(12345);
+///
+/// assert!(encoded["ref_".len()..].bytes().all(|c| c.is_ascii_digit()));
+/// assert_eq!(codec.decode(&encoded), Ok(12345));
+/// assert_eq!("decimal".parse::<Alphabet>().unwrap(), Alphabet::Decimal);
+/// assert_eq!(
+///     "custom:9876543210".parse::<Alphabet>().unwrap(),
+///     Alphabet::Custom("9876543210".into())
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Alphabet {
+    /// `0-9A-Za-z`, in this order.
+    #[default]
+    Base62,
+    /// Crockford's base32 in lowercase: `0-9` and `a-z` without `i`, `l`, `o` and `u`.
+    Base32,
+    /// `0-9`.
+    Decimal,
+    /// The given digits in order of their values, which must be 10 to 62 distinct ASCII
+    /// letters and digits, or `Config::alphabet` fails with `ConfigError::UnsupportedAlphabet`.
+    ///
+    /// Tokens sort in the order of the raw IDs in order-preserving mode only if the digits are
+    /// in ASCII order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{Alphabet, Codec, Config};
+    ///
+    /// // Base62 without `0`, `O`, `1`, `l` and `I`.
+    /// let digits = "23456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    /// let config = Config::new(b"your-secure-key").alphabet(Alphabet::Custom(digits.into()));
+    /// let config = config.unwrap();
+    /// let codec = Codec::new("ticket", &config);
+    /// let encoded = codec.encode(12345);
+    ///
+    /// assert!(!encoded["ticket_".len()..].contains(['0', 'O', '1', 'l', 'I']));
+    /// assert_eq!(codec.decode(&encoded), Ok(12345));
+    /// let short = Alphabet::Custom("0123".into());
+    /// assert!(Config::new(b"your-secure-key").alphabet(short).is_err());
+    /// ```
+    Custom(Arc<str>),
+}
+
+impl Alphabet {
+    /// Returns the digits of the alphabet in order of their values.
+    pub fn digits(&self) -> &str {
+        std::str::from_utf8(self.digit_bytes()).expect("Alphabet is ASCII")
+    }
+
+    /// Returns the name of the alphabet, as accepted by `parse`, or `"custom"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Alphabet::Base62 => "base62",
+            Alphabet::Base32 => "base32",
+            Alphabet::Decimal => "decimal",
+            Alphabet::Custom(_) => "custom",
+        }
+    }
+
+    fn digit_bytes(&self) -> &[u8] {
+        match self {
+            Alphabet::Base62 => ALPHABET,
+            Alphabet::Base32 => BASE32,
+            Alphabet::Decimal => DECIMAL,
+            Alphabet::Custom(digits) => digits.as_bytes(),
+        }
+    }
+
+    /// Returns `true` if the digits are 10 to 62 distinct ASCII letters and digits.  At least
+    /// 10 digits keep `u128` values within `MAX_U128_DIGITS`.
+    pub(crate) fn is_valid(&self) -> bool {
+        let digits = self.digit_bytes();
+        (10..=62).contains(&digits.len())
+            && digits
+                .iter()
+                .enumerate()
+                .all(|(i, c)| c.is_ascii_alphanumeric() && !digits[..i].contains(c))
+    }
+
+    /// Returns the number of digits in the alphabet.
+    pub(crate) fn radix(&self) -> u8 {
+        self.digit_bytes().len() as u8
+    }
+
+    /// Returns the digit of `value`, which must be below the radix.
+    pub(crate) fn digit(&self, value: u8) -> char {
+        self.digit_bytes()[value as usize] as char
+    }
+
+    /// Returns the value of the digit `c`, if it is one.
+    pub(crate) fn digit_value(&self, c: u8) -> Option<u8> {
+        self.digit_bytes()
+            .iter()
+            .position(|&d| d == c)
+            .map(|value| value as u8)
+    }
+
+    /// Returns the number of digits of `num`.
+    pub(crate) fn digit_count(&self, num: u128) -> usize {
+        digit_count(self.radix(), num)
+    }
+
+    /// Like `write_u128`, in this alphabet.
+    pub(crate) fn write_u128(&self, out: &mut [u8], num: u128, width: usize) -> usize {
+        if *self == Alphabet::Base62 {
+            return write_u128(out, num, width);
+        }
+        let mut buf = [0; MAX_U128_DIGITS];
+        let digits = self.u128_digits(num, &mut buf);
+        let padding = width.saturating_sub(digits.len());
+        out[..padding].fill(self.digit_bytes()[0]);
+        out[padding..padding + digits.len()].copy_from_slice(digits);
+        padding + digits.len()
+    }
+
+    /// Like `push_u128`, in this alphabet.
+    pub(crate) fn push_u128(&self, out: &mut String, num: u128, width: usize) {
+        if *self == Alphabet::Base62 {
+            return push_u128(out, num, width);
+        }
+        let mut buf = [0; MAX_U128_DIGITS];
+        let digits = self.u128_digits(num, &mut buf);
+        for _ in digits.len()..width {
+            out.push(self.digit(0));
+        }
+        out.push_str(std::str::from_utf8(digits).expect("Alphabet is ASCII"));
+    }
+
+    // Returns the digits of `num`, written into the end of `buf`.
+    fn u128_digits<'a>(&self, mut num: u128, buf: &'a mut [u8; MAX_U128_DIGITS]) -> &'a [u8] {
+        let radix = self.radix() as u128;
+        let mut start = MAX_U128_DIGITS;
+        loop {
+            start -= 1;
+            buf[start] = self.digit_bytes()[(num % radix) as usize];
+            num /= radix;
+            if num == 0 {
+                return &buf[start..];
+            }
+        }
+    }
+
+    /// Like `decode_u128`, in this alphabet.
+    pub(crate) fn decode_u128(&self, encoded: &str) -> Result<u128, Error> {
+        if *self == Alphabet::Base62 {
+            return decode_u128(encoded);
+        }
+        if encoded.is_empty() {
+            return Err(Error::DecodingFailed);
+        }
+        encoded.bytes().try_fold(0u128, |num, c| {
+            let digit = self.digit_value(c).ok_or(Error::DecodingFailed)?;
+            num.checked_mul(self.radix() as u128)
+                .and_then(|num| num.checked_add(digit as u128))
+                .ok_or(Error::PayloadTooLong {
+                    max_chars: self.digit_count(u128::MAX),
+                })
+        })
+    }
+
+    /// Encodes a little-endian number of arbitrary length.
+    pub(crate) fn encode(&self, le_bytes: &[u8]) -> String {
+        encode_digits(self.digit_bytes(), le_bytes)
+    }
+
+    /// Decodes a string into a little-endian number without trailing zero bytes.
+    pub(crate) fn decode(&self, encoded: &str) -> Result<Vec<u8>, Error> {
+        decode_digits(self.digit_bytes(), encoded)
+    }
+}
+
+impl FromStr for Alphabet {
+    type Err = ConfigError;
+
+    /// Parses the name of an alphabet, or its digits in order, or `custom:` followed by the
+    /// digits of a custom alphabet.
+    fn from_str(s: &str) -> Result<Self, ConfigError> {
+        if let Some(digits) = s.strip_prefix("custom:") {
+            let alphabet = Alphabet::Custom(digits.into());
+            if !alphabet.is_valid() {
+                return Err(ConfigError::UnsupportedAlphabet);
+            }
+            return Ok(alphabet);
+        }
+        [Alphabet::Base62, Alphabet::Base32, Alphabet::Decimal]
+            .into_iter()
+            .find(|alphabet| s == alphabet.name() || s == alphabet.digits())
+            .ok_or(ConfigError::UnsupportedAlphabet)
+    }
+}
+
+/// Returns the number of digits of `num` in base `radix`.  This is a `const fn`, so lengths
+/// can be computed at compile time.
+pub(crate) const fn digit_count(radix: u8, mut num: u128) -> usize {
+    let radix = radix as u128;
+    let mut count = 1;
+    while num >= radix {
+        num /= radix;
+        count += 1;
+    }
+    count
+}
+
+// The functions below encode and decode base62, identically to the `base62` crate.  The
+// alphabet is part of the format and never changes.
+
+/// Writes the base62 digits of `num` to the start of `out`, padded with leading zeros to
+/// `width` digits, and returns the number of bytes written.
+///
+/// Panics if `out` is too short.
+pub(crate) fn write_u128(out: &mut [u8], num: u128, width: usize) -> usize {
+    let mut buf = [0; U128_DIGITS];
+    let digits = u128_digits(num, &mut buf);
+    let padding = width.saturating_sub(digits.len());
+    out[..padding].fill(b'0');
+    out[padding..padding + digits.len()].copy_from_slice(digits);
+    padding + digits.len()
+}
+
+/// Appends the base62 digits of `num` to `out`, padded with leading zeros to `width` digits.
+pub(crate) fn push_u128(out: &mut String, num: u128, width: usize) {
+    let mut buf = [0; U128_DIGITS];
+    let digits = u128_digits(num, &mut buf);
+    for _ in digits.len()..width {
+        out.push('0');
+    }
+    out.push_str(std::str::from_utf8(digits).expect("Alphabet is ASCII"));
+}
+
+// Returns the base62 digits of `num`, written into `buf`.
+fn u128_digits(num: u128, buf: &mut [u8; U128_DIGITS]) -> &[u8] {
+    #[cfg(feature = "internal-base62")]
+    {
+        let start;
+        (*buf, start) = encode_u128_digits(num);
+        &buf[start..]
+    }
+    #[cfg(not(feature = "internal-base62"))]
+    {
+        let length = base62::encode_bytes(num, buf).expect("Buffer fits a u128");
+        &buf[..length]
+    }
+}
+
+/// Decodes a base62 string into a `u128`, identically to `base62::decode`.
+pub(crate) fn decode_u128(encoded: &str) -> Result<u128, Error> {
+    #[cfg(feature = "internal-base62")]
+    {
+        match decode_u128_digits(encoded.as_bytes()) {
+            Ok(num) => Ok(num),
+            Err(Overflow::Yes) => Err(Error::PayloadTooLong {
+                max_chars: U128_DIGITS,
+            }),
+            Err(Overflow::No) => Err(Error::DecodingFailed),
+        }
+    }
+    #[cfg(not(feature = "internal-base62"))]
+    {
+        base62::decode(encoded).map_err(Error::from)
+    }
+}
+
+/// Returns the base62 digits of `num`, right-aligned in the buffer, and the index of the
+/// first digit.  This is a `const fn`, so tokens can be computed at compile time.
+#[cfg(any(feature = "internal-base62", test))]
+pub(crate) const fn encode_u128_digits(mut num: u128) -> ([u8; U128_DIGITS], usize) {
+    let mut digits = [0; U128_DIGITS];
+    let mut start = U128_DIGITS;
+    loop {
+        start -= 1;
+        digits[start] = ALPHABET[(num % 62) as usize];
+        num /= 62;
+        if num == 0 {
+            return (digits, start);
+        }
+    }
+}
+
+// Whether `decode_u128_digits` failed because the value didn't fit in a `u128`.
+#[cfg(any(feature = "internal-base62", test))]
+pub(crate) enum Overflow {
+    Yes,
+    No,
+}
+
+/// Decodes base62 digits into a `u128`.  This is a `const fn` like `encode_u128_digits`.
+#[cfg(any(feature = "internal-base62", test))]
+pub(crate) const fn decode_u128_digits(encoded: &[u8]) -> Result<u128, Overflow> {
+    if encoded.is_empty() {
+        return Err(Overflow::No);
+    }
+    let mut num: u128 = 0;
+    let mut i = 0;
+    while i < encoded.len() {
+        let digit = match encoded[i] {
+            c @ b'0'..=b'9' => c - b'0',
+            c @ b'A'..=b'Z' => c - b'A' + 10,
+            c @ b'a'..=b'z' => c - b'a' + 36,
+            _ => return Err(Overflow::No),
+        };
+        num = match num.checked_mul(62) {
+            Some(num) => match num.checked_add(digit as u128) {
+                Some(num) => num,
+                None => return Err(Overflow::Yes),
+            },
+            None => return Err(Overflow::Yes),
+        };
+        i += 1;
+    }
+    Ok(num)
+}
+
+// Encodes a little-endian number of arbitrary length with the digits of `alphabet`.
+//
+// For inputs of up to 16 bytes in base62, the output is identical to `base62::encode` of the
+// corresponding `u128`.
+fn encode_digits(alphabet: &[u8], le_bytes: &[u8]) -> String {
+    let radix = alphabet.len() as u32;
+    // Big-endian working copy without leading zeroes, divided by the radix until exhausted.
+    let mut num: Vec<u8> = le_bytes.iter().rev().copied().collect();
+    let mut digits = Vec::new();
+    loop {
+        while num.first() == Some(&0) {
+            num.remove(0);
+        }
+        if num.is_empty() {
+            break;
+        }
+        let mut remainder = 0u32;
+        for byte in num.iter_mut() {
+            let value = (remainder << 8) | *byte as u32;
+            *byte = (value / radix) as u8;
+            remainder = value % radix;
+        }
+        digits.push(alphabet[remainder as usize]);
+    }
+    if digits.is_empty() {
+        digits.push(alphabet[0]);
+    }
+    digits.reverse();
+    String::from_utf8(digits).expect("Alphabet is ASCII")
+}
+
+// Decodes digits of `alphabet` into a little-endian number without trailing zero bytes.
+fn decode_digits(alphabet: &[u8], encoded: &str) -> Result<Vec<u8>, Error> {
+    if encoded.is_empty() {
+        return Err(Error::DecodingFailed);
+    }
+    let mut num: Vec<u8> = Vec::new();
+    for c in encoded.bytes() {
+        let digit = alphabet
+            .iter()
+            .position(|&a| a == c)
+            .ok_or(Error::DecodingFailed)? as u32;
+        let mut carry = digit;
+        for byte in num.iter_mut() {
+            let value = *byte as u32 * alphabet.len() as u32 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        while carry > 0 {
+            num.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    Ok(num)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_matches_base62() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..1_000 {
+            let num: u128 = rng.gen::<u128>() >> rng.gen_range(0..128);
+            let encoded = Alphabet::Base62.encode(&num.to_le_bytes());
+            assert_eq!(encoded, base62::encode(num));
+
+            let mut decoded = Alphabet::Base62.decode(&encoded).unwrap();
+            decoded.resize(16, 0);
+            assert_eq!(decoded, num.to_le_bytes());
+        }
+        assert_eq!(Alphabet::Base62.encode(&[]), "0");
+        assert_eq!(Alphabet::Base62.decode("0").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_u128_matches_base62() {
+        let mut rng = rand::thread_rng();
+        let mut nums: Vec<u128> = (0..1_000)
+            .map(|_| rng.gen::<u128>() >> rng.gen_range(0..128))
+            .collect();
+        nums.extend([0, 1, 61, 62, u128::MAX]);
+        for num in nums {
+            let encoded = base62::encode(num);
+            let mut pushed = String::new();
+            push_u128(&mut pushed, num, 0);
+            assert_eq!(pushed, encoded);
+            assert_eq!(Alphabet::Base62.digit_count(num), encoded.len());
+            let mut out = [b'x'; U128_DIGITS + 2];
+            assert_eq!(write_u128(&mut out, num, U128_DIGITS + 1), U128_DIGITS + 1);
+            assert_eq!(&out, format!("{:0>23}x", encoded).as_bytes());
+            let (digits, start) = encode_u128_digits(num);
+            assert_eq!(&digits[start..], encoded.as_bytes());
+            assert_eq!(decode_u128(&encoded), Ok(num));
+            assert!(matches!(decode_u128_digits(encoded.as_bytes()), Ok(n) if n == num));
+        }
+
+        let overflow = format!("{}0", base62::encode(u128::MAX / 62 + 1));
+        assert!(matches!(
+            decode_u128_digits(overflow.as_bytes()),
+            Err(Overflow::Yes)
+        ));
+        assert_eq!(
+            decode_u128(&overflow),
+            Err(Error::PayloadTooLong {
+                max_chars: U128_DIGITS
+            })
+        );
+        for invalid in ["", "abc+", "ab c", "\u{e4}"] {
+            assert!(matches!(
+                decode_u128_digits(invalid.as_bytes()),
+                Err(Overflow::No)
+            ));
+            assert_eq!(decode_u128(invalid), Err(Error::DecodingFailed));
+        }
+
+        // Evaluated at compile time.
+        const DIGITS: ([u8; U128_DIGITS], usize) = encode_u128_digits(12345);
+        assert_eq!(&DIGITS.0[DIGITS.1..], b"3D7");
+    }
+
+    #[test]
+    fn test_long() {
+        let bytes: Vec<u8> = (1..=40).collect();
+        for alphabet in [Alphabet::Base62, Alphabet::Base32, Alphabet::Decimal] {
+            assert_eq!(alphabet.decode(&alphabet.encode(&bytes)).unwrap(), bytes);
+            assert_eq!(alphabet.decode("abc+"), Err(Error::DecodingFailed));
+            assert_eq!(alphabet.decode(""), Err(Error::DecodingFailed));
+        }
+        assert_eq!(Alphabet::Decimal.encode(&[57, 48]), "12345");
+    }
+
+    #[test]
+    fn test_alphabets() {
+        let mut rng = rand::thread_rng();
+        for alphabet in [Alphabet::Base62, Alphabet::Base32, Alphabet::Decimal] {
+            let max_digits = alphabet.digit_count(u128::MAX);
+            assert!(max_digits <= MAX_U128_DIGITS);
+            for num in [0, 1, 12345, u128::MAX]
+                .into_iter()
+                .chain((0..100).map(|_| rng.gen::<u128>() >> rng.gen_range(0..128)))
+            {
+                let mut encoded = String::new();
+                alphabet.push_u128(&mut encoded, num, 3);
+                assert_eq!(encoded.len(), alphabet.digit_count(num).max(3));
+                assert_eq!(alphabet.decode_u128(&encoded), Ok(num));
+                let mut out = [0; MAX_U128_DIGITS];
+                assert_eq!(alphabet.write_u128(&mut out, num, 3), encoded.len());
+                assert_eq!(&out[..encoded.len()], encoded.as_bytes());
+
+                let mut decoded = alphabet
+                    .decode(&alphabet.encode(&num.to_le_bytes()))
+                    .unwrap();
+                decoded.resize(16, 0);
+                assert_eq!(decoded, num.to_le_bytes());
+            }
+            let overflow = format!("1{}", "0".repeat(max_digits));
+            assert_eq!(
+                alphabet.decode_u128(&overflow),
+                Err(Error::PayloadTooLong {
+                    max_chars: max_digits
+                })
+            );
+            assert_eq!(alphabet.name().parse::<Alphabet>().unwrap(), alphabet);
+            assert_eq!(alphabet.digits().parse::<Alphabet>().unwrap(), alphabet);
+        }
+        assert_eq!(
+            Alphabet::Decimal.decode_u128("12a"),
+            Err(Error::DecodingFailed)
+        );
+        assert_eq!(
+            Alphabet::Base32.decode_u128("1A"),
+            Err(Error::DecodingFailed)
+        );
+        assert_eq!(Alphabet::Base32.decode_u128("10"), Ok(32));
+        assert!("base64".parse::<Alphabet>().is_err());
+    }
+
+    #[test]
+    fn test_custom() {
+        let custom = Alphabet::Custom("9876543210".into());
+        assert!(custom.is_valid());
+        assert_eq!(custom.encode(&[57, 48]), "87654");
+        assert_eq!(custom.decode("87654").unwrap(), [57, 48]);
+        let mut encoded = String::new();
+        custom.push_u128(&mut encoded, 12345, 7);
+        assert_eq!(encoded, "9987654");
+        assert_eq!(custom.decode_u128(&encoded), Ok(12345));
+        let mut out = [0; 7];
+        assert_eq!(custom.write_u128(&mut out, 12345, 7), 7);
+        assert_eq!(&out, b"9987654");
+        assert_eq!(custom.decode_u128("12a"), Err(Error::DecodingFailed));
+        assert!("9876543210".parse::<Alphabet>().is_err());
+        assert_eq!("custom:9876543210".parse::<Alphabet>().unwrap(), custom);
+        assert!("custom:0123".parse::<Alphabet>().is_err());
+
+        for valid in [Alphabet::Base62, Alphabet::Base32, Alphabet::Decimal] {
+            assert!(valid.is_valid());
+            assert!(Alphabet::Custom(valid.digits().into()).is_valid());
+        }
+        for digits in ["", "012345678", "0123456789+", "01234567899", "0123456789ä"] {
+            assert!(!Alphabet::Custom(digits.into()).is_valid(), "{}", digits);
+        }
+        let too_long = format!("{}_", Alphabet::Base62.digits());
+        assert!(!Alphabet::Custom(too_long.into()).is_valid());
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::field::{get_or_create_codec, try_get_or_create_codec, UNENCODED};
+use crate::{Codec, Error, Field, TypeMarker};
+
+/// A block of consecutive IDs encoded as one opaque token, for APIs which hand out ranges of
+/// IDs to clients, e.g. for allocating IDs offline.
+///
+/// With Serde, the range is encrypted with `Codec::encode_range` using the marker's codec, so
+/// neither the first ID nor the size of the block is revealed.  The IDs in the block are
+/// `Field<T>` values, which serialize as usual.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Config, Field, RangeToken, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct ExampleIdMarker;
+/// impl TypeMarker for ExampleIdMarker {
+///     fn name() -> &'static str { "example" }
+/// }
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let block = RangeToken::<ExampleIdMarker>::new(Field::from(1000), 50).unwrap();
+/// let json = serde_json::to_string(&block).unwrap();
+/// assert!(json.starts_with("\"example_"));
+///
+/// let decoded: RangeToken<ExampleIdMarker> = serde_json::from_str(&json).unwrap();
+/// assert_eq!(decoded, block);
+/// assert!(decoded.contains(&Field::from(1049)));
+/// assert_eq!(decoded.fields().last(), Some(Field::from(1049)));
+/// ```
+#[derive(Debug)]
+pub struct RangeToken<T: TypeMarker> {
+    start: Field<T>,
+    count: u64,
+}
+
+impl<T: TypeMarker> RangeToken<T> {
+    /// Creates a range of `count` IDs from `start`, returning `Error::IdOutOfRange` if the
+    /// range doesn't fit in `u64`.
+    pub fn new(start: Field<T>, count: u64) -> Result<Self, Error> {
+        start.id.checked_add(count).ok_or(Error::IdOutOfRange)?;
+        Ok(RangeToken { start, count })
+    }
+
+    /// Returns the first ID of the range.
+    pub fn start(&self) -> Field<T> {
+        self.start
+    }
+
+    /// Returns the number of IDs in the range.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns `true` if `field` is in the range.
+    pub fn contains(&self, field: &Field<T>) -> bool {
+        field.id >= self.start.id && field.id - self.start.id < self.count
+    }
+
+    /// Returns the IDs of the range in order.
+    pub fn fields(&self) -> impl Iterator<Item = Field<T>> {
+        (self.start.id..self.start.id + self.count).map(Field::from)
+    }
+
+    // Encodes the range with `codec`, failing if the IDs are outside its allowed range.
+    fn encode(&self, codec: &Codec) -> Result<String, Error> {
+        codec.encode_range(self.start.id, self.count)
+    }
+}
+
+impl<T: TypeMarker> Clone for RangeToken<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: TypeMarker> Copy for RangeToken<T> {}
+
+impl<T: TypeMarker> PartialEq for RangeToken<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.start == other.start && self.count == other.count
+    }
+}
+
+impl<T: TypeMarker> Eq for RangeToken<T> {}
+
+impl<T: TypeMarker> fmt::Display for RangeToken<T> {
+    /// Formats the range as it is serialized, so that the raw IDs aren't revealed.
+    ///
+    /// Writes `<unencoded id>` if the global config has not been set or the IDs are outside
+    /// the allowed range.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match try_get_or_create_codec::<T>().map(|codec| self.encode(&codec)) {
+            Some(Ok(encoded)) => f.write_str(&encoded),
+            _ => f.write_str(UNENCODED),
+        }
+    }
+}
+
+impl<T: TypeMarker> Serialize for RangeToken<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.encode(&get_or_create_codec::<T>())
+            .map_err(serde::ser::Error::custom)
+            .and_then(|encoded| serializer.serialize_str(&encoded))
+    }
+}
+
+impl<'de, T: TypeMarker> Deserialize<'de> for RangeToken<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        let codec = get_or_create_codec::<T>();
+        let range = codec
+            .decode_range(&encoded)
+            .map_err(serde::de::Error::custom)?;
+        Ok(RangeToken {
+            start: Field::from(range.start),
+            count: range.end - range.start,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+    }
+
+    type ExampleRange = RangeToken<ExampleIdMarker>;
+
+    #[derive(Debug)]
+    struct RangedMarker;
+    impl TypeMarker for RangedMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+        fn allowed_range() -> Option<std::ops::Range<u64>> {
+            Some(1..100)
+        }
+    }
+
+    #[test]
+    fn test_range_token() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let block = ExampleRange::new(Field::from(10), 3).unwrap();
+        assert_eq!(
+            block.fields().collect::<Vec<_>>(),
+            [10, 11, 12].map(Field::from)
+        );
+        assert!(!block.contains(&Field::from(9)));
+        assert!(!block.contains(&Field::from(13)));
+
+        let json = serde_json::to_value(block).unwrap();
+        assert_eq!(json, block.to_string());
+        assert_eq!(serde_json::from_value::<ExampleRange>(json).unwrap(), block);
+
+        let empty = ExampleRange::new(Field::from(u64::MAX), 0).unwrap();
+        assert_eq!(empty.fields().count(), 0);
+        assert!(!empty.contains(&Field::from(u64::MAX)));
+        assert_eq!(
+            ExampleRange::new(Field::from(u64::MAX), 1),
+            Err(Error::IdOutOfRange)
+        );
+
+        // Ranges reaching outside the allowed range are not encoded.
+        let block = RangeToken::<RangedMarker>::new(Field::from(90), 20).unwrap();
+        assert!(serde_json::to_value(block).is_err());
+        assert_eq!(block.to_string(), UNENCODED);
+
+        // The token of a single ID is not a range.
+        let json = serde_json::to_value(Field::<ExampleIdMarker>::from(10)).unwrap();
+        assert!(serde_json::from_value::<ExampleRange>(json).is_err());
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::codec::split_prefix;
+use crate::{Codec, Config, Envelope, Error};
+
+/// A set of codecs for different object types, looked up by name.
+///
+/// The registry can decode tokens of any registered type by dispatching on the token prefix,
+/// which is useful when the type of an incoming token is not known in advance.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{CodecRegistry, Config};
+///
+/// let config = Config::new(b"your-secure-key");
+/// let mut registry = CodecRegistry::new();
+/// registry.register("example", &config);
+/// registry.register("user", &config);
+///
+/// assert_eq!(registry.decode("example_VgwPy6rwatl").unwrap(), ("example", 12345));
+/// ```
+#[derive(Default)]
+pub struct CodecRegistry {
+    codecs: HashMap<String, Codec>,
+    trace: bool,
+    // Names of the codecs with a type ID.
+    type_ids: HashMap<u16, String>,
+}
+
+/// Details of a failed `CodecRegistry::decode_traced` call, for diagnosing misrouted tokens.
+#[derive(Debug, PartialEq)]
+pub struct DecodeTrace {
+    /// The error from decoding with the codec selected by the prefix, or `InvalidPrefix` if
+    /// there was no such codec.
+    pub error: Error,
+    /// The codecs tried, sorted by name.
+    pub attempts: Vec<DecodeAttempt>,
+}
+
+/// A codec tried by `CodecRegistry::decode_traced`.
+#[derive(Debug, PartialEq)]
+pub struct DecodeAttempt {
+    /// The name of the codec.
+    pub codec: String,
+    /// Why the payload of the token failed to decode with the codec, or `None` if it decoded,
+    /// meaning that the token has the wrong prefix.
+    pub error: Option<Error>,
+}
+
+impl fmt::Display for DecodeTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        for attempt in &self.attempts {
+            match &attempt.error {
+                Some(error) => write!(f, "; {}: {}", attempt.codec, error)?,
+                None => write!(f, "; {}: decodes", attempt.codec)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DecodeTrace {}
+
+impl CodecRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a codec for `name` with `config` and adds it to the registry, replacing any
+    /// previously registered codec with the same name.
+    pub fn register(&mut self, name: &str, config: &Config) -> &Codec {
+        self.insert(Codec::new(name, config))
+    }
+
+    /// Adds an existing codec to the registry, replacing any previously registered codec
+    /// with the same name.
+    pub fn insert(&mut self, codec: Codec) -> &Codec {
+        let name = codec.name().to_string();
+        let type_id = codec.type_id();
+        if let Some(old_type_id) = self
+            .codecs
+            .insert(name.clone(), codec)
+            .and_then(|old| old.type_id())
+        {
+            if self.type_ids.get(&old_type_id) == Some(&name) {
+                self.type_ids.remove(&old_type_id);
+            }
+        }
+        if let Some(type_id) = type_id {
+            self.type_ids.insert(type_id, name.clone());
+        }
+        &self.codecs[&name]
+    }
+
+    /// Returns the codec registered for `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&Codec> {
+        self.codecs.get(name)
+    }
+
+    /// Returns an iterator over the registered codecs in arbitrary order.
+    pub fn codecs(&self) -> impl Iterator<Item = &Codec> {
+        self.codecs.values()
+    }
+
+    /// Decodes a token of any registered type, returning the type name and the decoded
+    /// number.
+    ///
+    /// The codec is selected by the token prefix.  If no codec is registered for the prefix,
+    /// `Error::InvalidPrefix` is returned with an empty `expected` value.  See `decode_traced`
+    /// for diagnosing failures.
+    pub fn decode<'a>(&'a self, encoded: &str) -> Result<(&'a str, u64), Error> {
+        if encoded.is_empty() {
+            return Err(Error::EmptyToken);
+        }
+        let received = split_prefix(encoded).map_or("", |(prefix, _)| prefix);
+        let name = received.strip_suffix('_').unwrap_or(received);
+        match self.codecs.get(name) {
+            Some(codec) => Ok((codec.name(), codec.decode(encoded)?)),
+            None => Err(Error::InvalidPrefix {
+                received: received.to_string(),
+                expected: "".into(),
+            }),
+        }
+    }
+
+    /// Returns the codec with the type ID `type_id`, if any.  See `Codec::with_type_id`.
+    pub fn get_by_type_id(&self, type_id: u16) -> Option<&Codec> {
+        self.codecs.get(self.type_ids.get(&type_id)?)
+    }
+
+    /// Decodes an envelope of any registered type, returning the type name and the decoded
+    /// number.  See `Envelope`.
+    pub fn decode_envelope<'a>(&'a self, envelope: &Envelope) -> Result<(&'a str, u64), Error> {
+        let codec = self.envelope_codec(envelope)?;
+        Ok((codec.name(), codec.decode_envelope(envelope)?))
+    }
+
+    /// Renders an envelope of any registered type into its token.  See
+    /// `Codec::render_envelope`.
+    pub fn render_envelope(&self, envelope: &Envelope) -> Result<String, Error> {
+        self.envelope_codec(envelope)?.render_envelope(envelope)
+    }
+
+    // Returns the codec of `envelope`, or `InvalidPrefix` with the type ID if there is none.
+    fn envelope_codec(&self, envelope: &Envelope) -> Result<&Codec, Error> {
+        self.get_by_type_id(envelope.type_id())
+            .ok_or_else(|| Error::InvalidPrefix {
+                received: envelope.type_id().to_string(),
+                expected: "".into(),
+            })
+    }
+
+    /// Enables or disables trying all codecs in `decode_traced` (disabled by default).
+    ///
+    /// This is a debugging aid.  Each failed decode then costs a decode attempt per codec, so
+    /// it should not be left enabled for untrusted input.
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+
+    /// Like `decode`, but on failure returns a `DecodeTrace` listing the codecs tried and
+    /// why each failed.
+    ///
+    /// Without `set_trace`, only the codec selected by the prefix is tried.  With it, the
+    /// payload of the token is also tried with every other codec, which reveals tokens with
+    /// the wrong prefix.  No raw IDs are included in the trace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cryptid_rs::{CodecRegistry, Config, Error};
+    ///
+    /// let config = Config::new(b"your-secure-key");
+    /// let mut registry = CodecRegistry::new();
+    /// registry.register("example", &config);
+    /// registry.register("user", &config);
+    /// registry.set_trace(true);
+    ///
+    /// // An example token with the prefix of a user.
+    /// let trace = registry.decode_traced("user_VgwPy6rwatl").unwrap_err();
+    /// assert_eq!(trace.error, Error::IncorrectMAC);
+    /// assert_eq!(trace.attempts[0].codec, "example");
+    /// assert_eq!(trace.attempts[0].error, None);
+    /// ```
+    pub fn decode_traced<'a>(&'a self, encoded: &str) -> Result<(&'a str, u64), DecodeTrace> {
+        let error = match self.decode(encoded) {
+            Ok(decoded) => return Ok(decoded),
+            Err(error) => error,
+        };
+        let (name, tail) = encoded.rsplit_once('_').unwrap_or(("", encoded));
+        let mut codecs: Vec<&Codec> = self
+            .codecs()
+            .filter(|codec| self.trace || codec.name() == name)
+            .collect();
+        codecs.sort_by_key(|codec| codec.name());
+        let attempts = codecs
+            .into_iter()
+            .map(|codec| {
+                let retagged = format!("{}_{}", codec.name(), tail);
+                DecodeAttempt {
+                    codec: codec.name().to_string(),
+                    error: codec.decode_token(&retagged).err(),
+                }
+            })
+            .collect();
+        Err(DecodeTrace { error, attempts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_dispatch() {
+        let config = Config::new(b"Test key here");
+        let mut registry = CodecRegistry::new();
+        registry.register("test", &config);
+        registry.register("other", &config);
+
+        let test = registry.get("test").unwrap().encode(123);
+        let other = registry.get("other").unwrap().encode(456);
+        assert_eq!(test, "test_hHLBCl4rZ3u");
+        assert_eq!(registry.decode(&test), Ok(("test", 123)));
+        assert_eq!(registry.decode(&other), Ok(("other", 456)));
+        assert_eq!(registry.codecs().count(), 2);
+
+        assert_eq!(
+            registry.decode("missing_hHLBCl4rZ3u"),
+            Err(Error::InvalidPrefix {
+                received: "missing_".to_string(),
+                expected: "".into()
+            })
+        );
+        assert_eq!(
+            registry.decode("other_hHLBCl4rZ3u"),
+            Err(Error::IncorrectMAC)
+        );
+        assert_eq!(
+            registry.decode("tést_😀_é"),
+            Err(Error::InvalidPrefix {
+                received: "tést_😀_".to_string(),
+                expected: "".into()
+            })
+        );
+        assert!(registry.decode_traced("😀_é").is_err());
+    }
+
+    #[test]
+    fn test_overlapping_prefixes() {
+        let config = Config::new(b"Test key here");
+        let mut registry = CodecRegistry::new();
+        for name in ["user", "poweruser", "power_user"] {
+            registry.register(name, &config);
+        }
+        for (name, id) in [("user", 1), ("poweruser", 2), ("power_user", 3)] {
+            let token = registry.get(name).unwrap().encode(id);
+            assert_eq!(registry.decode(&token), Ok((name, id)));
+        }
+
+        // A suffix of a registered prefix is not the prefix.
+        let token = registry.get("user").unwrap().encode(1);
+        let other = format!("super{}", token);
+        assert_eq!(
+            registry.decode(&other),
+            Err(Error::InvalidPrefix {
+                received: "superuser_".to_string(),
+                expected: "".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_envelopes() {
+        let config = Config::new(b"Test key here");
+        let mut registry = CodecRegistry::new();
+        registry.insert(Codec::new("test", &config).with_type_id(1));
+        registry
+            .insert(Codec::new("other", &config.clone().order_preserving(true)).with_type_id(2));
+        registry.register("plain", &config);
+
+        for name in ["test", "other"] {
+            let codec = registry.get(name).unwrap();
+            for num in [0, 123, u64::MAX] {
+                let envelope = codec.encode_envelope(num);
+                assert_eq!(envelope.payload(), codec.encode_bytes16(num));
+                assert_eq!(registry.render_envelope(&envelope), Ok(codec.encode(num)));
+                assert_eq!(registry.decode_envelope(&envelope), Ok((name, num)));
+            }
+        }
+        let envelope = registry.get("test").unwrap().encode_envelope(123);
+        assert_eq!(
+            registry.render_envelope(&envelope).unwrap(),
+            "test_hHLBCl4rZ3u"
+        );
+        assert!(matches!(
+            registry.get("other").unwrap().decode_envelope(&envelope),
+            Err(Error::InvalidPrefix { .. })
+        ));
+        let forged = Envelope {
+            payload: envelope.payload ^ 1,
+            ..envelope
+        };
+        assert_eq!(registry.decode_envelope(&forged), Err(Error::IncorrectMAC));
+
+        // Replacing a codec drops its old type ID.
+        registry.insert(Codec::new("test", &config).with_type_id(3));
+        assert_eq!(
+            registry.decode_envelope(&envelope),
+            Err(Error::InvalidPrefix {
+                received: "1".to_string(),
+                expected: "".into()
+            })
+        );
+        assert_eq!(registry.get_by_type_id(3).unwrap().name(), "test");
+    }
+
+    #[test]
+    fn test_decode_traced() {
+        let config = Config::new(b"Test key here");
+        let mut registry = CodecRegistry::new();
+        registry.register("test", &config);
+        registry.register("other", &config);
+        let attempt = |codec: &str, error| DecodeAttempt {
+            codec: codec.to_string(),
+            error,
+        };
+
+        assert_eq!(
+            registry.decode_traced("test_hHLBCl4rZ3u"),
+            Ok(("test", 123))
+        );
+        let trace = registry.decode_traced("other_hHLBCl4rZ3u").unwrap_err();
+        assert_eq!(trace.error, Error::IncorrectMAC);
+        assert_eq!(
+            trace.attempts,
+            [attempt("other", Some(Error::IncorrectMAC))]
+        );
+
+        registry.set_trace(true);
+        let trace = registry.decode_traced("other_hHLBCl4rZ3u").unwrap_err();
+        assert_eq!(
+            trace.attempts,
+            [
+                attempt("other", Some(Error::IncorrectMAC)),
+                attempt("test", None)
+            ]
+        );
+        assert_eq!(
+            trace.to_string(),
+            "Incorrect MAC; other: Incorrect MAC; test: decodes"
+        );
+
+        let trace = registry.decode_traced("missing_hHLBCl4rZ3u").unwrap_err();
+        assert!(matches!(trace.error, Error::InvalidPrefix { .. }));
+        assert_eq!(trace.attempts.len(), 2);
+    }
+}
@@ -0,0 +1,116 @@
+//! Mapping tables for key rotation.
+//!
+//! Tokens stored outside the database, such as in search indexes and analytics events, can't
+//! be re-encoded by decoding them when the old key is retired.  `mapping` streams the pairs of
+//! old and new tokens for a range of raw IDs, so those stores can be updated with a lookup
+//! table before the rotation.  The raw IDs are not part of the output, so the table can be
+//! handed to the jobs updating the stores without exposing them.
+
+use std::ops::{Bound, RangeBounds, RangeInclusive};
+
+use crate::{Codec, Error};
+
+/// Returns the pairs of the old and the new token of each raw ID in `range`, in order.
+///
+/// The pairs are produced lazily, so ranges of any size can be streamed into a file or a
+/// table.  Old tokens are the tokens without a nonce: if `old` has nonces, stored tokens have
+/// random nonce digits and won't match, and they must be decoded with `old` instead.  New
+/// tokens are encoded with `encode`, including nonces and hooks.  IDs outside the
+/// `Config::allowed_range` of either codec yield `Error::IdOutOfRange`.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::rekey::mapping;
+/// use cryptid_rs::{Codec, Config};
+///
+/// let old = Codec::new("example", &Config::new(b"your-secure-key"));
+/// let new = Codec::new("example", &Config::new(b"your-new-secure-key").key_version(1).unwrap());
+///
+/// let pairs: Vec<(String, String)> = mapping(&old, &new, 12345..=12346)
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(pairs.len(), 2);
+/// assert_eq!(pairs[0].0, "example_VgwPy6rwatl");
+/// assert_eq!(new.decode(&pairs[0].1), Ok(12345));
+/// ```
+pub fn mapping<'a>(
+    old: &'a Codec,
+    new: &'a Codec,
+    range: impl RangeBounds<u64>,
+) -> impl Iterator<Item = Result<(String, String), Error>> + 'a {
+    inclusive(range).map(|num| {
+        old.check_allowed(num)?;
+        Ok((old.encode_canonical(num), new.try_encode(num)?))
+    })
+}
+
+// Converts `range` into an inclusive range, which is empty if `range` is.
+fn inclusive(range: impl RangeBounds<u64>) -> RangeInclusive<u64> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => Some(start),
+        Bound::Excluded(&start) => start.checked_add(1),
+        Bound::Unbounded => Some(0),
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => Some(end),
+        Bound::Excluded(&end) => end.checked_sub(1),
+        Bound::Unbounded => Some(u64::MAX),
+    };
+    match (start, end) {
+        (Some(start), Some(end)) => start..=end,
+        #[allow(clippy::reversed_empty_ranges)]
+        _ => 1..=0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn test_mapping() {
+        let old_config = Config::new(b"Test key here");
+        let new_config = Config::new(b"New key").key_version(1).unwrap();
+        let old = Codec::new("test", &old_config);
+        let new = Codec::new("test", &new_config);
+
+        let pairs: Vec<_> = mapping(&old, &new, 100..200).map(Result::unwrap).collect();
+        assert_eq!(pairs.len(), 100);
+        for (num, (old_token, new_token)) in (100..200).zip(&pairs) {
+            assert_eq!(old_token, &old.encode(num));
+            assert_eq!(new.decode(new_token), Ok(num));
+        }
+        assert_eq!(
+            mapping(&old, &new, 123..=123).next().unwrap().unwrap().0,
+            "test_hHLBCl4rZ3u"
+        );
+
+        // Old tokens are canonical, and new tokens get nonces.
+        let nonced = Codec::new("test", &old_config.nonce_length(2).unwrap());
+        let new_nonced = Codec::new("test", &new_config.nonce_length(2).unwrap());
+        let (old_token, new_token) = mapping(&nonced, &new_nonced, 123..)
+            .next()
+            .unwrap()
+            .unwrap();
+        let first = mapping(&nonced, &new, 123..).next().unwrap();
+        assert_eq!(first.unwrap().0, old_token);
+        assert_eq!(nonced.decode(&old_token), Ok(123));
+        assert_eq!(new_nonced.decode(&new_token), Ok(123));
+
+        // Ranges at the ends of u64.
+        assert_eq!(mapping(&old, &new, u64::MAX - 1..).count(), 2);
+        assert_eq!(mapping(&old, &new, ..1).count(), 1);
+        assert_eq!(mapping(&old, &new, 5..5).count(), 0);
+        let excluded = (Bound::Excluded(u64::MAX), Bound::Unbounded);
+        assert_eq!(mapping(&old, &new, excluded).count(), 0);
+
+        // IDs outside the allowed range of either codec are reported.
+        let ranged = Codec::new("test", &Config::new(b"New key").allowed_range(1..3));
+        let results: Vec<_> = mapping(&old, &ranged, 0..4).map(|r| r.is_ok()).collect();
+        assert_eq!(results, [false, true, true, false]);
+        let results: Vec<_> = mapping(&ranged, &new, 0..4).map(|r| r.err()).collect();
+        assert_eq!(results[3], Some(Error::IdOutOfRange));
+    }
+}
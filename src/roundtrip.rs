@@ -0,0 +1,187 @@
+use std::fmt;
+
+use crate::{Codec, Config, Error};
+
+/// The codec name of `Config::verify_roundtrip_sample`.
+const CODEC_NAME: &str = "roundtrip";
+
+/// The result of `Config::verify_roundtrip_sample` when some IDs didn't round trip.
+#[derive(Debug, Default, PartialEq)]
+pub struct RoundtripReport {
+    /// Number of IDs checked, each in every form.
+    pub checked: usize,
+    /// The IDs which didn't decode back to themselves, in the order they were checked.
+    pub failures: Vec<RoundtripFailure>,
+}
+
+/// An ID which didn't decode back to itself in one form.
+#[derive(Debug, PartialEq)]
+pub struct RoundtripFailure {
+    /// The raw ID.
+    pub id: u64,
+    /// The form which failed: `"token"`, `"uuid"` or `"bytes16"`.
+    pub form: &'static str,
+    /// The encoded ID, with bytes in hexadecimal.
+    pub encoded: String,
+    /// What decoding returned instead of the ID.
+    pub decoded: Result<u64, Error>,
+}
+
+impl RoundtripReport {
+    /// Returns `true` if every ID round tripped.
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for RoundtripReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} round trips of {} IDs failed",
+            self.failures.len(),
+            self.checked
+        )?;
+        for failure in &self.failures {
+            writeln!(
+                f,
+                "{} {} of {} decodes to {:?}",
+                failure.form, failure.encoded, failure.id, failure.decoded
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RoundtripReport {}
+
+/// Encodes and decodes the sample of `n` pseudorandom IDs in each form with a codec of
+/// `config`.
+pub(crate) fn verify(config: &Config, n: usize) -> RoundtripReport {
+    // The target only limits the names and lengths of codecs, and its alphabet is already in
+    // the config, so it is left out for the codec name not to matter.
+    let mut config = config.clone();
+    config.target = None;
+    let codec = Codec::new(CODEC_NAME, &config);
+    let mut report = RoundtripReport::default();
+    for id in sample(n) {
+        report.checked += 1;
+        let token = codec.encode(id);
+        let decoded = codec.decode(&token);
+        check(&mut report, id, "token", token, decoded);
+        let uuid = codec.encode_uuid(id);
+        let decoded = codec.decode_uuid(uuid);
+        check(&mut report, id, "uuid", uuid.to_string(), decoded);
+        let bytes = codec.encode_bytes16(id);
+        let decoded = codec.decode_bytes16(bytes);
+        check(&mut report, id, "bytes16", hex(&bytes), decoded);
+    }
+    report
+}
+
+fn check(
+    report: &mut RoundtripReport,
+    id: u64,
+    form: &'static str,
+    encoded: String,
+    decoded: Result<u64, Error>,
+) {
+    if decoded != Ok(id) {
+        report.failures.push(RoundtripFailure {
+            id,
+            form,
+            encoded,
+            decoded,
+        });
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Returns the IDs at the boundaries of each bit width, which cover the widths of the
+/// integer types and the sign of `i64`, followed by `n` pseudorandom IDs from a fixed seed.
+fn sample(n: usize) -> impl Iterator<Item = u64> {
+    let boundaries = [0, u64::MAX]
+        .into_iter()
+        .chain((1..64).flat_map(|bits| [(1 << bits) - 1, 1 << bits]));
+    // SplitMix64, so that every run checks the same IDs.
+    let mut state = 0u64;
+    let random = std::iter::repeat_with(move || {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    });
+    boundaries.chain(random.take(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Alphabet, ContextTarget};
+
+    #[test]
+    fn test_sample() {
+        let ids: Vec<u64> = sample(10).collect();
+        assert_eq!(ids.len(), 128 + 10);
+        assert_eq!(ids[..4], [0, u64::MAX, 1, 2]);
+        assert!(ids.contains(&(i64::MAX as u64)) && ids.contains(&(1 << 63)));
+        assert!(ids.contains(&(u32::MAX as u64)) && ids.contains(&(1 << 32)));
+        assert_eq!(ids, sample(10).collect::<Vec<_>>());
+        let mut unique = ids.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), ids.len());
+    }
+
+    #[test]
+    fn test_verify() {
+        let base = Config::new(b"Test key here");
+        let configs = [
+            base.clone(),
+            base.clone()
+                .hmac_length(0)
+                .unwrap()
+                .zero_pad_length(0)
+                .unwrap(),
+            base.clone()
+                .hmac_length(8)
+                .unwrap()
+                .zero_pad_length(8)
+                .unwrap(),
+            base.clone()
+                .nonce_length(2)
+                .unwrap()
+                .key_version(3)
+                .unwrap(),
+            base.clone().order_preserving(true),
+            base.clone().bind_parameters(true),
+            base.clone().alphabet(Alphabet::Decimal).unwrap(),
+            base.clone().target(ContextTarget::DnsLabel).unwrap(),
+        ];
+        for config in configs {
+            assert_eq!(config.verify_roundtrip_sample(100), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let report = RoundtripReport {
+            checked: 2,
+            failures: vec![RoundtripFailure {
+                id: 5,
+                form: "token",
+                encoded: "roundtrip_x".to_string(),
+                decoded: Err(Error::IncorrectMAC),
+            }],
+        };
+        assert!(!report.is_clean());
+        assert_eq!(
+            report.to_string(),
+            "1 round trips of 2 IDs failed\ntoken roundtrip_x of 5 decodes to Err(IncorrectMAC)\n"
+        );
+    }
+}
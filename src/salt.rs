@@ -0,0 +1,102 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::ConfigError;
+
+/// A per-deployment salt mixed into all key derivations (see `Config::salt`).
+///
+/// Installations sharing the same master key, e.g. created from the same template, produce
+/// disjoint tokens when they use different salts.  The salt is not secret.  It is generated
+/// once when a deployment is created and stored e.g. in the database, and it must never
+/// change afterwards, because changing it changes all tokens.
+///
+/// The text form is 32 hexadecimal digits.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Codec, Config, Salt};
+///
+/// let salt = Salt::generate();
+/// let stored = salt.to_string();
+///
+/// let loaded: Salt = stored.parse().unwrap();
+/// let config = Config::new(b"your-secure-key").salt(loaded);
+/// let codec = Codec::new("example", &config);
+/// assert_ne!(codec.encode(12345), "example_VgwPy6rwatl");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Salt([u8; 16]);
+
+impl Salt {
+    /// Generates a new random salt.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system random number generator fails.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 16];
+        getrandom::getrandom(&mut bytes).expect("System random number generator should work");
+        Salt(bytes)
+    }
+
+    /// Creates a salt from its raw bytes.
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Salt(bytes)
+    }
+
+    /// Returns the raw bytes of the salt.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Salt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:032x}", u128::from_be_bytes(self.0))
+    }
+}
+
+impl FromStr for Salt {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 32 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ConfigError::InvalidSalt);
+        }
+        let num = u128::from_str_radix(s, 16).map_err(|_| ConfigError::InvalidSalt)?;
+        Ok(Salt(num.to_be_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Codec, Config};
+
+    #[test]
+    fn test_salt() {
+        let salt = Salt::from_bytes(*b"0123456789abcdef");
+        let text = salt.to_string();
+        assert_eq!(text, "30313233343536373839616263646566");
+        assert_eq!(text.parse::<Salt>().unwrap(), salt);
+        assert_eq!(text.to_uppercase().parse::<Salt>().unwrap(), salt);
+        assert!(matches!(
+            "+0313233343536373839616263646566".parse::<Salt>(),
+            Err(ConfigError::InvalidSalt)
+        ));
+        assert!(matches!(
+            "3031".parse::<Salt>(),
+            Err(ConfigError::InvalidSalt)
+        ));
+        assert_ne!(Salt::generate(), Salt::generate());
+
+        let config = Config::new(b"Test key here");
+        let salted = Codec::new("test", &config.clone().salt(salt));
+        let other = Codec::new("test", &config.clone().salt(Salt::from_bytes([0; 16])));
+        let token = salted.encode(123);
+        assert_ne!(token, Codec::new("test", &config).encode(123));
+        assert_eq!(salted.decode(&token), Ok(123));
+        assert!(other.decode(&token).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! SeaORM support for `Field<T>` (`sea-orm` feature).
+//!
+//! Fields map to `BIGINT` columns like with Diesel, converting with `Field::try_from_i64` and
+//! `Field::try_to_i64`, so `T::negative_ids()` applies.  They can be used as the types of
+//! entity model attributes, including primary keys, and in query filters.
+
+use ::sea_orm::sea_query::{ArrayType, ColumnType, Nullable, ValueType, ValueTypeErr};
+use ::sea_orm::{ColIdx, DbErr, QueryResult, TryFromU64, TryGetError, TryGetable, Value};
+
+use crate::{Field, TypeMarker};
+
+impl<T: TypeMarker> From<Field<T>> for Value {
+    /// Converts the field into a `BIGINT` value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ID doesn't fit in an `i64` under `T::negative_ids()`, which can only
+    /// happen with IDs not read from a `BIGINT` column.
+    fn from(field: Field<T>) -> Self {
+        match field.try_to_i64() {
+            Ok(id) => Value::BigInt(Some(id)),
+            Err(_) => panic!("ID of {} doesn't fit in a BIGINT column", T::name()),
+        }
+    }
+}
+
+impl<T: TypeMarker> Nullable for Field<T> {
+    fn null() -> Value {
+        Value::BigInt(None)
+    }
+}
+
+impl<T: TypeMarker> ValueType for Field<T> {
+    fn try_from(value: Value) -> Result<Self, ValueTypeErr> {
+        match value {
+            Value::BigInt(Some(id)) => Field::try_from_i64(id).map_err(|_| ValueTypeErr),
+            _ => Err(ValueTypeErr),
+        }
+    }
+
+    fn type_name() -> String {
+        format!("Field<{}>", T::name())
+    }
+
+    fn array_type() -> ArrayType {
+        ArrayType::BigInt
+    }
+
+    fn column_type() -> ColumnType {
+        ColumnType::BigInteger
+    }
+}
+
+impl<T: TypeMarker> TryGetable for Field<T> {
+    fn try_get_by<I: ColIdx>(res: &QueryResult, index: I) -> Result<Self, TryGetError> {
+        let id = i64::try_get_by(res, index)?;
+        Field::try_from_i64(id).map_err(|error| TryGetError::DbErr(DbErr::Type(error.to_string())))
+    }
+}
+
+impl<T: TypeMarker> TryFromU64 for Field<T> {
+    fn try_from_u64(id: u64) -> Result<Self, DbErr> {
+        Ok(Field::from(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NegativeIds;
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+    }
+
+    #[derive(Debug)]
+    struct SignedIdMarker;
+    impl TypeMarker for SignedIdMarker {
+        fn name() -> &'static str {
+            "signed"
+        }
+        fn negative_ids() -> NegativeIds {
+            NegativeIds::Reinterpret
+        }
+    }
+
+    #[test]
+    fn test_values() {
+        let field = Field::<ExampleIdMarker>::from(12345);
+        let value = Value::from(field);
+        assert_eq!(value, Value::BigInt(Some(12345)));
+        assert_eq!(
+            <Field<ExampleIdMarker> as ValueType>::try_from(value).unwrap(),
+            field
+        );
+        assert_eq!(
+            Value::from(None::<Field<ExampleIdMarker>>),
+            Value::BigInt(None)
+        );
+
+        // Negative IDs follow the policy of the marker.
+        let negative = Value::BigInt(Some(-1));
+        assert!(<Field<ExampleIdMarker> as ValueType>::try_from(negative.clone()).is_err());
+        let signed = <Field<SignedIdMarker> as ValueType>::try_from(negative.clone());
+        assert_eq!(u64::from(signed.unwrap()), u64::MAX);
+        assert_eq!(
+            Value::from(Field::<SignedIdMarker>::from(u64::MAX)),
+            negative
+        );
+        assert!(<Field<ExampleIdMarker> as ValueType>::try_from(Value::Int(Some(1))).is_err());
+
+        assert_eq!(
+            Field::<ExampleIdMarker>::try_from_u64(5),
+            Ok(Field::from(5))
+        );
+        assert_eq!(
+            Field::<ExampleIdMarker>::column_type(),
+            ColumnType::BigInteger
+        );
+    }
+}
@@ -0,0 +1,430 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr::NonNull;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+use zeroize::Zeroize;
+
+use crate::hkdf::Hkdf;
+
+// Identifies a mapping as a key cache of this layout.
+const MAGIC: u64 = u64::from_le_bytes(*b"cryptid1");
+
+// A slot is a sequence number, the ID of the codec and its keys, in 64-bit words.  The
+// sequence number is odd while the slot is being written, and grows with every write, so
+// that readers can detect a concurrent write.  A slot with a zero ID is empty.
+const ID_WORDS: usize = 4;
+const KEY_WORDS: usize = 8;
+const SLOT_WORDS: usize = 1 + ID_WORDS + KEY_WORDS;
+
+/// A cache of derived codec keys in shared memory, so that the keys are derived once per
+/// machine instead of once per process, e.g. by the workers of a pre-fork server or by many
+/// small processes serving the same tenants.
+///
+/// Codecs created from a config with `Config::key_cache` read their FF1 and HMAC keys from
+/// the cache, and store the keys there when they are first derived.  The AES key schedules
+/// are still expanded from the keys in each process.  Entries are identified by a value
+/// derived from the master key, the salt and the codec name, which doesn't reveal the keys.
+/// When the cache is full, keys are derived as usual without caching them.
+///
+/// **Security note:** The cache holds derived keys, and anyone who can read it can encode
+/// and decode tokens of the cached codecs, although not recover the master key.  Files are
+/// created readable by the owner only and should be on a memory file system such as
+/// `/dev/shm`, so that the keys are never written to disk.  As other users can create files
+/// there too, `open` refuses symbolic links and files which are not owned by the effective
+/// user or which other users can access.  On Linux, the mapping is left
+/// out of core dumps.  The copies of the keys in each process are zeroized once the key
+/// schedules have been computed, and the key schedules when the codec is dropped.  Call
+/// `clear` to zeroize the cache after rotating keys or before shutting down the last process.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use cryptid_rs::{Codec, Config, SharedKeyCache};
+///
+/// let path = std::env::temp_dir().join(format!("cryptid-doctest-{}", std::process::id()));
+/// let cache = Arc::new(SharedKeyCache::open(&path, 64).unwrap());
+/// let config = Config::new(b"your-secure-key").key_cache(cache.clone());
+///
+/// assert_eq!(Codec::new("example", &config).encode(12345), "example_VgwPy6rwatl");
+/// assert_eq!(cache.len(), 1);
+///
+/// // Another process opening the same file finds the keys.
+/// let other = SharedKeyCache::open(&path, 64).unwrap();
+/// assert_eq!(other.len(), 1);
+///
+/// cache.clear();
+/// assert!(other.is_empty());
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct SharedKeyCache {
+    words: NonNull<AtomicU64>,
+    slots: usize,
+}
+
+// The mapping is only accessed through atomics.
+unsafe impl Send for SharedKeyCache {}
+unsafe impl Sync for SharedKeyCache {}
+
+impl SharedKeyCache {
+    /// Opens the cache in the file at `path`, creating it with room for `slots` codecs if it
+    /// doesn't exist.  An existing cache keeps its size.
+    ///
+    /// Fails if the file exists but is not a key cache, if `path` is a symbolic link, or with
+    /// `io::ErrorKind::PermissionDenied` if the file is not owned by the effective user or
+    /// its mode gives access to other users.
+    pub fn open(path: impl AsRef<Path>, slots: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(path)?;
+        // The mode only applies to a new file, and another user may have created it first.
+        let metadata = file.metadata()?;
+        if metadata.uid() != unsafe { libc::geteuid() } || metadata.mode() & 0o077 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Key cache is accessible to other users",
+            ));
+        }
+        // The lock keeps concurrent openers from sizing the file differently.
+        file.lock()?;
+        if file.metadata()?.len() == 0 {
+            file.set_len(Self::size(slots) as u64)?;
+        }
+        let size = file.metadata()?.len();
+        file.unlock()?;
+        Self::map(&file, size)
+    }
+
+    /// Creates a cache with room for `slots` codecs in anonymous shared memory (`memfd`),
+    /// which is shared with the processes forked after creating it.
+    #[cfg(target_os = "linux")]
+    pub fn anonymous(slots: usize) -> io::Result<Self> {
+        use std::os::unix::io::FromRawFd;
+
+        let fd = unsafe { libc::memfd_create(c"cryptid-keys".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let file = unsafe { File::from_raw_fd(fd) };
+        file.set_len(Self::size(slots) as u64)?;
+        Self::map(&file, Self::size(slots) as u64)
+    }
+
+    fn size(slots: usize) -> usize {
+        (1 + slots * SLOT_WORDS) * 8
+    }
+
+    fn map(file: &File, size: u64) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Not a cryptid key cache");
+        let slots = (size as usize / 8).saturating_sub(1) / SLOT_WORDS;
+        if size as usize != Self::size(slots) {
+            return Err(invalid());
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                size as usize,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::madvise(ptr, size as usize, libc::MADV_DONTDUMP);
+        }
+        let cache = SharedKeyCache {
+            words: NonNull::new(ptr as *mut AtomicU64).expect("Mapping should not be null"),
+            slots,
+        };
+        // A new file is all zeros, which is an empty cache.
+        match cache
+            .word(0)
+            .compare_exchange(0, MAGIC, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) | Err(MAGIC) => Ok(cache),
+            Err(_) => Err(invalid()),
+        }
+    }
+
+    /// Returns the number of codecs whose keys are in the cache.
+    pub fn len(&self) -> usize {
+        (0..self.slots)
+            .filter(|&slot| self.read_id(slot).is_some_and(|id| id != [0; ID_WORDS]))
+            .count()
+    }
+
+    /// Returns `true` if the cache holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Zeroizes all keys in the cache, for all processes.  Codecs which have already been
+    /// created keep working.
+    ///
+    /// Slots left half-written by a process which crashed are zeroized too, but they stay
+    /// unusable until the cache is created again.  Keys which another process is writing at
+    /// the same time may remain.
+    pub fn clear(&self) {
+        for slot in 0..self.slots {
+            let seq = self.seq(slot);
+            let mut current = seq.load(Ordering::Relaxed);
+            loop {
+                if is_writing(current) {
+                    // Being written by another process, which may have crashed.  The sequence
+                    // number is left odd, so that readers never take the slot as consistent.
+                    for i in 1..SLOT_WORDS {
+                        self.slot_word(slot, i).store(0, Ordering::Relaxed);
+                    }
+                    break;
+                }
+                match seq.compare_exchange(
+                    current,
+                    current + 1,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        fence(Ordering::Release);
+                        for i in 1..SLOT_WORDS {
+                            self.slot_word(slot, i).store(0, Ordering::Relaxed);
+                        }
+                        seq.store(current + 2, Ordering::Release);
+                        break;
+                    }
+                    Err(actual) => current = actual,
+                }
+            }
+        }
+    }
+
+    /// Fills `keys` with the cached keys of the codec `name` of `hkdf`, or with `derive` and
+    /// caches them if they are not in the cache.
+    pub(crate) fn get_or_derive(
+        &self,
+        hkdf: &Hkdf,
+        name: &str,
+        keys: &mut [u8; 64],
+        derive: impl FnOnce(&mut [u8; 64]),
+    ) {
+        let mut id_bytes = [0u8; ID_WORDS * 8];
+        hkdf.expand(format!("{}/key-cache", name).as_bytes(), &mut id_bytes)
+            .expect("Length 32 should be valid");
+        let id = to_words(&id_bytes);
+        if (0..self.slots).any(|slot| self.read_keys(slot, &id, keys)) {
+            return;
+        }
+        derive(keys);
+        self.insert(&id, keys);
+    }
+
+    // Copies the keys of the slot to `keys` if it holds `id` and isn't being written.
+    fn read_keys(&self, slot: usize, id: &[u64; ID_WORDS], keys: &mut [u8; 64]) -> bool {
+        let seq = self.seq(slot).load(Ordering::Acquire);
+        if is_writing(seq) || self.id_words(slot) != *id {
+            return false;
+        }
+        let mut words = [0u64; KEY_WORDS];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = self
+                .slot_word(slot, 1 + ID_WORDS + i)
+                .load(Ordering::Relaxed);
+        }
+        fence(Ordering::Acquire);
+        let unchanged = self.seq(slot).load(Ordering::Relaxed) == seq;
+        if unchanged {
+            for (chunk, word) in keys.chunks_mut(8).zip(&words) {
+                chunk.copy_from_slice(&word.to_le_bytes());
+            }
+        }
+        words.zeroize();
+        unchanged
+    }
+
+    // Returns the ID of the slot if it isn't being written.
+    fn read_id(&self, slot: usize) -> Option<[u64; ID_WORDS]> {
+        let seq = self.seq(slot).load(Ordering::Acquire);
+        let id = self.id_words(slot);
+        fence(Ordering::Acquire);
+        (!is_writing(seq) && self.seq(slot).load(Ordering::Relaxed) == seq).then_some(id)
+    }
+
+    // Stores the keys of `id` in the first empty slot, if any.
+    fn insert(&self, id: &[u64; ID_WORDS], keys: &[u8; 64]) {
+        for slot in 0..self.slots {
+            let seq = self.seq(slot).load(Ordering::Relaxed);
+            if is_writing(seq) || self.id_words(slot) != [0; ID_WORDS] {
+                continue;
+            }
+            if self
+                .seq(slot)
+                .compare_exchange(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                continue;
+            }
+            fence(Ordering::Release);
+            // Another process may have filled the slot before the exchange.
+            if self.id_words(slot) == [0; ID_WORDS] {
+                for (i, &word) in id.iter().enumerate() {
+                    self.slot_word(slot, 1 + i).store(word, Ordering::Relaxed);
+                }
+                for (i, chunk) in keys.chunks(8).enumerate() {
+                    let word = u64::from_le_bytes(chunk.try_into().expect("Chunks are 8 bytes"));
+                    self.slot_word(slot, 1 + ID_WORDS + i)
+                        .store(word, Ordering::Relaxed);
+                }
+                self.seq(slot).store(seq + 2, Ordering::Release);
+                return;
+            }
+            self.seq(slot).store(seq + 2, Ordering::Release);
+        }
+    }
+
+    fn id_words(&self, slot: usize) -> [u64; ID_WORDS] {
+        std::array::from_fn(|i| self.slot_word(slot, 1 + i).load(Ordering::Relaxed))
+    }
+
+    fn seq(&self, slot: usize) -> &AtomicU64 {
+        self.slot_word(slot, 0)
+    }
+
+    fn slot_word(&self, slot: usize, i: usize) -> &AtomicU64 {
+        self.word(1 + slot * SLOT_WORDS + i)
+    }
+
+    fn word(&self, i: usize) -> &AtomicU64 {
+        debug_assert!(i < 1 + self.slots * SLOT_WORDS);
+        // The mapping is page aligned and holds `1 + slots * SLOT_WORDS` words.
+        unsafe { &*self.words.as_ptr().add(i) }
+    }
+}
+
+impl Drop for SharedKeyCache {
+    /// Unmaps the cache.  The keys stay in the cache for the other processes; see `clear`.
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(
+                self.words.as_ptr() as *mut libc::c_void,
+                Self::size(self.slots),
+            );
+        }
+    }
+}
+
+fn is_writing(seq: u64) -> bool {
+    seq % 2 == 1
+}
+
+fn to_words(bytes: &[u8; ID_WORDS * 8]) -> [u64; ID_WORDS] {
+    std::array::from_fn(|i| {
+        u64::from_le_bytes(
+            bytes[i * 8..i * 8 + 8]
+                .try_into()
+                .expect("Slice is 8 bytes"),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{Codec, Config};
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("cryptid-test-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_shared_keys() {
+        let path = temp_path("shared-keys");
+        let cache = Arc::new(SharedKeyCache::open(&path, 2).unwrap());
+        let config = Config::new(b"Test key here").key_cache(cache.clone());
+        assert_eq!(Codec::new("test", &config).encode(123), "test_hHLBCl4rZ3u");
+        assert_eq!(Codec::new("test", &config).encode(123), "test_hHLBCl4rZ3u");
+        assert_eq!(cache.len(), 1);
+
+        // The size of an existing cache is kept.
+        let other = Arc::new(SharedKeyCache::open(&path, 10).unwrap());
+        assert_eq!(other.slots, 2);
+        let other_config = Config::new(b"Test key here").key_cache(other.clone());
+        let codec = Codec::new("test", &other_config);
+        assert_eq!(codec.decode("test_hHLBCl4rZ3u"), Ok(123));
+        assert_eq!(other.len(), 1);
+
+        // Other keys and names get their own entries, and a full cache is bypassed.
+        let other_key = Config::new(b"Another key").key_cache(cache.clone());
+        let expected = Codec::new("test", &Config::new(b"Another key")).encode(123);
+        assert_eq!(Codec::new("test", &other_key).encode(123), expected);
+        assert_eq!(Codec::new("other", &config).decode(&expected).ok(), None);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            Codec::new("third", &config).encode(123),
+            Codec::new("third", &Config::new(b"Test key here")).encode(123)
+        );
+        assert_eq!(cache.len(), 2);
+
+        cache.clear();
+        assert!(other.is_empty());
+        assert_eq!(
+            Codec::new("test", &other_config).encode(123),
+            "test_hHLBCl4rZ3u"
+        );
+        assert_eq!(cache.len(), 1);
+
+        // A slot left half-written by a crashed process is zeroized too.
+        cache.seq(0).fetch_add(1, Ordering::Relaxed);
+        cache.clear();
+        assert!((1..SLOT_WORDS).all(|i| cache.slot_word(0, i).load(Ordering::Relaxed) == 0));
+        assert!(is_writing(cache.seq(0).load(Ordering::Relaxed)));
+
+        std::fs::write(&path, b"not a key cache").unwrap();
+        assert!(SharedKeyCache::open(&path, 2).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_unsafe_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Files which other users can read or replace are refused.
+        let path = temp_path("readable");
+        std::fs::write(&path, b"").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let error = SharedKeyCache::open(&path, 2).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+        let link = temp_path("link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(SharedKeyCache::open(&link, 2).is_err());
+        assert!(SharedKeyCache::open(&path, 2).is_ok());
+        std::fs::remove_file(&link).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_anonymous() {
+        let cache = Arc::new(SharedKeyCache::anonymous(4).unwrap());
+        let config = Config::new(b"Test key here").key_cache(cache.clone());
+        assert_eq!(Codec::new("test", &config).encode(123), "test_hHLBCl4rZ3u");
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,340 @@
+//! Formal grammar of the token format.
+//!
+//! `GRAMMAR` describes tokens produced by `Codec::encode` in
+//! [ABNF](https://www.rfc-editor.org/rfc/rfc5234) (RFC 5234), and `grammar` completes it with
+//! the prefix rules of a given codec name.  A string matching the grammar has the syntax of a
+//! token; whether it decodes also depends on the key and the MAC.
+//!
+//! The grammar covers the default mode and alphabet.  Payloads of other alphabets consist of
+//! the digits of `Alphabet::digits`.  In order-preserving mode the payload always has the
+//! same length, and tokens made with `Codec::encode_str` and `TokenBuilder` have longer
+//! payloads.
+//!
+//! The unit tests of this module parse the grammar with a small ABNF interpreter and check
+//! that `Codec::decode` accepts the syntax of exactly the same strings.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_rs::spec;
+//!
+//! let grammar = spec::grammar("user");
+//! assert!(grammar.starts_with(spec::GRAMMAR));
+//! assert!(grammar.ends_with("screaming = %x55.53.45.52\n"));
+//! ```
+
+use crate::PrefixCase;
+
+/// The grammar of tokens, without the rules for the prefix (`snake`, `kebab` and `screaming`),
+/// which depend on the codec name.
+pub const GRAMMAR: &str = r#"token     = prefix "_" payload
+prefix    = snake / kebab / screaming
+payload   = 1*22base62
+        ; The encrypted value and its MAC as a base62 number, most significant
+        ; digit first.  The number is less than 2^128.
+base62    = DIGIT / %x41-5A / %x61-7A
+DIGIT     = %x30-39
+"#;
+
+/// Returns `GRAMMAR` completed with the prefix rules of the codec `name` (see `PrefixCase`).
+pub fn grammar(name: &str) -> String {
+    let rule = |rule: &str, value: String| {
+        let bytes: Vec<String> = value.bytes().map(|b| format!("{:02X}", b)).collect();
+        format!("{:<9} = %x{}\n", rule, bytes.join("."))
+    };
+    [
+        GRAMMAR.to_string(),
+        rule("snake", PrefixCase::Snake.apply(name)),
+        rule("kebab", PrefixCase::Kebab.apply(name)),
+        rule("screaming", PrefixCase::Screaming.apply(name)),
+    ]
+    .concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Codec, Config, Error};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    // A minimal interpreter for the subset of ABNF used in the grammar.
+    enum Node {
+        Alternation(Vec<Node>),
+        Concatenation(Vec<Node>),
+        Repetition(usize, Option<usize>, Box<Node>),
+        Rule(String),
+        Literal(Vec<u8>, bool),
+        Range(u8, u8),
+    }
+
+    struct Parser<'a> {
+        input: &'a [u8],
+        pos: usize,
+    }
+
+    impl Parser<'_> {
+        fn peek(&self) -> Option<u8> {
+            self.input.get(self.pos).copied()
+        }
+
+        fn skip_space(&mut self) {
+            while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+                self.pos += 1;
+            }
+        }
+
+        fn take_while(&mut self, f: impl Fn(u8) -> bool) -> &str {
+            let start = self.pos;
+            while self.peek().is_some_and(&f) {
+                self.pos += 1;
+            }
+            std::str::from_utf8(&self.input[start..self.pos]).unwrap()
+        }
+
+        fn expect(&mut self, c: u8) {
+            assert_eq!(self.peek(), Some(c), "at {}", self.pos);
+            self.pos += 1;
+        }
+
+        fn alternation(&mut self) -> Node {
+            let mut items = vec![self.concatenation()];
+            while self.peek() == Some(b'/') {
+                self.pos += 1;
+                items.push(self.concatenation());
+            }
+            Node::Alternation(items)
+        }
+
+        fn concatenation(&mut self) -> Node {
+            let mut items = Vec::new();
+            loop {
+                self.skip_space();
+                match self.peek() {
+                    None | Some(b'/' | b')' | b']') => break,
+                    _ => items.push(self.repetition()),
+                }
+            }
+            Node::Concatenation(items)
+        }
+
+        fn repetition(&mut self) -> Node {
+            let min = self.take_while(|c| c.is_ascii_digit()).to_string();
+            if self.peek() != Some(b'*') {
+                let element = self.element();
+                return match min.parse() {
+                    Ok(n) => Node::Repetition(n, Some(n), Box::new(element)),
+                    Err(_) => element,
+                };
+            }
+            self.pos += 1;
+            let max = self.take_while(|c| c.is_ascii_digit()).parse().ok();
+            let element = self.element();
+            Node::Repetition(min.parse().unwrap_or(0), max, Box::new(element))
+        }
+
+        fn element(&mut self) -> Node {
+            match self.peek().unwrap() {
+                b'(' | b'[' => {
+                    let optional = self.peek() == Some(b'[');
+                    self.pos += 1;
+                    let inner = self.alternation();
+                    self.expect(if optional { b']' } else { b')' });
+                    match optional {
+                        true => Node::Repetition(0, Some(1), Box::new(inner)),
+                        false => inner,
+                    }
+                }
+                b'"' => self.string(false),
+                b'%' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(b's') => {
+                            self.pos += 1;
+                            self.string(true)
+                        }
+                        _ => self.number(),
+                    }
+                }
+                _ => {
+                    let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == b'-');
+                    Node::Rule(name.to_ascii_lowercase())
+                }
+            }
+        }
+
+        fn string(&mut self, case_sensitive: bool) -> Node {
+            self.expect(b'"');
+            let value = self.take_while(|c| c != b'"').as_bytes().to_vec();
+            self.expect(b'"');
+            Node::Literal(value, case_sensitive)
+        }
+
+        fn number(&mut self) -> Node {
+            self.expect(b'x');
+            let hex = |parser: &mut Self| {
+                let digits = parser.take_while(|c| c.is_ascii_hexdigit());
+                u8::from_str_radix(digits, 16).unwrap()
+            };
+            let first = hex(self);
+            if self.peek() == Some(b'-') {
+                self.pos += 1;
+                return Node::Range(first, hex(self));
+            }
+            let mut bytes = vec![first];
+            while self.peek() == Some(b'.') {
+                self.pos += 1;
+                bytes.push(hex(self));
+            }
+            Node::Literal(bytes, true)
+        }
+    }
+
+    fn parse_grammar(text: &str) -> HashMap<String, Node> {
+        // Join continuation lines and remove comments.
+        let mut rules: Vec<String> = Vec::new();
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap();
+            match rules.last_mut() {
+                Some(rule) if line.starts_with(' ') => rule.push_str(line),
+                _ if line.trim().is_empty() => {}
+                _ => rules.push(line.to_string()),
+            }
+        }
+        rules
+            .iter()
+            .map(|rule| {
+                let (name, definition) = rule.split_once('=').unwrap();
+                let mut parser = Parser {
+                    input: definition.as_bytes(),
+                    pos: 0,
+                };
+                let node = parser.alternation();
+                assert_eq!(parser.pos, definition.len(), "in rule {}", name);
+                (name.trim().to_ascii_lowercase(), node)
+            })
+            .collect()
+    }
+
+    // Returns all positions where a match of `node` starting at `pos` can end.
+    fn match_node(
+        rules: &HashMap<String, Node>,
+        node: &Node,
+        input: &[u8],
+        pos: usize,
+    ) -> Vec<usize> {
+        let mut ends = match node {
+            Node::Alternation(items) => items
+                .iter()
+                .flat_map(|item| match_node(rules, item, input, pos))
+                .collect(),
+            Node::Concatenation(items) => items.iter().fold(vec![pos], |starts, item| {
+                starts
+                    .into_iter()
+                    .flat_map(|start| match_node(rules, item, input, start))
+                    .collect()
+            }),
+            Node::Repetition(min, max, item) => {
+                let mut ends = if *min == 0 { vec![pos] } else { vec![] };
+                let mut current = vec![pos];
+                for count in 1..=max.unwrap_or(input.len() + 1) {
+                    current = current
+                        .into_iter()
+                        .flat_map(|start| match_node(rules, item, input, start))
+                        .collect();
+                    current.sort();
+                    current.dedup();
+                    if current.is_empty() {
+                        break;
+                    }
+                    if count >= *min {
+                        ends.extend(&current);
+                    }
+                }
+                ends
+            }
+            Node::Rule(name) => match_node(rules, &rules[name], input, pos),
+            Node::Literal(bytes, case_sensitive) => {
+                let end = pos + bytes.len();
+                let matches = input
+                    .get(pos..end)
+                    .is_some_and(|slice| match case_sensitive {
+                        true => slice == bytes.as_slice(),
+                        false => slice.eq_ignore_ascii_case(bytes),
+                    });
+                if matches {
+                    vec![end]
+                } else {
+                    vec![]
+                }
+            }
+            Node::Range(low, high) => match input.get(pos) {
+                Some(c) if (*low..=*high).contains(c) => vec![pos + 1],
+                _ => vec![],
+            },
+        };
+        ends.sort();
+        ends.dedup();
+        ends
+    }
+
+    fn matches(rules: &HashMap<String, Node>, input: &str) -> bool {
+        match_node(rules, &rules["token"], input.as_bytes(), 0).contains(&input.len())
+    }
+
+    #[test]
+    fn test_grammar() {
+        let rules = parse_grammar(&grammar("user_account"));
+        assert!(matches(&rules, "user_account_hHLBCl4rZ3u"));
+        assert!(matches(&rules, "user-account_hHLBCl4rZ3u"));
+        assert!(matches(&rules, "USER_ACCOUNT_hHLBCl4rZ3u"));
+        assert!(!matches(&rules, "User_Account_hHLBCl4rZ3u"));
+        assert!(!matches(&rules, "user_account_"));
+        assert!(!matches(&rules, "user_account_hHLBCl+rZ3u"));
+        assert!(!matches(&rules, "hHLBCl4rZ3u"));
+    }
+
+    #[test]
+    fn test_grammar_agrees_with_decode() {
+        let name = "user_account";
+        let rules = parse_grammar(&grammar(name));
+        let codec = Codec::new(name, &Config::new(b"Test key here"));
+        let prefixes = [
+            "user_account_",
+            "user-account_",
+            "USER_ACCOUNT_",
+            "User_account_",
+            "user_",
+            "user_account",
+            "user_account__",
+            "_",
+            "",
+        ];
+        let alphabet = b"09AZaz_-+ ";
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..10_000 {
+            let prefix = prefixes[rng.gen_range(0..prefixes.len())];
+            // Payloads of at most 21 digits are always below 2^128.  Those of 22 digits may
+            // not be, which the grammar doesn't express, so they are left out.
+            let length = rng.gen_range(0..=30);
+            if length == 22 {
+                continue;
+            }
+            let payload: String = (0..length)
+                .map(|_| alphabet[rng.gen_range(0..alphabet.len())] as char)
+                .collect();
+            let token = format!("{}{}", prefix, payload);
+            let syntax_ok = !matches!(
+                codec.decode(&token),
+                Err(Error::EmptyToken
+                    | Error::InvalidPrefix { .. }
+                    | Error::WrongType { .. }
+                    | Error::DecodingFailed
+                    | Error::PayloadTooLong { .. })
+            );
+            assert_eq!(matches(&rules, &token), syntax_ok, "token {:?}", token);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::codec::is_valid_string;
+use crate::field::get_or_create_codec;
+use crate::{Error, TypeMarker};
+
+/// A generic type-safe string reference field, for objects whose natural key is a short
+/// string such as an invoice number.
+///
+/// This is the string counterpart of `Field<T>`: with Serde, the string is encrypted into an
+/// opaque token with the marker's prefix using `Codec::encode_str`, and tokens are decrypted
+/// back on deserialization.  The string must consist of 1 to `MAX_STRING_LENGTH` printable
+/// ASCII characters.  With Diesel (`diesel` feature), the plain string is stored in a Postgres
+/// `Text` column, or an SQLite one with `diesel-sqlite`.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Config, StrField, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct InvoiceNumberMarker;
+/// impl TypeMarker for InvoiceNumberMarker {
+///     fn name() -> &'static str { "invoice" }
+/// }
+///
+/// type InvoiceNumber = StrField<InvoiceNumberMarker>;
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let number = InvoiceNumber::new("INV-2024-0042").unwrap();
+/// let json = serde_json::to_string(&number).unwrap();
+/// assert!(json.starts_with("\"invoice_"));
+///
+/// let decoded: InvoiceNumber = serde_json::from_str(&json).unwrap();
+/// assert_eq!(decoded.as_str(), "INV-2024-0042");
+/// ```
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "diesel",
+    derive(diesel::expression::AsExpression),
+    diesel(sql_type = diesel::sql_types::Text)
+)]
+pub struct StrField<T: TypeMarker> {
+    value: String,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T: TypeMarker> StrField<T> {
+    /// Creates a `StrField<T>`, returning `Error::InvalidString` if `value` is empty, too long
+    /// or contains characters other than printable ASCII.
+    pub fn new(value: &str) -> Result<Self, Error> {
+        if !is_valid_string(value) {
+            return Err(Error::InvalidString);
+        }
+        Ok(Self::from_string(value.to_string()))
+    }
+
+    pub(crate) fn from_string(value: String) -> Self {
+        StrField {
+            value,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the plain string.
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+}
+
+impl<T: TypeMarker> Clone for StrField<T> {
+    fn clone(&self) -> Self {
+        Self::from_string(self.value.clone())
+    }
+}
+
+impl<T: TypeMarker> PartialEq for StrField<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: TypeMarker> Eq for StrField<T> {}
+
+impl<T: TypeMarker> fmt::Display for StrField<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "StrField {{ value: {}, marker: {} }}",
+            self.value,
+            T::name()
+        )
+    }
+}
+
+impl<T: TypeMarker> Serialize for StrField<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let codec = get_or_create_codec::<T>();
+        let encoded = codec
+            .encode_str(&self.value)
+            .map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&encoded)
+    }
+}
+
+impl<'de, T: TypeMarker> Deserialize<'de> for StrField<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        let codec = get_or_create_codec::<T>();
+        let value = codec
+            .decode_str(&encoded)
+            .map_err(serde::de::Error::custom)?;
+        Ok(Self::from_string(value))
+    }
+}
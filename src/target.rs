@@ -0,0 +1,164 @@
+use crate::Alphabet;
+
+/// Where tokens are embedded, set with `Config::target`, so that unsuitable configurations
+/// fail when creating a codec rather than when a token first breaks a URL or a file name.
+///
+/// A target constrains the characters and the length of the tokens of `Codec::encode`,
+/// including the prefix.  Codecs of codec names or settings which could produce longer
+/// tokens fail with `ConfigError::UnsatisfiableTarget`.  Strings of `Codec::encode_str` and
+/// the other formats are not covered.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Alphabet, Codec, Config, ContextTarget};
+///
+/// let config = Config::new(b"your-secure-key").target(ContextTarget::DnsLabel).unwrap();
+/// let codec = Codec::new("tenant", &config);
+/// let encoded = codec.encode(12345);
+///
+/// assert_eq!(encoded, encoded.to_lowercase());
+/// assert!(encoded.len() <= 63);
+/// assert!(config.clone().alphabet(Alphabet::Base62).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContextTarget {
+    /// A single DNS label, such as a subdomain: at most 63 characters, in lowercase because
+    /// DNS is case-insensitive.  The alphabet is base32 unless it has no uppercase digits,
+    /// like decimal.
+    ///
+    /// The separator of the prefix is an underscore, which DNS allows but hostnames strictly
+    /// don't, so use `Codec::encode_parts` and `Codec::accept_unprefixed` for the payload
+    /// alone where that matters.
+    DnsLabel,
+    /// A file name, such as an S3 key segment: at most 255 characters, in lowercase so that
+    /// tokens don't collide on case-insensitive file systems.  The alphabet is base32 unless
+    /// it has no uppercase digits, like decimal.
+    Filename,
+    /// A segment of a URL path: at most 255 characters, in any alphabet.
+    UrlPath,
+}
+
+impl ContextTarget {
+    /// Returns the maximum length of tokens in the target.
+    pub fn max_length(self) -> usize {
+        match self {
+            ContextTarget::DnsLabel => 63,
+            ContextTarget::Filename | ContextTarget::UrlPath => 255,
+        }
+    }
+
+    /// Returns `true` if tokens can be in `alphabet`.
+    pub(crate) fn allows_alphabet(self, alphabet: &Alphabet) -> bool {
+        match self {
+            ContextTarget::DnsLabel | ContextTarget::Filename => {
+                !alphabet.digits().bytes().any(|c| c.is_ascii_uppercase())
+            }
+            ContextTarget::UrlPath => true,
+        }
+    }
+
+    /// Returns `true` if tokens can have the prefix of the codec `name`.  Codec names are
+    /// ASCII letters, digits, underscores and hyphens, which are valid in every target except
+    /// for upper case letters in the case-insensitive ones, and a leading hyphen in DNS.
+    pub(crate) fn allows_name(self, name: &str) -> bool {
+        match self {
+            ContextTarget::DnsLabel => {
+                !name.starts_with('-') && !name.bytes().any(|c| c.is_ascii_uppercase())
+            }
+            ContextTarget::Filename => !name.bytes().any(|c| c.is_ascii_uppercase()),
+            ContextTarget::UrlPath => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Codec, Config, ConfigError};
+
+    #[test]
+    fn test_targets() {
+        let base = Config::new(b"Test key here");
+        for target in [
+            ContextTarget::DnsLabel,
+            ContextTarget::Filename,
+            ContextTarget::UrlPath,
+        ] {
+            let config = base.clone().target(target).unwrap();
+            assert!(target.allows_alphabet(&config.alphabet));
+            let codec = Codec::new("test-item", &config);
+            assert!(codec.max_encoded_len() <= target.max_length());
+            for num in [0, 123, u64::MAX] {
+                let token = codec.encode(num);
+                assert_eq!(codec.decode(&token), Ok(num));
+                if target != ContextTarget::UrlPath {
+                    assert_eq!(token, token.to_lowercase());
+                }
+            }
+            let long_name = "x".repeat(target.max_length());
+            assert!(matches!(
+                Codec::try_new(&long_name, &config),
+                Err(ConfigError::UnsatisfiableTarget)
+            ));
+        }
+
+        // The case-insensitive targets switch base62 to base32, but keep decimal.
+        let config = base.clone().target(ContextTarget::DnsLabel).unwrap();
+        assert_eq!(config.alphabet, Alphabet::Base32);
+        assert!(config.clone().alphabet(Alphabet::Base62).is_err());
+        assert!(config.clone().alphabet(Alphabet::Decimal).is_ok());
+        let decimal = base.clone().alphabet(Alphabet::Decimal).unwrap();
+        let config = decimal.target(ContextTarget::Filename).unwrap();
+        assert_eq!(config.alphabet, Alphabet::Decimal);
+        let config = base.clone().target(ContextTarget::UrlPath).unwrap();
+        assert_eq!(config.alphabet, Alphabet::Base62);
+
+        // Custom alphabets are allowed without uppercase digits.
+        let config = base.clone().target(ContextTarget::Filename).unwrap();
+        assert!(config
+            .clone()
+            .alphabet(Alphabet::Custom("23456789abcdefghjkmnpqrstuvwxyz".into()))
+            .is_ok());
+        assert!(config
+            .clone()
+            .alphabet(Alphabet::Custom("23456789ABCDEFGHJKMNPQRSTUVWXYZ".into()))
+            .is_err());
+
+        // Names must be lowercase, and labels can't start with a hyphen.
+        let config = base.clone().target(ContextTarget::DnsLabel).unwrap();
+        for name in ["Test", "-test"] {
+            assert!(matches!(
+                Codec::try_new(name, &config),
+                Err(ConfigError::UnsatisfiableTarget)
+            ));
+        }
+        let config = base.clone().target(ContextTarget::Filename).unwrap();
+        assert!(Codec::try_new("-test", &config).is_ok());
+        assert!(Codec::try_new("Test", &config).is_err());
+        let config = base.clone().target(ContextTarget::UrlPath).unwrap();
+        assert!(Codec::try_new("-Test", &config).is_ok());
+
+        // Settings which make tokens longer count against the limit.
+        let config = base
+            .clone()
+            .hmac_length(8)
+            .unwrap()
+            .nonce_length(4)
+            .unwrap()
+            .key_version(1)
+            .unwrap()
+            .target(ContextTarget::DnsLabel)
+            .unwrap();
+        let name = "x".repeat(63 - Codec::new("x", &config).max_encoded_len() + 1);
+        assert!(Codec::try_new(&name, &config).is_ok());
+        assert!(Codec::try_new(&format!("{}x", name), &config).is_err());
+
+        // Key versions must remain single digits in base32.
+        let config = base.clone().key_version(40).unwrap();
+        assert!(matches!(
+            config.target(ContextTarget::DnsLabel),
+            Err(ConfigError::InvalidVersion)
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+//! Metrics of `Codec::encode` and `Codec::decode`, recorded with the
+//! [`metrics`](https://docs.rs/metrics) facade (`metrics` feature).
+//!
+//! Install any `metrics` exporter, such as `metrics-exporter-prometheus`, to collect them.  All
+//! metrics have the label `prefix` with the codec name:
+//!
+//! - `ENCODE_DURATION`: histogram of encode latencies in seconds.
+//! - `DECODE_DURATION`: histogram of decode latencies in seconds, also labeled with `result`,
+//!   which is `ok` or `error`.
+//! - `DECODE_ERRORS`: counter of failed decodes, also labeled with `error`, the snake case
+//!   name of the `Error` variant, e.g. `incorrect_mac`.
+//!
+//! The durations include the hooks of the codec.  Hot paths which bypass `decode`, such as
+//! hits in a `CachedCodec`, are not recorded.
+//!
+//! Each codec also counts its encodes and decodes, without an exporter.  `prefix_stats`
+//! returns the counts of the codecs of `Field<T>` types, e.g. for an admin diagnostics
+//! endpoint showing which types of IDs the process handles.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::field::cached_codecs;
+use crate::Error;
+
+/// Name of the encode latency histogram.
+pub const ENCODE_DURATION: &str = "cryptid_encode_duration_seconds";
+
+/// Name of the decode latency histogram.
+pub const DECODE_DURATION: &str = "cryptid_decode_duration_seconds";
+
+/// Name of the decode error counter.
+pub const DECODE_ERRORS: &str = "cryptid_decode_errors_total";
+
+/// The encode and decode counts of a codec.  See `prefix_stats`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrefixStats {
+    /// The name of the codec, which is the prefix of its tokens without the underscore.
+    pub prefix: String,
+    /// Number of encodes.
+    pub encodes: u64,
+    /// Number of decodes, including failed ones.
+    pub decodes: u64,
+    /// Number of failed decodes.
+    pub decode_errors: u64,
+}
+
+/// Returns the counts of the codecs of the `Field<T>` types used so far, sorted by prefix.
+/// See also `registered_prefixes`.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::telemetry::prefix_stats;
+/// use cryptid_rs::{Config, Field, TypeMarker};
+///
+/// #[derive(Debug)]
+/// pub struct InvoiceIdMarker;
+/// impl TypeMarker for InvoiceIdMarker {
+///     fn name() -> &'static str { "invoice" }
+/// }
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// Field::<InvoiceIdMarker>::from(5).to_string();
+///
+/// let stats = prefix_stats();
+/// let invoice = stats.iter().find(|stats| stats.prefix == "invoice").unwrap();
+/// assert_eq!(invoice.encodes, 1);
+/// ```
+pub fn prefix_stats() -> Vec<PrefixStats> {
+    let mut stats: Vec<PrefixStats> = cached_codecs().iter().map(|codec| codec.stats()).collect();
+    stats.sort_by(|a, b| a.prefix.cmp(&b.prefix));
+    stats
+}
+
+/// The counts behind `Codec::stats`.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    encodes: AtomicU64,
+    decodes: AtomicU64,
+    decode_errors: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn stats(&self, prefix: &str) -> PrefixStats {
+        PrefixStats {
+            prefix: prefix.to_string(),
+            encodes: self.encodes.load(Ordering::Relaxed),
+            decodes: self.decodes.load(Ordering::Relaxed),
+            decode_errors: self.decode_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) fn record_encode(name: &Arc<str>, counters: &Counters, elapsed: Duration) {
+    counters.encodes.fetch_add(1, Ordering::Relaxed);
+    metrics::histogram!(ENCODE_DURATION, "prefix" => name.clone()).record(elapsed);
+}
+
+pub(crate) fn record_decode<T>(
+    name: &Arc<str>,
+    counters: &Counters,
+    elapsed: Duration,
+    result: &Result<T, Error>,
+) {
+    counters.decodes.fetch_add(1, Ordering::Relaxed);
+    if result.is_err() {
+        counters.decode_errors.fetch_add(1, Ordering::Relaxed);
+    }
+    let outcome = if result.is_ok() { "ok" } else { "error" };
+    metrics::histogram!(DECODE_DURATION, "prefix" => name.clone(), "result" => outcome)
+        .record(elapsed);
+    if let Err(error) = result {
+        metrics::counter!(
+            DECODE_ERRORS,
+            "prefix" => name.clone(),
+            "error" => error_label(error)
+        )
+        .increment(1);
+    }
+}
+
+fn error_label(error: &Error) -> &'static str {
+    match error {
+        Error::DecodingFailed => "decoding_failed",
+        Error::DecryptionFailed => "decryption_failed",
+        Error::EmptyToken => "empty_token",
+        Error::EncryptionFailed => "encryption_failed",
+        Error::Forbidden => "forbidden",
+        Error::IdOutOfRange => "id_out_of_range",
+        Error::IncorrectMAC => "incorrect_mac",
+        Error::InvalidDataLength => "invalid_data_length",
+        Error::InvalidPrefix { .. } => "invalid_prefix",
+        Error::InvalidString => "invalid_string",
+        Error::LimitExceeded => "limit_exceeded",
+        Error::NegativeId => "negative_id",
+        Error::ParameterMismatch => "parameter_mismatch",
+        Error::PayloadTooLong { .. } => "payload_too_long",
+        Error::RateLimited => "rate_limited",
+        Error::Rejected => "rejected",
+        Error::ReservedId => "reserved_id",
+        Error::SentinelMismatch { .. } => "sentinel_mismatch",
+        Error::WrongType { .. } => "wrong_type",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Codec, Config};
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+
+    // Counts the increments and recorded values of each metric, keyed by name and labels.
+    #[derive(Default)]
+    struct Counts(Mutex<HashMap<String, Arc<Count>>>);
+
+    #[derive(Default)]
+    struct Count(AtomicU64);
+
+    impl CounterFn for Count {
+        fn increment(&self, value: u64) {
+            self.0.fetch_add(value, Ordering::Relaxed);
+        }
+        fn absolute(&self, _: u64) {}
+    }
+
+    impl HistogramFn for Count {
+        fn record(&self, _: f64) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    impl Counts {
+        fn count(&self, key: &Key) -> Arc<Count> {
+            let mut labels: Vec<String> = key
+                .labels()
+                .map(|label| format!("{}={}", label.key(), label.value()))
+                .collect();
+            labels.sort();
+            let name = format!("{}{{{}}}", key.name(), labels.join(","));
+            self.0.lock().unwrap().entry(name).or_default().clone()
+        }
+
+        fn get(&self, name: &str) -> u64 {
+            self.0
+                .lock()
+                .unwrap()
+                .get(name)
+                .map_or(0, |count| count.0.load(Ordering::Relaxed))
+        }
+    }
+
+    impl Recorder for Counts {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.count(key))
+        }
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.count(key))
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        let counts = Counts::default();
+        let codec = Codec::new("test", &Config::new(b"Test key here"));
+        metrics::with_local_recorder(&counts, || {
+            codec.encode(123);
+            assert_eq!(codec.decode("test_hHLBCl4rZ3u"), Ok(123));
+            assert!(codec.decode("test_hHLBCl4rZ3v").is_err());
+            assert!(codec.decode("test_hHLBCl4rZ3v").is_err());
+            assert!(codec.decode("other_hHLBCl4rZ3u").is_err());
+        });
+
+        assert_eq!(
+            counts.get(&format!("{}{{prefix=test}}", ENCODE_DURATION)),
+            1
+        );
+        let decodes = |result| format!("{}{{prefix=test,result={}}}", DECODE_DURATION, result);
+        assert_eq!(counts.get(&decodes("ok")), 1);
+        assert_eq!(counts.get(&decodes("error")), 3);
+        let errors = |error| format!("{}{{error={},prefix=test}}", DECODE_ERRORS, error);
+        assert_eq!(counts.get(&errors("incorrect_mac")), 2);
+        assert_eq!(counts.get(&errors("wrong_type")), 1);
+
+        let stats = codec.stats();
+        assert_eq!(
+            stats,
+            PrefixStats {
+                prefix: "test".to_string(),
+                encodes: 1,
+                decodes: 4,
+                decode_errors: 3,
+            }
+        );
+    }
+}
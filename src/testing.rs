@@ -0,0 +1,223 @@
+//! Helpers for tests of crates using `cryptid`.
+//!
+//! `with_test_key` runs a closure with the global config set to `TEST_KEY` and the default
+//! parameters, so snapshot tests get the same tokens on every machine without configuring a
+//! key.  The global config is restored afterwards, and calls are serialized so that tests
+//! running in parallel threads don't see each other's configs.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_rs::testing::with_test_key;
+//! use cryptid_rs::{Codec, Config, Field, TypeMarker};
+//!
+//! #[derive(Debug)]
+//! pub struct ExampleIdMarker;
+//! impl TypeMarker for ExampleIdMarker {
+//!     fn name() -> &'static str { "example" }
+//! }
+//!
+//! Config::set_global(Config::new(b"another key"));
+//! with_test_key(|codecs| {
+//!     let id = Field::<ExampleIdMarker>::from(12345);
+//!     assert_eq!(serde_json::to_string(&id).unwrap(), "\"example_VgwPy6rwatl\"");
+//!     assert_eq!(codecs.codec("example").encode(12345), "example_VgwPy6rwatl");
+//! });
+//!
+//! // The previous config is restored.
+//! let codec = Codec::new("example", &Config::global().unwrap());
+//! assert_ne!(codec.encode(12345), "example_VgwPy6rwatl");
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use uuid::Uuid;
+
+use crate::codec::split_prefix;
+use crate::field::clear_codec_cache;
+use crate::{Codec, Config, Error, IdCodec};
+
+/// The fixed key installed by `with_test_key`, the same one as in the examples of this crate.
+/// Never use it outside tests.
+pub const TEST_KEY: &[u8] = b"your-secure-key";
+
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Creates codecs with the test config, passed to the closure of `with_test_key`.
+pub struct TestCodecs {
+    config: Config,
+}
+
+impl TestCodecs {
+    /// Returns a codec named `name` with the test config.
+    pub fn codec(&self, name: &str) -> Codec {
+        Codec::new(name, &self.config)
+    }
+
+    /// Returns the test config.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+/// Runs `f` with the global config set to `TEST_KEY` and the default parameters, and then
+/// restores the previous global config, also if `f` panics.
+///
+/// Calls from different threads are serialized, so `f` must not call `with_test_key` itself.
+/// Code which calls `Config::set_global` directly can still change the config while `f` runs.
+pub fn with_test_key<R>(f: impl FnOnce(&TestCodecs) -> R) -> R {
+    // A panic in another test doesn't make the lock unusable.
+    let _lock = TEST_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+    let codecs = TestCodecs {
+        config: Config::new(TEST_KEY),
+    };
+    let _restore = Restore(Config::replace_global(Some(codecs.config.clone())));
+    clear_codec_cache();
+    f(&codecs)
+}
+
+// Restores the global config when dropped.
+struct Restore(Option<Config>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        Config::replace_global(self.0.take());
+        clear_codec_cache();
+    }
+}
+
+/// A trivially reversible `IdCodec` for unit tests, which needs no key.
+///
+/// Tokens are the name, an underscore and the decimal ID, such as `user_12345`, and UUIDs
+/// hold the ID in their low 64 bits.  Decoding checks the prefix like `Codec` does, but there
+/// is no MAC, so never use it outside tests.
+#[derive(Clone, Debug)]
+pub struct MockCodec {
+    prefix: Arc<str>,
+}
+
+impl MockCodec {
+    /// Creates a mock codec for tokens of the type `name`.
+    pub fn new(name: &str) -> Self {
+        MockCodec {
+            prefix: format!("{}_", name).into(),
+        }
+    }
+}
+
+impl IdCodec for MockCodec {
+    fn encode(&self, num: u64) -> String {
+        format!("{}{}", self.prefix, num)
+    }
+
+    fn decode(&self, encoded: &str) -> Result<u64, Error> {
+        if encoded.is_empty() {
+            return Err(Error::EmptyToken);
+        }
+        let (received, digits) = split_prefix(encoded).unwrap_or(("", encoded));
+        if received != &*self.prefix {
+            return Err(Error::InvalidPrefix {
+                received: received.to_string(),
+                expected: self.prefix.clone(),
+            });
+        }
+        digits.parse().map_err(|_| Error::DecodingFailed)
+    }
+
+    fn encode_uuid(&self, num: u64) -> Uuid {
+        Uuid::from_u64_pair(0, num)
+    }
+
+    fn decode_uuid(&self, uuid: Uuid) -> Result<u64, Error> {
+        match uuid.as_u64_pair() {
+            (0, num) => Ok(num),
+            _ => Err(Error::DecodingFailed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Field, SerdeFormat, TypeMarker};
+
+    #[derive(Debug)]
+    struct UserIdMarker;
+    impl TypeMarker for UserIdMarker {
+        fn name() -> &'static str {
+            "user"
+        }
+    }
+
+    // Other tests use the same key as the global config, so they aren't disturbed by this.
+    #[test]
+    fn test_with_test_key() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let token = with_test_key(|codecs| {
+            assert_eq!(&codecs.config().key[..], TEST_KEY);
+            serde_json::to_value(Field::<UserIdMarker>::from(12345)).unwrap()
+        });
+        assert_eq!(token, "user_Qo4cTPVnos2");
+
+        let result = std::panic::catch_unwind(|| with_test_key(|_| panic!("test panic")));
+        assert!(result.is_err());
+        assert_eq!(&Config::global().unwrap().key[..], b"your-secure-key");
+        assert_eq!(
+            with_test_key(|codecs| codecs.codec("user").encode(12345)),
+            token
+        );
+    }
+
+    #[derive(Debug)]
+    struct OrderIdMarker;
+    impl TypeMarker for OrderIdMarker {
+        fn name() -> &'static str {
+            "order"
+        }
+        fn serde_format() -> SerdeFormat {
+            SerdeFormat::Uuid
+        }
+    }
+
+    #[test]
+    fn test_mock_codec() {
+        let codec = MockCodec::new("user");
+        assert_eq!(codec.encode(12345), "user_12345");
+        assert_eq!(codec.decode("user_12345"), Ok(12345));
+        assert_eq!(codec.decode("user_x"), Err(Error::DecodingFailed));
+        assert_eq!(
+            codec.decode("12345"),
+            Err(Error::InvalidPrefix {
+                received: "".to_string(),
+                expected: "user_".into()
+            })
+        );
+        assert!(codec.decode("team_12345").is_err());
+
+        let field = Field::<UserIdMarker>::from(12345);
+        let mut json = Vec::new();
+        field
+            .serialize_with(&codec, &mut serde_json::Serializer::new(&mut json))
+            .unwrap();
+        assert_eq!(json, b"\"user_12345\"");
+        let mut deserializer = serde_json::Deserializer::from_slice(&json);
+        assert_eq!(
+            Field::<UserIdMarker>::deserialize_with(&codec, &mut deserializer).unwrap(),
+            field
+        );
+
+        let codec = MockCodec::new("order");
+        let field = Field::<OrderIdMarker>::from(12345);
+        let uuid = field.encode_with(&codec);
+        assert_eq!(uuid, "00000000-0000-0000-0000-000000003039");
+        assert_eq!(Field::decode_with(&codec, &uuid), Ok(field));
+        assert_eq!(
+            Field::<OrderIdMarker>::decode_with(&codec, "00000000-0000-0001-0000-000000003039"),
+            Err(Error::DecodingFailed)
+        );
+
+        // Trait objects work too.
+        let codec: &dyn IdCodec = &codec;
+        assert_eq!(Field::decode_with(codec, &uuid), Ok(field));
+    }
+}
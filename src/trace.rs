@@ -0,0 +1,113 @@
+//! Logging `Field<T>` values with `tracing` without leaking raw IDs.
+//!
+//! `Field::traced` returns a value which can be recorded in spans and events, and which is
+//! always rendered as the encoded token, or as a masked token if
+//! `set_redaction(Redaction::Masked)` has been called.  The `Debug` output of `Field<T>`
+//! contains the raw ID, so avoid recording fields with `?field`.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_rs::trace::{set_redaction, Redaction};
+//! use cryptid_rs::{Config, Field, TypeMarker};
+//!
+//! #[derive(Debug)]
+//! pub struct UserIdMarker;
+//! impl TypeMarker for UserIdMarker {
+//!     fn name() -> &'static str { "user" }
+//! }
+//!
+//! Config::set_global(Config::new(b"your-secure-key"));
+//! let user_id = Field::<UserIdMarker>::from(12345);
+//! tracing::info!(user = user_id.traced(), "user logged in");
+//!
+//! set_redaction(Redaction::Masked);
+//! assert_eq!(user_id.traced().to_string(), "user_Qo4c…");
+//! # set_redaction(Redaction::Token);
+//! ```
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::field::{try_get_or_create_codec, UNENCODED};
+use crate::{Field, TypeMarker};
+
+/// How `Field::traced` renders IDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Redaction {
+    /// The full encoded token (the default).
+    Token,
+    /// The prefix and the first four characters of the encoded token, enough to tell
+    /// different IDs apart in logs while not allowing the token to be used.
+    Masked,
+}
+
+static REDACTION: AtomicU8 = AtomicU8::new(0);
+
+/// Sets how `Field::traced` renders IDs in the whole process.
+pub fn set_redaction(redaction: Redaction) {
+    REDACTION.store(redaction as u8, Ordering::Relaxed);
+}
+
+/// Returns the current redaction mode.
+pub fn redaction() -> Redaction {
+    match REDACTION.load(Ordering::Relaxed) {
+        0 => Redaction::Token,
+        _ => Redaction::Masked,
+    }
+}
+
+/// A `Field<T>` rendered according to the current redaction mode.
+pub struct Redacted<T: TypeMarker>(Field<T>);
+
+impl<T: TypeMarker> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let token = try_get_or_create_codec::<T>().map(|codec| codec.try_encode(self.0.id));
+        let Some(Ok(token)) = token else {
+            return f.write_str(UNENCODED);
+        };
+        match redaction() {
+            Redaction::Token => f.write_str(&token),
+            Redaction::Masked => {
+                let visible = token.len().min(token.rfind('_').map_or(0, |i| i + 1) + 4);
+                write!(f, "{}…", &token[..visible])
+            }
+        }
+    }
+}
+
+impl<T: TypeMarker> Field<T> {
+    /// Returns a value for recording the field in `tracing` spans and events, rendered as the
+    /// encoded or masked token.  See the `trace` module.
+    pub fn traced(&self) -> tracing::field::DisplayValue<Redacted<T>> {
+        tracing::field::display(Redacted(*self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::get_or_create_codec;
+    use crate::Config;
+
+    #[derive(Debug)]
+    struct TestMarker;
+    impl TypeMarker for TestMarker {
+        fn name() -> &'static str {
+            "test"
+        }
+    }
+
+    #[test]
+    fn test_redaction() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let field = Field::<TestMarker>::from(123);
+        let token = get_or_create_codec::<TestMarker>().encode(123);
+
+        assert_eq!(redaction(), Redaction::Token);
+        assert_eq!(field.traced().to_string(), token);
+        set_redaction(Redaction::Masked);
+        assert_eq!(field.traced().to_string(), format!("{}…", &token[..9]));
+        set_redaction(Redaction::Token);
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Field, TypeMarker};
+
+/// An object reference serialized with the object type next to the encoded ID, Stripe-style.
+///
+/// With Serde, a `TypedRef<T>` is a map with the encoded `id` and an `object` field holding
+/// the name of the marker, e.g. `{"id": "user_Qo4cTPVnos2", "object": "user"}`.  Use
+/// `#[serde(flatten)]` to put the fields directly in the containing object.  Deserialization
+/// fails if `object` is not the name of the marker.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Config, Field, TypeMarker, TypedRef};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug)]
+/// pub struct UserIdMarker;
+/// impl TypeMarker for UserIdMarker {
+///     fn name() -> &'static str { "user" }
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct User {
+///     #[serde(flatten)]
+///     id: TypedRef<UserIdMarker>,
+///     name: String,
+/// }
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let user = User { id: TypedRef::new(Field::from(12345)), name: "Alice".to_string() };
+/// let json = serde_json::to_string(&user).unwrap();
+/// assert_eq!(json, r#"{"id":"user_Qo4cTPVnos2","object":"user","name":"Alice"}"#);
+///
+/// let decoded: User = serde_json::from_str(&json).unwrap();
+/// assert_eq!(decoded.id.field(), Field::from(12345));
+/// ```
+#[derive(Debug)]
+pub struct TypedRef<T: TypeMarker>(Field<T>);
+
+impl<T: TypeMarker> TypedRef<T> {
+    /// Creates a reference to the object with the ID `field`.
+    pub fn new(field: Field<T>) -> Self {
+        TypedRef(field)
+    }
+
+    /// Returns the ID of the object.
+    pub fn field(&self) -> Field<T> {
+        self.0
+    }
+}
+
+impl<T: TypeMarker> From<Field<T>> for TypedRef<T> {
+    fn from(field: Field<T>) -> Self {
+        TypedRef(field)
+    }
+}
+
+impl<T: TypeMarker> Clone for TypedRef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: TypeMarker> Copy for TypedRef<T> {}
+
+impl<T: TypeMarker> PartialEq for TypedRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: TypeMarker> Eq for TypedRef<T> {}
+
+#[derive(Serialize)]
+#[serde(bound = "")]
+struct SerializedRef<'a, T: TypeMarker> {
+    id: &'a Field<T>,
+    object: &'static str,
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "")]
+struct DeserializedRef<T: TypeMarker> {
+    id: Field<T>,
+    object: String,
+}
+
+impl<T: TypeMarker> Serialize for TypedRef<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SerializedRef {
+            id: &self.0,
+            object: T::name(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: TypeMarker> Deserialize<'de> for TypedRef<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = DeserializedRef::<T>::deserialize(deserializer)?;
+        if raw.object != T::name() {
+            return Err(serde::de::Error::custom(format!(
+                "Object was {}, expected {}",
+                raw.object,
+                T::name()
+            )));
+        }
+        Ok(TypedRef(raw.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[derive(Debug)]
+    struct ExampleIdMarker;
+    impl TypeMarker for ExampleIdMarker {
+        fn name() -> &'static str {
+            "example"
+        }
+    }
+
+    #[test]
+    fn test_typed_ref() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let typed_ref = TypedRef::<ExampleIdMarker>::from(Field::from(12345));
+        let json = serde_json::to_value(typed_ref).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"id": "example_VgwPy6rwatl", "object": "example"})
+        );
+        assert_eq!(
+            serde_json::from_value::<TypedRef<ExampleIdMarker>>(json).unwrap(),
+            typed_ref
+        );
+
+        let wrong = serde_json::json!({"id": "example_VgwPy6rwatl", "object": "user"});
+        let error = serde_json::from_value::<TypedRef<ExampleIdMarker>>(wrong).unwrap_err();
+        assert_eq!(error.to_string(), "Object was user, expected example");
+
+        let missing = serde_json::json!({"id": "example_VgwPy6rwatl"});
+        assert!(serde_json::from_value::<TypedRef<ExampleIdMarker>>(missing).is_err());
+    }
+}
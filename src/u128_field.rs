@@ -0,0 +1,157 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::field::{get_or_create_codec, try_get_or_create_codec, UNENCODED};
+use crate::TypeMarker;
+
+/// A generic type-safe 128-bit object ID field, for UUID or Snowflake-style IDs.
+///
+/// This is the 128-bit counterpart of `Field<T>`: with Serde, the ID is encrypted into an
+/// opaque token with the marker's prefix using `Codec::encode_u128`, and tokens are decrypted
+/// back on deserialization.  Databases without a 128-bit integer type usually store such IDs
+/// as two `BIGINT` columns, which `from_parts` and `parts` convert from and to.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Config, TypeMarker, U128Field};
+///
+/// #[derive(Debug)]
+/// pub struct EventIdMarker;
+/// impl TypeMarker for EventIdMarker {
+///     fn name() -> &'static str { "event" }
+/// }
+///
+/// type EventId = U128Field<EventIdMarker>;
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let id = EventId::from(0x0123_4567_89ab_cdef_fedc_ba98_7654_3210);
+/// let json = serde_json::to_string(&id).unwrap();
+/// assert!(json.starts_with("\"event_"));
+///
+/// let decoded: EventId = serde_json::from_str(&json).unwrap();
+/// assert_eq!(decoded, id);
+/// assert_eq!(EventId::from_parts(decoded.parts().0, decoded.parts().1), id);
+/// ```
+#[derive(Debug)]
+pub struct U128Field<T: TypeMarker> {
+    id: u128,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T: TypeMarker> U128Field<T> {
+    /// Creates a field from the high and low 64 bits of the ID, as stored in two `BIGINT`
+    /// columns.  The columns hold the bits as is, so either may be negative.
+    pub fn from_parts(high: i64, low: i64) -> Self {
+        Self::from((high as u64 as u128) << 64 | low as u64 as u128)
+    }
+
+    /// Returns the high and low 64 bits of the ID, for storing in two `BIGINT` columns.
+    pub fn parts(&self) -> (i64, i64) {
+        ((self.id >> 64) as u64 as i64, self.id as u64 as i64)
+    }
+}
+
+// The standard traits are implemented manually, as for `Field<T>`.
+impl<T: TypeMarker> Clone for U128Field<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: TypeMarker> Copy for U128Field<T> {}
+
+impl<T: TypeMarker> PartialEq for U128Field<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T: TypeMarker> Eq for U128Field<T> {}
+
+impl<T: TypeMarker> Hash for U128Field<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T: TypeMarker> From<u128> for U128Field<T> {
+    fn from(id: u128) -> Self {
+        U128Field {
+            id,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: TypeMarker> From<U128Field<T>> for u128 {
+    /// Returns the raw `u128` value.
+    fn from(field: U128Field<T>) -> Self {
+        field.id
+    }
+}
+
+impl<T: TypeMarker> fmt::Display for U128Field<T> {
+    /// Formats the field as it is serialized, so that the raw ID isn't revealed.
+    ///
+    /// Writes `<unencoded id>` if the global config has not been set.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match try_get_or_create_codec::<T>() {
+            Some(codec) => f.write_str(&codec.encode_u128(self.id)),
+            None => f.write_str(UNENCODED),
+        }
+    }
+}
+
+impl<T: TypeMarker> Serialize for U128Field<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let codec = get_or_create_codec::<T>();
+        serializer.serialize_str(&codec.encode_u128(self.id))
+    }
+}
+
+impl<'de, T: TypeMarker> Deserialize<'de> for U128Field<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        let codec = get_or_create_codec::<T>();
+        let id = codec
+            .decode_u128(&encoded)
+            .map_err(serde::de::Error::custom)?;
+        Ok(Self::from(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct EventMarker;
+    impl TypeMarker for EventMarker {
+        fn name() -> &'static str {
+            "event"
+        }
+    }
+
+    type EventId = U128Field<EventMarker>;
+
+    #[test]
+    fn test_parts() {
+        for id in [0, 1, u64::MAX as u128, 1 << 64, 1 << 127, u128::MAX] {
+            let field = EventId::from(id);
+            let (high, low) = field.parts();
+            assert_eq!(EventId::from_parts(high, low), field);
+        }
+        assert_eq!(EventId::from_parts(0, -1), EventId::from(u64::MAX as u128));
+        assert_eq!(EventId::from_parts(1, 0), EventId::from(1 << 64));
+        assert_eq!(EventId::from(u128::MAX).parts(), (-1, -1));
+    }
+}
@@ -0,0 +1,158 @@
+//! OpenAPI schemas for `Field<T>` with utoipa (`utoipa` feature).
+//!
+//! A field is documented as a string with the prefix of its marker in a pattern and an
+//! example token, or as a UUID with `SerdeFormat::Uuid`.  The schema of the marker `user` is
+//! named `UserId`, so each marker gets its own component.
+//!
+//! The example is encoded with the key `your-secure-key` of the documentation rather than
+//! the global config, so that OpenAPI documents don't contain valid IDs.
+//!
+//! # Examples
+//!
+//! ```
+//! use cryptid_rs::{Field, TypeMarker};
+//! use utoipa::openapi::{RefOr, Schema};
+//! use utoipa::{PartialSchema, ToSchema};
+//!
+//! #[derive(Debug)]
+//! pub struct UserIdMarker;
+//! impl TypeMarker for UserIdMarker {
+//!     fn name() -> &'static str { "user" }
+//! }
+//!
+//! assert_eq!(Field::<UserIdMarker>::name(), "UserId");
+//! let RefOr::T(Schema::Object(schema)) = Field::<UserIdMarker>::schema() else {
+//!     panic!("Expected an object schema");
+//! };
+//! assert_eq!(schema.pattern.as_deref(), Some("^user_[0-9A-Za-z]+$"));
+//! assert_eq!(schema.examples[0], "user_Qo4cTPVnos2");
+//! ```
+
+use std::borrow::Cow;
+
+use ::utoipa::openapi::schema::{KnownFormat, ObjectBuilder, SchemaFormat, Type};
+use ::utoipa::openapi::{RefOr, Schema};
+use ::utoipa::{PartialSchema, ToSchema};
+
+use crate::field::schema_name;
+use crate::{Alphabet, Codec, Config, Field, SerdeFormat, TypeMarker};
+
+// The ID encoded in the examples.
+const EXAMPLE_ID: u64 = 12345;
+
+impl<T: TypeMarker> PartialSchema for Field<T> {
+    fn schema() -> RefOr<Schema> {
+        let alphabet = T::alphabet().unwrap_or_default();
+        let config = Config::new(b"your-secure-key")
+            .alphabet(alphabet.clone())
+            .expect("Version 0 should be valid");
+        let codec = Codec::new(T::name(), &config).with_prefix_case(T::prefix_case());
+        let schema = ObjectBuilder::new()
+            .schema_type(Type::String)
+            .description(Some(format!("The ID of a {}.", T::name())));
+        let schema = match T::serde_format() {
+            SerdeFormat::Token => schema
+                .pattern(Some(format!(
+                    "^{}_[{}]+$",
+                    T::prefix_case().apply(T::name()),
+                    character_class(&alphabet)
+                )))
+                .examples([codec.encode(EXAMPLE_ID)]),
+            SerdeFormat::Uuid => schema
+                .format(Some(SchemaFormat::KnownFormat(KnownFormat::Uuid)))
+                .examples([codec.encode_uuid(EXAMPLE_ID).hyphenated().to_string()]),
+        };
+        schema.into()
+    }
+}
+
+// Returns the digits of `alphabet` as a regular expression character class.
+fn character_class(alphabet: &Alphabet) -> &str {
+    match alphabet {
+        Alphabet::Base62 => "0-9A-Za-z",
+        Alphabet::Base32 => "0-9a-hjkmnp-tv-z",
+        Alphabet::Decimal => "0-9",
+        // Custom digits are ASCII letters and digits, which need no escaping.
+        Alphabet::Custom(digits) => digits,
+    }
+}
+
+impl<T: TypeMarker> ToSchema for Field<T> {
+    /// Returns the name of the marker in Pascal case with `Id` appended, e.g. `UserAccountId`
+    /// for `user_account`.
+    fn name() -> Cow<'static, str> {
+        Cow::Owned(schema_name::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PrefixCase;
+    use ::utoipa::openapi::schema::Object;
+
+    #[derive(Debug)]
+    struct AccountIdMarker;
+    impl TypeMarker for AccountIdMarker {
+        fn name() -> &'static str {
+            "user_account"
+        }
+        fn prefix_case() -> PrefixCase {
+            PrefixCase::Kebab
+        }
+    }
+
+    #[derive(Debug)]
+    struct OrderIdMarker;
+    impl TypeMarker for OrderIdMarker {
+        fn name() -> &'static str {
+            "order"
+        }
+        fn serde_format() -> SerdeFormat {
+            SerdeFormat::Uuid
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReferenceIdMarker;
+    impl TypeMarker for ReferenceIdMarker {
+        fn name() -> &'static str {
+            "ref"
+        }
+        fn alphabet() -> Option<Alphabet> {
+            Some(Alphabet::Decimal)
+        }
+    }
+
+    fn object<T: TypeMarker>() -> Object {
+        match Field::<T>::schema() {
+            RefOr::T(Schema::Object(object)) => object,
+            _ => panic!("Expected an object schema"),
+        }
+    }
+
+    #[test]
+    fn test_schemas() {
+        assert_eq!(Field::<AccountIdMarker>::name(), "UserAccountId");
+        let account = object::<AccountIdMarker>();
+        let pattern = account.pattern.as_deref().unwrap();
+        assert_eq!(pattern, "^user-account_[0-9A-Za-z]+$");
+        let example = account.examples[0].as_str().unwrap();
+        assert!(example.starts_with("user-account_"), "{}", example);
+
+        assert_eq!(Field::<OrderIdMarker>::name(), "OrderId");
+        let order = object::<OrderIdMarker>();
+        assert_eq!(order.pattern, None);
+        assert!(matches!(
+            order.format,
+            Some(SchemaFormat::KnownFormat(KnownFormat::Uuid))
+        ));
+        let example = order.examples[0].as_str().unwrap();
+        assert!(uuid::Uuid::parse_str(example).is_ok(), "{}", example);
+
+        let reference = object::<ReferenceIdMarker>();
+        assert_eq!(reference.pattern.as_deref(), Some("^ref_[0-9]+$"));
+        let example = reference.examples[0].as_str().unwrap();
+        assert!(example["ref_".len()..].bytes().all(|c| c.is_ascii_digit()));
+    }
+}
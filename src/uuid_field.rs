@@ -0,0 +1,161 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+use crate::field::{get_or_create_codec, try_get_or_create_codec, UNENCODED};
+use crate::TypeMarker;
+
+/// A generic type-safe field for existing UUID primary keys.
+///
+/// With Serde, the UUID is encrypted into an opaque token with the marker's prefix using
+/// `Codec::encode_uuid_pk`, and tokens are decrypted back on deserialization.  With the
+/// `diesel` feature, the field maps to Postgres `UUID` columns holding the plain UUID.
+///
+/// # Examples
+///
+/// ```
+/// use cryptid_rs::{Config, TypeMarker, UuidField};
+/// use uuid::Uuid;
+///
+/// #[derive(Debug)]
+/// pub struct OrderIdMarker;
+/// impl TypeMarker for OrderIdMarker {
+///     fn name() -> &'static str { "order" }
+/// }
+///
+/// type OrderId = UuidField<OrderIdMarker>;
+///
+/// Config::set_global(Config::new(b"your-secure-key"));
+/// let uuid = Uuid::parse_str("0190d2b4-7c5e-7a3b-9f1e-2d4c6b8a0e13").unwrap();
+/// let id = OrderId::from(uuid);
+/// let json = serde_json::to_string(&id).unwrap();
+/// assert!(json.starts_with("\"order_"));
+///
+/// let decoded: OrderId = serde_json::from_str(&json).unwrap();
+/// assert_eq!(Uuid::from(decoded), uuid);
+/// ```
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "diesel",
+    derive(diesel::expression::AsExpression),
+    diesel(sql_type = diesel::sql_types::Uuid)
+)]
+pub struct UuidField<T: TypeMarker> {
+    id: Uuid,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T: TypeMarker> UuidField<T> {
+    /// Returns the raw UUID.
+    pub fn uuid(&self) -> Uuid {
+        self.id
+    }
+}
+
+// The standard traits are implemented manually, as for `Field<T>`.
+impl<T: TypeMarker> Clone for UuidField<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: TypeMarker> Copy for UuidField<T> {}
+
+impl<T: TypeMarker> PartialEq for UuidField<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T: TypeMarker> Eq for UuidField<T> {}
+
+impl<T: TypeMarker> Hash for UuidField<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T: TypeMarker> From<Uuid> for UuidField<T> {
+    fn from(id: Uuid) -> Self {
+        UuidField {
+            id,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: TypeMarker> From<UuidField<T>> for Uuid {
+    /// Returns the raw UUID.
+    fn from(field: UuidField<T>) -> Self {
+        field.id
+    }
+}
+
+impl<T: TypeMarker> fmt::Display for UuidField<T> {
+    /// Formats the field as it is serialized, so that the raw UUID isn't revealed.
+    ///
+    /// Writes `<unencoded id>` if the global config has not been set.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match try_get_or_create_codec::<T>() {
+            Some(codec) => f.write_str(&codec.encode_uuid_pk(self.id)),
+            None => f.write_str(UNENCODED),
+        }
+    }
+}
+
+impl<T: TypeMarker> Serialize for UuidField<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let codec = get_or_create_codec::<T>();
+        serializer.serialize_str(&codec.encode_uuid_pk(self.id))
+    }
+}
+
+impl<'de, T: TypeMarker> Deserialize<'de> for UuidField<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        let codec = get_or_create_codec::<T>();
+        let id = codec
+            .decode_uuid_pk(&encoded)
+            .map_err(serde::de::Error::custom)?;
+        Ok(Self::from(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[derive(Debug)]
+    struct OrderIdMarker;
+    impl TypeMarker for OrderIdMarker {
+        fn name() -> &'static str {
+            "order"
+        }
+    }
+
+    type OrderId = UuidField<OrderIdMarker>;
+
+    #[test]
+    fn test_uuid_field() {
+        Config::set_global(Config::new(b"your-secure-key"));
+        let id = OrderId::from(Uuid::from_u128(0x0123_4567_89ab_cdef_fedc_ba98_7654_3210));
+        let json = serde_json::to_value(id).unwrap();
+        assert_eq!(json, id.to_string());
+        assert_eq!(serde_json::from_value::<OrderId>(json).unwrap(), id);
+
+        // The plain UUID is not accepted, nor is an integer token.
+        let plain = serde_json::Value::from(id.uuid().to_string());
+        assert!(serde_json::from_value::<OrderId>(plain).is_err());
+        let json = serde_json::to_value(crate::Field::<OrderIdMarker>::from(12345)).unwrap();
+        assert!(serde_json::from_value::<OrderId>(json).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+//! Runs requests through the router of the `axum-api` example.
+
+#[path = "../examples/axum-api/app.rs"]
+mod app;
+
+use app::{AppState, CustomerId, Invoice};
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use cryptid_rs::Config;
+use http_body_util::BodyExt;
+use serde_json::Value;
+use tower::ServiceExt;
+
+async fn request(router: &Router, method: &str, uri: &str, body: &str) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+#[tokio::test]
+async fn test_axum_api() {
+    Config::set_global(Config::new(b"your-secure-key"));
+    let router = app::router(AppState::default());
+    let alice = CustomerId::from(1).to_string();
+    let bob = CustomerId::from(2).to_string();
+
+    let uri = format!("/customers/{}/invoices", alice);
+    let (status, created) = request(&router, "POST", &uri, r#"{"amount_cents": 100}"#).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let invoice: Invoice = serde_json::from_value(created.clone()).unwrap();
+    assert_eq!(u64::from(invoice.customer_id), 1);
+    let token = created["id"].as_str().unwrap().to_string();
+    assert!(token.starts_with("invoice_"));
+
+    let (status, listed) = request(&router, "GET", &uri, "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed, Value::Array(vec![created.clone()]));
+
+    let (status, fetched) = request(&router, "GET", &format!("{}/{}", uri, token), "").await;
+    assert_eq!((status, fetched), (StatusCode::OK, created));
+
+    // Another customer's invoice, a tampered token and a token of another type are not found.
+    let other = format!("/customers/{}/invoices/{}", bob, token);
+    let (status, error) = request(&router, "GET", &other, "").await;
+    assert_eq!(
+        (status, &error["error"]),
+        (StatusCode::NOT_FOUND, &"Forbidden".into())
+    );
+    let tampered = format!("{}/{}x", uri, token);
+    let (status, error) = request(&router, "GET", &tampered, "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_ne!(error["error"], "Forbidden");
+    let (status, _) = request(&router, "GET", &format!("{}/{}", uri, alice), "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Invalid customer tokens are not found either, with the `IdPath` extractor.
+    let (status, _) = request(&router, "GET", "/customers/customer_x/invoices", "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // The OpenAPI document describes the IDs as tokens with their prefixes.
+    let (status, doc) = request(&router, "GET", "/openapi.json", "").await;
+    assert_eq!(status, StatusCode::OK);
+    let schemas = &doc["components"]["schemas"];
+    assert_eq!(
+        schemas["Invoice"]["properties"]["customer_id"]["$ref"],
+        "#/components/schemas/CustomerId"
+    );
+    assert_eq!(schemas["CustomerId"]["pattern"], "^customer_[0-9A-Za-z]+$");
+    assert_eq!(schemas["InvoiceId"]["type"], "string");
+}